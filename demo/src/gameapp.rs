@@ -44,6 +44,11 @@ impl App for GameApp {
     if let Some(screen) = self.screens.get_mut(&self.current_screen) {
       let mut screen = std::mem::replace(screen, Box::new(EmptyScreen{}));
       screen.update(self, state);
+      if let Some(track) = screen.music() {
+        if let Err(e) = state.music_mut().play(track) {
+          eprintln!("failed to play screen music: {:#}", e);
+        }
+      }
       self.screens.insert(self.current_screen.clone(), screen);
     }
   }
@@ -31,6 +31,9 @@ impl Screen<GameApp> for DemoScreen {
     }
 
     fn update(&mut self, game_state: &mut GameApp, engine_state: &mut State) {
-        self.camera_controller.update_camera(&mut engine_state.camera);
+        let main_camera = engine_state.main_camera();
+        if let Some(camera) = engine_state.camera_mut(main_camera) {
+            self.camera_controller.update_camera(camera);
+        }
     }
 }
\ No newline at end of file
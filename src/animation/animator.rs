@@ -0,0 +1,487 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ultraviolet as uv;
+
+use super::graph::{AnimationGraph, AnimationState, Condition, LayerBlend, Motion, Parameter};
+use super::skeleton::{Pose, Skeleton};
+
+// An event reached by a layer's current state this update.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FiredEvent {
+    pub name: String,
+    pub layer: usize,
+    pub clip: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Playback {
+    state: usize,
+    // How far through the state, in plays; looping states go past 1.
+    played: f32,
+    // Not advanced since it started, so events at its very start fire.
+    entered: bool,
+}
+
+impl Playback {
+    fn start(state: usize) -> Self {
+        Self {
+            state,
+            played: 0.0,
+            entered: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LayerState {
+    current: Playback,
+    // The state being faded out, with the fade's progress and length.
+    previous: Option<(Playback, f32, f32)>,
+    weight: f32,
+}
+
+// Plays an `AnimationGraph` for one skeleton: holds its parameters and
+// where every layer is, and works out the pose each update.
+pub struct Animator {
+    graph: Arc<AnimationGraph>,
+    skeleton: Arc<Skeleton>,
+    parameters: HashMap<String, Parameter>,
+    layers: Vec<LayerState>,
+    pose: Pose,
+    events: Vec<FiredEvent>,
+}
+
+impl Animator {
+    pub fn new(graph: Arc<AnimationGraph>, skeleton: Arc<Skeleton>) -> Self {
+        let layers = graph
+            .layers
+            .iter()
+            .map(|layer| LayerState {
+                current: Playback::start(layer.initial),
+                previous: None,
+                weight: layer.weight,
+            })
+            .collect();
+        let mut animator = Self {
+            parameters: graph.parameters.clone(),
+            pose: skeleton.rest_pose(),
+            graph,
+            skeleton,
+            layers,
+            events: Vec::new(),
+        };
+        animator.evaluate();
+        animator
+    }
+
+    pub fn graph(&self) -> &Arc<AnimationGraph> {
+        &self.graph
+    }
+
+    pub fn skeleton(&self) -> &Arc<Skeleton> {
+        &self.skeleton
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<Parameter> {
+        self.parameters.get(name).copied()
+    }
+
+    pub fn set_float(&mut self, name: &str, value: f32) {
+        self.parameters.insert(name.to_string(), Parameter::Float(value));
+    }
+
+    pub fn set_bool(&mut self, name: &str, value: bool) {
+        self.parameters.insert(name.to_string(), Parameter::Bool(value));
+    }
+
+    // Stays set until a transition uses it.
+    pub fn set_trigger(&mut self, name: &str) {
+        self.parameters.insert(name.to_string(), Parameter::Trigger(true));
+    }
+
+    pub fn reset_trigger(&mut self, name: &str) {
+        self.parameters.insert(name.to_string(), Parameter::Trigger(false));
+    }
+
+    // Starts at the graph's weight for the layer.
+    pub fn set_layer_weight(&mut self, layer: usize, weight: f32) {
+        if let Some(layer) = self.layers.get_mut(layer) {
+            layer.weight = weight;
+        }
+    }
+
+    pub fn current_state(&self, layer: usize) -> Option<&str> {
+        let state = self.layers.get(layer)?.current.state;
+        Some(&self.graph.layers[layer].states[state].name)
+    }
+
+    pub fn is_transitioning(&self, layer: usize) -> bool {
+        self.layers.get(layer).is_some_and(|layer| layer.previous.is_some())
+    }
+
+    // Crossfades straight to a state, whatever the transitions say. Returns
+    // false if the layer has no such state.
+    pub fn play(&mut self, layer: usize, state: &str, duration: f32) -> bool {
+        let Some(target) = self.graph.layers.get(layer).and_then(|l| l.find_state(state)) else {
+            return false;
+        };
+        let layer = &mut self.layers[layer];
+        layer.previous = (duration > 0.0).then_some((layer.current, 0.0, duration));
+        layer.current = Playback::start(target);
+        true
+    }
+
+    // This update's pose, in skeleton order.
+    pub fn pose(&self) -> &Pose {
+        &self.pose
+    }
+
+    pub fn joint_matrices(&self) -> Vec<uv::Mat4> {
+        self.pose.joint_matrices(&self.skeleton)
+    }
+
+    // Events from the last update, bottom layer first.
+    pub fn events(&self) -> &[FiredEvent] {
+        &self.events
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.events.clear();
+        let graph = self.graph.clone();
+        for (index, layer) in graph.layers.iter().enumerate() {
+            let current = self.layers[index].current;
+            let transition = layer.transitions.iter().find(|transition| {
+                let leaves = match transition.from {
+                    Some(from) => from == current.state,
+                    None => transition.to != current.state,
+                };
+                leaves
+                    && transition.exit_time.is_none_or(|exit| current.played >= exit)
+                    && transition.conditions.iter().all(|condition| condition.passes(&self.parameters))
+            });
+            if let Some(transition) = transition {
+                for condition in &transition.conditions {
+                    if let Condition::Trigger(name) = condition {
+                        self.parameters.insert(name.clone(), Parameter::Trigger(false));
+                    }
+                }
+                let state = &mut self.layers[index];
+                state.previous = (transition.duration > 0.0).then_some((current, 0.0, transition.duration));
+                state.current = Playback::start(transition.to);
+            }
+
+            let state = &mut self.layers[index];
+            let playing = &layer.states[state.current.state];
+            let (before, after) = advance(&mut state.current, playing, &self.parameters, dt);
+            if let Some((previous, elapsed, duration)) = state.previous.as_mut() {
+                advance(previous, &layer.states[previous.state], &self.parameters, dt);
+                *elapsed += dt;
+                if *elapsed >= *duration {
+                    state.previous = None;
+                }
+            }
+            if state.weight > 0.0 {
+                self.fire_events(index, &playing.motion, before, after, playing.looping);
+            }
+        }
+        self.evaluate();
+    }
+
+    // Events of the heaviest clip of a state that went from `before` to
+    // `after` plays, counting the start on its first update.
+    fn fire_events(&mut self, layer: usize, motion: &Motion, before: Option<f32>, after: f32, looping: bool) {
+        let weights = motion.weights(&self.parameters);
+        let Some((clip, _)) = weights.iter().max_by(|a, b| a.1.total_cmp(&b.1)) else {
+            return;
+        };
+        if clip.duration() <= 0.0 {
+            return;
+        }
+        for event in clip.events() {
+            let at = event.time / clip.duration();
+            let times = match before {
+                // Every loop's copy of the event in (before, after].
+                Some(before) if looping => (after - at).floor() - (before - at).floor(),
+                Some(before) => (before < at && at <= after) as u8 as f32,
+                None if looping => ((after - at).floor() + 1.0).max(0.0),
+                None => (at <= after) as u8 as f32,
+            };
+            for _ in 0..times as usize {
+                self.events.push(FiredEvent {
+                    name: event.name.clone(),
+                    layer,
+                    clip: clip.name.clone(),
+                });
+            }
+        }
+    }
+
+    fn evaluate(&mut self) {
+        let mut pose = self.skeleton.rest_pose();
+        for (layer, state) in self.graph.layers.iter().zip(&self.layers) {
+            if state.weight <= 0.0 {
+                continue;
+            }
+            let additive = layer.blend == LayerBlend::Additive;
+            let mut layer_pose = self.sample(&layer.states[state.current.state].motion, state.current, additive);
+            if let Some((previous, elapsed, duration)) = state.previous {
+                let mut faded = self.sample(&layer.states[previous.state].motion, previous, additive);
+                faded.blend(&layer_pose, elapsed / duration, None);
+                layer_pose = faded;
+            }
+            match layer.blend {
+                LayerBlend::Override => pose.blend(&layer_pose, state.weight, layer.mask.as_deref()),
+                LayerBlend::Additive => pose.add(&layer_pose, state.weight, layer.mask.as_deref()),
+            }
+        }
+        self.pose = pose;
+    }
+
+    // The motion's pose, or for additive layers its change from its first
+    // frame.
+    fn sample(&self, motion: &Motion, playback: Playback, additive: bool) -> Pose {
+        let pose = self.sample_at(motion, playback.played);
+        if !additive {
+            return pose;
+        }
+        pose.difference(&self.sample_at(motion, 0.0))
+    }
+
+    fn sample_at(&self, motion: &Motion, played: f32) -> Pose {
+        let phase = played - played.floor();
+        // A finished clip that doesn't loop holds its last frame.
+        let phase = if played >= 1.0 && phase == 0.0 { 1.0 } else { phase };
+        let mut pose = self.skeleton.rest_pose();
+        let mut total = 0.0;
+        for (clip, weight) in motion.weights(&self.parameters) {
+            let mut clip_pose = self.skeleton.rest_pose();
+            clip.sample(phase * clip.duration(), &mut clip_pose);
+            total += weight;
+            pose.blend(&clip_pose, weight / total, None);
+        }
+        pose
+    }
+}
+
+// Moves a playback on by `dt`, returning how far through it was before
+// (None if it had only just started) and after.
+fn advance(
+    playback: &mut Playback,
+    state: &AnimationState,
+    parameters: &HashMap<String, Parameter>,
+    dt: f32,
+) -> (Option<f32>, f32) {
+    let before = (!playback.entered).then_some(playback.played);
+    playback.entered = false;
+    let duration = state.motion.duration(parameters);
+    if duration > 0.0 {
+        playback.played += dt * state.speed / duration;
+    }
+    if !state.looping {
+        playback.played = playback.played.clamp(0.0, 1.0);
+    }
+    (before, playback.played)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::clip::{AnimationClip, Channel, Interpolation, Property};
+    use crate::animation::graph::{joint_mask, AnimationLayer, Transition};
+    use crate::animation::skeleton::Joint;
+
+    const HIPS: usize = 0;
+    const SPINE: usize = 1;
+
+    fn skeleton() -> Arc<Skeleton> {
+        Arc::new(Skeleton::new(vec![Joint::new("hips", None), Joint::new("spine", Some(HIPS))]).unwrap())
+    }
+
+    // A clip moving each joint in a straight line from one translation to
+    // another over `seconds`.
+    fn slide(name: &str, seconds: f32, moves: &[(usize, uv::Vec3, uv::Vec3)]) -> AnimationClip {
+        let channels = moves
+            .iter()
+            .map(|&(joint, from, to)| {
+                let values = vec![from.into_homogeneous_point(), to.into_homogeneous_point()];
+                Channel::new(joint, Property::Translation, Interpolation::Linear, vec![0.0, seconds], values).unwrap()
+            })
+            .collect();
+        AnimationClip::new(name, channels)
+    }
+
+    // A one second clip holding each joint at a translation.
+    fn hold(name: &str, joints: &[(usize, uv::Vec3)]) -> AnimationClip {
+        let moves: Vec<_> = joints.iter().map(|&(joint, at)| (joint, at, at)).collect();
+        slide(name, 1.0, &moves)
+    }
+
+    fn clip_state(name: &str, clip: AnimationClip) -> AnimationState {
+        AnimationState::new(name, Motion::Clip(Arc::new(clip)))
+    }
+
+    fn at(x: f32, y: f32) -> uv::Vec3 {
+        uv::Vec3::new(x, y, 0.0)
+    }
+
+    fn animator(graph: AnimationGraph) -> Animator {
+        Animator::new(Arc::new(graph), skeleton())
+    }
+
+    fn translation(animator: &Animator, joint: usize) -> uv::Vec3 {
+        animator.pose().joints[joint].translation
+    }
+
+    fn assert_near(found: uv::Vec3, expected: uv::Vec3) {
+        assert!((found - expected).mag() < 1e-4, "{:?} isn't {:?}", found, expected);
+    }
+
+    fn names(animator: &Animator) -> Vec<&str> {
+        animator.events().iter().map(|event| event.name.as_str()).collect()
+    }
+
+    #[test]
+    fn transitions_wait_for_exit_times_and_triggers() {
+        let mut layer = AnimationLayer::new("base");
+        let once = layer.add_state(clip_state("once", hold("once", &[(HIPS, at(0.0, 0.0))])).with_looping(false));
+        let after = layer.add_state(clip_state("after", hold("after", &[(HIPS, at(1.0, 0.0))])));
+        layer.add_transition(Transition::new(Some(once), after).with_exit_time(1.0));
+        layer.add_transition(Transition::new(Some(after), once).with_condition(Condition::Trigger("back".into())));
+        let graph = AnimationGraph::new()
+            .with_parameter("back", Parameter::Trigger(false))
+            .with_layer(layer);
+        let mut animator = animator(graph);
+
+        animator.update(0.5);
+        animator.update(0.6);
+        assert_eq!(animator.current_state(0), Some("once"));
+        animator.update(0.1);
+        assert_eq!(animator.current_state(0), Some("after"));
+        animator.update(5.0);
+        assert_eq!(animator.current_state(0), Some("after"));
+
+        animator.set_trigger("back");
+        animator.update(0.1);
+        assert_eq!(animator.current_state(0), Some("once"));
+        assert_eq!(animator.parameter("back"), Some(Parameter::Trigger(false)));
+        // Replayed from the start, so the exit time counts again.
+        animator.update(0.5);
+        assert_eq!(animator.current_state(0), Some("once"));
+    }
+
+    #[test]
+    fn crossfades_move_between_states_over_their_duration() {
+        let mut layer = AnimationLayer::new("base");
+        let idle = layer.add_state(clip_state("idle", hold("idle", &[(HIPS, at(0.0, 0.0))])));
+        let run = layer.add_state(clip_state("run", hold("run", &[(HIPS, at(1.0, 0.0))])));
+        let go = Condition::Equals("go".into(), true);
+        layer.add_transition(Transition::new(Some(idle), run).with_duration(0.5).with_condition(go));
+        let graph = AnimationGraph::new().with_parameter("go", Parameter::Bool(false)).with_layer(layer);
+        let mut animator = animator(graph);
+
+        animator.update(0.125);
+        assert_near(translation(&animator, HIPS), at(0.0, 0.0));
+        animator.set_bool("go", true);
+        for x in [0.25, 0.5, 0.75] {
+            animator.update(0.125);
+            assert!(animator.is_transitioning(0));
+            assert_near(translation(&animator, HIPS), at(x, 0.0));
+        }
+        animator.update(0.125);
+        assert!(!animator.is_transitioning(0));
+        assert_eq!(animator.current_state(0), Some("run"));
+        assert_near(translation(&animator, HIPS), at(1.0, 0.0));
+    }
+
+    #[test]
+    fn blend_states_pose_between_their_clips() {
+        let slow = Arc::new(hold("slow", &[(HIPS, at(0.0, 0.0))]));
+        let fast = Arc::new(hold("fast", &[(HIPS, at(2.0, 0.0))]));
+        let mut layer = AnimationLayer::new("base");
+        layer.add_state(AnimationState::new("move", Motion::blend_1d("speed", vec![(0.0, slow), (2.0, fast)])));
+        let graph = AnimationGraph::new().with_parameter("speed", Parameter::Float(0.0)).with_layer(layer);
+        let mut animator = animator(graph);
+
+        animator.set_float("speed", 0.5);
+        animator.update(0.1);
+        assert_near(translation(&animator, HIPS), at(0.5, 0.0));
+        animator.set_float("speed", 5.0);
+        animator.update(0.1);
+        assert_near(translation(&animator, HIPS), at(2.0, 0.0));
+    }
+
+    #[test]
+    fn additive_layers_add_their_change_on_top() {
+        let mut base = AnimationLayer::new("base");
+        base.add_state(clip_state("pose", hold("pose", &[(HIPS, at(1.0, 0.0)), (SPINE, at(2.0, 0.0))])));
+        let mut breathe = AnimationLayer::new("breathe").with_blend(LayerBlend::Additive).with_weight(0.5);
+        // Starts away from the rest pose; only its change counts.
+        let rise = slide("rise", 1.0, &[(SPINE, at(5.0, 0.0), at(5.0, 1.0))]);
+        breathe.add_state(clip_state("rise", rise));
+        let mut animator = animator(AnimationGraph::new().with_layer(base).with_layer(breathe));
+
+        animator.update(0.5);
+        assert_near(translation(&animator, HIPS), at(1.0, 0.0));
+        assert_near(translation(&animator, SPINE), at(2.0, 0.25));
+
+        animator.set_layer_weight(1, 0.0);
+        animator.update(0.0);
+        assert_near(translation(&animator, SPINE), at(2.0, 0.0));
+    }
+
+    #[test]
+    fn masks_keep_layers_to_their_joints() {
+        let mut base = AnimationLayer::new("base");
+        base.add_state(clip_state("stand", hold("stand", &[(HIPS, at(1.0, 0.0)), (SPINE, at(1.0, 0.0))])));
+        let mask = joint_mask(&skeleton(), &["spine"]).unwrap();
+        let mut upper = AnimationLayer::new("upper").with_mask(mask);
+        upper.add_state(clip_state("wave", hold("wave", &[(HIPS, at(3.0, 0.0)), (SPINE, at(3.0, 0.0))])));
+        let mut animator = animator(AnimationGraph::new().with_layer(base).with_layer(upper));
+
+        animator.update(0.1);
+        assert_near(translation(&animator, HIPS), at(1.0, 0.0));
+        assert_near(translation(&animator, SPINE), at(3.0, 0.0));
+    }
+
+    #[test]
+    fn looping_states_fire_events_every_loop() {
+        let walk = hold("walk", &[(HIPS, at(0.0, 0.0))]).with_event(0.0, "start").with_event(0.25, "step");
+        let mut layer = AnimationLayer::new("base");
+        layer.add_state(clip_state("walk", walk));
+        let mut animator = animator(AnimationGraph::new().with_layer(layer));
+
+        // The first update counts the start of the clip.
+        animator.update(0.1);
+        assert_eq!(names(&animator), ["start"]);
+        let fired = FiredEvent {
+            name: "start".into(),
+            layer: 0,
+            clip: "walk".into(),
+        };
+        assert_eq!(animator.events(), [fired]);
+        animator.update(0.2);
+        assert_eq!(names(&animator), ["step"]);
+        // Two whole loops at once fire each event twice.
+        animator.update(2.0);
+        assert_eq!(names(&animator), ["start", "start", "step", "step"]);
+        animator.update(0.0);
+        assert!(animator.events().is_empty());
+    }
+
+    #[test]
+    fn finished_states_fire_no_more_events() {
+        let swing = hold("swing", &[(HIPS, at(0.0, 0.0))]).with_event(0.5, "hit");
+        let mut layer = AnimationLayer::new("base");
+        layer.add_state(clip_state("swing", swing).with_looping(false));
+        let mut animator = animator(AnimationGraph::new().with_layer(layer));
+
+        animator.update(0.4);
+        assert!(animator.events().is_empty());
+        animator.update(0.4);
+        assert_eq!(names(&animator), ["hit"]);
+        for _ in 0..3 {
+            animator.update(1.0);
+            assert!(animator.events().is_empty());
+        }
+    }
+}
@@ -0,0 +1,227 @@
+// Keyframed joint animation, laid out like glTF's: each channel animates
+// one property of one joint and is sampled on its own.
+use anyhow::*;
+use ultraviolet as uv;
+use uv::Lerp;
+
+use super::skeleton::Pose;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    // Holds each key until the next.
+    Step,
+    // Straight lines, and the shortest arc for rotations.
+    Linear,
+    // Hermite curves through each key with its in and out tangents.
+    CubicSpline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+// Rotations are quaternions as (x, y, z, w); translations and scales leave
+// w unused. Cubic splines store an in-tangent, the value and an out-tangent
+// for every key, in that order.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub joint: usize,
+    pub property: Property,
+    pub interpolation: Interpolation,
+    times: Vec<f32>,
+    values: Vec<uv::Vec4>,
+}
+
+impl Channel {
+    // Fails unless there is at least one key, the times increase and there
+    // are as many values as the interpolation needs.
+    pub fn new(
+        joint: usize,
+        property: Property,
+        interpolation: Interpolation,
+        times: Vec<f32>,
+        values: Vec<uv::Vec4>,
+    ) -> Result<Self> {
+        ensure!(!times.is_empty(), "animation channel has no keys");
+        ensure!(
+            times.windows(2).all(|pair| pair[0] < pair[1]),
+            "animation channel key times must increase"
+        );
+        let per_key = match interpolation {
+            Interpolation::CubicSpline => 3,
+            _ => 1,
+        };
+        ensure!(
+            values.len() == times.len() * per_key,
+            "animation channel has {} values for {} keys",
+            values.len(),
+            times.len()
+        );
+        Ok(Self {
+            joint,
+            property,
+            interpolation,
+            times,
+            values,
+        })
+    }
+
+    pub fn times(&self) -> &[f32] {
+        &self.times
+    }
+
+    pub fn values(&self) -> &[uv::Vec4] {
+        &self.values
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.0)
+    }
+
+    fn value(&self, key: usize) -> uv::Vec4 {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.values[key * 3 + 1],
+            _ => self.values[key],
+        }
+    }
+
+    // Times before the first key or after the last hold that key.
+    pub fn sample(&self, time: f32) -> uv::Vec4 {
+        let last = self.times.len() - 1;
+        if time <= self.times[0] {
+            return self.value(0);
+        }
+        if time >= self.times[last] {
+            return self.value(last);
+        }
+        let key = self.times.partition_point(|&t| t <= time) - 1;
+        let (t0, t1) = (self.times[key], self.times[key + 1]);
+        let t = (time - t0) / (t1 - t0);
+        let rotation = self.property == Property::Rotation;
+        match self.interpolation {
+            Interpolation::Step => self.value(key),
+            Interpolation::Linear if rotation => slerp(self.value(key), self.value(key + 1), t),
+            Interpolation::Linear => self.value(key).lerp(self.value(key + 1), t),
+            Interpolation::CubicSpline => {
+                let span = t1 - t0;
+                let start = self.values[key * 3 + 1];
+                let out_tangent = self.values[key * 3 + 2] * span;
+                let end = self.values[key * 3 + 4];
+                let in_tangent = self.values[key * 3 + 3] * span;
+                let (t2, t3) = (t * t, t * t * t);
+                let value = start * (2.0 * t3 - 3.0 * t2 + 1.0)
+                    + out_tangent * (t3 - 2.0 * t2 + t)
+                    + end * (-2.0 * t3 + 3.0 * t2)
+                    + in_tangent * (t3 - t2);
+                if rotation {
+                    value.normalized()
+                } else {
+                    value
+                }
+            }
+        }
+    }
+
+    // Writes the sampled value into the channel's joint. Joints the pose
+    // doesn't have are skipped.
+    pub fn apply(&self, time: f32, pose: &mut Pose) {
+        let Some(joint) = pose.joints.get_mut(self.joint) else {
+            return;
+        };
+        let value = self.sample(time);
+        match self.property {
+            Property::Translation => joint.translation = value.xyz(),
+            Property::Rotation => joint.rotation = rotor(value),
+            Property::Scale => joint.scale = value.xyz(),
+        }
+    }
+}
+
+// The shortest way between two unit quaternions.
+fn slerp(from: uv::Vec4, mut to: uv::Vec4, t: f32) -> uv::Vec4 {
+    let mut cos = from.dot(to);
+    if cos < 0.0 {
+        to = -to;
+        cos = -cos;
+    }
+    // Nearly the same rotation: a straight line is as good and avoids
+    // dividing by almost zero.
+    if cos > 0.9995 {
+        return from.lerp(to, t).normalized();
+    }
+    let angle = cos.acos();
+    (from * ((1.0 - t) * angle).sin() + to * (t * angle).sin()) / angle.sin()
+}
+
+// The rotation of a unit quaternion stored as (x, y, z, w).
+pub fn rotor(quaternion: uv::Vec4) -> uv::Rotor3 {
+    uv::Rotor3::from_quaternion_array(quaternion.into()).normalized()
+}
+
+// Something to react to at a point in a clip, like a footstep sound.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationEvent {
+    pub time: f32,
+    pub name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    pub name: String,
+    channels: Vec<Channel>,
+    duration: f32,
+    events: Vec<AnimationEvent>,
+}
+
+impl AnimationClip {
+    pub fn new(name: &str, channels: Vec<Channel>) -> Self {
+        let duration = channels.iter().map(Channel::duration).fold(0.0, f32::max);
+        Self {
+            name: name.to_string(),
+            channels,
+            duration,
+            events: Vec::new(),
+        }
+    }
+
+    // Events are kept in time order.
+    pub fn with_event(mut self, time: f32, name: &str) -> Self {
+        self.add_event(time, name);
+        self
+    }
+
+    pub fn add_event(&mut self, time: f32, name: &str) {
+        let at = self.events.partition_point(|event| event.time <= time);
+        self.events.insert(
+            at,
+            AnimationEvent {
+                time,
+                name: name.to_string(),
+            },
+        );
+    }
+
+    pub fn events(&self) -> &[AnimationEvent] {
+        &self.events
+    }
+
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    // When the last key of any channel is.
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    // Poses the joints this clip animates at `time`; the rest of `pose` is
+    // left as it was, e.g. the skeleton's rest pose.
+    pub fn sample(&self, time: f32, pose: &mut Pose) {
+        for channel in &self.channels {
+            channel.apply(time, pose);
+        }
+    }
+}
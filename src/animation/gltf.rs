@@ -0,0 +1,640 @@
+// glTF 2.0 import for skinned meshes: their geometry, skins as skeletons,
+// and animations as clips. Both .glb files and .gltf JSON are read by the
+// `gltf` crate; buffers can be embedded as base64 data URIs or come from
+// `from_bytes_with`. Materials, cameras, lights and morph targets are ignored.
+use std::collections::HashMap;
+
+use ::gltf::accessor::{DataType, Dimensions};
+use ::gltf::animation::util::ReadOutputs;
+use ::gltf::buffer::Source;
+use ::gltf::mesh::Mode;
+use ::gltf::scene::Transform;
+use ::gltf::{Accessor, Node};
+use anyhow::*;
+use base64::Engine;
+use ultraviolet as uv;
+
+use super::clip::{AnimationClip, Channel, Interpolation, Property};
+use super::skeleton::{Joint, JointTransform, Skeleton};
+use crate::graphics::gl::SkinnedVertex;
+use crate::graphics::objects::mesh::SkinnedMeshData;
+
+#[derive(Debug, Clone)]
+pub struct GltfMesh {
+    pub name: String,
+    // Every triangle primitive of the mesh, merged. Vertices stay in the
+    // mesh's own space; unskinned ones get joint 0 at full weight.
+    pub data: SkinnedMeshData,
+    // Index into `Gltf::skins` of the skin the first node using this mesh
+    // deforms it with.
+    pub skin: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GltfAnimation {
+    // Index into `Gltf::skins` of the skeleton the clip's joints are in.
+    pub skin: usize,
+    pub clip: AnimationClip,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Gltf {
+    pub meshes: Vec<GltfMesh>,
+    // In file order; joints are in the skin's order, which is what the
+    // meshes' joint indices refer to.
+    pub skins: Vec<Skeleton>,
+    // One clip per animation and skin it moves, in file order. Channels
+    // animating anything but joints, like other nodes or morph weights,
+    // are dropped.
+    pub animations: Vec<GltfAnimation>,
+}
+
+impl Gltf {
+    // Fails on buffers outside the file; see `from_bytes_with`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Self::from_bytes_with(bytes, |uri| bail!("gltf: external buffer '{}' can't be loaded", uri))
+    }
+
+    // `load` fetches buffers referenced by URI, relative to the file.
+    pub fn from_bytes_with(bytes: &[u8], mut load: impl FnMut(&str) -> Result<Vec<u8>>) -> Result<Self> {
+        let ::gltf::Gltf { document, mut blob } = ::gltf::Gltf::from_slice(bytes).context("gltf")?;
+        let version = &document.as_json().asset.version;
+        ensure!(version.starts_with("2."), "gltf: only version 2 is supported, not {:?}", version);
+
+        let mut buffers = Vec::new();
+        for buffer in document.buffers() {
+            let data = match buffer.source() {
+                Source::Uri(uri) if uri.starts_with("data:") => decode_data_uri(uri)?,
+                Source::Uri(uri) => load(uri)?,
+                Source::Bin => blob
+                    .take()
+                    .with_context(|| format!("gltf: buffer {} has no data", buffer.index()))?,
+            };
+            ensure!(
+                data.len() >= buffer.length(),
+                "gltf: buffer {} is shorter than its byteLength",
+                buffer.index()
+            );
+            buffers.push(data);
+        }
+
+        let import = Import {
+            nodes: document.nodes().collect(),
+            buffers,
+        };
+        let parents = import.parents();
+        let mut gltf = Gltf::default();
+        for skin in document.skins() {
+            gltf.skins.push(
+                import
+                    .skin(&skin, &parents)
+                    .with_context(|| format!("gltf: skin {}", skin.index()))?,
+            );
+        }
+        for mesh in document.meshes() {
+            let skin = document
+                .nodes()
+                .find(|node| node.mesh().is_some_and(|m| m.index() == mesh.index()))
+                .and_then(|node| node.skin());
+            // A mesh without a skin may only use joint 0, like unskinned vertices.
+            let joint_count = skin.as_ref().map_or(1, |skin| skin.joints().count());
+            gltf.meshes.push(GltfMesh {
+                name: name(mesh.name(), "mesh", mesh.index()),
+                data: import
+                    .mesh(&mesh, joint_count)
+                    .with_context(|| format!("gltf: mesh {}", mesh.index()))?,
+                skin: skin.map(|skin| skin.index()),
+            });
+        }
+        // Each skin gets its own copy of the animations that move its joints,
+        // with channels indexing that skin's joints.
+        for animation in document.animations() {
+            let animation_name = name(animation.name(), "animation", animation.index());
+            for skin in document.skins() {
+                let joints: HashMap<usize, usize> =
+                    skin.joints().enumerate().map(|(joint, node)| (node.index(), joint)).collect();
+                let clip = import
+                    .animation(&animation, &joints, &animation_name)
+                    .with_context(|| format!("gltf: animation {}", animation.index()))?;
+                if !clip.channels().is_empty() {
+                    gltf.animations.push(GltfAnimation {
+                        skin: skin.index(),
+                        clip,
+                    });
+                }
+            }
+        }
+        Ok(gltf)
+    }
+
+    // The clip called `name` for the skeleton at `skin`.
+    pub fn animation(&self, skin: usize, name: &str) -> Option<&AnimationClip> {
+        self.clips(skin).find(|clip| clip.name == name)
+    }
+
+    // Every clip for the skeleton at `skin`.
+    pub fn clips(&self, skin: usize) -> impl Iterator<Item = &AnimationClip> {
+        self.animations
+            .iter()
+            .filter(move |animation| animation.skin == skin)
+            .map(|animation| &animation.clip)
+    }
+}
+
+fn name(name: Option<&str>, kind: &str, index: usize) -> String {
+    match name {
+        Some(name) => name.to_string(),
+        None => format!("{}{}", kind, index),
+    }
+}
+
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>> {
+    let (header, data) = uri.split_once(',').context("gltf: malformed data URI")?;
+    ensure!(header.ends_with(";base64"), "gltf: data URIs must be base64");
+    base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .context("gltf: invalid base64")
+}
+
+// The `gltf` crate's readers assume an accessor's layout matches what they
+// read and panic otherwise, so each is checked first.
+fn check(accessor: &Accessor, dimensions: Dimensions, types: &[DataType]) -> Result<()> {
+    let index = accessor.index();
+    ensure!(
+        accessor.dimensions() == dimensions && types.contains(&accessor.data_type()),
+        "accessor {} must be {:?} of one of {:?}, not {:?} of {:?}",
+        index,
+        dimensions,
+        types,
+        accessor.dimensions(),
+        accessor.data_type()
+    );
+    ensure!(accessor.count() > 0, "accessor {} is empty", index);
+    if let Some(stride) = accessor.view().and_then(|view| view.stride()) {
+        ensure!(stride >= accessor.size(), "accessor {} has elements wider than its stride", index);
+    }
+    ensure!(
+        accessor.sparse().is_none_or(|sparse| sparse.count() > 0),
+        "accessor {} has an empty sparse section",
+        index
+    );
+    Ok(())
+}
+
+// What the readers return when an accessor's data isn't all there.
+fn truncated(accessor: &Accessor) -> Error {
+    anyhow!("accessor {} runs past its buffer view", accessor.index())
+}
+
+struct Import<'a> {
+    nodes: Vec<Node<'a>>,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl<'a> Import<'a> {
+    fn buffer(&self, buffer: ::gltf::Buffer) -> Option<&[u8]> {
+        self.buffers.get(buffer.index()).map(Vec::as_slice)
+    }
+
+    // Parent of every node that has one.
+    fn parents(&self) -> HashMap<usize, usize> {
+        let mut parents = HashMap::new();
+        for node in &self.nodes {
+            for child in node.children() {
+                parents.insert(child.index(), node.index());
+            }
+        }
+        parents
+    }
+
+    fn local_transform(&self, node: usize) -> Result<JointTransform> {
+        let node = self.nodes.get(node).with_context(|| format!("no node {}", node))?;
+        Ok(match node.transform() {
+            Transform::Matrix { matrix } => JointTransform::from_matrix(matrix.into()),
+            Transform::Decomposed {
+                translation,
+                rotation,
+                scale,
+            } => JointTransform {
+                translation: translation.into(),
+                rotation: super::clip::rotor(rotation.into()),
+                scale: scale.into(),
+            },
+        })
+    }
+
+    // The node's transform in scene space.
+    fn global_matrix(&self, node: usize, parents: &HashMap<usize, usize>) -> Result<uv::Mat4> {
+        let mut matrix = self.local_transform(node)?.to_matrix();
+        let mut ancestor = parents.get(&node);
+        let mut depth = 0;
+        while let Some(&parent) = ancestor {
+            depth += 1;
+            ensure!(depth <= self.nodes.len(), "node hierarchy has a cycle");
+            matrix = self.local_transform(parent)?.to_matrix() * matrix;
+            ancestor = parents.get(&parent);
+        }
+        Ok(matrix)
+    }
+
+    fn skin(&self, skin: &::gltf::Skin<'a>, parents: &HashMap<usize, usize>) -> Result<Skeleton> {
+        let nodes: Vec<Node> = skin.joints().collect();
+        let index: HashMap<usize, usize> =
+            nodes.iter().enumerate().map(|(joint, node)| (node.index(), joint)).collect();
+        let inverse_binds: Vec<uv::Mat4> = match skin.inverse_bind_matrices() {
+            Some(accessor) => {
+                check(&accessor, Dimensions::Mat4, &[DataType::F32])?;
+                skin.reader(|buffer| self.buffer(buffer))
+                    .read_inverse_bind_matrices()
+                    .ok_or_else(|| truncated(&accessor))?
+                    .map(uv::Mat4::from)
+                    .collect()
+            }
+            None => vec![uv::Mat4::identity(); nodes.len()],
+        };
+        ensure!(inverse_binds.len() >= nodes.len(), "too few inverse bind matrices");
+
+        // Joints hang from the nearest ancestor that is also a joint; roots
+        // keep whatever is above them in `Skeleton::root`.
+        let mut joints = Vec::with_capacity(nodes.len());
+        let mut root = None;
+        for (joint, node) in nodes.iter().enumerate() {
+            let mut ancestor = parents.get(&node.index()).copied();
+            while let Some(a) = ancestor.filter(|a| !index.contains_key(a)) {
+                ancestor = parents.get(&a).copied();
+            }
+            let parent = ancestor.map(|a| index[&a]);
+            if parent.is_none() && root.is_none() {
+                root = Some(match parents.get(&node.index()) {
+                    Some(&above) => self.global_matrix(above, parents)?,
+                    None => uv::Mat4::identity(),
+                });
+            }
+            joints.push(
+                Joint::new(&name(node.name(), "joint", joint), parent)
+                    .with_rest(self.local_transform(node.index())?)
+                    .with_inverse_bind(inverse_binds[joint]),
+            );
+        }
+        Ok(Skeleton::new(joints)?.with_root(root.unwrap_or_else(uv::Mat4::identity)))
+    }
+
+    // `joint_count` is the size of the skin the mesh is drawn with, which
+    // its joint indices have to stay within.
+    fn mesh(&self, mesh: &::gltf::Mesh, joint_count: usize) -> Result<SkinnedMeshData> {
+        let mut data = SkinnedMeshData::default();
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                log::warn!("gltf: skipping a primitive that isn't a triangle list");
+                continue;
+            }
+            let attribute = |semantic: ::gltf::Semantic, dimensions, types: &[DataType]| -> Result<Option<Accessor>> {
+                let Some(accessor) = primitive.get(&semantic) else {
+                    return Ok(None);
+                };
+                check(&accessor, dimensions, types).with_context(|| format!("{:?}", semantic))?;
+                Ok(Some(accessor))
+            };
+            let normalized = [DataType::U8, DataType::U16, DataType::F32];
+            let position = attribute(::gltf::Semantic::Positions, Dimensions::Vec3, &[DataType::F32])?
+                .context("primitive has no POSITION")?;
+            let tex_coords = attribute(::gltf::Semantic::TexCoords(0), Dimensions::Vec2, &normalized)?;
+            let joints = attribute(::gltf::Semantic::Joints(0), Dimensions::Vec4, &[DataType::U8, DataType::U16])?;
+            let weights = attribute(::gltf::Semantic::Weights(0), Dimensions::Vec4, &normalized)?;
+            let indices = match primitive.indices() {
+                Some(accessor) => {
+                    check(&accessor, Dimensions::Scalar, &[DataType::U8, DataType::U16, DataType::U32])?;
+                    Some(accessor)
+                }
+                None => None,
+            };
+
+            let reader = primitive.reader(|buffer| self.buffer(buffer));
+            let positions: Vec<[f32; 3]> = reader.read_positions().ok_or_else(|| truncated(&position))?.collect();
+            let count = positions.len();
+            let tex_coords: Option<Vec<[f32; 2]>> = match &tex_coords {
+                Some(accessor) => {
+                    let read = reader.read_tex_coords(0).ok_or_else(|| truncated(accessor))?;
+                    Some(read.into_f32().collect())
+                }
+                None => None,
+            };
+            let joints: Option<Vec<[u16; 4]>> = match &joints {
+                Some(accessor) => Some(reader.read_joints(0).ok_or_else(|| truncated(accessor))?.into_u16().collect()),
+                None => None,
+            };
+            let weights: Option<Vec<[f32; 4]>> = match &weights {
+                Some(accessor) => {
+                    let read = reader.read_weights(0).ok_or_else(|| truncated(accessor))?;
+                    Some(read.into_f32().collect())
+                }
+                None => None,
+            };
+            let lengths = [
+                tex_coords.as_ref().map(Vec::len),
+                joints.as_ref().map(Vec::len),
+                weights.as_ref().map(Vec::len),
+            ];
+            ensure!(
+                lengths.iter().all(|length| length.is_none_or(|length| length == count)),
+                "attributes have different vertex counts"
+            );
+
+            let base = data.vertices.len() as u32;
+            for (i, &position) in positions.iter().enumerate() {
+                let mut vertex = SkinnedVertex {
+                    position,
+                    weights: [1.0, 0.0, 0.0, 0.0],
+                    ..Default::default()
+                };
+                if let Some(tex_coords) = &tex_coords {
+                    vertex.tex_coords = tex_coords[i];
+                }
+                if let (Some(joints), Some(weights)) = (&joints, &weights) {
+                    let total: f32 = weights[i].iter().sum();
+                    if total > 0.0 {
+                        for k in 0..4 {
+                            // Unweighted slots are often left with junk
+                            // indices; point them at joint 0 instead.
+                            if weights[i][k] == 0.0 {
+                                continue;
+                            }
+                            let joint = joints[i][k];
+                            ensure!(
+                                (joint as usize) < joint_count,
+                                "vertex {} uses joint {}, but the skin has {}",
+                                i,
+                                joint,
+                                joint_count
+                            );
+                            vertex.joints[k] = joint;
+                        }
+                        vertex.weights = weights[i].map(|weight| weight / total);
+                    }
+                }
+                data.vertices.push(vertex);
+            }
+            match &indices {
+                Some(accessor) => {
+                    let read = reader.read_indices().ok_or_else(|| truncated(accessor))?;
+                    let indices: Vec<u32> = read.into_u32().collect();
+                    ensure!(indices.iter().all(|&i| (i as usize) < count), "index out of range");
+                    data.indices.extend(indices.iter().map(|&i| base + i));
+                }
+                None => data.indices.extend(base..base + count as u32),
+            }
+        }
+        Ok(data)
+    }
+
+    // The channels of `animation` that move a node in `joints`, which maps
+    // nodes to joints of one skin.
+    fn animation(
+        &self,
+        animation: &::gltf::Animation,
+        joints: &HashMap<usize, usize>,
+        name: &str,
+    ) -> Result<AnimationClip> {
+        let mut channels = Vec::new();
+        for channel in animation.channels() {
+            let target = channel.target();
+            let property = match target.property() {
+                ::gltf::animation::Property::Translation => Property::Translation,
+                ::gltf::animation::Property::Rotation => Property::Rotation,
+                ::gltf::animation::Property::Scale => Property::Scale,
+                ::gltf::animation::Property::MorphTargetWeights => continue,
+            };
+            let Some(&joint) = joints.get(&target.node().index()) else {
+                continue;
+            };
+            let sampler = channel.sampler();
+            let interpolation = match sampler.interpolation() {
+                ::gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                ::gltf::animation::Interpolation::Step => Interpolation::Step,
+                ::gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+            };
+            let (input, output) = (sampler.input(), sampler.output());
+            check(&input, Dimensions::Scalar, &[DataType::F32])?;
+            if property == Property::Rotation {
+                let types = [DataType::I8, DataType::U8, DataType::I16, DataType::U16, DataType::F32];
+                check(&output, Dimensions::Vec4, &types)?;
+            } else {
+                check(&output, Dimensions::Vec3, &[DataType::F32])?;
+            }
+
+            let reader = channel.reader(|buffer| self.buffer(buffer));
+            let times = reader.read_inputs().ok_or_else(|| truncated(&input))?.collect();
+            let values = match reader.read_outputs().ok_or_else(|| truncated(&output))? {
+                ReadOutputs::Translations(values) | ReadOutputs::Scales(values) => {
+                    values.map(|[x, y, z]| uv::Vec4::new(x, y, z, 0.0)).collect()
+                }
+                ReadOutputs::Rotations(values) => values.into_f32().map(uv::Vec4::from).collect(),
+                ReadOutputs::MorphTargetWeights(_) => continue,
+            };
+            channels.push(Channel::new(joint, property, interpolation, times, values)?);
+        }
+        Ok(AnimationClip::new(name, channels))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    // Two skins: nodes 0 and 1 (1 a child of 0), and node 2 alone. Node 3
+    // draws a triangle skinned to the second one. "wave" moves a joint of
+    // each skin.
+    fn document() -> (Value, Vec<u8>) {
+        let mut bin = Vec::new();
+        let mut push = |values: &[f32]| values.iter().for_each(|v| bin.extend(v.to_le_bytes()));
+        push(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]); // 0: positions
+        push(&[0.0, 0.0, 1.0, 0.0, 0.0, 1.0]); // 36: tex coords
+        push(&[2.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]); // 60: weights
+        push(&[0.0, 1.0]); // 108: times
+        push(&[0.0, 1.0, 0.0, 0.0, 2.0, 0.0]); // 116: translations
+        push(&[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0]); // 140: rotations
+        for index in [0u16, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 0, 0] {
+            bin.extend(index.to_le_bytes()); // 172: joints, 196: indices
+        }
+        let view = |offset: usize, length: usize| json!({"buffer": 0, "byteOffset": offset, "byteLength": length});
+        let gltf = json!({
+            "asset": {"version": "2.0"},
+            "nodes": [
+                {"name": "hips", "children": [1], "translation": [0.0, 1.0, 0.0]},
+                {"name": "spine", "rotation": [0.0, 0.0, 0.0, 1.0]},
+                {"name": "tail"},
+                {"mesh": 0, "skin": 1},
+            ],
+            "skins": [{"joints": [0, 1]}, {"joints": [2]}],
+            "meshes": [{"name": "fin", "primitives": [{
+                "attributes": {"POSITION": 0, "TEXCOORD_0": 1, "WEIGHTS_0": 2, "JOINTS_0": 6},
+                "indices": 7,
+            }]}],
+            "animations": [{
+                "name": "wave",
+                "samplers": [
+                    {"input": 3, "output": 4},
+                    {"input": 3, "output": 5, "interpolation": "STEP"},
+                ],
+                "channels": [
+                    {"sampler": 0, "target": {"node": 1, "path": "translation"}},
+                    {"sampler": 1, "target": {"node": 2, "path": "rotation"}},
+                    {"sampler": 0, "target": {"node": 3, "path": "translation"}},
+                ],
+            }],
+            "accessors": [
+                {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]},
+                {"bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2"},
+                {"bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC4"},
+                {"bufferView": 3, "componentType": 5126, "count": 2, "type": "SCALAR"},
+                {"bufferView": 4, "componentType": 5126, "count": 2, "type": "VEC3"},
+                {"bufferView": 5, "componentType": 5126, "count": 2, "type": "VEC4"},
+                {"bufferView": 6, "componentType": 5123, "count": 3, "type": "VEC4"},
+                {"bufferView": 7, "componentType": 5123, "count": 3, "type": "SCALAR"},
+            ],
+            "bufferViews": [
+                view(0, 36), view(36, 24), view(60, 48), view(108, 8),
+                view(116, 24), view(140, 32), view(172, 24), view(196, 6),
+            ],
+            "buffers": [{"byteLength": bin.len()}],
+        });
+        (gltf, bin)
+    }
+
+    fn embedded(mut gltf: Value, bin: &[u8]) -> Vec<u8> {
+        let data = base64::engine::general_purpose::STANDARD.encode(bin);
+        let uri = format!("data:application/octet-stream;base64,{}", data);
+        gltf["buffers"][0]["uri"] = uri.into();
+        serde_json::to_vec(&gltf).unwrap()
+    }
+
+    fn glb(gltf: &Value, bin: &[u8]) -> Vec<u8> {
+        let mut text = serde_json::to_vec(gltf).unwrap();
+        text.resize(text.len().next_multiple_of(4), b' ');
+        let mut bin = bin.to_vec();
+        bin.resize(bin.len().next_multiple_of(4), 0);
+        let mut bytes = b"glTF".to_vec();
+        for word in [2, (12 + 8 + text.len() + 8 + bin.len()) as u32] {
+            bytes.extend(word.to_le_bytes());
+        }
+        for (kind, chunk) in [(b"JSON", text), (b"BIN\0", bin)] {
+            bytes.extend((chunk.len() as u32).to_le_bytes());
+            bytes.extend(kind);
+            bytes.extend(chunk);
+        }
+        bytes
+    }
+
+    #[test]
+    fn reads_skins_and_meshes() {
+        let (gltf, bin) = document();
+        let gltf = Gltf::from_bytes(&embedded(gltf, &bin)).unwrap();
+
+        assert_eq!(gltf.skins.len(), 2);
+        let hips = &gltf.skins[0].joints();
+        assert_eq!(hips.len(), 2);
+        assert_eq!((hips[0].name.as_str(), hips[0].parent), ("hips", None));
+        assert_eq!((hips[1].name.as_str(), hips[1].parent), ("spine", Some(0)));
+        assert_eq!(hips[0].rest.translation, uv::Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(gltf.skins[1].joints()[0].name, "tail");
+
+        let mesh = &gltf.meshes[0];
+        assert_eq!((mesh.name.as_str(), mesh.skin), ("fin", Some(1)));
+        assert_eq!(mesh.data.indices, vec![2, 1, 0]);
+        let vertices = &mesh.data.vertices;
+        assert_eq!(vertices[1].position, [1.0, 0.0, 0.0]);
+        assert_eq!(vertices[1].tex_coords, [1.0, 0.0]);
+        // Weights are normalized; a vertex with none keeps joint 0.
+        assert_eq!(vertices[0].weights, [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(vertices[1].weights, [0.5, 0.5, 0.0, 0.0]);
+        assert_eq!(vertices[2].weights, [1.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn keys_animations_by_skin() {
+        let (gltf, bin) = document();
+        let gltf = Gltf::from_bytes(&embedded(gltf, &bin)).unwrap();
+
+        // One clip for each skin "wave" moves; the channel on node 3, which
+        // is no joint, is dropped.
+        assert_eq!(gltf.animations.len(), 2);
+        let hips = gltf.animation(0, "wave").unwrap();
+        assert_eq!(hips.channels().len(), 1);
+        let channel = &hips.channels()[0];
+        assert_eq!((channel.joint, channel.property), (1, Property::Translation));
+        assert_eq!(channel.times(), [0.0, 1.0]);
+        assert_eq!(channel.values()[1], uv::Vec4::new(0.0, 2.0, 0.0, 0.0));
+
+        let tail = gltf.animation(1, "wave").unwrap();
+        assert_eq!(tail.channels().len(), 1);
+        let channel = &tail.channels()[0];
+        assert_eq!((channel.joint, channel.property), (0, Property::Rotation));
+        assert_eq!(channel.interpolation, Interpolation::Step);
+        assert_eq!(channel.values()[1], uv::Vec4::new(0.0, 0.0, 1.0, 0.0));
+
+        assert!(gltf.animation(2, "wave").is_none());
+        assert_eq!(gltf.clips(1).count(), 1);
+    }
+
+    #[test]
+    fn reads_glb_and_external_buffers() {
+        let (gltf, bin) = document();
+        let from_glb = Gltf::from_bytes(&glb(&gltf, &bin)).unwrap();
+        assert_eq!(from_glb.meshes[0].data.vertices.len(), 3);
+
+        let mut external = gltf.clone();
+        external["buffers"][0]["uri"] = "fin.bin".into();
+        let text = serde_json::to_vec(&external).unwrap();
+        assert!(Gltf::from_bytes(&text).is_err());
+        let loaded = Gltf::from_bytes_with(&text, |uri| {
+            ensure!(uri == "fin.bin", "unexpected buffer {}", uri);
+            Ok(bin.clone())
+        })
+        .unwrap();
+        assert_eq!(loaded.animations.len(), 2);
+    }
+
+    #[test]
+    fn rejects_joints_outside_the_skin() {
+        let (gltf, mut bin) = document();
+        // Vertex 1's second joint, weighted 0.5, becomes joint 1.
+        bin[182..184].copy_from_slice(&1u16.to_le_bytes());
+        let error = Gltf::from_bytes(&embedded(gltf.clone(), &bin)).unwrap_err();
+        assert!(format!("{:#}", error).contains("joint 1"), "{:#}", error);
+
+        // Fine on the two joint skin.
+        let mut hips = gltf;
+        hips["nodes"][3]["skin"] = 0.into();
+        let hips = Gltf::from_bytes(&embedded(hips, &bin)).unwrap();
+        assert_eq!(hips.meshes[0].data.vertices[1].joints, [0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn rejects_malformed_files_without_panicking() {
+        let (gltf, bin) = document();
+        let mut mistyped = gltf.clone();
+        // Joints as floats and inverse binds that are vectors.
+        mistyped["accessors"][6]["componentType"] = 5126.into();
+        assert!(Gltf::from_bytes(&embedded(mistyped, &bin)).is_err());
+        let mut mistyped = gltf.clone();
+        mistyped["skins"][1]["inverseBindMatrices"] = 2.into();
+        assert!(Gltf::from_bytes(&embedded(mistyped, &bin)).is_err());
+
+        // Past the end of its view, and a view past the end of the buffer.
+        let mut overrun = gltf.clone();
+        overrun["accessors"][0]["count"] = 4.into();
+        assert!(Gltf::from_bytes(&embedded(overrun, &bin)).is_err());
+        let mut short = gltf.clone();
+        short["bufferViews"][7]["byteLength"] = 64.into();
+        assert!(Gltf::from_bytes(&embedded(short, &bin)).is_err());
+
+        assert!(Gltf::from_bytes(&embedded(gltf.clone(), &bin[..100])).is_err());
+        let mut version = gltf.clone();
+        version["asset"]["version"] = "1.0".into();
+        assert!(Gltf::from_bytes(&embedded(version, &bin)).is_err());
+        assert!(Gltf::from_bytes(b"{\"asset\": ").is_err());
+    }
+}
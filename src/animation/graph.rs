@@ -0,0 +1,673 @@
+// Animation graphs: layers of state machines whose states play clips or
+// blend spaces, switching between them with crossfades when parameters
+// meet a transition's conditions. A graph is shared; each character plays
+// it through its own `Animator`.
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::*;
+use serde_json::Value;
+use ultraviolet as uv;
+
+use super::clip::AnimationClip;
+use super::skeleton::Skeleton;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parameter {
+    Float(f32),
+    Bool(bool),
+    // Set until a transition using it is taken.
+    Trigger(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Greater(String, f32),
+    Less(String, f32),
+    Equals(String, bool),
+    Trigger(String),
+}
+
+impl Condition {
+    // Parameters that are missing or of another type never pass.
+    pub fn passes(&self, parameters: &HashMap<String, Parameter>) -> bool {
+        match self {
+            Condition::Greater(name, value) => matches!(parameters.get(name), Some(Parameter::Float(x)) if x > value),
+            Condition::Less(name, value) => matches!(parameters.get(name), Some(Parameter::Float(x)) if x < value),
+            Condition::Equals(name, value) => matches!(parameters.get(name), Some(Parameter::Bool(x)) if x == value),
+            Condition::Trigger(name) => matches!(parameters.get(name), Some(Parameter::Trigger(true))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Motion {
+    Clip(Arc<AnimationClip>),
+    // Clips placed along a float parameter, e.g. idle, walk and run by
+    // speed; the two either side of its value are blended.
+    Blend1d {
+        parameter: String,
+        clips: Vec<(f32, Arc<AnimationClip>)>,
+    },
+    // Clips placed on the plane of two float parameters, e.g. strafing by
+    // velocity. Weights fall off across the bands between clips, so any
+    // layout works, not just grids.
+    Blend2d {
+        parameters: [String; 2],
+        clips: Vec<(uv::Vec2, Arc<AnimationClip>)>,
+    },
+}
+
+impl Motion {
+    // Sorts the clips by position.
+    pub fn blend_1d(parameter: &str, mut clips: Vec<(f32, Arc<AnimationClip>)>) -> Self {
+        clips.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Motion::Blend1d {
+            parameter: parameter.to_string(),
+            clips,
+        }
+    }
+
+    pub fn blend_2d(x: &str, y: &str, clips: Vec<(uv::Vec2, Arc<AnimationClip>)>) -> Self {
+        Motion::Blend2d {
+            parameters: [x.to_string(), y.to_string()],
+            clips,
+        }
+    }
+
+    // Every clip with its weight for the current parameters. The weights
+    // add up to one; clips left out have none.
+    pub fn weights(&self, parameters: &HashMap<String, Parameter>) -> Vec<(&Arc<AnimationClip>, f32)> {
+        let float = |name: &str| match parameters.get(name) {
+            Some(Parameter::Float(value)) => *value,
+            _ => 0.0,
+        };
+        match self {
+            Motion::Clip(clip) => vec![(clip, 1.0)],
+            Motion::Blend1d { parameter, clips } => {
+                let value = float(parameter);
+                let after = clips.partition_point(|(position, _)| *position <= value);
+                match (after.checked_sub(1), clips.get(after)) {
+                    (Some(before), Some((end, clip))) => {
+                        let (start, previous) = &clips[before];
+                        let t = (value - start) / (end - start);
+                        vec![(previous, 1.0 - t), (clip, t)]
+                    }
+                    (Some(before), None) => vec![(&clips[before].1, 1.0)],
+                    (None, Some((_, clip))) => vec![(clip, 1.0)],
+                    (None, None) => Vec::new(),
+                }
+            }
+            Motion::Blend2d { parameters, clips } => {
+                let point = uv::Vec2::new(float(&parameters[0]), float(&parameters[1]));
+                let mut weights: Vec<f32> = clips
+                    .iter()
+                    .map(|(position, _)| {
+                        clips
+                            .iter()
+                            .filter(|(other, _)| other != position)
+                            .map(|(other, _)| {
+                                let band = *other - *position;
+                                (1.0 - (point - *position).dot(band) / band.mag_sq()).clamp(0.0, 1.0)
+                            })
+                            .fold(1.0, f32::min)
+                    })
+                    .collect();
+                let total: f32 = weights.iter().sum();
+                if total > 0.0 {
+                    weights.iter_mut().for_each(|weight| *weight /= total);
+                } else if let Some(nearest) = (0..clips.len())
+                    .min_by(|&a, &b| (clips[a].0 - point).mag_sq().total_cmp(&(clips[b].0 - point).mag_sq()))
+                {
+                    weights[nearest] = 1.0;
+                }
+                clips
+                    .iter()
+                    .zip(weights)
+                    .filter(|(_, weight)| *weight > 0.0)
+                    .map(|((_, clip), weight)| (clip, weight))
+                    .collect()
+            }
+        }
+    }
+
+    // Blended clips are played in step, so this is the weighted average of
+    // their durations.
+    pub fn duration(&self, parameters: &HashMap<String, Parameter>) -> f32 {
+        self.weights(parameters)
+            .iter()
+            .map(|(clip, weight)| clip.duration() * weight)
+            .sum()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationState {
+    pub name: String,
+    pub motion: Motion,
+    pub speed: f32,
+    pub looping: bool,
+}
+
+impl AnimationState {
+    pub fn new(name: &str, motion: Motion) -> Self {
+        Self {
+            name: name.to_string(),
+            motion,
+            speed: 1.0,
+            looping: true,
+        }
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Transition {
+    // None to leave any state other than `to`.
+    pub from: Option<usize>,
+    pub to: usize,
+    // Crossfade length in seconds.
+    pub duration: f32,
+    // How far through `from` it has to be, in plays of the state, so 1.0 is
+    // the end of the first loop.
+    pub exit_time: Option<f32>,
+    // All must pass.
+    pub conditions: Vec<Condition>,
+}
+
+impl Transition {
+    pub fn new(from: Option<usize>, to: usize) -> Self {
+        Self {
+            from,
+            to,
+            duration: 0.0,
+            exit_time: None,
+            conditions: Vec::new(),
+        }
+    }
+
+    pub fn with_duration(mut self, duration: f32) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn with_exit_time(mut self, exit_time: f32) -> Self {
+        self.exit_time = Some(exit_time);
+        self
+    }
+
+    pub fn with_condition(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerBlend {
+    // Replaces the layers below.
+    Override,
+    // Adds its motion's change from the motion's first frame on top of the
+    // layers below, e.g. breathing or a flinch over any pose.
+    Additive,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationLayer {
+    pub name: String,
+    pub states: Vec<AnimationState>,
+    // Checked in order; the first that can be taken is.
+    pub transitions: Vec<Transition>,
+    pub initial: usize,
+    pub weight: f32,
+    pub blend: LayerBlend,
+    // Weight per joint, in skeleton order; joints past its end get none.
+    // Without a mask the layer covers the whole skeleton.
+    pub mask: Option<Vec<f32>>,
+}
+
+impl AnimationLayer {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            states: Vec::new(),
+            transitions: Vec::new(),
+            initial: 0,
+            weight: 1.0,
+            blend: LayerBlend::Override,
+            mask: None,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: LayerBlend) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: Vec<f32>) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    // Returns the state's index, for transitions. The first state added is
+    // where the layer starts.
+    pub fn add_state(&mut self, state: AnimationState) -> usize {
+        self.states.push(state);
+        self.states.len() - 1
+    }
+
+    pub fn add_transition(&mut self, transition: Transition) {
+        self.transitions.push(transition);
+    }
+
+    pub fn find_state(&self, name: &str) -> Option<usize> {
+        self.states.iter().position(|state| state.name == name)
+    }
+}
+
+// Full weight for the named joints and everything below them, e.g. the
+// spine for an upper-body layer.
+pub fn joint_mask(skeleton: &Skeleton, joints: &[&str]) -> Result<Vec<f32>> {
+    let mut mask = vec![0.0; skeleton.joint_count()];
+    for name in joints {
+        let joint = skeleton.find(name).with_context(|| format!("no joint '{}'", name))?;
+        for j in skeleton.descendants(joint) {
+            mask[j] = 1.0;
+        }
+    }
+    Ok(mask)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AnimationGraph {
+    // Names and starting values of the parameters transitions and blend
+    // spaces read.
+    pub parameters: HashMap<String, Parameter>,
+    // Bottom first.
+    pub layers: Vec<AnimationLayer>,
+}
+
+impl AnimationGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_parameter(mut self, name: &str, value: Parameter) -> Self {
+        self.parameters.insert(name.to_string(), value);
+        self
+    }
+
+    pub fn with_layer(mut self, layer: AnimationLayer) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    // Reads a graph from JSON, playing `clips` by name and masking joints
+    // of `skeleton`. It looks like:
+    //
+    //   {
+    //     "parameters": {"speed": 0.0, "grounded": true, "jump": "trigger"},
+    //     "events": [{"clip": "walk", "time": 0.4, "name": "footstep"}],
+    //     "layers": [{
+    //       "name": "base",
+    //       "states": [
+    //         {"name": "idle", "clip": "idle"},
+    //         {"name": "move", "blend1d": {"parameter": "speed",
+    //           "clips": [[0, "idle"], [2, "walk"], [6, "run"]]}},
+    //         {"name": "jump", "clip": "jump", "loop": false}
+    //       ],
+    //       "transitions": [
+    //         {"from": "idle", "to": "move", "duration": 0.2,
+    //           "conditions": [{"parameter": "speed", "greater": 0.1}]},
+    //         {"from": "*", "to": "jump", "duration": 0.1,
+    //           "conditions": [{"trigger": "jump"}]},
+    //         {"from": "jump", "to": "idle", "duration": 0.2, "exit_time": 0.9}
+    //       ]
+    //     }, {
+    //       "name": "aim", "blend": "override", "mask": ["spine"],
+    //       "states": [{"name": "aim", "blend2d": {"parameters": ["yaw", "pitch"],
+    //         "clips": [[0, 0, "aim_center"], [1, 0, "aim_right"]]}}]
+    //     }]
+    //   }
+    //
+    // States also take "speed"; layers take "weight", "blend" ("override"
+    // or "additive") and "initial". Conditions can test "less" and
+    // "equals" too. Events are added to the clips the graph plays.
+    pub fn from_json(text: &str, clips: &[AnimationClip], skeleton: &Skeleton) -> Result<Self> {
+        let json: Value = serde_json::from_str(text).context("animation graph is not valid JSON")?;
+        let mut graph = AnimationGraph::new();
+        if let Some(parameters) = json.get("parameters").and_then(Value::as_object) {
+            for (name, value) in parameters {
+                let parameter = match value {
+                    Value::Number(value) => Parameter::Float(value.as_f64().unwrap_or_default() as f32),
+                    Value::Bool(value) => Parameter::Bool(*value),
+                    Value::String(kind) if kind == "trigger" => Parameter::Trigger(false),
+                    _ => bail!("parameter '{}' must be a number, a bool or \"trigger\"", name),
+                };
+                graph.parameters.insert(name.clone(), parameter);
+            }
+        }
+
+        let mut library: HashMap<&str, AnimationClip> =
+            clips.iter().map(|clip| (clip.name.as_str(), clip.clone())).collect();
+        for event in items(&json, "events") {
+            let clip = event.get("clip").and_then(Value::as_str).context("event has no clip")?;
+            let time = event.get("time").and_then(as_f32).context("event has no time")?;
+            let name = event.get("name").and_then(Value::as_str).context("event has no name")?;
+            library
+                .get_mut(clip)
+                .with_context(|| format!("event '{}' is on unknown clip '{}'", name, clip))?
+                .add_event(time, name);
+        }
+        let library: HashMap<&str, Arc<AnimationClip>> =
+            library.into_iter().map(|(name, clip)| (name, Arc::new(clip))).collect();
+
+        for (i, layer) in items(&json, "layers").iter().enumerate() {
+            let name = layer.get("name").and_then(Value::as_str).map_or_else(|| format!("layer{}", i), str::to_string);
+            let parsed = graph
+                .parse_layer(layer, &name, &library, skeleton)
+                .with_context(|| format!("layer '{}'", name))?;
+            graph.layers.push(parsed);
+        }
+        ensure!(!graph.layers.is_empty(), "animation graph has no layers");
+        Ok(graph)
+    }
+
+    fn parse_layer(
+        &self,
+        json: &Value,
+        name: &str,
+        library: &HashMap<&str, Arc<AnimationClip>>,
+        skeleton: &Skeleton,
+    ) -> Result<AnimationLayer> {
+        let mut layer = AnimationLayer::new(name);
+        if let Some(weight) = json.get("weight").and_then(as_f32) {
+            layer.weight = weight;
+        }
+        layer.blend = match json.get("blend").and_then(Value::as_str) {
+            None | Some("override") => LayerBlend::Override,
+            Some("additive") => LayerBlend::Additive,
+            Some(other) => bail!("unknown blend '{}'", other),
+        };
+        if let Some(mask) = json.get("mask") {
+            let joints = mask
+                .as_array()
+                .and_then(|joints| joints.iter().map(Value::as_str).collect::<Option<Vec<_>>>())
+                .context("mask must be a list of joint names")?;
+            layer.mask = Some(joint_mask(skeleton, &joints)?);
+        }
+
+        for state in items(json, "states") {
+            let state_name = state.get("name").and_then(Value::as_str).context("state has no name")?;
+            ensure!(layer.find_state(state_name).is_none(), "two states are called '{}'", state_name);
+            let motion = self
+                .parse_motion(state, library)
+                .with_context(|| format!("state '{}'", state_name))?;
+            let mut parsed = AnimationState::new(state_name, motion);
+            if let Some(speed) = state.get("speed").and_then(as_f32) {
+                parsed.speed = speed;
+            }
+            if let Some(looping) = state.get("loop").and_then(Value::as_bool) {
+                parsed.looping = looping;
+            }
+            layer.add_state(parsed);
+        }
+        ensure!(!layer.states.is_empty(), "layer has no states");
+        if let Some(initial) = json.get("initial").and_then(Value::as_str) {
+            layer.initial = layer.find_state(initial).with_context(|| format!("no state '{}'", initial))?;
+        }
+        let find = |name: &str| layer.find_state(name).with_context(|| format!("no state '{}'", name));
+
+        let mut transitions = Vec::new();
+        for transition in items(json, "transitions") {
+            let field = |key: &str| transition.get(key).and_then(Value::as_str);
+            let from = match field("from").context("transition has no 'from'")? {
+                "*" => None,
+                from => Some(find(from)?),
+            };
+            let mut parsed = Transition::new(from, find(field("to").context("transition has no 'to'")?)?);
+            if let Some(duration) = transition.get("duration").and_then(as_f32) {
+                ensure!(duration >= 0.0, "transition durations can't be negative");
+                parsed.duration = duration;
+            }
+            parsed.exit_time = transition.get("exit_time").and_then(as_f32);
+            for condition in items(transition, "conditions") {
+                parsed.conditions.push(self.parse_condition(condition)?);
+            }
+            transitions.push(parsed);
+        }
+        layer.transitions = transitions;
+        Ok(layer)
+    }
+
+    fn parse_motion(&self, state: &Value, library: &HashMap<&str, Arc<AnimationClip>>) -> Result<Motion> {
+        let clip = |name: Option<&Value>| -> Result<Arc<AnimationClip>> {
+            let name = name.and_then(Value::as_str).context("expected a clip name")?;
+            library.get(name).cloned().with_context(|| format!("no clip '{}'", name))
+        };
+        if let Some(name) = state.get("clip") {
+            return Ok(Motion::Clip(clip(Some(name))?));
+        }
+        if let Some(blend) = state.get("blend1d") {
+            let parameter = blend.get("parameter").and_then(Value::as_str).context("blend1d has no parameter")?;
+            self.expect_float(parameter)?;
+            let clips = items(blend, "clips")
+                .iter()
+                .map(|point| {
+                    let point = point.as_array().filter(|point| point.len() == 2).context("expected [position, clip]")?;
+                    Ok((as_f32(&point[0]).context("expected a position")?, clip(point.get(1))?))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            ensure!(!clips.is_empty(), "blend1d has no clips");
+            return Ok(Motion::blend_1d(parameter, clips));
+        }
+        if let Some(blend) = state.get("blend2d") {
+            let parameters = blend
+                .get("parameters")
+                .and_then(Value::as_array)
+                .and_then(|names| names.iter().map(Value::as_str).collect::<Option<Vec<_>>>())
+                .filter(|names| names.len() == 2)
+                .context("blend2d needs two parameters")?;
+            for parameter in &parameters {
+                self.expect_float(parameter)?;
+            }
+            let clips = items(blend, "clips")
+                .iter()
+                .map(|point| {
+                    let point = point.as_array().filter(|point| point.len() == 3).context("expected [x, y, clip]")?;
+                    let x = as_f32(&point[0]).context("expected a position")?;
+                    let y = as_f32(&point[1]).context("expected a position")?;
+                    Ok((uv::Vec2::new(x, y), clip(point.get(2))?))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            ensure!(!clips.is_empty(), "blend2d has no clips");
+            return Ok(Motion::blend_2d(parameters[0], parameters[1], clips));
+        }
+        bail!("state needs a clip, blend1d or blend2d")
+    }
+
+    fn parse_condition(&self, json: &Value) -> Result<Condition> {
+        if let Some(trigger) = json.get("trigger").and_then(Value::as_str) {
+            ensure!(
+                matches!(self.parameters.get(trigger), Some(Parameter::Trigger(_))),
+                "'{}' is not a trigger",
+                trigger
+            );
+            return Ok(Condition::Trigger(trigger.to_string()));
+        }
+        let parameter = json.get("parameter").and_then(Value::as_str).context("condition has no parameter")?;
+        if let Some(value) = json.get("greater").and_then(as_f32) {
+            self.expect_float(parameter)?;
+            return Ok(Condition::Greater(parameter.to_string(), value));
+        }
+        if let Some(value) = json.get("less").and_then(as_f32) {
+            self.expect_float(parameter)?;
+            return Ok(Condition::Less(parameter.to_string(), value));
+        }
+        if let Some(value) = json.get("equals").and_then(Value::as_bool) {
+            ensure!(
+                matches!(self.parameters.get(parameter), Some(Parameter::Bool(_))),
+                "'{}' is not a bool",
+                parameter
+            );
+            return Ok(Condition::Equals(parameter.to_string(), value));
+        }
+        bail!("condition on '{}' needs greater, less or equals", parameter)
+    }
+
+    fn expect_float(&self, parameter: &str) -> Result<()> {
+        ensure!(
+            matches!(self.parameters.get(parameter), Some(Parameter::Float(_))),
+            "'{}' is not a float parameter",
+            parameter
+        );
+        Ok(())
+    }
+}
+
+// The array under `key`, empty if there isn't one.
+fn items<'a>(json: &'a Value, key: &str) -> &'a [Value] {
+    json.get(key).and_then(Value::as_array).map_or(&[], Vec::as_slice)
+}
+
+fn as_f32(json: &Value) -> Option<f32> {
+    json.as_f64().map(|value| value as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::skeleton::Joint;
+
+    // The example from `from_json`, with the aim parameters it leaves out.
+    const GRAPH: &str = r#"{
+        "parameters": {"speed": 0.0, "grounded": true, "jump": "trigger", "yaw": 0, "pitch": 0},
+        "events": [{"clip": "walk", "time": 0.4, "name": "footstep"}],
+        "layers": [{
+            "name": "base",
+            "states": [
+                {"name": "idle", "clip": "idle"},
+                {"name": "move", "blend1d": {"parameter": "speed",
+                    "clips": [[0, "idle"], [2, "walk"], [6, "run"]]}},
+                {"name": "jump", "clip": "jump", "loop": false, "speed": 1.5}
+            ],
+            "transitions": [
+                {"from": "idle", "to": "move", "duration": 0.2,
+                    "conditions": [{"parameter": "speed", "greater": 0.1}]},
+                {"from": "*", "to": "jump", "duration": 0.1,
+                    "conditions": [{"trigger": "jump"}]},
+                {"from": "jump", "to": "idle", "duration": 0.2, "exit_time": 0.9}
+            ]
+        }, {
+            "name": "aim", "blend": "additive", "mask": ["spine"], "weight": 0.5,
+            "states": [{"name": "aim", "blend2d": {"parameters": ["yaw", "pitch"],
+                "clips": [[0, 0, "aim_center"], [1, 0, "aim_right"]]}}]
+        }]
+    }"#;
+
+    fn clips() -> Vec<AnimationClip> {
+        ["idle", "walk", "run", "jump", "aim_center", "aim_right"]
+            .into_iter()
+            .map(|name| AnimationClip::new(name, Vec::new()))
+            .collect()
+    }
+
+    fn skeleton() -> Skeleton {
+        Skeleton::new(vec![Joint::new("hips", None), Joint::new("spine", Some(0))]).unwrap()
+    }
+
+    #[test]
+    fn reads_graphs_from_json() {
+        let graph = AnimationGraph::from_json(GRAPH, &clips(), &skeleton()).unwrap();
+        assert_eq!(graph.parameters["speed"], Parameter::Float(0.0));
+        assert_eq!(graph.parameters["grounded"], Parameter::Bool(true));
+        assert_eq!(graph.parameters["jump"], Parameter::Trigger(false));
+
+        let base = &graph.layers[0];
+        let names: Vec<&str> = base.states.iter().map(|state| state.name.as_str()).collect();
+        assert_eq!(names, ["idle", "move", "jump"]);
+        assert!(!base.states[2].looping);
+        assert_eq!(base.states[2].speed, 1.5);
+        let Motion::Blend1d { parameter, clips } = &base.states[1].motion else {
+            panic!("move should be a blend1d");
+        };
+        assert_eq!(parameter, "speed");
+        let walk = &clips[1];
+        assert_eq!((walk.0, walk.1.name.as_str()), (2.0, "walk"));
+        assert_eq!(walk.1.events().len(), 1);
+        assert_eq!(base.transitions.len(), 3);
+        assert_eq!(base.transitions[0].conditions, [Condition::Greater("speed".into(), 0.1)]);
+        assert_eq!((base.transitions[1].from, base.transitions[1].to), (None, 2));
+        assert_eq!(base.transitions[2].exit_time, Some(0.9));
+
+        let aim = &graph.layers[1];
+        assert_eq!(aim.blend, LayerBlend::Additive);
+        assert_eq!(aim.weight, 0.5);
+        assert_eq!(aim.mask, Some(vec![0.0, 1.0]));
+    }
+
+    #[test]
+    fn rejects_bad_graphs() {
+        let (clips, skeleton) = (clips(), skeleton());
+        let parse = |text: &str| AnimationGraph::from_json(text, &clips, &skeleton);
+        assert!(parse("{\"layers\": [").is_err());
+        assert!(parse(r#"{"layers": []}"#).is_err());
+        assert!(parse(r#"{"layers": [{"states": [{"name": "a", "clip": "fly"}]}]}"#).is_err());
+        assert!(parse(&GRAPH.replace(r#""yaw": 0, "#, "")).is_err());
+        assert!(parse(&GRAPH.replace("\"time\": 0.4, ", "")).is_err());
+    }
+
+    #[test]
+    fn blend_weights_sum_to_one() {
+        let clip = |name: &str| Arc::new(AnimationClip::new(name, Vec::new()));
+        let line = Motion::blend_1d("speed", vec![(2.0, clip("walk")), (0.0, clip("idle")), (6.0, clip("run"))]);
+        let plane = Motion::blend_2d(
+            "x",
+            "y",
+            vec![
+                (uv::Vec2::zero(), clip("center")),
+                (uv::Vec2::unit_x(), clip("right")),
+                (-uv::Vec2::unit_x(), clip("left")),
+                (uv::Vec2::unit_y(), clip("forward")),
+            ],
+        );
+        let weights = |motion: &Motion, parameters: &[(&str, f32)]| {
+            let parameters = parameters
+                .iter()
+                .map(|(name, value)| (name.to_string(), Parameter::Float(*value)))
+                .collect();
+            motion
+                .weights(&parameters)
+                .into_iter()
+                .map(|(clip, weight)| (clip.name.clone(), weight))
+                .collect::<Vec<_>>()
+        };
+
+        for speed in [-1.0, 0.0, 0.5, 2.0, 3.0, 6.0, 9.0] {
+            let total: f32 = weights(&line, &[("speed", speed)]).iter().map(|(_, weight)| weight).sum();
+            assert!((total - 1.0).abs() < 1e-5, "{} at speed {}", total, speed);
+        }
+        assert_eq!(weights(&line, &[("speed", 3.0)]), [("walk".into(), 0.75), ("run".into(), 0.25)]);
+
+        for x in [-2.0, -0.5, 0.0, 0.3, 1.0, 1.5] {
+            for y in [-1.0, 0.0, 0.4, 1.0, 2.0] {
+                let found = weights(&plane, &[("x", x), ("y", y)]);
+                let total: f32 = found.iter().map(|(_, weight)| weight).sum();
+                assert!((total - 1.0).abs() < 1e-5, "{} at ({}, {}): {:?}", total, x, y, found);
+            }
+        }
+        assert_eq!(weights(&plane, &[("x", 1.0), ("y", 0.0)]), [("right".into(), 1.0)]);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod skeleton;
+pub mod clip;
+pub mod player;
+pub mod gltf;
+pub mod graph;
+pub mod animator;
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use super::clip::AnimationClip;
+use super::skeleton::Pose;
+
+// Plays one clip: keeps its time, wrapping or stopping at the end.
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    clip: Arc<AnimationClip>,
+    time: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub paused: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new(clip: Arc<AnimationClip>) -> Self {
+        Self {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+            paused: false,
+        }
+    }
+
+    // Negative speeds play backwards.
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    pub fn clip(&self) -> &Arc<AnimationClip> {
+        &self.clip
+    }
+
+    // Starts `clip` from the beginning.
+    pub fn play(&mut self, clip: Arc<AnimationClip>) {
+        self.clip = clip;
+        self.time = 0.0;
+        self.paused = false;
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = self.wrap(time);
+    }
+
+    // Whether a clip that doesn't loop has reached its end, or its start
+    // when playing backwards.
+    pub fn is_finished(&self) -> bool {
+        !self.looping
+            && ((self.speed > 0.0 && self.time >= self.clip.duration())
+                || (self.speed < 0.0 && self.time <= 0.0))
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        if !self.paused {
+            self.time = self.wrap(self.time + dt * self.speed);
+        }
+    }
+
+    pub fn sample(&self, pose: &mut Pose) {
+        self.clip.sample(self.time, pose);
+    }
+
+    fn wrap(&self, time: f32) -> f32 {
+        let duration = self.clip.duration();
+        if self.looping && duration > 0.0 {
+            time.rem_euclid(duration)
+        } else {
+            time.clamp(0.0, duration)
+        }
+    }
+}
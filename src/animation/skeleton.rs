@@ -0,0 +1,276 @@
+// Joint hierarchies and the poses they are animated through. A pose holds
+// every joint's transform relative to its parent; the skinning shader wants
+// each joint's model-space transform times its inverse bind matrix.
+use anyhow::*;
+use ultraviolet as uv;
+
+// Translation, rotation and scale, applied scale first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JointTransform {
+    pub translation: uv::Vec3,
+    pub rotation: uv::Rotor3,
+    pub scale: uv::Vec3,
+}
+
+impl Default for JointTransform {
+    fn default() -> Self {
+        Self {
+            translation: uv::Vec3::zero(),
+            rotation: uv::Rotor3::identity(),
+            scale: uv::Vec3::one(),
+        }
+    }
+}
+
+impl JointTransform {
+    // Part way from `self` to `other`, turning the short way round.
+    pub fn lerp(&self, other: &JointTransform, t: f32) -> Self {
+        Self {
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: nlerp(self.rotation, other.rotation, t),
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+
+    // How `self` differs from `reference`, in `reference`'s own space; see
+    // `add`.
+    pub fn difference(&self, reference: &JointTransform) -> Self {
+        let ratio = |scale: f32, reference: f32| if reference != 0.0 { scale / reference } else { 1.0 };
+        Self {
+            translation: self.translation - reference.translation,
+            rotation: (reference.rotation.reversed() * self.rotation).normalized(),
+            scale: uv::Vec3::new(
+                ratio(self.scale.x, reference.scale.x),
+                ratio(self.scale.y, reference.scale.y),
+                ratio(self.scale.z, reference.scale.z),
+            ),
+        }
+    }
+
+    // Applies a `difference` on top of `self`, `weight` of the way.
+    pub fn add(&self, difference: &JointTransform, weight: f32) -> Self {
+        let partial = JointTransform::default().lerp(difference, weight);
+        Self {
+            translation: self.translation + partial.translation,
+            rotation: (self.rotation * partial.rotation).normalized(),
+            scale: self.scale * partial.scale,
+        }
+    }
+
+    pub fn to_matrix(&self) -> uv::Mat4 {
+        uv::Mat4::from_translation(self.translation)
+            * self.rotation.into_matrix().into_homogeneous()
+            * uv::Mat4::from_nonuniform_scale(self.scale)
+    }
+
+    // Splits an affine matrix back up. Shear can't be represented and is
+    // lost; a mirroring matrix gets a negative x scale.
+    pub fn from_matrix(matrix: uv::Mat4) -> Self {
+        let basis = matrix.truncate();
+        let mut scale = uv::Vec3::new(basis.cols[0].mag(), basis.cols[1].mag(), basis.cols[2].mag());
+        if basis.determinant() < 0.0 {
+            scale.x = -scale.x;
+        }
+        let unscale = |axis: uv::Vec3, length: f32| if length != 0.0 { axis / length } else { axis };
+        let rotation = uv::Mat3::new(
+            unscale(basis.cols[0], scale.x),
+            unscale(basis.cols[1], scale.y),
+            unscale(basis.cols[2], scale.z),
+        )
+        .into_rotor3()
+        .normalized();
+        Self {
+            translation: matrix.extract_translation(),
+            rotation,
+            scale,
+        }
+    }
+}
+
+// Good enough for blending poses, and cheaper than a true slerp.
+fn nlerp(from: uv::Rotor3, mut to: uv::Rotor3, t: f32) -> uv::Rotor3 {
+    if from.dot(to) < 0.0 {
+        to *= -1.0;
+    }
+    (from * (1.0 - t) + to * t).normalized()
+}
+
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub name: String,
+    pub parent: Option<usize>,
+    // Where the joint sits when nothing animates it.
+    pub rest: JointTransform,
+    // From model space into the joint's space at bind time.
+    pub inverse_bind: uv::Mat4,
+}
+
+impl Joint {
+    pub fn new(name: &str, parent: Option<usize>) -> Self {
+        Self {
+            name: name.to_string(),
+            parent,
+            rest: JointTransform::default(),
+            inverse_bind: uv::Mat4::identity(),
+        }
+    }
+
+    pub fn with_rest(mut self, rest: JointTransform) -> Self {
+        self.rest = rest;
+        self
+    }
+
+    pub fn with_inverse_bind(mut self, inverse_bind: uv::Mat4) -> Self {
+        self.inverse_bind = inverse_bind;
+        self
+    }
+}
+
+// Joints are kept in the order they were given, which is the order vertex
+// joint indices refer to; parents don't have to come first.
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    joints: Vec<Joint>,
+    // Every joint after its parent, for building model-space transforms.
+    order: Vec<usize>,
+    // Applied above the root joints, e.g. the transform of the node a
+    // glTF skeleton hangs from.
+    pub root: uv::Mat4,
+}
+
+impl Skeleton {
+    // Fails when a parent is out of range or the parents form a cycle.
+    pub fn new(joints: Vec<Joint>) -> Result<Self> {
+        let mut order = Vec::with_capacity(joints.len());
+        // 0 unvisited, 1 on the current path, 2 placed.
+        let mut state = vec![0u8; joints.len()];
+        for start in 0..joints.len() {
+            let mut path = Vec::new();
+            let mut joint = Some(start);
+            while let Some(j) = joint {
+                match state[j] {
+                    0 => {
+                        state[j] = 1;
+                        path.push(j);
+                        joint = joints[j].parent;
+                        if let Some(parent) = joint {
+                            ensure!(parent < joints.len(), "joint '{}' has no parent {}", joints[j].name, parent);
+                        }
+                    }
+                    1 => bail!("joint '{}' is its own ancestor", joints[j].name),
+                    _ => break,
+                }
+            }
+            for &j in path.iter().rev() {
+                state[j] = 2;
+                order.push(j);
+            }
+        }
+        Ok(Self {
+            joints,
+            order,
+            root: uv::Mat4::identity(),
+        })
+    }
+
+    pub fn with_root(mut self, root: uv::Mat4) -> Self {
+        self.root = root;
+        self
+    }
+
+    pub fn joints(&self) -> &[Joint] {
+        &self.joints
+    }
+
+    pub fn joint_count(&self) -> usize {
+        self.joints.len()
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.name == name)
+    }
+
+    // `joint` and every joint below it.
+    pub fn descendants(&self, joint: usize) -> Vec<usize> {
+        let mut below = vec![false; self.joints.len()];
+        let mut descendants = Vec::new();
+        for &j in &self.order {
+            below[j] = j == joint || self.joints[j].parent.is_some_and(|parent| below[parent]);
+            if below[j] {
+                descendants.push(j);
+            }
+        }
+        descendants
+    }
+
+    pub fn rest_pose(&self) -> Pose {
+        Pose {
+            joints: self.joints.iter().map(|joint| joint.rest).collect(),
+        }
+    }
+}
+
+// A transform per joint, relative to its parent, in skeleton order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub joints: Vec<JointTransform>,
+}
+
+impl Pose {
+    // Moves every joint `weight` of the way to `other`. A mask scales the
+    // weight per joint, e.g. zero for the legs to leave them alone.
+    pub fn blend(&mut self, other: &Pose, weight: f32, mask: Option<&[f32]>) {
+        for (j, (joint, target)) in self.joints.iter_mut().zip(&other.joints).enumerate() {
+            let weight = weight * mask.map_or(1.0, |mask| mask.get(j).copied().unwrap_or(0.0));
+            if weight > 0.0 {
+                *joint = joint.lerp(target, weight.min(1.0));
+            }
+        }
+    }
+
+    // Each joint's change from `reference`, for layering with `add`.
+    pub fn difference(&self, reference: &Pose) -> Pose {
+        Pose {
+            joints: self
+                .joints
+                .iter()
+                .zip(&reference.joints)
+                .map(|(joint, reference)| joint.difference(reference))
+                .collect(),
+        }
+    }
+
+    // Layers a `difference` on top, masked like `blend`.
+    pub fn add(&mut self, difference: &Pose, weight: f32, mask: Option<&[f32]>) {
+        for (j, (joint, difference)) in self.joints.iter_mut().zip(&difference.joints).enumerate() {
+            let weight = weight * mask.map_or(1.0, |mask| mask.get(j).copied().unwrap_or(0.0));
+            if weight > 0.0 {
+                *joint = joint.add(difference, weight);
+            }
+        }
+    }
+
+    // Each joint's transform in model space.
+    pub fn model_matrices(&self, skeleton: &Skeleton) -> Vec<uv::Mat4> {
+        let mut matrices = vec![uv::Mat4::identity(); skeleton.joint_count()];
+        for &j in &skeleton.order {
+            let parent = match skeleton.joints[j].parent {
+                Some(parent) => matrices[parent],
+                None => skeleton.root,
+            };
+            let local = self.joints.get(j).unwrap_or(&skeleton.joints[j].rest);
+            matrices[j] = parent * local.to_matrix();
+        }
+        matrices
+    }
+
+    // What the skinning shader reads: model-space transforms times inverse
+    // bind matrices, so the bind pose gives identities.
+    pub fn joint_matrices(&self, skeleton: &Skeleton) -> Vec<uv::Mat4> {
+        let mut matrices = self.model_matrices(skeleton);
+        for (matrix, joint) in matrices.iter_mut().zip(&skeleton.joints) {
+            *matrix = *matrix * joint.inverse_bind;
+        }
+        matrices
+    }
+}
@@ -0,0 +1,363 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+// Layout of a packed archive, integers little-endian:
+//   header  "ONPK", version u32, entry count u32, index offset u64
+//   blobs   one per file, deflated unless that made it bigger
+//   index   per file: path length u16, '/'-separated UTF-8 path, blob offset
+//           u64, blob size u64, file size u64, CRC-32 of the file u32,
+//           compression u8
+const MAGIC: &[u8; 4] = b"ONPK";
+const VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 20;
+// An index entry with an empty path.
+const MIN_ENTRY_SIZE: usize = 2 + 8 + 8 + 8 + 4 + 1;
+const MAX_DEFLATE_RATIO: u64 = 1032;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ArchiveCompression {
+    Stored,
+    Deflate,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArchiveEntry {
+    pub path: String,
+    pub offset: u64,
+    pub stored_size: u64,
+    pub size: u64,
+    pub crc32: u32,
+    pub compression: ArchiveCompression,
+}
+
+// Collects files and writes them into an archive `Archive::open` can read.
+#[derive(Default)]
+pub struct ArchiveBuilder {
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl ArchiveBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `path` is the file's name inside the archive; a later file with the
+    // same path replaces the earlier one.
+    pub fn add(&mut self, path: &str, bytes: Vec<u8>) {
+        let path = path.trim_start_matches('/').to_string();
+        self.files.retain(|(existing, _)| *existing != path);
+        self.files.push((path, bytes));
+    }
+
+    // Adds every file under `dir`, named relative to it.
+    pub fn add_dir(&mut self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(current) = pending.pop() {
+            for entry in fs::read_dir(&current)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                let name = path
+                    .strip_prefix(dir)
+                    .expect("walked from dir")
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                self.add(&name, fs::read(&path)?);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    // Files are written sorted by path so the same input always produces
+    // the same archive.
+    pub fn write(mut self, out: &mut impl Write) -> io::Result<Vec<ArchiveEntry>> {
+        self.files.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut blobs = Vec::new();
+        let mut entries = Vec::with_capacity(self.files.len());
+        for (path, bytes) in &self.files {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(bytes)?;
+            let deflated = encoder.finish()?;
+            let (compression, blob) = if deflated.len() < bytes.len() {
+                (ArchiveCompression::Deflate, deflated.as_slice())
+            } else {
+                (ArchiveCompression::Stored, bytes.as_slice())
+            };
+            entries.push(ArchiveEntry {
+                path: path.clone(),
+                offset: HEADER_SIZE + blobs.len() as u64,
+                stored_size: blob.len() as u64,
+                size: bytes.len() as u64,
+                crc32: crc32fast::hash(bytes),
+                compression,
+            });
+            blobs.extend_from_slice(blob);
+        }
+
+        let mut index = Vec::new();
+        for entry in &entries {
+            let path_len = u16::try_from(entry.path.len())
+                .map_err(|_| invalid_data(format!("path too long: {}", entry.path)))?;
+            index.extend_from_slice(&path_len.to_le_bytes());
+            index.extend_from_slice(entry.path.as_bytes());
+            index.extend_from_slice(&entry.offset.to_le_bytes());
+            index.extend_from_slice(&entry.stored_size.to_le_bytes());
+            index.extend_from_slice(&entry.size.to_le_bytes());
+            index.extend_from_slice(&entry.crc32.to_le_bytes());
+            index.push(match entry.compression {
+                ArchiveCompression::Stored => 0,
+                ArchiveCompression::Deflate => 1,
+            });
+        }
+
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(entries.len() as u32).to_le_bytes())?;
+        out.write_all(&(HEADER_SIZE + blobs.len() as u64).to_le_bytes())?;
+        out.write_all(&blobs)?;
+        out.write_all(&index)?;
+        Ok(entries)
+    }
+}
+
+// An archive opened for reading. Only the index is kept in memory; files are
+// read from disk on demand and checked against their CRC-32.
+pub struct Archive {
+    path: PathBuf,
+    file: Mutex<File>,
+    entries: HashMap<String, ArchiveEntry>,
+}
+
+impl Archive {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = File::open(&path)?;
+
+        let mut header = [0u8; HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        if &header[0..4] != MAGIC {
+            return Err(invalid_data(format!("{} is not an archive", path.display())));
+        }
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "{} has unsupported archive version {}",
+                path.display(),
+                version
+            )));
+        }
+        let count = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        let index_offset = u64::from_le_bytes(header[12..20].try_into().unwrap());
+        let file_size = file.metadata()?.len();
+        if !(HEADER_SIZE..=file_size).contains(&index_offset) {
+            return Err(invalid_data(format!("{} has its index out of bounds", path.display())));
+        }
+
+        let mut index = Vec::new();
+        file.seek(SeekFrom::Start(index_offset))?;
+        file.read_to_end(&mut index)?;
+        let mut reader = IndexReader { bytes: &index, pos: 0 };
+        // The count is only trusted as far as the index could hold it.
+        let mut entries = HashMap::with_capacity((count as usize).min(index.len() / MIN_ENTRY_SIZE));
+        for _ in 0..count {
+            let path_len = u16::from_le_bytes(reader.take()?) as usize;
+            let entry_path = String::from_utf8(reader.bytes(path_len)?.to_vec())
+                .map_err(|_| invalid_data("archive path is not UTF-8".to_string()))?;
+            let entry = ArchiveEntry {
+                path: entry_path.clone(),
+                offset: u64::from_le_bytes(reader.take()?),
+                stored_size: u64::from_le_bytes(reader.take()?),
+                size: u64::from_le_bytes(reader.take()?),
+                crc32: u32::from_le_bytes(reader.take()?),
+                compression: match reader.take::<1>()?[0] {
+                    0 => ArchiveCompression::Stored,
+                    1 => ArchiveCompression::Deflate,
+                    other => return Err(invalid_data(format!("unknown compression {}", other))),
+                },
+            };
+            // Blobs sit between the header and the index. A deflated blob
+            // can't expand more than 1032 times, so a bigger size is as
+            // corrupt as a blob running past the index.
+            let blob_end = entry.offset.checked_add(entry.stored_size);
+            let size_fits = match entry.compression {
+                ArchiveCompression::Stored => entry.size == entry.stored_size,
+                ArchiveCompression::Deflate => entry.size <= entry.stored_size.saturating_mul(MAX_DEFLATE_RATIO),
+            };
+            if entry.offset < HEADER_SIZE || blob_end.is_none_or(|end| end > index_offset) || !size_fits {
+                return Err(invalid_data(format!(
+                    "{} in {} has an invalid offset or size",
+                    entry_path,
+                    path.display()
+                )));
+            }
+            entries.insert(entry_path, entry);
+        }
+
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+            entries,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &ArchiveEntry> {
+        self.entries.values()
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    // Ok(None) when the archive has no such file.
+    pub fn read(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        let Some(entry) = self.entries.get(path) else {
+            return Ok(None);
+        };
+        let mut blob = vec![0; entry.stored_size as usize];
+        {
+            let mut file = self.file.lock().unwrap();
+            file.seek(SeekFrom::Start(entry.offset))?;
+            file.read_exact(&mut blob)?;
+        }
+        let bytes = match entry.compression {
+            ArchiveCompression::Stored => blob,
+            ArchiveCompression::Deflate => {
+                // One byte more than expected is enough to tell the stream
+                // is too long without inflating all of it.
+                let mut bytes = Vec::with_capacity(entry.size as usize);
+                DeflateDecoder::new(blob.as_slice())
+                    .take(entry.size + 1)
+                    .read_to_end(&mut bytes)?;
+                bytes
+            }
+        };
+        if bytes.len() as u64 != entry.size {
+            return Err(invalid_data(format!(
+                "{} in {} is corrupt (expected {} bytes, got {})",
+                path,
+                self.path.display(),
+                entry.size,
+                bytes.len()
+            )));
+        }
+        if crc32fast::hash(&bytes) != entry.crc32 {
+            return Err(invalid_data(format!(
+                "{} in {} is corrupt (checksum mismatch)",
+                path,
+                self.path.display()
+            )));
+        }
+        Ok(Some(bytes))
+    }
+}
+
+struct IndexReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> IndexReader<'a> {
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid_data("archive index is truncated".to_string()))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An archive holding one deflated file, written to a temporary file
+    // after `corrupt` had a go at its bytes. `corrupt` gets the position of
+    // the file's index entry, just past its path.
+    fn open_with(name: &str, corrupt: impl FnOnce(&mut Vec<u8>, usize)) -> io::Result<Archive> {
+        let mut builder = ArchiveBuilder::new();
+        builder.add("a.txt", b"hello ".repeat(64));
+        let mut bytes = Vec::new();
+        let entries = builder.write(&mut bytes).unwrap();
+        assert_eq!(entries[0].compression, ArchiveCompression::Deflate);
+
+        let index = u64::from_le_bytes(bytes[12..20].try_into().unwrap()) as usize;
+        corrupt(&mut bytes, index + 2 + "a.txt".len());
+        let path = std::env::temp_dir().join(format!("onion-archive-{}-{}.pak", std::process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let archive = Archive::open(&path);
+        fs::remove_file(&path).unwrap();
+        archive
+    }
+
+    fn set_u64(bytes: &mut [u8], at: usize, value: u64) {
+        bytes[at..at + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn reads_back_files() {
+        let archive = open_with("intact", |_, _| {}).unwrap();
+        assert_eq!(archive.read("a.txt").unwrap(), Some(b"hello ".repeat(64)));
+        assert_eq!(archive.read("b.txt").unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_blobs_outside_the_archive() {
+        let error = open_with("offset", |bytes, entry| set_u64(bytes, entry, 1 << 40)).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let error = open_with("stored_size", |bytes, entry| set_u64(bytes, entry + 8, u64::MAX)).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_counts_the_index_cannot_hold() {
+        let error = open_with("count", |bytes, _| bytes[8..12].copy_from_slice(&u32::MAX.to_le_bytes()))
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("truncated"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_size_mismatches() {
+        for (name, size) in [("shorter", 383), ("longer", 385)] {
+            let archive = open_with(name, |bytes, entry| set_u64(bytes, entry + 16, size)).unwrap();
+            let error = archive.read("a.txt").unwrap_err();
+            assert!(error.to_string().contains("expected"), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use super::handle::{AssetEvent, AssetId, Handle};
+use super::server::AssetServer;
+use crate::graphics::texture::Texture;
+
+// Texture + sampler bind groups for texture assets, rebuilt when a texture
+// is reloaded and freed with it. Bind groups are cached by texture alone, so
+// keep one cache per layout.
+#[derive(Default)]
+pub struct TextureBindGroups {
+    bind_groups: HashMap<AssetId, wgpu::BindGroup>,
+}
+
+impl TextureBindGroups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // None until the texture has loaded.
+    pub fn get(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        assets: &AssetServer,
+        handle: &Handle<Texture>,
+    ) -> Option<&wgpu::BindGroup> {
+        let texture = assets.get(handle)?;
+        Some(self.bind_groups.entry(handle.id()).or_insert_with(|| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                    },
+                ],
+                label: handle.path().and_then(|path| path.to_str()),
+            })
+        }))
+    }
+
+    // Forgets bind groups of reloaded and removed textures. Call after
+    // `AssetServer::update`.
+    pub fn update(&mut self, assets: &AssetServer) {
+        for event in assets.events::<Texture>() {
+            if let AssetEvent::Reloaded(id) | AssetEvent::Removed(id) = event {
+                self.bind_groups.remove(id);
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    any::TypeId,
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::{mpsc::Sender, Arc},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AssetId(pub(crate) u64);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadState {
+    Loading,
+    Loaded,
+    // The error, including the asset's path.
+    Failed(String),
+}
+
+// What happened to an asset during the last `AssetServer::update`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AssetEvent {
+    Loaded(AssetId),
+    // Its file changed and the new version replaced it under the same
+    // handle. Anything built from the old version, like a bind group,
+    // should be rebuilt.
+    Reloaded(AssetId),
+    Failed(AssetId),
+    Removed(AssetId),
+}
+
+// Shared by every clone of a handle. Dropping the last one tells the server
+// to free the asset on its next update.
+pub(crate) struct HandleInner {
+    pub(crate) id: AssetId,
+    pub(crate) type_id: TypeId,
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) drops: Sender<(TypeId, AssetId)>,
+}
+
+impl Drop for HandleInner {
+    fn drop(&mut self) {
+        // The server may already be gone, then there is nothing to free.
+        let _ = self.drops.send((self.type_id, self.id));
+    }
+}
+
+// Reference counted handle to an asset of type `T` owned by an `AssetServer`.
+// The asset stays loaded while any clone of the handle is alive.
+pub struct Handle<T> {
+    pub(crate) inner: Arc<HandleInner>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub(crate) fn new(inner: Arc<HandleInner>) -> Self {
+        Self {
+            inner,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> AssetId {
+        self.inner.id
+    }
+
+    // None for assets added from memory.
+    pub fn path(&self) -> Option<&Path> {
+        self.inner.path.as_deref()
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone())
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("id", &self.id())
+            .field("path", &self.path())
+            .finish()
+    }
+}
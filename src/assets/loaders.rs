@@ -0,0 +1,153 @@
+use std::path::Path;
+
+use anyhow::*;
+
+use super::server::{AssetLoader, LoadContext};
+use crate::animation::gltf::Gltf;
+use crate::audio::sound::Sound;
+use crate::graphics::compressed::TextureData;
+use crate::graphics::objects::mesh::{Mesh, MeshData};
+use crate::graphics::shader::{self, Shader};
+use crate::graphics::texture::{Texture, TextureOptions};
+
+fn extension(path: &Path) -> &str {
+    path.extension().and_then(|ext| ext.to_str()).unwrap_or("")
+}
+
+fn label(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+// Images the `image` crate reads, plus KTX2 and DDS containers. Every
+// texture is loaded with the same `options`.
+#[derive(Default)]
+pub struct TextureAssetLoader {
+    pub options: TextureOptions,
+}
+
+pub enum DecodedTexture {
+    Image(image::DynamicImage),
+    Data(TextureData),
+}
+
+impl AssetLoader for TextureAssetLoader {
+    type Asset = Texture;
+    type Decoded = DecodedTexture;
+
+    fn decode(&self, bytes: Vec<u8>, _path: &Path) -> Result<DecodedTexture> {
+        if TextureData::is_container(&bytes) {
+            return Ok(DecodedTexture::Data(TextureData::from_bytes(&bytes)?));
+        }
+        Ok(DecodedTexture::Image(image::load_from_memory(&bytes)?))
+    }
+
+    fn finish(&self, decoded: DecodedTexture, path: &Path, ctx: &mut LoadContext) -> Result<Texture> {
+        let label = label(path);
+        match decoded {
+            DecodedTexture::Image(img) => {
+                ctx.textures
+                    .load_image(ctx.device, ctx.queue, &img, Some(&label), &self.options)
+            }
+            DecodedTexture::Data(data) => {
+                ctx.textures
+                    .load_data(ctx.device, ctx.queue, &data, Some(&label), &self.options.sampler)
+            }
+        }
+    }
+
+    // Same-sized images are written into the existing texture.
+    fn reload(
+        &self,
+        decoded: DecodedTexture,
+        texture: &mut Texture,
+        path: &Path,
+        ctx: &mut LoadContext,
+    ) -> Result<()> {
+        if let DecodedTexture::Image(img) = &decoded {
+            if ctx
+                .textures
+                .reload_image(ctx.device, ctx.queue, texture, img, &self.options)
+            {
+                return Ok(());
+            }
+        }
+        *texture = self.finish(decoded, path, ctx)?;
+        Ok(())
+    }
+}
+
+// WGSL files. Sources without `#include`s are validated while decoding so a
+// broken shader fails its load; the rest are checked when a pipeline is built.
+pub struct ShaderAssetLoader;
+
+impl AssetLoader for ShaderAssetLoader {
+    type Asset = Shader;
+    type Decoded = Shader;
+
+    fn decode(&self, bytes: Vec<u8>, path: &Path) -> Result<Shader> {
+        let path = path.to_string_lossy().replace('\\', "/");
+        let source = String::from_utf8(bytes)?;
+        if !source.lines().any(|line| line.trim_start().starts_with("#include")) {
+            shader::validate_wgsl(&source, &path)?;
+        }
+        Ok(Shader { path, source })
+    }
+
+    fn finish(&self, decoded: Shader, _path: &Path, _ctx: &mut LoadContext) -> Result<Shader> {
+        Ok(decoded)
+    }
+}
+
+// OBJ models, merged into one mesh.
+pub struct MeshAssetLoader;
+
+impl AssetLoader for MeshAssetLoader {
+    type Asset = Mesh;
+    type Decoded = MeshData;
+
+    fn decode(&self, bytes: Vec<u8>, path: &Path) -> Result<MeshData> {
+        match extension(path) {
+            "obj" => MeshData::from_obj(&bytes),
+            other => bail!("unsupported mesh format '{}'", other),
+        }
+    }
+
+    fn finish(&self, decoded: MeshData, path: &Path, ctx: &mut LoadContext) -> Result<Mesh> {
+        Ok(Mesh::from_data(ctx.device, &decoded, Some(&label(path))))
+    }
+}
+
+// glTF models with their skins and animations, kept on the CPU; upload
+// meshes with `Mesh::from_skinned_data`. Only buffers inside the file, in
+// a .glb or as base64 data URIs, are read here; use `Gltf::from_bytes_with`
+// for models whose buffers are separate files.
+pub struct GltfAssetLoader;
+
+impl AssetLoader for GltfAssetLoader {
+    type Asset = Gltf;
+    type Decoded = Gltf;
+
+    fn decode(&self, bytes: Vec<u8>, _path: &Path) -> Result<Gltf> {
+        Gltf::from_bytes(&bytes)
+    }
+
+    fn finish(&self, decoded: Gltf, _path: &Path, _ctx: &mut LoadContext) -> Result<Gltf> {
+        Ok(decoded)
+    }
+}
+
+// WAV, Ogg Vorbis and FLAC files, decoded to PCM up front.
+pub struct SoundAssetLoader;
+
+impl AssetLoader for SoundAssetLoader {
+    type Asset = Sound;
+    type Decoded = Sound;
+
+    fn decode(&self, bytes: Vec<u8>, path: &Path) -> Result<Sound> {
+        Sound::decode(&bytes, extension(path))
+    }
+
+    fn finish(&self, decoded: Sound, _path: &Path, _ctx: &mut LoadContext) -> Result<Sound> {
+        Ok(decoded)
+    }
+}
@@ -0,0 +1,7 @@
+pub mod handle;
+pub mod server;
+pub mod loaders;
+pub mod watcher;
+pub mod bind_groups;
+pub mod archive;
+pub mod vfs;
@@ -0,0 +1,594 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Weak,
+    },
+};
+
+use anyhow::Context;
+
+use super::handle::{AssetEvent, AssetId, Handle, HandleInner, LoadState};
+use super::vfs::{self, Vfs};
+use super::watcher::FileWatcher;
+use crate::graphics::texture::TextureLoader;
+
+// What the main-thread half of a load gets to create GPU resources with.
+pub struct LoadContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub textures: &'a mut TextureLoader,
+}
+
+// Turns a file's bytes into an asset in two steps. `decode` runs on a
+// background thread and should do the heavy lifting; `finish` runs on the
+// main thread during `AssetServer::update`, e.g. to upload to the GPU.
+pub trait AssetLoader: Send + Sync + 'static {
+    type Asset: 'static;
+    type Decoded: Send + 'static;
+
+    fn decode(&self, bytes: Vec<u8>, path: &Path) -> anyhow::Result<Self::Decoded>;
+
+    fn finish(
+        &self,
+        decoded: Self::Decoded,
+        path: &Path,
+        ctx: &mut LoadContext,
+    ) -> anyhow::Result<Self::Asset>;
+
+    // Updates `asset` after its file changed. Replacing it is the default;
+    // loaders can override this to update GPU resources in place, so
+    // bind groups referencing them stay valid.
+    fn reload(
+        &self,
+        decoded: Self::Decoded,
+        asset: &mut Self::Asset,
+        path: &Path,
+        ctx: &mut LoadContext,
+    ) -> anyhow::Result<()> {
+        *asset = self.finish(decoded, path, ctx)?;
+        Ok(())
+    }
+}
+
+type Decoded = Box<dyn Any + Send>;
+
+// `AssetLoader` with its decoded type erased, so loaders can be stored by
+// asset type alone.
+trait DynLoader<A>: Send + Sync {
+    fn decode(&self, bytes: Vec<u8>, path: &Path) -> anyhow::Result<Decoded>;
+    fn finish(&self, decoded: Decoded, path: &Path, ctx: &mut LoadContext) -> anyhow::Result<A>;
+    fn reload(&self, decoded: Decoded, asset: &mut A, path: &Path, ctx: &mut LoadContext) -> anyhow::Result<()>;
+}
+
+impl<L: AssetLoader> DynLoader<L::Asset> for L {
+    fn decode(&self, bytes: Vec<u8>, path: &Path) -> anyhow::Result<Decoded> {
+        AssetLoader::decode(self, bytes, path).map(|decoded| Box::new(decoded) as Decoded)
+    }
+
+    fn finish(&self, decoded: Decoded, path: &Path, ctx: &mut LoadContext) -> anyhow::Result<L::Asset> {
+        let decoded = decoded
+            .downcast::<L::Decoded>()
+            .expect("decoded by the same loader");
+        AssetLoader::finish(self, *decoded, path, ctx)
+    }
+
+    fn reload(&self, decoded: Decoded, asset: &mut L::Asset, path: &Path, ctx: &mut LoadContext) -> anyhow::Result<()> {
+        let decoded = decoded
+            .downcast::<L::Decoded>()
+            .expect("decoded by the same loader");
+        AssetLoader::reload(self, *decoded, asset, path, ctx)
+    }
+}
+
+enum Entry<A> {
+    Loading,
+    Loaded(A),
+    Failed(String),
+}
+
+struct Assets<A> {
+    loader: Option<Arc<dyn DynLoader<A>>>,
+    entries: HashMap<AssetId, Entry<A>>,
+    events: Vec<AssetEvent>,
+}
+
+// One `Assets<A>` per asset type, behind a common interface.
+trait AnyAssets {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn finish(&mut self, id: AssetId, path: &Path, decoded: Decoded, ctx: &mut LoadContext);
+    fn fail(&mut self, id: AssetId, error: String);
+    fn remove(&mut self, id: AssetId);
+    fn spawn_decode(&self, type_id: TypeId, id: AssetId, path: PathBuf, vfs: &Vfs, sender: Sender<Completed>);
+    fn clear_events(&mut self);
+}
+
+impl<A: 'static> AnyAssets for Assets<A> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn finish(&mut self, id: AssetId, path: &Path, decoded: Decoded, ctx: &mut LoadContext) {
+        let loader = self.loader.as_ref().expect("loads are only started with a loader");
+        let result = match self.entries.get_mut(&id) {
+            // Every handle was dropped while it loaded.
+            None => return,
+            Some(Entry::Loaded(asset)) => loader
+                .reload(decoded, asset, path, ctx)
+                .map(|()| AssetEvent::Reloaded(id)),
+            Some(entry) => loader.finish(decoded, path, ctx).map(|asset| {
+                *entry = Entry::Loaded(asset);
+                AssetEvent::Loaded(id)
+            }),
+        };
+        match result {
+            Ok(event) => self.events.push(event),
+            Err(e) => self.fail(id, format!("{}: {:#}", path.display(), e)),
+        }
+    }
+
+    fn fail(&mut self, id: AssetId, error: String) {
+        match self.entries.get_mut(&id) {
+            None => {}
+            // A reload. The previous version stays in use.
+            Some(Entry::Loaded(_)) => log::error!("failed to reload {}", error),
+            Some(entry) => {
+                log::error!("failed to load {}", error);
+                self.events.push(AssetEvent::Failed(id));
+                *entry = Entry::Failed(error);
+            }
+        }
+    }
+
+    fn remove(&mut self, id: AssetId) {
+        if self.entries.remove(&id).is_some() {
+            self.events.push(AssetEvent::Removed(id));
+        }
+    }
+
+    // Reads and decodes `path` on a background thread. Nothing happens
+    // without a loader.
+    fn spawn_decode(&self, type_id: TypeId, id: AssetId, path: PathBuf, vfs: &Vfs, sender: Sender<Completed>) {
+        let Some(loader) = self.loader.clone() else {
+            return;
+        };
+        let vfs = vfs.clone();
+        rayon::spawn(move || {
+            let result = vfs
+                .read(&path)
+                .context("failed to read")
+                .and_then(|bytes| loader.decode(bytes, &path))
+                .map_err(|e| format!("{}: {:#}", path.display(), e));
+            let _ = sender.send(Completed {
+                type_id,
+                id,
+                path,
+                result,
+            });
+        });
+    }
+
+    fn clear_events(&mut self) {
+        self.events.clear();
+    }
+}
+
+struct Completed {
+    type_id: TypeId,
+    id: AssetId,
+    path: PathBuf,
+    result: Result<Decoded, String>,
+}
+
+// Loads assets by path into typed handles. Files are read through the VFS
+// and decoded on background threads and become available in `update`;
+// loading a path that is still held returns the same handle, and an asset is
+// freed, along with any GPU resources it owns, on the first `update` after
+// its last handle drops.
+pub struct AssetServer {
+    vfs: Vfs,
+    next_id: u64,
+    storages: HashMap<TypeId, Box<dyn AnyAssets>>,
+    paths: HashMap<(TypeId, PathBuf), Weak<HandleInner>>,
+    watcher: Option<FileWatcher>,
+    drop_sender: Sender<(TypeId, AssetId)>,
+    drop_receiver: Receiver<(TypeId, AssetId)>,
+    completed_sender: Sender<Completed>,
+    completed_receiver: Receiver<Completed>,
+}
+
+impl AssetServer {
+    pub fn new(vfs: Vfs) -> Self {
+        let (drop_sender, drop_receiver) = mpsc::channel();
+        let (completed_sender, completed_receiver) = mpsc::channel();
+        Self {
+            vfs,
+            next_id: 0,
+            storages: HashMap::new(),
+            paths: HashMap::new(),
+            watcher: None,
+            drop_sender,
+            drop_receiver,
+            completed_sender,
+            completed_receiver,
+        }
+    }
+
+    // Reloads assets whose files change on disk, keeping their handles. See
+    // `AssetLoader::reload` and `AssetEvent::Reloaded`.
+    pub fn with_hot_reload(mut self) -> Self {
+        let mut watcher = FileWatcher::new();
+        for (_, path) in self.paths.keys() {
+            for disk_path in self.vfs.disk_paths(path) {
+                watcher.watch(disk_path);
+            }
+        }
+        self.watcher = Some(watcher);
+        self
+    }
+
+    pub fn vfs(&self) -> &Vfs {
+        &self.vfs
+    }
+
+    // Replaces any loader registered for the same asset type.
+    pub fn register_loader<L: AssetLoader>(&mut self, loader: L) {
+        self.storage_mut::<L::Asset>().loader = Some(Arc::new(loader));
+    }
+
+    pub fn load<T: 'static>(&mut self, path: impl AsRef<Path>) -> Handle<T> {
+        let path = PathBuf::from(vfs::normalize(path.as_ref()));
+        let type_id = TypeId::of::<T>();
+        let key = (type_id, path.clone());
+        if let Some(inner) = self.paths.get(&key).and_then(Weak::upgrade) {
+            return Handle::new(inner);
+        }
+
+        let inner = self.new_handle::<T>(Some(path.clone()));
+        let id = inner.id;
+        self.paths.insert(key, Arc::downgrade(&inner));
+
+        if let Some(watcher) = self.watcher.as_mut() {
+            for disk_path in self.vfs.disk_paths(&path) {
+                watcher.watch(disk_path);
+            }
+        }
+        let sender = self.completed_sender.clone();
+        let vfs = self.vfs.clone();
+        let storage = self.storage_mut::<T>();
+        if storage.loader.is_none() {
+            let error = format!("{}: no loader registered for {}", path.display(), type_name::<T>());
+            log::error!("failed to load {}", error);
+            storage.entries.insert(id, Entry::Failed(error));
+            return Handle::new(inner);
+        }
+        storage.entries.insert(id, Entry::Loading);
+        storage.spawn_decode(type_id, id, path, &vfs, sender);
+        Handle::new(inner)
+    }
+
+    // Stores an asset created in code, e.g. from `include_bytes!` data.
+    pub fn add<T: 'static>(&mut self, asset: T) -> Handle<T> {
+        let inner = self.new_handle::<T>(None);
+        self.storage_mut::<T>()
+            .entries
+            .insert(inner.id, Entry::Loaded(asset));
+        Handle::new(inner)
+    }
+
+    pub fn get<T: 'static>(&self, handle: &Handle<T>) -> Option<&T> {
+        match self.storage::<T>()?.entries.get(&handle.id())? {
+            Entry::Loaded(asset) => Some(asset),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, handle: &Handle<T>) -> Option<&mut T> {
+        let storage = self
+            .storages
+            .get_mut(&TypeId::of::<T>())?
+            .as_any_mut()
+            .downcast_mut::<Assets<T>>()?;
+        match storage.entries.get_mut(&handle.id())? {
+            Entry::Loaded(asset) => Some(asset),
+            _ => None,
+        }
+    }
+
+    pub fn load_state<T: 'static>(&self, handle: &Handle<T>) -> LoadState {
+        match self.storage::<T>().and_then(|storage| storage.entries.get(&handle.id())) {
+            Some(Entry::Loading) => LoadState::Loading,
+            Some(Entry::Loaded(_)) => LoadState::Loaded,
+            Some(Entry::Failed(e)) => LoadState::Failed(e.clone()),
+            None => LoadState::Failed(format!("{:?} is not an asset of this server", handle)),
+        }
+    }
+
+    // Assets of type `T` currently held, loaded or not.
+    pub fn count<T: 'static>(&self) -> usize {
+        self.storage::<T>().map_or(0, |storage| storage.entries.len())
+    }
+
+    // What happened to assets of type `T` during the last `update`.
+    pub fn events<T: 'static>(&self) -> &[AssetEvent] {
+        self.storage::<T>().map_or(&[], |storage| &storage.events)
+    }
+
+    // Frees assets whose handles were all dropped, starts reloading changed
+    // files, then finishes loads whose background decode completed. Call
+    // once per frame.
+    pub fn update(&mut self, ctx: &mut LoadContext) {
+        for (type_id, id, path, decoded) in self.poll() {
+            if let Some(storage) = self.storages.get_mut(&type_id) {
+                storage.finish(id, &path, decoded, ctx);
+            }
+        }
+    }
+
+    // The part of `update` that needs no device. Failed decodes are recorded
+    // here; the successful ones are returned to be finished.
+    fn poll(&mut self) -> Vec<(TypeId, AssetId, PathBuf, Decoded)> {
+        for storage in self.storages.values_mut() {
+            storage.clear_events();
+        }
+
+        let mut dropped = false;
+        for (type_id, id) in self.drop_receiver.try_iter() {
+            if let Some(storage) = self.storages.get_mut(&type_id) {
+                storage.remove(id);
+            }
+            dropped = true;
+        }
+        if dropped {
+            let mut dead = Vec::new();
+            self.paths.retain(|(_, path), inner| {
+                let alive = inner.strong_count() > 0;
+                if !alive {
+                    dead.push(path.clone());
+                }
+                alive
+            });
+            // A file stays watched while an asset of another type, or under
+            // another path of the same file, still uses it.
+            if let (false, Some(watcher)) = (dead.is_empty(), self.watcher.as_mut()) {
+                let live = self
+                    .paths
+                    .keys()
+                    .flat_map(|(_, path)| self.vfs.disk_paths(path))
+                    .collect::<HashSet<_>>();
+                for disk_path in dead.iter().flat_map(|path| self.vfs.disk_paths(path)) {
+                    if !live.contains(&disk_path) {
+                        watcher.unwatch(disk_path);
+                    }
+                }
+            }
+        }
+
+        let changed = self.watcher.as_mut().map(FileWatcher::poll_changed).unwrap_or_default();
+        for full_path in changed {
+            for ((type_id, path), inner) in &self.paths {
+                let (Some(inner), Some(storage)) = (inner.upgrade(), self.storages.get(type_id)) else {
+                    continue;
+                };
+                if self.vfs.disk_paths(path).contains(&full_path) {
+                    log::info!("reloading {}", path.display());
+                    let sender = self.completed_sender.clone();
+                    storage.spawn_decode(*type_id, inner.id, path.clone(), &self.vfs, sender);
+                }
+            }
+        }
+
+        let mut decoded = Vec::new();
+        for completed in self.completed_receiver.try_iter() {
+            let Some(storage) = self.storages.get_mut(&completed.type_id) else {
+                continue;
+            };
+            match completed.result {
+                Ok(result) => decoded.push((completed.type_id, completed.id, completed.path, result)),
+                Err(e) => storage.fail(completed.id, e),
+            }
+        }
+        decoded
+    }
+
+    fn new_handle<T: 'static>(&mut self, path: Option<PathBuf>) -> Arc<HandleInner> {
+        let id = AssetId(self.next_id);
+        self.next_id += 1;
+        Arc::new(HandleInner {
+            id,
+            type_id: TypeId::of::<T>(),
+            path,
+            drops: self.drop_sender.clone(),
+        })
+    }
+
+    fn storage<T: 'static>(&self) -> Option<&Assets<T>> {
+        self.storages.get(&TypeId::of::<T>())?.as_any().downcast_ref()
+    }
+
+    fn storage_mut<T: 'static>(&mut self) -> &mut Assets<T> {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| {
+                Box::new(Assets::<T> {
+                    loader: None,
+                    entries: HashMap::new(),
+                    events: Vec::new(),
+                })
+            })
+            .as_any_mut()
+            .downcast_mut()
+            .expect("storage is keyed by its asset type")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use anyhow::Context;
+
+    use super::*;
+    use crate::assets::vfs::VfsSource;
+
+    struct MemorySource(HashMap<String, Vec<u8>>);
+
+    impl VfsSource for MemorySource {
+        fn read(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+            Ok(self.0.get(path).cloned())
+        }
+    }
+
+    struct TextLoader;
+
+    impl AssetLoader for TextLoader {
+        type Asset = String;
+        type Decoded = String;
+
+        fn decode(&self, bytes: Vec<u8>, _path: &Path) -> anyhow::Result<String> {
+            String::from_utf8(bytes).context("not UTF-8")
+        }
+
+        fn finish(&self, decoded: String, _path: &Path, _ctx: &mut LoadContext) -> anyhow::Result<String> {
+            Ok(decoded)
+        }
+    }
+
+    fn server() -> AssetServer {
+        let files = [("a.txt", b"first".to_vec()), ("b.txt", b"second".to_vec()), ("bad.txt", vec![0xff, 0xfe])];
+        let vfs = Vfs::new();
+        vfs.mount(
+            "text",
+            0,
+            MemorySource(files.into_iter().map(|(path, bytes)| (path.to_string(), bytes)).collect()),
+        );
+        let mut server = AssetServer::new(vfs);
+        server.register_loader(TextLoader);
+        server
+    }
+
+    // Polls until the background decode of `handle` has come back.
+    fn wait_for(server: &mut AssetServer, handle: &Handle<String>) -> Vec<(TypeId, AssetId, PathBuf, Decoded)> {
+        let start = Instant::now();
+        loop {
+            let decoded = server.poll();
+            if !decoded.is_empty() || server.load_state(handle) != LoadState::Loading {
+                return decoded;
+            }
+            assert!(start.elapsed() < Duration::from_secs(10), "decode never finished");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn loading_a_held_path_returns_the_same_handle() {
+        let mut server = server();
+        let first = server.load::<String>("text/a.txt");
+        let second = server.load::<String>("text/./a.txt");
+        assert_eq!(first, second);
+        assert_eq!(server.count::<String>(), 1);
+
+        let decoded = wait_for(&mut server, &first);
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].1, first.id());
+        assert_eq!(decoded[0].3.downcast_ref::<String>().map(String::as_str), Some("first"));
+        assert_ne!(server.load::<String>("text/b.txt"), first);
+
+        // Once every handle is gone the path loads afresh.
+        let id = first.id();
+        drop((first, second));
+        server.poll();
+        assert_ne!(server.load::<String>("text/a.txt").id(), id);
+    }
+
+    #[test]
+    fn failed_decodes_reach_the_handle() {
+        let mut server = server();
+        let bad = server.load::<String>("text/bad.txt");
+        assert_eq!(server.load_state(&bad), LoadState::Loading);
+        assert!(wait_for(&mut server, &bad).is_empty());
+        match server.load_state(&bad) {
+            LoadState::Failed(e) => assert!(e.starts_with("text/bad.txt: not UTF-8"), "{}", e),
+            state => panic!("expected a failure, got {:?}", state),
+        }
+        assert_eq!(server.events::<String>(), [AssetEvent::Failed(bad.id())]);
+        assert_eq!(server.get(&bad), None);
+
+        let missing = server.load::<String>("text/missing.txt");
+        wait_for(&mut server, &missing);
+        match server.load_state(&missing) {
+            LoadState::Failed(e) => assert!(e.starts_with("text/missing.txt: failed to read"), "{}", e),
+            state => panic!("expected a failure, got {:?}", state),
+        }
+    }
+
+    #[test]
+    fn dropping_the_last_handle_frees_the_asset() {
+        let mut server = server();
+        let handle = server.add(String::from("in memory"));
+        let clone = handle.clone();
+        let id = handle.id();
+
+        drop(handle);
+        server.poll();
+        assert_eq!(server.get(&clone).map(String::as_str), Some("in memory"));
+        assert!(server.events::<String>().is_empty());
+
+        drop(clone);
+        server.poll();
+        assert_eq!(server.count::<String>(), 0);
+        assert_eq!(server.events::<String>(), [AssetEvent::Removed(id)]);
+    }
+
+    #[test]
+    fn files_stay_watched_while_any_asset_uses_them() {
+        struct ByteLoader;
+
+        impl AssetLoader for ByteLoader {
+            type Asset = Vec<u8>;
+            type Decoded = Vec<u8>;
+
+            fn decode(&self, bytes: Vec<u8>, _path: &Path) -> anyhow::Result<Vec<u8>> {
+                Ok(bytes)
+            }
+
+            fn finish(&self, decoded: Vec<u8>, _path: &Path, _ctx: &mut LoadContext) -> anyhow::Result<Vec<u8>> {
+                Ok(decoded)
+            }
+        }
+
+        let dir = std::env::temp_dir().join(format!("onion-server-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.txt"), "first").unwrap();
+        let vfs = Vfs::new();
+        vfs.mount_directory("", 0, &dir);
+        let mut server = AssetServer::new(vfs).with_hot_reload();
+        server.register_loader(TextLoader);
+        server.register_loader(ByteLoader);
+        let disk_path = dir.join("a.txt");
+
+        let text = server.load::<String>("a.txt");
+        let bytes = server.load::<Vec<u8>>("a.txt");
+        wait_for(&mut server, &text);
+        drop(text);
+        server.poll();
+        assert!(server.watcher.as_ref().unwrap().is_watched(&disk_path));
+
+        drop(bytes);
+        server.poll();
+        assert!(!server.watcher.as_ref().unwrap().is_watched(&disk_path));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    fs,
+    io::{self, Cursor, Read, Seek},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use super::archive::Archive;
+use crate::graphics::shader::{EmbeddedShaderLoader, ShaderLoader};
+
+// Something files can be read from, e.g. a directory or an archive. Paths
+// are '/'-separated and relative to the source.
+pub trait VfsSource: Send + Sync {
+    // Ok(None) when the source has no such file.
+    fn read(&self, path: &str) -> io::Result<Option<Vec<u8>>>;
+
+    // Where `path` would live on disk, whether or not it exists, for
+    // sources backed by plain files.
+    fn disk_path(&self, _path: &str) -> Option<PathBuf> {
+        None
+    }
+
+    // For reading incrementally, e.g. streamed music. Sources without real
+    // files hand out an in-memory copy.
+    fn open(&self, path: &str) -> io::Result<Option<Box<dyn ReadSeek>>> {
+        Ok(self
+            .read(path)?
+            .map(|bytes| Box::new(Cursor::new(bytes)) as Box<dyn ReadSeek>))
+    }
+}
+
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+pub struct DirectorySource {
+    root: PathBuf,
+}
+
+impl DirectorySource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl VfsSource for DirectorySource {
+    fn read(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.root.join(path)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn disk_path(&self, path: &str) -> Option<PathBuf> {
+        Some(self.root.join(path))
+    }
+
+    fn open(&self, path: &str) -> io::Result<Option<Box<dyn ReadSeek>>> {
+        match fs::File::open(self.root.join(path)) {
+            Ok(file) => Ok(Some(Box::new(io::BufReader::new(file)))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl VfsSource for Archive {
+    fn read(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        Archive::read(self, path)
+    }
+}
+
+// Mounted below everything else, the engine's built-in shaders fill in for
+// whatever the other mounts don't override.
+impl VfsSource for EmbeddedShaderLoader {
+    fn read(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+        match ShaderLoader::load(self, path) {
+            Ok(source) => Ok(Some(source.into_bytes())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+struct Mount {
+    // Virtual directory the source appears under, "" for the root.
+    point: String,
+    priority: i32,
+    source: Arc<dyn VfsSource>,
+}
+
+// Layers sources under mount points. A read goes to the highest-priority
+// mount holding the file, so a mod mounted above the game's archive
+// overrides its files; among equal priorities the latest mount wins.
+// Clones share their mounts.
+#[derive(Clone, Default)]
+pub struct Vfs {
+    mounts: Arc<RwLock<Vec<Mount>>>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mount(&self, point: &str, priority: i32, source: impl VfsSource + 'static) {
+        let point = normalize(Path::new(point));
+        let mut mounts = self.mounts.write().unwrap();
+        let index = mounts
+            .iter()
+            .position(|mount| mount.priority <= priority)
+            .unwrap_or(mounts.len());
+        mounts.insert(
+            index,
+            Mount {
+                point,
+                priority,
+                source: Arc::new(source),
+            },
+        );
+    }
+
+    pub fn mount_directory(&self, point: &str, priority: i32, root: impl Into<PathBuf>) {
+        self.mount(point, priority, DirectorySource::new(root));
+    }
+
+    pub fn mount_archive(&self, point: &str, priority: i32, path: impl AsRef<Path>) -> io::Result<()> {
+        self.mount(point, priority, Archive::open(path)?);
+        Ok(())
+    }
+
+    pub fn unmount_all(&self, point: &str) {
+        let point = normalize(Path::new(point));
+        self.mounts.write().unwrap().retain(|mount| mount.point != point);
+    }
+
+    pub fn read(&self, path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        let path = normalize(path.as_ref());
+        for (source, relative) in self.candidates(&path) {
+            if let Some(bytes) = source.read(&relative)? {
+                return Ok(bytes);
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not in any mount", path),
+        ))
+    }
+
+    pub fn open(&self, path: impl AsRef<Path>) -> io::Result<Box<dyn ReadSeek>> {
+        let path = normalize(path.as_ref());
+        for (source, relative) in self.candidates(&path) {
+            if let Some(reader) = source.open(&relative)? {
+                return Ok(reader);
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} is not in any mount", path),
+        ))
+    }
+
+    pub fn read_to_string(&self, path: impl AsRef<Path>) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // Every on-disk location that could provide `path`, highest priority
+    // first. Watching all of them catches a file being added to a layer
+    // that overrides the one currently used.
+    pub fn disk_paths(&self, path: impl AsRef<Path>) -> Vec<PathBuf> {
+        let path = normalize(path.as_ref());
+        self.candidates(&path)
+            .into_iter()
+            .filter_map(|(source, relative)| source.disk_path(&relative))
+            .collect()
+    }
+
+    fn candidates(&self, path: &str) -> Vec<(Arc<dyn VfsSource>, String)> {
+        self.mounts
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|mount| {
+                let relative = if mount.point.is_empty() {
+                    path
+                } else {
+                    path.strip_prefix(mount.point.as_str())?.strip_prefix('/')?
+                };
+                Some((mount.source.clone(), relative.to_string()))
+            })
+            .collect()
+    }
+}
+
+// Lets shaders be loaded through the VFS as well.
+impl ShaderLoader for Vfs {
+    fn load(&self, path: &str) -> io::Result<String> {
+        self.read_to_string(path)
+    }
+
+    fn disk_path(&self, path: &str) -> Option<PathBuf> {
+        self.disk_paths(path).into_iter().find(|path| path.exists())
+    }
+}
+
+// Shaders under one directory of the VFS, e.g. "shaders", so a mod can
+// replace a single file and the rest still come from lower mounts.
+pub struct VfsShaderLoader {
+    vfs: Vfs,
+    root: String,
+}
+
+impl VfsShaderLoader {
+    pub fn new(vfs: Vfs, root: &str) -> Self {
+        Self {
+            vfs,
+            root: normalize(Path::new(root)),
+        }
+    }
+
+    fn path(&self, path: &str) -> String {
+        format!("{}/{}", self.root, path)
+    }
+}
+
+impl ShaderLoader for VfsShaderLoader {
+    fn load(&self, path: &str) -> io::Result<String> {
+        self.vfs.read_to_string(self.path(path))
+    }
+
+    fn disk_path(&self, path: &str) -> Option<PathBuf> {
+        ShaderLoader::disk_path(&self.vfs, &self.path(path))
+    }
+}
+
+// '/'-separated with `.` and `..` resolved, so one file has one name.
+pub fn normalize(path: &Path) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            _ => {}
+        }
+    }
+    parts.join("/")
+}
@@ -0,0 +1,74 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+// Polls files' modification times. Polling rather than OS notifications
+// keeps it working everywhere, including network mounts.
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant,
+    interval: Duration,
+}
+
+impl FileWatcher {
+    pub fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            last_poll: Instant::now(),
+            interval: Duration::from_millis(250),
+        }
+    }
+
+    pub fn watch(&mut self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        if self.files.iter().all(|(watched, _)| watched != path) {
+            self.files.push((path.to_path_buf(), modified_time(path)));
+        }
+    }
+
+    pub fn unwatch(&mut self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        self.files.retain(|(watched, _)| watched != path);
+    }
+
+    pub fn is_watched(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        self.files.iter().any(|(watched, _)| watched == path)
+    }
+
+    // True when any watched file changed since the last poll.
+    pub fn poll(&mut self) -> bool {
+        !self.poll_changed().is_empty()
+    }
+
+    // Watched files that changed since the last poll. A file that did not
+    // exist counts as changed once it is created.
+    pub fn poll_changed(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let mut changed = Vec::new();
+        for (path, last_modified) in &mut self.files {
+            let modified = modified_time(path);
+            if modified.is_some() && modified != *last_modified {
+                *last_modified = modified;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
@@ -0,0 +1,561 @@
+use super::sound::Sound;
+use super::stream::MusicStream;
+
+// The mixer always renders interleaved stereo.
+pub const OUTPUT_CHANNELS: usize = 2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Bus {
+    Music,
+    Sfx,
+    Voice,
+}
+
+impl Bus {
+    pub const ALL: [Bus; 3] = [Bus::Music, Bus::Sfx, Bus::Voice];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct VoiceId(u64);
+
+// How a sound is played. Pitch scales the playback rate (2.0 is an octave
+// up), pan goes from -1 (left) to 1 (right).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PlaySettings {
+    pub bus: Bus,
+    pub volume: f32,
+    pub pitch: f32,
+    pub pan: f32,
+    pub looping: bool,
+}
+
+impl Default for PlaySettings {
+    fn default() -> Self {
+        Self {
+            bus: Bus::Sfx,
+            volume: 1.0,
+            pitch: 1.0,
+            pan: 0.0,
+            looping: false,
+        }
+    }
+}
+
+impl PlaySettings {
+    pub fn music() -> Self {
+        Self {
+            bus: Bus::Music,
+            looping: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_bus(mut self, bus: Bus) -> Self {
+        self.bus = bus;
+        self
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    pub fn with_pitch(mut self, pitch: f32) -> Self {
+        self.pitch = pitch;
+        self
+    }
+
+    pub fn with_pan(mut self, pan: f32) -> Self {
+        self.pan = pan;
+        self
+    }
+
+    pub fn looped(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+}
+
+enum Source {
+    Sound(Sound),
+    Stream(MusicStream),
+}
+
+impl Source {
+    fn sample_rate(&self) -> u32 {
+        match self {
+            Source::Sound(sound) => sound.sample_rate,
+            Source::Stream(stream) => stream.sample_rate(),
+        }
+    }
+}
+
+// Ramps a gain towards `target` at `rate` per second.
+#[derive(Debug, Copy, Clone)]
+struct Ramp {
+    value: f32,
+    target: f32,
+    rate: f32,
+}
+
+impl Ramp {
+    fn new(value: f32) -> Self {
+        Self {
+            value,
+            target: value,
+            rate: 0.0,
+        }
+    }
+
+    // A ramp of zero seconds jumps straight to `target`. An infinite rate
+    // would do the same, but turns into NaN on an empty render.
+    fn set(&mut self, target: f32, seconds: f32) {
+        self.target = target;
+        if seconds > 0.0 {
+            self.rate = (target - self.value).abs() / seconds;
+        } else {
+            self.value = target;
+            self.rate = 0.0;
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.value == self.target
+    }
+
+    // Value now and after `seconds`.
+    fn advance(&mut self, seconds: f32) -> (f32, f32) {
+        let from = self.value;
+        let step = self.rate * seconds;
+        self.value = if (self.target - self.value).abs() <= step {
+            self.target
+        } else {
+            self.value + step.copysign(self.target - self.value)
+        };
+        (from, self.value)
+    }
+}
+
+struct Voice {
+    id: VoiceId,
+    source: Source,
+    settings: PlaySettings,
+    // Fractional frame position in the source. Streams count from the
+    // oldest frame they still buffer.
+    position: f64,
+    paused: bool,
+    fade: Ramp,
+    stop_after_fade: bool,
+}
+
+// Source frame `frame` as a stereo pair. Mono is duplicated, anything past
+// two channels is dropped.
+fn sound_frame(sound: &Sound, frame: usize) -> (f32, f32) {
+    let channels = sound.channels.max(1) as usize;
+    let start = frame * channels;
+    match sound.samples.get(start..start + channels) {
+        Some([mono]) => (*mono, *mono),
+        Some([left, right, ..]) => (*left, *right),
+        _ => (0.0, 0.0),
+    }
+}
+
+impl Voice {
+    // Mixes into `out`, ramping the gain from `gain.0` to `gain.1`, and
+    // returns false once the voice has ended.
+    fn mix(&mut self, out: &mut [f32], output_rate: u32, gain: (f32, f32)) -> bool {
+        if let Source::Sound(sound) = &self.source {
+            if sound.frames() == 0 {
+                return false;
+            }
+        }
+        if self.paused {
+            return true;
+        }
+
+        let step = self.source.sample_rate() as f64 / output_rate as f64 * self.settings.pitch.max(0.0) as f64;
+        let pan = self.settings.pan.clamp(-1.0, 1.0);
+        // Balance law: the centre keeps both channels at full volume.
+        let (left_pan, right_pan) = ((1.0 - pan).min(1.0), (1.0 + pan).min(1.0));
+        let frame_count = (out.len() / OUTPUT_CHANNELS).max(1) as f32;
+
+        for (i, frame) in out.chunks_exact_mut(OUTPUT_CHANNELS).enumerate() {
+            let index = self.position as usize;
+            let t = (self.position - index as f64) as f32;
+            let ((l0, r0), (l1, r1)) = match &mut self.source {
+                Source::Sound(sound) => {
+                    let frames = sound.frames();
+                    let next = if index + 1 < frames {
+                        index + 1
+                    } else if self.settings.looping {
+                        0
+                    } else {
+                        // Past the end, so the last frame fades towards silence.
+                        frames
+                    };
+                    (sound_frame(sound, index), sound_frame(sound, next))
+                }
+                // Streams loop by themselves.
+                Source::Stream(stream) => match (stream.frame(index), stream.frame(index + 1)) {
+                    (Some(current), Some(next)) => (current, next),
+                    (Some(current), None) if stream.is_ended() => (current, (0.0, 0.0)),
+                    (None, _) if stream.is_ended() => return false,
+                    // The decoder fell behind; hold position rather than skip.
+                    _ => return true,
+                },
+            };
+            let gain = gain.0 + (gain.1 - gain.0) * i as f32 / frame_count;
+            frame[0] += (l0 + (l1 - l0) * t) * gain * left_pan;
+            frame[1] += (r0 + (r1 - r0) * t) * gain * right_pan;
+
+            self.position += step;
+            match &mut self.source {
+                Source::Sound(sound) => {
+                    let frames = sound.frames() as f64;
+                    if self.position >= frames {
+                        if !self.settings.looping {
+                            return false;
+                        }
+                        self.position %= frames;
+                    }
+                }
+                Source::Stream(stream) => {
+                    let played = self.position as usize;
+                    stream.consume(played);
+                    self.position -= played as f64;
+                }
+            }
+        }
+        true
+    }
+}
+
+// Lowers one bus while voices on another play, e.g. music under dialogue.
+// `attack` and `release` are the seconds taken to duck and to recover.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ducking {
+    pub bus: Bus,
+    pub trigger: Bus,
+    pub level: f32,
+    pub attack: f32,
+    pub release: f32,
+}
+
+impl Default for Ducking {
+    fn default() -> Self {
+        Self {
+            bus: Bus::Music,
+            trigger: Bus::Voice,
+            level: 0.3,
+            attack: 0.1,
+            release: 0.6,
+        }
+    }
+}
+
+// Software mixer. Voices are resampled to the output rate, scaled by their
+// own, their bus' and the master volume and summed into a stereo buffer.
+pub struct Mixer {
+    sample_rate: u32,
+    voices: Vec<Voice>,
+    bus_volumes: [f32; 3],
+    master_volume: f32,
+    ducking: Option<Ducking>,
+    duck: Ramp,
+    next_id: u64,
+}
+
+impl Mixer {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            voices: Vec::new(),
+            bus_volumes: [1.0; 3],
+            master_volume: 1.0,
+            ducking: Some(Ducking::default()),
+            duck: Ramp::new(1.0),
+            next_id: 0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn play(&mut self, sound: &Sound, settings: PlaySettings) -> VoiceId {
+        self.push(Source::Sound(sound.clone()), settings)
+    }
+
+    // Streams loop according to their track, `settings.looping` is ignored.
+    pub fn play_stream(&mut self, stream: MusicStream, settings: PlaySettings) -> VoiceId {
+        self.push(Source::Stream(stream), settings)
+    }
+
+    pub fn stop(&mut self, id: VoiceId) {
+        self.voices.retain(|voice| voice.id != id);
+    }
+
+    pub fn stop_bus(&mut self, bus: Bus) {
+        self.voices.retain(|voice| voice.settings.bus != bus);
+    }
+
+    pub fn stop_all(&mut self) {
+        self.voices.clear();
+    }
+
+    // False once the voice has finished or was stopped.
+    pub fn is_playing(&self, id: VoiceId) -> bool {
+        self.voices.iter().any(|voice| voice.id == id)
+    }
+
+    pub fn voice_count(&self) -> usize {
+        self.voices.len()
+    }
+
+    // True while a stream voice is waiting on its decoder for the frames it
+    // plays next, e.g. just after it started. Rendering then holds it where
+    // it is.
+    pub fn is_buffering(&mut self) -> bool {
+        self.voices.iter_mut().any(|voice| match &mut voice.source {
+            Source::Stream(stream) => stream.frame(voice.position as usize + 1).is_none() && !stream.is_ended(),
+            Source::Sound(_) => false,
+        })
+    }
+
+    pub fn set_paused(&mut self, id: VoiceId, paused: bool) {
+        if let Some(voice) = self.voice_mut(id) {
+            voice.paused = paused;
+        }
+    }
+
+    // Starts the voice silent and brings it up to full volume.
+    pub fn fade_in(&mut self, id: VoiceId, seconds: f32) {
+        if let Some(voice) = self.voice_mut(id) {
+            voice.fade = Ramp::new(0.0);
+            voice.fade.set(1.0, seconds);
+            voice.stop_after_fade = false;
+        }
+    }
+
+    // Fades the voice to silence and stops it.
+    pub fn fade_out(&mut self, id: VoiceId, seconds: f32) {
+        if let Some(voice) = self.voice_mut(id) {
+            voice.fade.set(0.0, seconds);
+            voice.stop_after_fade = true;
+        }
+    }
+
+    // Volume, pitch, pan and looping can be changed while the voice plays.
+    pub fn settings_mut(&mut self, id: VoiceId) -> Option<&mut PlaySettings> {
+        self.voice_mut(id).map(|voice| &mut voice.settings)
+    }
+
+    pub fn bus_volume(&self, bus: Bus) -> f32 {
+        self.bus_volumes[bus.index()]
+    }
+
+    pub fn set_bus_volume(&mut self, bus: Bus, volume: f32) {
+        self.bus_volumes[bus.index()] = volume.max(0.0);
+    }
+
+    pub fn master_volume(&self) -> f32 {
+        self.master_volume
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume.max(0.0);
+    }
+
+    pub fn ducking(&self) -> Option<Ducking> {
+        self.ducking
+    }
+
+    pub fn set_ducking(&mut self, ducking: Option<Ducking>) {
+        self.ducking = ducking;
+    }
+
+    // Overwrites `out` (interleaved stereo) with the next frames of the mix
+    // and drops voices that ended.
+    pub fn render(&mut self, out: &mut [f32]) {
+        out.fill(0.0);
+        let seconds = (out.len() / OUTPUT_CHANNELS) as f32 / self.sample_rate as f32;
+        let duck = self.ducking.map(|ducking| {
+            let triggered = self
+                .voices
+                .iter()
+                .any(|voice| voice.settings.bus == ducking.trigger && !voice.paused);
+            let (target, time) = if triggered {
+                (ducking.level, ducking.attack)
+            } else {
+                (1.0, ducking.release)
+            };
+            if self.duck.target != target {
+                self.duck.set(target, time);
+            }
+            (ducking.bus, self.duck.advance(seconds))
+        });
+
+        let (rate, master, buses) = (self.sample_rate, self.master_volume, self.bus_volumes);
+        self.voices.retain_mut(|voice| {
+            let gain = voice.settings.volume.max(0.0) * buses[voice.settings.bus.index()] * master;
+            let fade = voice.fade.advance(seconds);
+            let duck = match duck {
+                Some((bus, duck)) if bus == voice.settings.bus => duck,
+                _ => (1.0, 1.0),
+            };
+            let playing = voice.mix(out, rate, (gain * fade.0 * duck.0, gain * fade.1 * duck.1));
+            playing && !(voice.stop_after_fade && voice.fade.is_done())
+        });
+        for sample in out.iter_mut() {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+
+    fn push(&mut self, source: Source, settings: PlaySettings) -> VoiceId {
+        let id = VoiceId(self.next_id);
+        self.next_id += 1;
+        self.voices.push(Voice {
+            id,
+            source,
+            settings,
+            position: 0.0,
+            paused: false,
+            fade: Ramp::new(1.0),
+            stop_after_fade: false,
+        });
+        id
+    }
+
+    fn voice_mut(&mut self, id: VoiceId) -> Option<&mut Voice> {
+        self.voices.iter_mut().find(|voice| voice.id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A low rate keeps the buffers small; the mixer doesn't care.
+    const RATE: u32 = 100;
+
+    fn mono(samples: impl IntoIterator<Item = f32>, sample_rate: u32) -> Sound {
+        Sound {
+            sample_rate,
+            channels: 1,
+            samples: samples.into_iter().collect(),
+        }
+    }
+
+    fn render(mixer: &mut Mixer, frames: usize) -> Vec<f32> {
+        let mut out = vec![0.0; frames * OUTPUT_CHANNELS];
+        mixer.render(&mut out);
+        out
+    }
+
+    fn left(out: &[f32]) -> Vec<f32> {
+        out.chunks_exact(OUTPUT_CHANNELS).map(|frame| frame[0]).collect()
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn scales_by_voice_bus_and_master_volume() {
+        let mut mixer = Mixer::new(RATE);
+        let sound = mono([0.8; 100], RATE);
+        mixer.play(&sound, PlaySettings::default().with_volume(0.5));
+        mixer.play(&sound, PlaySettings::default().with_bus(Bus::Music));
+        mixer.set_bus_volume(Bus::Sfx, 0.5);
+        mixer.set_bus_volume(Bus::Music, 0.25);
+        mixer.set_master_volume(0.5);
+
+        // (0.8 * 0.5 * 0.5 + 0.8 * 0.25) * 0.5, in both channels.
+        for sample in render(&mut mixer, 10) {
+            assert_close(sample, 0.2);
+        }
+    }
+
+    #[test]
+    fn fades_in_and_out() {
+        let mut mixer = Mixer::new(RATE);
+        let id = mixer.play(&mono([1.0; 1000], RATE), PlaySettings::default());
+        mixer.fade_in(id, 1.0);
+        let out = left(&render(&mut mixer, 100));
+        assert_close(out[0], 0.0);
+        assert_close(out[50], 0.5);
+        assert_close(out[99], 0.99);
+
+        mixer.fade_out(id, 0.5);
+        let out = left(&render(&mut mixer, 50));
+        assert_close(out[0], 1.0);
+        assert_close(out[25], 0.5);
+        assert!(!mixer.is_playing(id));
+        assert!(left(&render(&mut mixer, 10)).iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn instant_ramps_survive_empty_renders() {
+        let mut mixer = Mixer::new(RATE);
+        let id = mixer.play(&mono([1.0; 1000], RATE), PlaySettings::default());
+        mixer.fade_in(id, 0.0);
+        render(&mut mixer, 0);
+        assert_eq!(left(&render(&mut mixer, 10)), [1.0; 10]);
+        mixer.fade_out(id, 0.0);
+        render(&mut mixer, 0);
+        assert!(!mixer.is_playing(id));
+
+        // With no attack the music drops straight to the duck level.
+        mixer.set_ducking(Some(Ducking {
+            attack: 0.0,
+            release: 0.0,
+            ..Ducking::default()
+        }));
+        mixer.play(&mono([1.0; 1000], RATE), PlaySettings::default().with_bus(Bus::Music));
+        mixer.play(&mono([0.0; 1000], RATE), PlaySettings::default().with_bus(Bus::Voice));
+        render(&mut mixer, 0);
+        for sample in render(&mut mixer, 10) {
+            assert_close(sample, 0.3);
+        }
+    }
+
+    #[test]
+    fn resamples_by_rate_and_pitch() {
+        let ramp = |sample_rate| mono((0..100).map(|i| i as f32 / 100.0), sample_rate);
+
+        // Twice the output rate and pitched up an octave: four source frames
+        // per output frame.
+        let mut mixer = Mixer::new(RATE);
+        mixer.play(&ramp(2 * RATE), PlaySettings::default().with_pitch(2.0));
+        let out = left(&render(&mut mixer, 10));
+        for (i, sample) in out.into_iter().enumerate() {
+            assert_close(sample, 4.0 * i as f32 / 100.0);
+        }
+
+        // Half speed lands between source frames, which are interpolated.
+        let mut mixer = Mixer::new(RATE);
+        mixer.play(&ramp(RATE), PlaySettings::default().with_pitch(0.5));
+        let out = left(&render(&mut mixer, 10));
+        for (i, sample) in out.into_iter().enumerate() {
+            assert_close(sample, 0.5 * i as f32 / 100.0);
+        }
+    }
+
+    #[test]
+    fn finished_voices_stop_playing() {
+        let mut mixer = Mixer::new(RATE);
+        let id = mixer.play(&mono([0.5; 10], RATE), PlaySettings::default());
+        let looped = mixer.play(&mono([0.25; 10], RATE), PlaySettings::default().looped());
+
+        let out = left(&render(&mut mixer, 20));
+        assert_close(out[9], 0.75);
+        assert_close(out[10], 0.25);
+        assert!(!mixer.is_playing(id));
+        assert!(mixer.is_playing(looped));
+        assert_eq!(mixer.voice_count(), 1);
+    }
+}
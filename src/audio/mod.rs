@@ -0,0 +1,6 @@
+pub mod sound;
+pub mod mixer;
+pub mod output;
+pub mod spatial;
+pub mod stream;
+pub mod music;
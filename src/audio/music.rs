@@ -0,0 +1,371 @@
+use anyhow::*;
+
+use super::mixer::{Bus, PlaySettings, VoiceId};
+use super::output::AudioPlayer;
+use super::stream::{MusicStream, MusicTrack};
+use crate::assets::vfs::Vfs;
+
+pub const DEFAULT_CROSSFADE: f32 = 1.5;
+
+// Tracks played one after another, optionally shuffled. Shuffling draws a
+// new order every pass, never starting it with the track that just ended.
+#[derive(Debug, Clone)]
+pub struct Playlist {
+    tracks: Vec<MusicTrack>,
+    shuffle: bool,
+    repeat: bool,
+    order: Vec<usize>,
+    cursor: usize,
+}
+
+impl Playlist {
+    pub fn new(tracks: Vec<MusicTrack>) -> Self {
+        let order = (0..tracks.len()).collect();
+        Self {
+            tracks,
+            shuffle: false,
+            repeat: true,
+            order,
+            cursor: 0,
+        }
+    }
+
+    pub fn with_shuffle(mut self) -> Self {
+        self.shuffle = true;
+        self.reorder(None);
+        self
+    }
+
+    // Stop after the last track instead of starting over.
+    pub fn once(mut self) -> Self {
+        self.repeat = false;
+        self
+    }
+
+    pub fn tracks(&self) -> &[MusicTrack] {
+        &self.tracks
+    }
+
+    pub fn next_track(&mut self) -> Option<&MusicTrack> {
+        if self.cursor == self.order.len() {
+            if !self.repeat || self.tracks.is_empty() {
+                return None;
+            }
+            let last = self.order.last().copied();
+            self.reorder(last);
+        }
+        let track = self.order[self.cursor];
+        self.cursor += 1;
+        self.tracks.get(track)
+    }
+
+    fn reorder(&mut self, last: Option<usize>) {
+        self.cursor = 0;
+        if !self.shuffle {
+            return;
+        }
+        fastrand::shuffle(&mut self.order);
+        if self.order.len() > 1 && self.order.first() == last.as_ref() {
+            let swap = fastrand::usize(1..self.order.len());
+            self.order.swap(0, swap);
+        }
+    }
+}
+
+// Background music on the music bus. Changing track crossfades from the
+// old one, and a playlist moves on when its track ends.
+pub struct Music {
+    player: AudioPlayer,
+    vfs: Vfs,
+    crossfade: f32,
+    current: Option<(MusicTrack, VoiceId)>,
+    playlist: Option<Playlist>,
+}
+
+impl Music {
+    pub fn new(player: AudioPlayer, vfs: Vfs) -> Self {
+        Self {
+            player,
+            vfs,
+            crossfade: DEFAULT_CROSSFADE,
+            current: None,
+            playlist: None,
+        }
+    }
+
+    pub fn crossfade(&self) -> f32 {
+        self.crossfade
+    }
+
+    pub fn set_crossfade(&mut self, seconds: f32) {
+        self.crossfade = seconds.max(0.0);
+    }
+
+    pub fn current(&self) -> Option<&MusicTrack> {
+        self.current.as_ref().map(|(track, _)| track)
+    }
+
+    // Crossfades to `track`, leaving any playlist. Does nothing if it is
+    // already playing, so screens can ask for their music every frame.
+    pub fn play(&mut self, track: MusicTrack) -> Result<()> {
+        self.playlist = None;
+        if self.current() == Some(&track) {
+            return Ok(());
+        }
+        self.start(track)
+    }
+
+    pub fn play_playlist(&mut self, mut playlist: Playlist) -> Result<()> {
+        let first = playlist.next_track().cloned();
+        self.playlist = Some(playlist);
+        match first {
+            Some(track) => self.start(track),
+            None => {
+                self.fade_out_current();
+                Ok(())
+            }
+        }
+    }
+
+    // Crossfades to the next playlist track, or fades out without one.
+    pub fn skip(&mut self) -> Result<()> {
+        match self.playlist.as_mut().and_then(|playlist| playlist.next_track().cloned()) {
+            Some(track) => self.start(track),
+            None => {
+                self.stop();
+                Ok(())
+            }
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.playlist = None;
+        self.fade_out_current();
+    }
+
+    // Moves the playlist on once the current track has ended.
+    pub fn update(&mut self) {
+        let Some((_, voice)) = &self.current else {
+            return;
+        };
+        if self.player.is_playing(*voice) {
+            return;
+        }
+        self.current = None;
+        if self.playlist.is_some() {
+            if let Err(e) = self.skip() {
+                log::error!("failed to play the next track: {:#}", e);
+            }
+        }
+    }
+
+    fn start(&mut self, track: MusicTrack) -> Result<()> {
+        let stream = MusicStream::open(&self.vfs, &track)?;
+        self.fade_out_current();
+        let mut mixer = self.player.mixer();
+        let voice = mixer.play_stream(stream, PlaySettings::default().with_bus(Bus::Music));
+        mixer.fade_in(voice, self.crossfade);
+        self.current = Some((track, voice));
+        Ok(())
+    }
+
+    fn fade_out_current(&mut self) {
+        if let Some((_, voice)) = self.current.take() {
+            self.player.mixer().fade_out(voice, self.crossfade);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::assets::vfs::VfsSource;
+    use crate::audio::mixer::{Ducking, OUTPUT_CHANNELS};
+    use crate::audio::output::Audio;
+    use crate::audio::sound::Sound;
+    use crate::audio::stream::LoopRegion;
+
+    const RATE: u32 = 100;
+
+    struct MemorySource(HashMap<String, Vec<u8>>);
+
+    impl VfsSource for MemorySource {
+        fn read(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+            io::Result::Ok(self.0.get(path).cloned())
+        }
+    }
+
+    fn wav(samples: impl IntoIterator<Item = f32>) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: RATE,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut bytes = io::Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for sample in samples {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+        bytes.into_inner()
+    }
+
+    // Four seconds at a level each, and a track whose intro differs from
+    // its loop.
+    fn music(audio: &Audio) -> Music {
+        let files = [
+            ("loud.wav".to_string(), wav([0.5; 400])),
+            ("quiet.wav".to_string(), wav([0.25; 400])),
+            ("intro.wav".to_string(), wav([0.1, 0.1, 0.2, 0.3])),
+        ];
+        let vfs = Vfs::new();
+        vfs.mount("music", 0, MemorySource(files.into_iter().collect()));
+        Music::new(audio.player().clone(), vfs)
+    }
+
+    // The left channel of the next `frames` frames. Each is rendered once
+    // the decoders have it, so a slow decoder thread can't leave gaps.
+    fn render(audio: &Audio, frames: usize) -> Vec<f32> {
+        let start = Instant::now();
+        let mut left = Vec::new();
+        for _ in 0..frames {
+            while audio.player().mixer().is_buffering() {
+                assert!(start.elapsed() < Duration::from_secs(10), "the decoder stalled");
+                thread::sleep(Duration::from_millis(1));
+            }
+            let mut out = [0.0; OUTPUT_CHANNELS];
+            audio.render(&mut out).unwrap();
+            left.push(out[0]);
+        }
+        left
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn crossfades_between_tracks() {
+        let audio = Audio::null(RATE);
+        let mut music = music(&audio);
+        music.set_crossfade(1.0);
+        music.play(MusicTrack::new("music/loud.wav")).unwrap();
+        let out = render(&audio, 100);
+        assert_close(out[0], 0.0);
+        assert_close(out[50], 0.25);
+
+        // Asking again for what is playing changes nothing.
+        music.play(MusicTrack::new("music/loud.wav")).unwrap();
+        assert_eq!(audio.player().mixer().voice_count(), 1);
+
+        music.play(MusicTrack::new("music/quiet.wav")).unwrap();
+        let out = render(&audio, 100);
+        assert_close(out[0], 0.5);
+        assert_close(out[25], 0.5 * 0.75 + 0.25 * 0.25);
+        assert_close(out[75], 0.5 * 0.25 + 0.25 * 0.75);
+        assert_eq!(music.current(), Some(&MusicTrack::new("music/quiet.wav")));
+        assert!(render(&audio, 10).into_iter().all(|sample| (sample - 0.25).abs() < 1e-4));
+        // The old track stopped once faded out.
+        assert_eq!(audio.player().mixer().voice_count(), 1);
+
+        music.stop();
+        render(&audio, 110);
+        assert_eq!(music.current(), None);
+        assert_eq!(audio.player().mixer().voice_count(), 0);
+    }
+
+    #[test]
+    fn plays_the_intro_once_then_loops() {
+        let audio = Audio::null(RATE);
+        let mut music = music(&audio);
+        music.set_crossfade(0.0);
+        let region = LoopRegion {
+            start: 2,
+            end: None,
+        };
+        music.play(MusicTrack::new("music/intro.wav").with_loop(region)).unwrap();
+        let out = render(&audio, 9);
+        let expected = [0.1, 0.1, 0.2, 0.3, 0.2, 0.3, 0.2, 0.3, 0.2];
+        for (sample, expected) in out.into_iter().zip(expected) {
+            assert_close(sample, expected);
+        }
+    }
+
+    #[test]
+    fn ducks_under_voices() {
+        let audio = Audio::null(RATE);
+        let mut music = music(&audio);
+        music.set_crossfade(0.0);
+        audio.player().mixer().set_ducking(Some(Ducking {
+            level: 0.2,
+            attack: 0.5,
+            release: 0.5,
+            ..Ducking::default()
+        }));
+        music.play(MusicTrack::new("music/loud.wav")).unwrap();
+        assert_close(render(&audio, 10)[9], 0.5);
+
+        // Half a second of silent dialogue.
+        let line = Sound {
+            sample_rate: RATE,
+            channels: 1,
+            samples: [0.0; 50].into(),
+        };
+        audio.player().play(&line, PlaySettings::default().with_bus(Bus::Voice));
+        let out = render(&audio, 60);
+        assert_close(out[0], 0.5);
+        assert_close(out[25], 0.5 * 0.6);
+        assert_close(out[49], 0.5 * (1.0 - 0.8 * 49.0 / 50.0));
+        // Released once the line ends.
+        assert_close(out[50], 0.5 * 0.2);
+        assert_close(out[55], 0.5 * (0.2 + 0.8 * 5.0 / 50.0));
+        assert_close(*render(&audio, 50).last().unwrap(), 0.5);
+    }
+
+    #[test]
+    fn playlists_move_on_when_tracks_end() {
+        let audio = Audio::null(RATE);
+        let mut music = music(&audio);
+        music.set_crossfade(0.0);
+        let playlist = Playlist::new(vec![MusicTrack::new("music/intro.wav"), MusicTrack::new("music/quiet.wav")]);
+        music.play_playlist(playlist.once()).unwrap();
+        render(&audio, 10);
+        music.update();
+        assert_eq!(music.current(), Some(&MusicTrack::new("music/quiet.wav")));
+        assert_close(render(&audio, 1)[0], 0.25);
+    }
+
+    #[test]
+    fn shuffles_every_pass_without_repeating_across_passes() {
+        fastrand::seed(7);
+        let tracks: Vec<MusicTrack> = (0..5).map(|i| MusicTrack::new(format!("{}.ogg", i))).collect();
+        let mut playlist = Playlist::new(tracks.clone()).with_shuffle();
+        let mut last = None;
+        let mut orders = Vec::new();
+        for _ in 0..50 {
+            let pass: Vec<MusicTrack> = (0..5).map(|_| playlist.next_track().unwrap().clone()).collect();
+            let mut sorted = pass.clone();
+            sorted.sort_by(|a, b| a.path.cmp(&b.path));
+            assert_eq!(sorted, tracks);
+            assert_ne!(pass.first(), last.as_ref());
+            last = pass.last().cloned();
+            orders.push(pass);
+        }
+        assert!(orders.iter().any(|order| order != &orders[0]));
+
+        // In order without shuffling, and only once if asked.
+        let mut playlist = Playlist::new(tracks.clone()).once();
+        for track in &tracks {
+            assert_eq!(playlist.next_track(), Some(track));
+        }
+        assert_eq!(playlist.next_track(), None);
+    }
+}
@@ -0,0 +1,256 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::*;
+
+use super::mixer::{Bus, Mixer, PlaySettings, VoiceId};
+use super::sound::Sound;
+use super::spatial::AudioListener;
+
+// Rate used when there is no device to ask.
+pub const DEFAULT_SAMPLE_RATE: u32 = 48_000;
+
+// Cheap to clone handle to the mixer. Screens reach it through
+// `State::audio`, ECS systems can keep a clone.
+#[derive(Clone)]
+pub struct AudioPlayer {
+    mixer: Arc<Mutex<Mixer>>,
+    listener: Arc<Mutex<AudioListener>>,
+}
+
+impl AudioPlayer {
+    fn new(mixer: Mixer) -> Self {
+        Self {
+            mixer: Arc::new(Mutex::new(mixer)),
+            listener: Arc::new(Mutex::new(AudioListener::default())),
+        }
+    }
+
+    // The output callback runs mid-mix while this is held, keep it short.
+    pub fn mixer(&self) -> MutexGuard<'_, Mixer> {
+        self.mixer.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.mixer().sample_rate()
+    }
+
+    pub fn play(&self, sound: &Sound, settings: PlaySettings) -> VoiceId {
+        self.mixer().play(sound, settings)
+    }
+
+    pub fn stop(&self, id: VoiceId) {
+        self.mixer().stop(id);
+    }
+
+    pub fn stop_all(&self) {
+        self.mixer().stop_all();
+    }
+
+    pub fn is_playing(&self, id: VoiceId) -> bool {
+        self.mixer().is_playing(id)
+    }
+
+    pub fn pause(&self, id: VoiceId) {
+        self.mixer().set_paused(id, true);
+    }
+
+    pub fn resume(&self, id: VoiceId) {
+        self.mixer().set_paused(id, false);
+    }
+
+    pub fn set_volume(&self, id: VoiceId, volume: f32) {
+        self.update(id, |settings| settings.volume = volume);
+    }
+
+    pub fn set_pitch(&self, id: VoiceId, pitch: f32) {
+        self.update(id, |settings| settings.pitch = pitch);
+    }
+
+    pub fn set_pan(&self, id: VoiceId, pan: f32) {
+        self.update(id, |settings| settings.pan = pan);
+    }
+
+    pub fn set_looping(&self, id: VoiceId, looping: bool) {
+        self.update(id, |settings| settings.looping = looping);
+    }
+
+    pub fn bus_volume(&self, bus: Bus) -> f32 {
+        self.mixer().bus_volume(bus)
+    }
+
+    pub fn set_bus_volume(&self, bus: Bus, volume: f32) {
+        self.mixer().set_bus_volume(bus, volume);
+    }
+
+    pub fn master_volume(&self) -> f32 {
+        self.mixer().master_volume()
+    }
+
+    pub fn set_master_volume(&self, volume: f32) {
+        self.mixer().set_master_volume(volume);
+    }
+
+    // Where spatial sounds are heard from; `State` keeps it on the listener
+    // camera.
+    pub fn listener(&self) -> AudioListener {
+        *self.listener.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn set_listener(&self, listener: AudioListener) {
+        *self.listener.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = listener;
+    }
+
+    fn update(&self, id: VoiceId, f: impl FnOnce(&mut PlaySettings)) {
+        if let Some(settings) = self.mixer().settings_mut(id) {
+            f(settings);
+        }
+    }
+}
+
+enum Output {
+    // Nothing pulls from the mixer; `Audio::render` does it by hand.
+    Null,
+    // Kept alive so the stream keeps playing.
+    #[cfg(feature = "audio-output")]
+    Device { _stream: cpal::Stream },
+}
+
+// The mixer together with whatever consumes it. Dropping this stops the
+// device stream.
+pub struct Audio {
+    player: AudioPlayer,
+    output: Output,
+}
+
+impl Audio {
+    // Plays through the default output device, falling back to the null
+    // output when there is none or the `audio-output` feature is off.
+    pub fn new() -> Self {
+        #[cfg(feature = "audio-output")]
+        match device::open() {
+            Result::Ok((player, stream)) => {
+                return Self {
+                    player,
+                    output: Output::Device { _stream: stream },
+                }
+            }
+            Err(e) => log::warn!("no audio output device, sound will not be heard: {:#}", e),
+        }
+        #[cfg(not(feature = "audio-output"))]
+        log::warn!("built without the `audio-output` feature, sound will not be heard");
+        Self::null(DEFAULT_SAMPLE_RATE)
+    }
+
+    // An output nobody listens to. Use `render` to pull the mix, e.g. to
+    // check it offline.
+    pub fn null(sample_rate: u32) -> Self {
+        Self {
+            player: AudioPlayer::new(Mixer::new(sample_rate)),
+            output: Output::Null,
+        }
+    }
+
+    pub fn player(&self) -> &AudioPlayer {
+        &self.player
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self.output, Output::Null)
+    }
+
+    // Mixes the next `out.len() / 2` frames into `out`, interleaved stereo,
+    // as a device would. Only the null output allows this.
+    pub fn render(&self, out: &mut [f32]) -> Result<()> {
+        ensure!(self.is_null(), "the mix is consumed by an output device");
+        self.player.mixer().render(out);
+        Ok(())
+    }
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "audio-output")]
+mod device {
+    use anyhow::*;
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+    use super::super::mixer::{Mixer, OUTPUT_CHANNELS};
+    use super::AudioPlayer;
+
+    pub(super) fn open() -> Result<(AudioPlayer, cpal::Stream)> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .context("no default output device")?;
+        let supported = device.default_output_config()?;
+        let format = supported.sample_format();
+        let config: cpal::StreamConfig = supported.into();
+        let player = AudioPlayer::new(Mixer::new(config.sample_rate.0));
+        let stream = match format {
+            cpal::SampleFormat::F32 => build::<f32>(&device, &config, player.clone())?,
+            cpal::SampleFormat::I16 => build::<i16>(&device, &config, player.clone())?,
+            cpal::SampleFormat::U16 => build::<u16>(&device, &config, player.clone())?,
+            other => bail!("unsupported sample format {:?}", other),
+        };
+        stream.play()?;
+        Ok((player, stream))
+    }
+
+    fn build<T>(device: &cpal::Device, config: &cpal::StreamConfig, player: AudioPlayer) -> Result<cpal::Stream>
+    where
+        T: cpal::SizedSample + cpal::FromSample<f32>,
+    {
+        let channels = config.channels as usize;
+        let mut mix = Vec::new();
+        let stream = device.build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                let frames = data.len() / channels;
+                mix.resize(frames * OUTPUT_CHANNELS, 0.0);
+                player.mixer().render(&mut mix);
+                // Mono devices get the downmix, extra channels stay silent.
+                for (out, stereo) in data.chunks_exact_mut(channels).zip(mix.chunks_exact(OUTPUT_CHANNELS)) {
+                    for (channel, sample) in out.iter_mut().enumerate() {
+                        let value = match (channels, channel) {
+                            (1, _) => (stereo[0] + stereo[1]) * 0.5,
+                            (_, 0) => stereo[0],
+                            (_, 1) => stereo[1],
+                            _ => 0.0,
+                        };
+                        *sample = T::from_sample(value);
+                    }
+                }
+            },
+            |e| log::error!("audio output error: {}", e),
+            None,
+        )?;
+        Ok(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn null_output_renders_what_the_player_plays() {
+        let audio = Audio::null(100);
+        let sound = Sound {
+            sample_rate: 100,
+            channels: 2,
+            samples: [0.5, -0.5].repeat(4).into(),
+        };
+        let id = audio.player().play(&sound, PlaySettings::default());
+        audio.player().set_volume(id, 0.5);
+
+        let mut out = vec![1.0; 12];
+        audio.render(&mut out).unwrap();
+        // Four frames of sound, then silence.
+        let expected: Vec<f32> = [0.25, -0.25].repeat(4).into_iter().chain([0.0; 4]).collect();
+        assert_eq!(out, expected);
+        assert!(!audio.player().is_playing(id));
+    }
+}
@@ -0,0 +1,83 @@
+use std::{io::Cursor, sync::Arc};
+
+use anyhow::*;
+
+// Decoded PCM audio. Samples are interleaved by channel and normalised to
+// -1..1; cloning shares them.
+#[derive(Debug, Clone)]
+pub struct Sound {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub samples: Arc<[f32]>,
+}
+
+impl Sound {
+    // `extension` picks the decoder: wav, ogg (Vorbis) or flac.
+    pub fn decode(bytes: &[u8], extension: &str) -> Result<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "wav" => decode_wav(bytes),
+            "ogg" => decode_ogg(bytes),
+            "flac" => decode_flac(bytes),
+            other => bail!("unsupported sound format '{}'", other),
+        }
+    }
+
+    // Frames, i.e. samples per channel.
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+
+    pub fn duration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(self.frames() as f64 / self.sample_rate.max(1) as f64)
+    }
+}
+
+fn decode_wav(bytes: &[u8]) -> Result<Sound> {
+    let mut reader = hound::WavReader::new(Cursor::new(bytes))?;
+    let spec = reader.spec();
+    let samples = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<_>, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|s| s as f32 * scale))
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+    Ok(Sound {
+        sample_rate: spec.sample_rate,
+        channels: spec.channels,
+        samples: samples.into(),
+    })
+}
+
+fn decode_ogg(bytes: &[u8]) -> Result<Sound> {
+    let mut reader = lewton::inside_ogg::OggStreamReader::new(Cursor::new(bytes))?;
+    let sample_rate = reader.ident_hdr.audio_sample_rate;
+    let channels = reader.ident_hdr.audio_channels as u16;
+    let mut samples = Vec::new();
+    while let Some(packet) = reader.read_dec_packet_itl()? {
+        samples.extend(packet.iter().map(|&s| s as f32 / 32768.0));
+    }
+    Ok(Sound {
+        sample_rate,
+        channels,
+        samples: samples.into(),
+    })
+}
+
+fn decode_flac(bytes: &[u8]) -> Result<Sound> {
+    let mut reader = claxon::FlacReader::new(Cursor::new(bytes))?;
+    let info = reader.streaminfo();
+    let scale = 1.0 / (1u64 << (info.bits_per_sample - 1)) as f32;
+    let samples = reader
+        .samples()
+        .map(|sample| sample.map(|s| s as f32 * scale))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Sound {
+        sample_rate: info.sample_rate,
+        channels: info.channels as u16,
+        samples: samples.into(),
+    })
+}
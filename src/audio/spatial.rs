@@ -0,0 +1,450 @@
+use ultraviolet as uv;
+
+use super::mixer::{Mixer, PlaySettings, VoiceId};
+use super::output::AudioPlayer;
+use super::sound::Sound;
+use crate::camera::Camera;
+
+// Metres per second, assuming world units are metres.
+pub const SPEED_OF_SOUND: f32 = 343.0;
+
+// Where the mix is heard from. `State` keeps the player's listener on the
+// active camera; an entity with this component overrides it in
+// `SpatialAudioSystem`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AudioListener {
+    pub position: uv::Vec3,
+    pub forward: uv::Vec3,
+    pub up: uv::Vec3,
+    pub velocity: uv::Vec3,
+}
+
+impl Default for AudioListener {
+    fn default() -> Self {
+        Self {
+            position: uv::Vec3::zero(),
+            forward: -uv::Vec3::unit_z(),
+            up: uv::Vec3::unit_y(),
+            velocity: uv::Vec3::zero(),
+        }
+    }
+}
+
+impl AudioListener {
+    pub fn from_camera(camera: &Camera) -> Self {
+        let mut listener = Self::default();
+        listener.follow(camera, 0.0);
+        listener
+    }
+
+    // Moves to `camera`, taking the velocity from how far it moved over `dt`
+    // seconds. A zero `dt` leaves the listener at rest.
+    pub fn follow(&mut self, camera: &Camera, dt: f32) {
+        self.velocity = if dt > 0.0 {
+            (camera.eye - self.position) / dt
+        } else {
+            uv::Vec3::zero()
+        };
+        self.position = camera.eye;
+        let forward = camera.target - camera.eye;
+        if forward.mag_sq() > 0.0 {
+            self.forward = forward.normalized();
+        }
+        self.up = camera.up.normalized();
+    }
+
+    pub fn right(&self) -> uv::Vec3 {
+        self.forward.cross(self.up).normalized()
+    }
+}
+
+// How gain falls off with distance from the listener.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Attenuation {
+    None,
+    // Full volume up to `min_distance`, silent from `max_distance`.
+    Linear { min_distance: f32, max_distance: f32 },
+    // Physically plausible 1/d falloff, full volume inside `reference_distance`.
+    Inverse { reference_distance: f32, rolloff: f32 },
+    Exponential { reference_distance: f32, rolloff: f32 },
+}
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Attenuation::Inverse {
+            reference_distance: 1.0,
+            rolloff: 1.0,
+        }
+    }
+}
+
+impl Attenuation {
+    pub fn gain(&self, distance: f32) -> f32 {
+        match *self {
+            Attenuation::None => 1.0,
+            Attenuation::Linear { min_distance, max_distance } => {
+                if distance <= min_distance {
+                    1.0
+                } else if distance >= max_distance {
+                    0.0
+                } else {
+                    1.0 - (distance - min_distance) / (max_distance - min_distance)
+                }
+            }
+            Attenuation::Inverse { reference_distance, rolloff } => {
+                let d = distance.max(reference_distance);
+                reference_distance / (reference_distance + rolloff * (d - reference_distance))
+            }
+            Attenuation::Exponential { reference_distance, rolloff } => {
+                let d = distance.max(reference_distance);
+                (d / reference_distance).powf(-rolloff)
+            }
+        }
+    }
+}
+
+// What the listener makes of a source: multipliers for the voice's volume
+// and pitch, and a pan offset.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Spatialized {
+    pub gain: f32,
+    pub pan: f32,
+    pub pitch: f32,
+}
+
+// `doppler_factor` scales the pitch shift, 0 turns it off.
+pub fn spatialize(
+    listener: &AudioListener,
+    position: uv::Vec3,
+    velocity: uv::Vec3,
+    attenuation: Attenuation,
+    doppler_factor: f32,
+) -> Spatialized {
+    let offset = position - listener.position;
+    let distance = offset.mag();
+    if distance <= f32::EPSILON {
+        return Spatialized {
+            gain: attenuation.gain(0.0),
+            pan: 0.0,
+            pitch: 1.0,
+        };
+    }
+    let direction = offset / distance;
+    // Positive speeds close the gap: listener towards source, source
+    // towards listener.
+    let max_speed = SPEED_OF_SOUND * 0.99;
+    let listener_speed = (listener.velocity.dot(direction) * doppler_factor).clamp(-max_speed, max_speed);
+    let source_speed = (-velocity.dot(direction) * doppler_factor).clamp(-max_speed, max_speed);
+    Spatialized {
+        gain: attenuation.gain(distance),
+        pan: direction.dot(listener.right()).clamp(-1.0, 1.0),
+        pitch: (SPEED_OF_SOUND + listener_speed) / (SPEED_OF_SOUND - source_speed),
+    }
+}
+
+// A sound placed in the world. Plays once it is first updated; the voice
+// follows the emitter until it ends or `stop` is called.
+pub struct AudioEmitter {
+    pub sound: Sound,
+    pub settings: PlaySettings,
+    pub attenuation: Attenuation,
+    pub position: uv::Vec3,
+    velocity: uv::Vec3,
+    last_position: Option<uv::Vec3>,
+    voice: Option<VoiceId>,
+    wants_playing: bool,
+    // Set by `play`, consumed by the next update: the voice starts over even
+    // if it is still playing or has already ended.
+    restart: bool,
+}
+
+impl AudioEmitter {
+    pub fn new(sound: Sound, settings: PlaySettings) -> Self {
+        Self {
+            sound,
+            settings,
+            attenuation: Attenuation::default(),
+            position: uv::Vec3::zero(),
+            velocity: uv::Vec3::zero(),
+            last_position: None,
+            voice: None,
+            wants_playing: true,
+            restart: false,
+        }
+    }
+
+    pub fn with_attenuation(mut self, attenuation: Attenuation) -> Self {
+        self.attenuation = attenuation;
+        self
+    }
+
+    pub fn with_position(mut self, position: uv::Vec3) -> Self {
+        self.position = position;
+        self
+    }
+
+    // Restarts the sound on the next update.
+    pub fn play(&mut self) {
+        self.wants_playing = true;
+        self.restart = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.wants_playing = false;
+        self.restart = false;
+    }
+
+    pub fn voice(&self) -> Option<VoiceId> {
+        self.voice
+    }
+
+    // Derived from how far the emitter moved between updates.
+    pub fn velocity(&self) -> uv::Vec3 {
+        self.velocity
+    }
+}
+
+// Ray from the listener to an emitter in, 0 (clear) to 1 (fully blocked)
+// out. Fed by whatever can trace the scene.
+pub type OcclusionFn = Box<dyn FnMut(uv::Vec3, uv::Vec3) -> f32>;
+
+// Drives emitter voices from their position relative to a listener.
+pub struct SpatialAudio {
+    player: AudioPlayer,
+    doppler_factor: f32,
+    occlusion: Option<OcclusionFn>,
+}
+
+impl SpatialAudio {
+    pub fn new(player: AudioPlayer) -> Self {
+        Self {
+            player,
+            doppler_factor: 1.0,
+            occlusion: None,
+        }
+    }
+
+    pub fn with_doppler_factor(mut self, doppler_factor: f32) -> Self {
+        self.doppler_factor = doppler_factor;
+        self
+    }
+
+    pub fn with_occlusion(mut self, occlusion: impl FnMut(uv::Vec3, uv::Vec3) -> f32 + 'static) -> Self {
+        self.occlusion = Some(Box::new(occlusion));
+        self
+    }
+
+    pub fn player(&self) -> &AudioPlayer {
+        &self.player
+    }
+
+    // Starts and stops voices as requested and respatializes the playing
+    // ones. `dt` is the time since the last update, for Doppler.
+    pub fn update<'a>(&mut self, listener: &AudioListener, emitters: impl IntoIterator<Item = &'a mut AudioEmitter>, dt: f32) {
+        let mut mixer = self.player.mixer();
+        for emitter in emitters {
+            emitter.velocity = match emitter.last_position {
+                Some(last) if dt > 0.0 => (emitter.position - last) / dt,
+                _ => uv::Vec3::zero(),
+            };
+            emitter.last_position = Some(emitter.position);
+
+            if let Some(voice) = emitter.voice {
+                if !mixer.is_playing(voice) {
+                    // Ran out on its own, don't start it again unless
+                    // `play` asked for it since.
+                    emitter.voice = None;
+                    emitter.wants_playing = emitter.restart;
+                } else if !emitter.wants_playing || emitter.restart {
+                    mixer.stop(voice);
+                    emitter.voice = None;
+                }
+            }
+            emitter.restart = false;
+            if !emitter.wants_playing {
+                continue;
+            }
+
+            let mut spatial = spatialize(
+                listener,
+                emitter.position,
+                emitter.velocity,
+                emitter.attenuation,
+                self.doppler_factor,
+            );
+            if let Some(occlusion) = self.occlusion.as_mut() {
+                spatial.gain *= 1.0 - occlusion(listener.position, emitter.position).clamp(0.0, 1.0);
+            }
+            let settings = PlaySettings {
+                volume: emitter.settings.volume * spatial.gain,
+                pitch: emitter.settings.pitch * spatial.pitch,
+                pan: (emitter.settings.pan + spatial.pan).clamp(-1.0, 1.0),
+                ..emitter.settings
+            };
+            apply(&mut mixer, emitter, settings);
+        }
+    }
+}
+
+fn apply(mixer: &mut Mixer, emitter: &mut AudioEmitter, settings: PlaySettings) {
+    match emitter.voice.and_then(|voice| mixer.settings_mut(voice)) {
+        Some(current) => *current = settings,
+        None => emitter.voice = Some(mixer.play(&emitter.sound, settings)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::mixer::OUTPUT_CHANNELS;
+    use crate::audio::output::Audio;
+
+    const RATE: u32 = 100;
+
+    fn tone(frames: usize) -> Sound {
+        Sound {
+            sample_rate: RATE,
+            channels: 1,
+            samples: vec![1.0; frames].into(),
+        }
+    }
+
+    fn render(player: &AudioPlayer, frames: usize) -> Vec<f32> {
+        let mut out = vec![0.0; frames * OUTPUT_CHANNELS];
+        player.mixer().render(&mut out);
+        out
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn pans_towards_the_side_of_the_source() {
+        // The default listener faces -z, so +x is to its right.
+        let listener = AudioListener::default();
+        let left = spatialize(&listener, uv::Vec3::new(-2.0, 0.0, 0.0), uv::Vec3::zero(), Attenuation::None, 1.0);
+        let right = spatialize(&listener, uv::Vec3::new(2.0, 0.0, 0.0), uv::Vec3::zero(), Attenuation::None, 1.0);
+        let ahead = spatialize(&listener, uv::Vec3::new(0.0, 0.0, -2.0), uv::Vec3::zero(), Attenuation::None, 1.0);
+        assert_close(left.pan, -1.0);
+        assert_close(right.pan, 1.0);
+        assert_close(ahead.pan, 0.0);
+
+        // And the mix follows: a source on the right is silent on the left.
+        let audio = Audio::null(RATE);
+        let mut spatial = SpatialAudio::new(audio.player().clone());
+        let mut emitter = AudioEmitter::new(tone(100), PlaySettings::default())
+            .with_attenuation(Attenuation::None)
+            .with_position(uv::Vec3::new(3.0, 0.0, 0.0));
+        spatial.update(&listener, [&mut emitter], 0.0);
+        for frame in render(audio.player(), 10).chunks_exact(OUTPUT_CHANNELS) {
+            assert_close(frame[0], 0.0);
+            assert_close(frame[1], 1.0);
+        }
+    }
+
+    #[test]
+    fn attenuation_falls_off_with_distance() {
+        let inverse = Attenuation::Inverse {
+            reference_distance: 1.0,
+            rolloff: 1.0,
+        };
+        assert_close(inverse.gain(0.5), 1.0);
+        assert_close(inverse.gain(2.0), 0.5);
+        assert_close(inverse.gain(4.0), 0.25);
+
+        let linear = Attenuation::Linear {
+            min_distance: 2.0,
+            max_distance: 6.0,
+        };
+        assert_close(linear.gain(1.0), 1.0);
+        assert_close(linear.gain(3.0), 0.75);
+        assert_close(linear.gain(10.0), 0.0);
+
+        let exponential = Attenuation::Exponential {
+            reference_distance: 1.0,
+            rolloff: 2.0,
+        };
+        assert_close(exponential.gain(2.0), 0.25);
+
+        let listener = AudioListener::default();
+        let gains: Vec<f32> = [1.0, 2.0, 4.0, 8.0]
+            .iter()
+            .map(|&z| spatialize(&listener, uv::Vec3::new(0.0, 0.0, -z), uv::Vec3::zero(), inverse, 1.0).gain)
+            .collect();
+        assert!(gains.windows(2).all(|pair| pair[1] < pair[0]), "{:?}", gains);
+    }
+
+    #[test]
+    fn doppler_raises_the_pitch_of_approaching_sources() {
+        let listener = AudioListener::default();
+        let position = uv::Vec3::new(0.0, 0.0, -10.0);
+        let towards = uv::Vec3::new(0.0, 0.0, SPEED_OF_SOUND * 0.1);
+
+        let approaching = spatialize(&listener, position, towards, Attenuation::None, 1.0);
+        assert!(approaching.pitch > 1.0);
+        assert_close(approaching.pitch, 1.0 / 0.9);
+
+        let receding = spatialize(&listener, position, -towards, Attenuation::None, 1.0);
+        assert!(receding.pitch < 1.0);
+
+        let moving_listener = AudioListener {
+            velocity: -towards,
+            ..listener
+        };
+        assert!(spatialize(&moving_listener, position, uv::Vec3::zero(), Attenuation::None, 1.0).pitch > 1.0);
+
+        assert_close(spatialize(&listener, position, towards, Attenuation::None, 0.0).pitch, 1.0);
+    }
+
+    #[test]
+    fn play_restarts_a_playing_voice() {
+        let audio = Audio::null(RATE);
+        let player = audio.player();
+        let mut spatial = SpatialAudio::new(player.clone());
+        let listener = AudioListener::default();
+        let mut emitter = AudioEmitter::new(tone(100), PlaySettings::default());
+
+        spatial.update(&listener, [&mut emitter], 0.0);
+        let first = emitter.voice().unwrap();
+        render(player, 50);
+
+        emitter.play();
+        spatial.update(&listener, [&mut emitter], 0.0);
+        let second = emitter.voice().unwrap();
+        assert_ne!(first, second);
+        assert!(!player.is_playing(first));
+        assert!(player.is_playing(second));
+        // Back at the start, so the full sound is left.
+        render(player, 99);
+        assert!(player.is_playing(second));
+    }
+
+    #[test]
+    fn play_after_a_one_shot_ended_starts_it_again() {
+        let audio = Audio::null(RATE);
+        let player = audio.player();
+        let mut spatial = SpatialAudio::new(player.clone());
+        let listener = AudioListener::default();
+        let mut emitter = AudioEmitter::new(tone(10), PlaySettings::default());
+
+        spatial.update(&listener, [&mut emitter], 0.0);
+        let first = emitter.voice().unwrap();
+        render(player, 20);
+        assert!(!player.is_playing(first));
+
+        // Asked for before the update noticed it ended.
+        emitter.play();
+        spatial.update(&listener, [&mut emitter], 0.0);
+        let second = emitter.voice().unwrap();
+        assert_ne!(first, second);
+        assert!(player.is_playing(second));
+
+        // Without a `play`, an ended one-shot stays stopped.
+        render(player, 20);
+        spatial.update(&listener, [&mut emitter], 0.0);
+        assert_eq!(emitter.voice(), None);
+        spatial.update(&listener, [&mut emitter], 0.0);
+        assert_eq!(emitter.voice(), None);
+    }
+}
@@ -0,0 +1,356 @@
+use std::{
+    collections::VecDeque,
+    path::Path,
+    sync::mpsc::{self, Receiver, SyncSender, TryRecvError},
+    thread,
+};
+
+use anyhow::*;
+
+use crate::assets::vfs::{ReadSeek, Vfs};
+
+// Decoded chunks queued ahead of the mixer, a second or so of audio.
+const QUEUED_CHUNKS: usize = 16;
+const WAV_CHUNK_FRAMES: usize = 4096;
+
+// Frames to jump back to on reaching `end` (or the end of the file), so a
+// track can have an intro that is only played once.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LoopRegion {
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+impl LoopRegion {
+    pub fn whole() -> Self {
+        Self { start: 0, end: None }
+    }
+}
+
+// A music file played by streaming from the VFS.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MusicTrack {
+    pub path: String,
+    pub loop_region: Option<LoopRegion>,
+}
+
+impl MusicTrack {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            loop_region: None,
+        }
+    }
+
+    pub fn looped(self) -> Self {
+        self.with_loop(LoopRegion::whole())
+    }
+
+    pub fn with_loop(mut self, loop_region: LoopRegion) -> Self {
+        self.loop_region = Some(loop_region);
+        self
+    }
+}
+
+enum Decoder {
+    Wav(hound::WavReader<Box<dyn ReadSeek>>),
+    Ogg(Box<lewton::inside_ogg::OggStreamReader<Box<dyn ReadSeek>>>),
+    Flac(claxon::FlacReader<Box<dyn ReadSeek>>),
+}
+
+impl Decoder {
+    fn open(input: Box<dyn ReadSeek>, extension: &str) -> Result<Self> {
+        Ok(match extension.to_ascii_lowercase().as_str() {
+            "wav" => Decoder::Wav(hound::WavReader::new(input)?),
+            "ogg" => Decoder::Ogg(Box::new(lewton::inside_ogg::OggStreamReader::new(input)?)),
+            "flac" => Decoder::Flac(claxon::FlacReader::new(input)?),
+            other => bail!("unsupported sound format '{}'", other),
+        })
+    }
+
+    fn format(&self) -> (u32, u16) {
+        match self {
+            Decoder::Wav(reader) => (reader.spec().sample_rate, reader.spec().channels),
+            Decoder::Ogg(reader) => (reader.ident_hdr.audio_sample_rate, reader.ident_hdr.audio_channels as u16),
+            Decoder::Flac(reader) => (reader.streaminfo().sample_rate, reader.streaminfo().channels as u16),
+        }
+    }
+
+    // Next run of interleaved samples, None at the end.
+    fn next_chunk(&mut self) -> Result<Option<Vec<f32>>> {
+        let chunk = match self {
+            Decoder::Wav(reader) => {
+                let spec = reader.spec();
+                let count = WAV_CHUNK_FRAMES * spec.channels as usize;
+                match spec.sample_format {
+                    hound::SampleFormat::Float => reader.samples::<f32>().take(count).collect::<Result<Vec<_>, _>>()?,
+                    hound::SampleFormat::Int => {
+                        let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                        reader
+                            .samples::<i32>()
+                            .take(count)
+                            .map(|sample| sample.map(|s| s as f32 * scale))
+                            .collect::<Result<Vec<_>, _>>()?
+                    }
+                }
+            }
+            Decoder::Ogg(reader) => match reader.read_dec_packet_itl()? {
+                Some(packet) => packet.iter().map(|&s| s as f32 / 32768.0).collect(),
+                None => return Ok(None),
+            },
+            Decoder::Flac(reader) => {
+                let scale = 1.0 / (1u64 << (reader.streaminfo().bits_per_sample - 1)) as f32;
+                let Some(block) = reader.blocks().read_next_or_eof(Vec::new())? else {
+                    return Ok(None);
+                };
+                let mut samples = Vec::with_capacity(block.len() as usize);
+                for frame in 0..block.duration() {
+                    for channel in 0..block.channels() {
+                        samples.push(block.sample(channel, frame) as f32 * scale);
+                    }
+                }
+                samples
+            }
+        };
+        // Vorbis can hand out empty packets mid-stream.
+        if chunk.is_empty() && !matches!(self, Decoder::Ogg(_)) {
+            return Ok(None);
+        }
+        Ok(Some(chunk))
+    }
+}
+
+// Plays a track while a background thread decodes it, so only a little of
+// it is ever held decoded. Pass it to `Mixer::play_stream`.
+pub struct MusicStream {
+    sample_rate: u32,
+    channels: u16,
+    chunks: Receiver<Vec<f32>>,
+    buffer: VecDeque<f32>,
+    ended: bool,
+}
+
+impl MusicStream {
+    pub fn open(vfs: &Vfs, track: &MusicTrack) -> Result<Self> {
+        let extension = Path::new(&track.path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default()
+            .to_string();
+        // Opened here too so a missing or broken file fails the call.
+        let decoder = Decoder::open(vfs.open(&track.path)?, &extension)
+            .with_context(|| format!("failed to open {}", track.path))?;
+        let (sample_rate, channels) = decoder.format();
+        ensure!(channels > 0, "{} has no channels", track.path);
+
+        let (sender, chunks) = mpsc::sync_channel(QUEUED_CHUNKS);
+        let (vfs, track) = (vfs.clone(), track.clone());
+        thread::Builder::new()
+            .name(format!("music {}", track.path))
+            .spawn(move || {
+                if let Err(e) = decode(decoder, &vfs, &track, &extension, channels, sender) {
+                    log::error!("streaming {} failed: {:#}", track.path, e);
+                }
+            })?;
+        Ok(Self {
+            sample_rate,
+            channels,
+            chunks,
+            buffer: VecDeque::new(),
+            ended: false,
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    // Stereo frame `index` counting from the oldest one still buffered.
+    // None if it hasn't been decoded yet or the stream is over.
+    pub(crate) fn frame(&mut self, index: usize) -> Option<(f32, f32)> {
+        let channels = self.channels as usize;
+        while self.buffer.len() < (index + 1) * channels && !self.ended {
+            match self.chunks.try_recv() {
+                Result::Ok(chunk) => self.buffer.extend(chunk),
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => self.ended = true,
+            }
+        }
+        let start = index * channels;
+        match channels {
+            1 => self.buffer.get(start).map(|&s| (s, s)),
+            _ => Some((*self.buffer.get(start)?, *self.buffer.get(start + 1)?)),
+        }
+    }
+
+    pub(crate) fn consume(&mut self, frames: usize) {
+        let samples = (frames * self.channels as usize).min(self.buffer.len());
+        self.buffer.drain(..samples);
+    }
+
+    // True once the whole track has been decoded; what is left is buffered.
+    pub(crate) fn is_ended(&self) -> bool {
+        self.ended
+    }
+}
+
+// Runs on the stream's thread until the track ends or the stream is dropped.
+// Looping reopens the file and decodes up to the loop start, as the
+// decoders can't seek to an exact frame.
+fn decode(
+    mut decoder: Decoder,
+    vfs: &Vfs,
+    track: &MusicTrack,
+    extension: &str,
+    channels: u16,
+    sender: SyncSender<Vec<f32>>,
+) -> Result<()> {
+    let channels = channels as u64;
+    let mut skip: u64 = 0;
+    loop {
+        let end = track.loop_region.and_then(|region| region.end);
+        let mut frame = 0;
+        let mut sent = false;
+        while let Some(chunk) = decoder.next_chunk()? {
+            let len = chunk.len() as u64 / channels;
+            let from = skip.saturating_sub(frame).min(len);
+            let to = end.map_or(len, |end| end.saturating_sub(frame).min(len));
+            if from < to {
+                let samples = chunk[(from * channels) as usize..(to * channels) as usize].to_vec();
+                if sender.send(samples).is_err() {
+                    return Ok(());
+                }
+                sent = true;
+            }
+            frame += len;
+            if end.is_some_and(|end| frame >= end) {
+                break;
+            }
+        }
+        let Some(region) = track.loop_region else {
+            return Ok(());
+        };
+        // An empty loop would spin forever.
+        ensure!(sent, "loop region {:?} is empty", region);
+        skip = region.start;
+        decoder = Decoder::open(vfs.open(&track.path)?, extension)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::assets::vfs::VfsSource;
+
+    struct MemorySource(HashMap<String, Vec<u8>>);
+
+    impl VfsSource for MemorySource {
+        fn read(&self, path: &str) -> io::Result<Option<Vec<u8>>> {
+            io::Result::Ok(self.0.get(path).cloned())
+        }
+    }
+
+    // Mono float WAV counting up from 0 in steps of 1, one frame per step.
+    fn counting(frames: usize) -> Vec<u8> {
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 100,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut bytes = io::Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for frame in 0..frames {
+            writer.write_sample(frame as f32).unwrap();
+        }
+        writer.finalize().unwrap();
+        bytes.into_inner()
+    }
+
+    fn vfs() -> Vfs {
+        let vfs = Vfs::new();
+        let files = [("count.wav".to_string(), counting(10)), ("bad.wav".to_string(), b"RIFF".to_vec())];
+        vfs.mount("", 0, MemorySource(files.into_iter().collect()));
+        vfs
+    }
+
+    // The next `frames` frames' left channel, waiting on the decoder for
+    // each; stops early if the stream ends.
+    fn play(stream: &mut MusicStream, frames: usize) -> Vec<f32> {
+        let start = Instant::now();
+        let mut played = Vec::new();
+        while played.len() < frames {
+            match stream.frame(0) {
+                Some((left, _)) => {
+                    played.push(left);
+                    stream.consume(1);
+                }
+                None if stream.is_ended() => break,
+                None => {
+                    assert!(start.elapsed() < Duration::from_secs(10), "the decoder stalled");
+                    thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }
+        played
+    }
+
+    #[test]
+    fn plays_tracks_once_without_a_loop() {
+        let mut stream = MusicStream::open(&vfs(), &MusicTrack::new("count.wav")).unwrap();
+        assert_eq!((stream.sample_rate(), stream.channels()), (100, 1));
+        assert_eq!(play(&mut stream, 20), [0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]);
+        assert!(stream.is_ended());
+    }
+
+    #[test]
+    fn loops_whole_tracks() {
+        let mut stream = MusicStream::open(&vfs(), &MusicTrack::new("count.wav").looped()).unwrap();
+        let expected: Vec<f32> = (0..25).map(|frame| (frame % 10) as f32).collect();
+        assert_eq!(play(&mut stream, 25), expected);
+    }
+
+    #[test]
+    fn plays_the_intro_once_then_loops_the_region() {
+        let region = LoopRegion {
+            start: 4,
+            end: Some(8),
+        };
+        let mut stream = MusicStream::open(&vfs(), &MusicTrack::new("count.wav").with_loop(region)).unwrap();
+        let expected = [0, 1, 2, 3, 4, 5, 6, 7, 4, 5, 6, 7, 4, 5, 6, 7, 4].map(|frame| frame as f32);
+        assert_eq!(play(&mut stream, expected.len()), expected);
+
+        // Without an end the region runs to the end of the file.
+        let region = LoopRegion { start: 7, end: None };
+        let mut stream = MusicStream::open(&vfs(), &MusicTrack::new("count.wav").with_loop(region)).unwrap();
+        let expected = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 7, 8, 9, 7].map(|frame| frame as f32);
+        assert_eq!(play(&mut stream, expected.len()), expected);
+    }
+
+    #[test]
+    fn empty_loop_regions_end_the_stream() {
+        let region = LoopRegion {
+            start: 5,
+            end: Some(5),
+        };
+        let mut stream = MusicStream::open(&vfs(), &MusicTrack::new("count.wav").with_loop(region)).unwrap();
+        assert_eq!(play(&mut stream, 20), [0.0, 1.0, 2.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn opening_fails_for_missing_or_broken_files() {
+        assert!(MusicStream::open(&vfs(), &MusicTrack::new("missing.wav")).is_err());
+        assert!(MusicStream::open(&vfs(), &MusicTrack::new("bad.wav")).is_err());
+        assert!(MusicStream::open(&vfs(), &MusicTrack::new("count.mp3")).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+// Builds and inspects asset archives.
+//
+//   onion-pack build <asset-dir> <archive>
+//   onion-pack list <archive>
+//   onion-pack verify <archive>
+
+use std::{fs::File, io::BufWriter, process::ExitCode};
+
+use anyhow::*;
+use onion_engine::assets::archive::{Archive, ArchiveBuilder, ArchiveCompression};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["build", dir, archive] => build(dir, archive),
+        ["list", archive] => list(archive),
+        ["verify", archive] => verify(archive),
+        _ => {
+            eprintln!("usage: onion-pack build <asset-dir> <archive>");
+            eprintln!("       onion-pack list <archive>");
+            eprintln!("       onion-pack verify <archive>");
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Result::Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn build(dir: &str, archive: &str) -> Result<()> {
+    let mut builder = ArchiveBuilder::new();
+    builder
+        .add_dir(dir)
+        .with_context(|| format!("failed to read {}", dir))?;
+    let mut out = BufWriter::new(File::create(archive).with_context(|| format!("failed to create {}", archive))?);
+    let entries = builder.write(&mut out)?;
+    let size: u64 = entries.iter().map(|entry| entry.size).sum();
+    let stored: u64 = entries.iter().map(|entry| entry.stored_size).sum();
+    println!("packed {} files, {} -> {} bytes", entries.len(), size, stored);
+    Ok(())
+}
+
+fn list(archive: &str) -> Result<()> {
+    let archive = Archive::open(archive).with_context(|| format!("failed to open {}", archive))?;
+    let mut entries: Vec<_> = archive.entries().collect();
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    for entry in entries {
+        let compression = match entry.compression {
+            ArchiveCompression::Stored => "stored",
+            ArchiveCompression::Deflate => "deflate",
+        };
+        println!("{:>10} {:>10} {:<8} {:08x} {}", entry.size, entry.stored_size, compression, entry.crc32, entry.path);
+    }
+    Ok(())
+}
+
+// Reads every file back, checking its checksum.
+fn verify(archive: &str) -> Result<()> {
+    let archive = Archive::open(archive).with_context(|| format!("failed to open {}", archive))?;
+    let mut failed = 0;
+    for entry in archive.entries() {
+        if let Err(e) = archive.read(&entry.path) {
+            eprintln!("{}", e);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        bail!("{} corrupt files", failed);
+    }
+    println!("all {} files ok", archive.entries().count());
+    Ok(())
+}
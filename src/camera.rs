@@ -1,5 +1,7 @@
 use ultraviolet as uv;
 
+use crate::physics::ray::Ray;
+
 pub struct Camera {
     pub eye: uv::Vec3,
     pub target: uv::Vec3,
@@ -41,6 +43,118 @@ impl Camera {
         );
         proj * view
     }
+
+    // Ray from the eye through a point in normalized device coordinates,
+    // (-1, -1) the bottom-left of the view and (1, 1) the top-right.
+    pub fn ray(&self, ndc: uv::Vec2) -> Ray {
+        let inverse = self.build_view_projection_matrix().inversed();
+        let far = inverse * uv::Vec4::new(ndc.x, ndc.y, 1.0, 1.0);
+        Ray::new(self.eye, far.xyz() / far.w - self.eye)
+    }
+}
+
+// Normalized viewport rect, (0, 0) is the top-left of the target and
+// (1, 1) the bottom-right.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    pub const FULL: Viewport = Viewport {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    // Pixel rect (x, y, width, height) inside a target of the given size.
+    pub fn to_pixels(&self, target_width: u32, target_height: u32) -> (f32, f32, f32, f32) {
+        let x = (self.x * target_width as f32).clamp(0.0, target_width as f32);
+        let y = (self.y * target_height as f32).clamp(0.0, target_height as f32);
+        let width = (self.width * target_width as f32).clamp(0.0, target_width as f32 - x);
+        let height = (self.height * target_height as f32).clamp(0.0, target_height as f32 - y);
+        (x, y, width, height)
+    }
+
+    // Normalized device coordinates of a pixel in the target, None when it
+    // falls outside the viewport.
+    pub fn to_ndc(&self, x: f32, y: f32, target_width: u32, target_height: u32) -> Option<uv::Vec2> {
+        let (left, top, width, height) = self.to_pixels(target_width, target_height);
+        let (u, v) = ((x - left) / width, (y - top) / height);
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+        Some(uv::Vec2::new(u * 2.0 - 1.0, 1.0 - v * 2.0))
+    }
+
+    pub fn aspect(&self, target_width: u32, target_height: u32) -> f32 {
+        let (_, _, width, height) = self.to_pixels(target_width, target_height);
+        if height > 0.0 {
+            width / height
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RenderTargetId(pub(crate) usize);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RenderTarget {
+    Window,
+    Texture(RenderTargetId),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CameraId(pub(crate) usize);
+
+// How a camera is placed in the frame. Cameras are drawn in ascending `order`,
+// so offscreen cameras that feed materials should come before the cameras that
+// sample them. The first camera drawing into a target each frame clears all of
+// it to its `clear_color` (black when None); later cameras on the same target
+// draw over what is already there, so split-screen views don't wipe each other.
+#[derive(Debug, Copy, Clone)]
+pub struct CameraSettings {
+    pub viewport: Viewport,
+    pub order: i32,
+    pub clear_color: Option<wgpu::Color>,
+    pub target: RenderTarget,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            viewport: Viewport::FULL,
+            order: 0,
+            clear_color: Some(wgpu::Color {
+                r: 0.1,
+                g: 0.2,
+                b: 0.3,
+                a: 1.0,
+            }),
+            target: RenderTarget::Window,
+        }
+    }
 }
 
 // We need this for Rust to store our data correctly for the shaders
@@ -51,16 +165,24 @@ pub struct CameraUniform {
     // We can't use cgmath with bytemuck directly, so we'll have
     // to convert the Matrix4 into a 4x4 f32 array
     view_proj: [[f32; 4]; 4],
+    // Unprojects screen positions, e.g. into skybox directions
+    inv_view_proj: [[f32; 4]; 4],
+    view_position: [f32; 4],
 }
 
 impl CameraUniform {
     pub fn new() -> Self {
         Self {
             view_proj: uv::Mat4::identity().into(),
+            inv_view_proj: uv::Mat4::identity().into(),
+            view_position: [0.0; 4],
         }
     }
 
     pub fn update_view_proj(&mut self, camera: &Camera) {
-        self.view_proj = camera.build_view_projection_matrix().into();
+        let view_proj = camera.build_view_projection_matrix();
+        self.view_proj = view_proj.into();
+        self.inv_view_proj = view_proj.inversed().into();
+        self.view_position = camera.eye.into_homogeneous_point().into();
     }
 }
@@ -1,3 +1,8 @@
+use ultraviolet as uv;
+
+use crate::physics::ray::Layers;
+use crate::physics::shape::{Aabb, Shape};
+
 #[derive(Default)]
 pub struct Position {
   pub x: f32,
@@ -8,4 +13,45 @@ pub struct Position {
 pub struct Velocity {
   pub dx: f32,
   pub dy: f32,
-}
\ No newline at end of file
+}
+
+// Full 3D placement, kept in sync with the entity's rigid body if it has one.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+  pub position: uv::Vec3,
+  pub rotation: uv::Rotor3,
+}
+
+impl Default for Transform {
+  fn default() -> Self {
+    Self {
+      position: uv::Vec3::zero(),
+      rotation: uv::Rotor3::identity(),
+    }
+  }
+}
+
+// Local-space extent of an entity for the spatial index systems.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Bounds(pub Aabb);
+
+// Makes an entity hittable by `PickingSystem::pick`, placed like `Bounds`.
+#[derive(Debug, Clone)]
+pub struct Pickable {
+  pub shape: Shape,
+  pub layers: Layers,
+}
+
+impl Pickable {
+  pub fn new(shape: Shape) -> Self {
+    Self {
+      shape,
+      layers: Layers::ALL,
+    }
+  }
+
+  pub fn with_layers(mut self, layers: Layers) -> Self {
+    self.layers = layers;
+    self
+  }
+}
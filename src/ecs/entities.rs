@@ -0,0 +1,2 @@
+// Entities are plain ids; their components live in `ECSWorld`.
+pub type Entity = u32;
@@ -1,4 +1,10 @@
 pub mod movement_system;
+pub mod spatial_audio_system;
+pub mod physics_system;
+pub mod physics2d_system;
+pub mod spatial_index_system;
+pub mod spatial_hash_system;
+pub mod picking_system;
 
 use std::{any::Any, collections::HashMap};
 
@@ -17,14 +17,19 @@ impl System for MovementSystem {
       // Create a clone of the entities hashmap to avoid borrow checker issues
       
       for (entity_id, components) in entities.iter_mut() {
+          // Bodies in a 2D physics world are moved by it
+          if components.contains_key("RigidBody2d") {
+              continue;
+          }
           // Check if the entity has both Position and Velocity components
-          if let (Some(position), Some(velocity)) = (
+          let velocity = components.get("Velocity").and_then(|vel| vel.downcast_ref::<Velocity>()).map(|v| (v.dx, v.dy));
+          if let (Some(position), Some((dx, dy))) = (
               components.get_mut("Position").and_then(|pos| pos.downcast_mut::<Position>()),
-              components.get_mut("Velocity").and_then(|vel| vel.downcast_mut::<Velocity>()),
+              velocity,
           ) {
               // Update the position based on velocity
-              position.x += velocity.dx;
-              position.y += velocity.dy;
+              position.x += dx;
+              position.y += dy;
           }
       }
   }
@@ -0,0 +1,109 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use ultraviolet as uv;
+
+use crate::ecs::components::{Position, Transform};
+use crate::physics::{
+    body::{BodyHandle, BodyKind},
+    events::CollisionEvent,
+};
+use crate::physics2d::world::PhysicsWorld;
+use crate::time::FrameClock;
+
+use super::System;
+
+// The 2D counterpart of `PhysicsSystem`: steps a shared 2D world at its
+// fixed rate and syncs the bodies of entities with a "RigidBody2d" component
+// (a `BodyHandle` from that world) through "Position". Kinematic bodies
+// follow it; everything else writes back to it, and to x, y and the turn
+// about z of "Transform" if there is one. `MovementSystem` leaves these
+// entities alone, as their velocity is the body's.
+pub struct Physics2dSystem {
+    world: Arc<Mutex<PhysicsWorld>>,
+    clock: FrameClock,
+    events: Arc<Mutex<Vec<CollisionEvent<u32>>>>,
+}
+
+impl Physics2dSystem {
+    pub fn new(world: Arc<Mutex<PhysicsWorld>>) -> Self {
+        Physics2dSystem {
+            world,
+            clock: FrameClock::new(),
+            events: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn world(&self) -> &Arc<Mutex<PhysicsWorld>> {
+        &self.world
+    }
+
+    // This frame's events between entities; ones involving bodies without
+    // an entity are left out.
+    pub fn events(&self) -> &Arc<Mutex<Vec<CollisionEvent<u32>>>> {
+        &self.events
+    }
+}
+
+impl System for Physics2dSystem {
+  fn update(&mut self, entities: &mut HashMap<u32, HashMap<String, Box<dyn Any>>>) {
+      let dt = self.clock.tick();
+      let mut world = self.world.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+      let mut owners = HashMap::new();
+      for (entity_id, components) in entities.iter() {
+          let Some(handle) = components.get("RigidBody2d").and_then(|h| h.downcast_ref::<BodyHandle>()) else {
+              continue;
+          };
+          owners.insert(*handle, *entity_id);
+          let position = components.get("Position").and_then(|p| p.downcast_ref::<Position>());
+          let transform = components.get("Transform").and_then(|t| t.downcast_ref::<Transform>());
+          if let (Some(body), Some(position)) = (world.body_mut(*handle), position) {
+              if body.kind == BodyKind::Kinematic {
+                  let rotation = transform.map_or(body.rotation, |transform| {
+                      let x = transform.rotation * uv::Vec3::unit_x();
+                      x.y.atan2(x.x)
+                  });
+                  body.set_kinematic_target(uv::Vec2::new(position.x, position.y), rotation);
+              }
+          }
+      }
+
+      let steps = world.update(dt);
+      let mut events = self.events.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+      events.clear();
+      events.extend(
+          world
+              .events()
+              .iter()
+              .filter_map(|event| event.clone().map(|handle| owners.get(&handle).copied())),
+      );
+      drop(events);
+      if steps == 0 {
+          return;
+      }
+
+      for components in entities.values_mut() {
+          let Some(body) = components
+              .get("RigidBody2d")
+              .and_then(|h| h.downcast_ref::<BodyHandle>())
+              .and_then(|handle| world.body(*handle))
+          else {
+              continue;
+          };
+          let (position, rotation) = (body.position, body.rotation);
+          if let Some(flat) = components.get_mut("Position").and_then(|p| p.downcast_mut::<Position>()) {
+              flat.x = position.x;
+              flat.y = position.y;
+          }
+          if let Some(transform) = components.get_mut("Transform").and_then(|t| t.downcast_mut::<Transform>()) {
+              transform.position.x = position.x;
+              transform.position.y = position.y;
+              transform.rotation = uv::Rotor3::from_rotation_xy(rotation);
+          }
+      }
+  }
+}
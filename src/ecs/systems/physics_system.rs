@@ -0,0 +1,100 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::ecs::components::{Position, Transform};
+use crate::physics::{
+    body::{BodyHandle, BodyKind},
+    events::CollisionEvent,
+    world::PhysicsWorld,
+};
+use crate::time::FrameClock;
+
+use super::System;
+
+// Steps a shared physics world at its fixed rate and syncs the bodies of
+// entities with a "RigidBody" component (a `BodyHandle`). Kinematic bodies
+// follow the entity's "Transform"; everything else writes back to it, and
+// to x and y of "Position". Each frame's collision and trigger events are
+// passed on by entity for other systems and screens to read.
+pub struct PhysicsSystem {
+    world: Arc<Mutex<PhysicsWorld>>,
+    clock: FrameClock,
+    events: Arc<Mutex<Vec<CollisionEvent<u32>>>>,
+}
+
+impl PhysicsSystem {
+    pub fn new(world: Arc<Mutex<PhysicsWorld>>) -> Self {
+        PhysicsSystem {
+            world,
+            clock: FrameClock::new(),
+            events: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn world(&self) -> &Arc<Mutex<PhysicsWorld>> {
+        &self.world
+    }
+
+    // This frame's events between entities; ones involving bodies without
+    // an entity are left out.
+    pub fn events(&self) -> &Arc<Mutex<Vec<CollisionEvent<u32>>>> {
+        &self.events
+    }
+}
+
+impl System for PhysicsSystem {
+  fn update(&mut self, entities: &mut HashMap<u32, HashMap<String, Box<dyn Any>>>) {
+      let dt = self.clock.tick();
+      let mut world = self.world.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+      let mut owners = HashMap::new();
+      for (entity_id, components) in entities.iter() {
+          let Some(handle) = components.get("RigidBody").and_then(|h| h.downcast_ref::<BodyHandle>()) else {
+              continue;
+          };
+          owners.insert(*handle, *entity_id);
+          let transform = components.get("Transform").and_then(|t| t.downcast_ref::<Transform>());
+          if let (Some(body), Some(transform)) = (world.body_mut(*handle), transform) {
+              if body.kind == BodyKind::Kinematic {
+                  body.set_kinematic_target(transform.position, transform.rotation);
+              }
+          }
+      }
+
+      let steps = world.update(dt);
+      let mut events = self.events.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+      events.clear();
+      events.extend(
+          world
+              .events()
+              .iter()
+              .filter_map(|event| event.clone().map(|handle| owners.get(&handle).copied())),
+      );
+      drop(events);
+      if steps == 0 {
+          return;
+      }
+
+      for components in entities.values_mut() {
+          let Some(body) = components
+              .get("RigidBody")
+              .and_then(|h| h.downcast_ref::<BodyHandle>())
+              .and_then(|handle| world.body(*handle))
+          else {
+              continue;
+          };
+          let (position, rotation) = (body.position, body.rotation);
+          if let Some(transform) = components.get_mut("Transform").and_then(|t| t.downcast_mut::<Transform>()) {
+              transform.position = position;
+              transform.rotation = rotation;
+          }
+          if let Some(flat) = components.get_mut("Position").and_then(|p| p.downcast_mut::<Position>()) {
+              flat.x = position.x;
+              flat.y = position.y;
+          }
+      }
+  }
+}
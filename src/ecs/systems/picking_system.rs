@@ -0,0 +1,85 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use ultraviolet as uv;
+
+use crate::ecs::components::{Pickable, Position, Transform};
+use crate::physics::{
+    bvh::ProxyId,
+    picking::{PickScene, PickTarget},
+    ray::{Layers, Ray, RayHit},
+};
+
+use super::System;
+
+// Mirrors entities with a "Pickable" component into a shared pick scene,
+// placed by their "Transform" or else by x and y of their "Position".
+pub struct PickingSystem {
+    scene: Arc<Mutex<PickScene<u32>>>,
+    targets: BTreeMap<u32, ProxyId>,
+}
+
+impl PickingSystem {
+    pub fn new(scene: Arc<Mutex<PickScene<u32>>>) -> Self {
+        PickingSystem {
+            scene,
+            targets: BTreeMap::new(),
+        }
+    }
+
+    pub fn scene(&self) -> &Arc<Mutex<PickScene<u32>>> {
+        &self.scene
+    }
+
+    // The nearest entity `ray` hits on `mask`, as of the last update.
+    pub fn pick(&self, ray: &Ray, max_distance: f32, mask: Layers) -> Option<(u32, RayHit)> {
+        let scene = self.scene.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (id, hit) = scene.raycast(ray, max_distance, mask)?;
+        Some((scene.get(id)?.data, hit))
+    }
+}
+
+fn placement(components: &HashMap<String, Box<dyn Any>>) -> (uv::Vec3, uv::Rotor3) {
+    if let Some(transform) = components.get("Transform").and_then(|t| t.downcast_ref::<Transform>()) {
+        return (transform.position, transform.rotation);
+    }
+    let position = components
+        .get("Position")
+        .and_then(|p| p.downcast_ref::<Position>())
+        .map_or(uv::Vec3::zero(), |p| uv::Vec3::new(p.x, p.y, 0.0));
+    (position, uv::Rotor3::identity())
+}
+
+impl System for PickingSystem {
+  fn update(&mut self, entities: &mut HashMap<u32, HashMap<String, Box<dyn Any>>>) {
+      let mut scene = self.scene.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+      let mut seen = BTreeMap::new();
+      for (entity_id, components) in entities.iter() {
+          let Some(pickable) = components.get("Pickable").and_then(|p| p.downcast_ref::<Pickable>()) else {
+              continue;
+          };
+          let (position, rotation) = placement(components);
+          let target = PickTarget::new(pickable.shape.clone(), *entity_id)
+              .with_position(position)
+              .with_rotation(rotation)
+              .with_layers(pickable.layers);
+          let id = match self.targets.get(entity_id) {
+              Some(id) if scene.get(*id).is_some() => {
+                  scene.set(*id, target);
+                  *id
+              }
+              _ => scene.insert(target),
+          };
+          seen.insert(*entity_id, id);
+      }
+      for (entity_id, id) in &self.targets {
+          if !seen.contains_key(entity_id) {
+              scene.remove(*id);
+          }
+      }
+      self.targets = seen;
+  }
+}
@@ -0,0 +1,53 @@
+use std::{any::Any, collections::HashMap};
+
+use crate::audio::output::AudioPlayer;
+use crate::audio::spatial::{AudioEmitter, AudioListener, SpatialAudio};
+use crate::time::FrameClock;
+use crate::ecs::components::Position;
+
+use super::System;
+
+// Plays "AudioEmitter" components relative to the first "AudioListener"
+// component, or to the player's listener (the camera) when there is none.
+// Emitters on entities with a Position follow it in x and y.
+pub struct SpatialAudioSystem {
+    audio: SpatialAudio,
+    clock: FrameClock,
+}
+
+impl SpatialAudioSystem {
+    pub fn new(audio: SpatialAudio) -> Self {
+        SpatialAudioSystem {
+            audio,
+            clock: FrameClock::new(),
+        }
+    }
+
+    pub fn from_player(player: AudioPlayer) -> Self {
+        Self::new(SpatialAudio::new(player))
+    }
+}
+
+impl System for SpatialAudioSystem {
+  fn update(&mut self, entities: &mut HashMap<u32, HashMap<String, Box<dyn Any>>>) {
+      let dt = self.clock.tick();
+      let listener = entities
+          .values()
+          .find_map(|components| components.get("AudioListener").and_then(|l| l.downcast_ref::<AudioListener>()))
+          .copied()
+          .unwrap_or_else(|| self.audio.player().listener());
+
+      let mut emitters = Vec::new();
+      for components in entities.values_mut() {
+          let position = components.get("Position").and_then(|pos| pos.downcast_ref::<Position>()).map(|p| (p.x, p.y));
+          if let Some(emitter) = components.get_mut("AudioEmitter").and_then(|e| e.downcast_mut::<AudioEmitter>()) {
+              if let Some((x, y)) = position {
+                  emitter.position.x = x;
+                  emitter.position.y = y;
+              }
+              emitters.push(emitter);
+          }
+      }
+      self.audio.update(&listener, emitters, dt);
+  }
+}
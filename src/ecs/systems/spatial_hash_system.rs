@@ -0,0 +1,59 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use ultraviolet as uv;
+
+use crate::physics::spatial_hash::{GridId, Rect, SpatialHash};
+
+use super::spatial_index_system::entity_bounds;
+use super::System;
+
+// The 2D counterpart of `SpatialIndexSystem`: keeps a shared grid of entity
+// ids up to date with the x and y of their bounds.
+pub struct SpatialHashSystem {
+    grid: Arc<Mutex<SpatialHash<u32>>>,
+    ids: BTreeMap<u32, GridId>,
+}
+
+impl SpatialHashSystem {
+    pub fn new(grid: Arc<Mutex<SpatialHash<u32>>>) -> Self {
+        SpatialHashSystem {
+            grid,
+            ids: BTreeMap::new(),
+        }
+    }
+
+    pub fn grid(&self) -> &Arc<Mutex<SpatialHash<u32>>> {
+        &self.grid
+    }
+}
+
+impl System for SpatialHashSystem {
+  fn update(&mut self, entities: &mut HashMap<u32, HashMap<String, Box<dyn Any>>>) {
+      let mut grid = self.grid.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+      let mut seen = BTreeMap::new();
+      for (entity_id, components) in entities.iter() {
+          let Some(aabb) = entity_bounds(components) else {
+              continue;
+          };
+          let rect = Rect::new(uv::Vec2::new(aabb.min.x, aabb.min.y), uv::Vec2::new(aabb.max.x, aabb.max.y));
+          let id = match self.ids.get(entity_id) {
+              Some(id) => {
+                  grid.update(*id, rect);
+                  *id
+              }
+              None => grid.insert(rect, *entity_id),
+          };
+          seen.insert(*entity_id, id);
+      }
+      for (entity_id, id) in &self.ids {
+          if !seen.contains_key(entity_id) {
+              grid.remove(*id);
+          }
+      }
+      self.ids = seen;
+  }
+}
@@ -0,0 +1,75 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use ultraviolet as uv;
+
+use crate::ecs::components::{Bounds, Position, Transform};
+use crate::physics::{
+    bvh::{DynamicBvh, ProxyId},
+    shape::Aabb,
+};
+
+use super::System;
+
+// World-space bounds of an entity with a "Bounds" component, placed by its
+// "Transform" or else by x and y of its "Position".
+pub fn entity_bounds(components: &HashMap<String, Box<dyn Any>>) -> Option<Aabb> {
+    let Bounds(local) = components.get("Bounds")?.downcast_ref::<Bounds>()?;
+    if let Some(transform) = components.get("Transform").and_then(|t| t.downcast_ref::<Transform>()) {
+        return Some(local.transformed(transform.position, transform.rotation));
+    }
+    let offset = components
+        .get("Position")
+        .and_then(|p| p.downcast_ref::<Position>())
+        .map_or(uv::Vec3::zero(), |p| uv::Vec3::new(p.x, p.y, 0.0));
+    Some(Aabb::new(local.min + offset, local.max + offset))
+}
+
+// Keeps a shared BVH of entity ids up to date with `entity_bounds`, so
+// picking and AI can query it. Entities that go away are removed.
+pub struct SpatialIndexSystem {
+    index: Arc<Mutex<DynamicBvh<u32>>>,
+    proxies: BTreeMap<u32, ProxyId>,
+}
+
+impl SpatialIndexSystem {
+    pub fn new(index: Arc<Mutex<DynamicBvh<u32>>>) -> Self {
+        SpatialIndexSystem {
+            index,
+            proxies: BTreeMap::new(),
+        }
+    }
+
+    pub fn index(&self) -> &Arc<Mutex<DynamicBvh<u32>>> {
+        &self.index
+    }
+}
+
+impl System for SpatialIndexSystem {
+  fn update(&mut self, entities: &mut HashMap<u32, HashMap<String, Box<dyn Any>>>) {
+      let mut index = self.index.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+      let mut seen = BTreeMap::new();
+      for (entity_id, components) in entities.iter() {
+          let Some(aabb) = entity_bounds(components) else {
+              continue;
+          };
+          let proxy = match self.proxies.get(entity_id) {
+              Some(proxy) => {
+                  index.update(*proxy, aabb);
+                  *proxy
+              }
+              None => index.insert(aabb, *entity_id),
+          };
+          seen.insert(*entity_id, proxy);
+      }
+      for (entity_id, proxy) in &self.proxies {
+          if !seen.contains_key(entity_id) {
+              index.remove(*proxy);
+          }
+      }
+      self.proxies = seen;
+  }
+}
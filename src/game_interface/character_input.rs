@@ -0,0 +1,167 @@
+use std::f32::consts::FRAC_PI_2;
+
+use ultraviolet as uv;
+use winit::{
+  event::KeyEvent,
+  keyboard::{KeyCode, PhysicalKey},
+};
+
+use crate::{
+  camera::Camera,
+  physics::{character::CharacterController, world::PhysicsWorld},
+};
+
+// Turns key events into movement for a character controller: WASD to walk,
+// arrow keys to look, space to jump. Looking can also be driven with `turn`,
+// e.g. from the mouse.
+#[derive(Debug, Clone)]
+pub struct CharacterInput {
+  pub speed: f32,
+  // Radians per second for the arrow keys.
+  pub turn_speed: f32,
+  // Radians; 0 looks down -z, positive turns left.
+  pub yaw: f32,
+  pub pitch: f32,
+  forward: bool,
+  back: bool,
+  left: bool,
+  right: bool,
+  turn_left: bool,
+  turn_right: bool,
+  look_up: bool,
+  look_down: bool,
+  jump: bool,
+}
+
+impl Default for CharacterInput {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl CharacterInput {
+  pub fn new() -> Self {
+    Self {
+      speed: 4.0,
+      turn_speed: 2.0,
+      yaw: 0.0,
+      pitch: 0.0,
+      forward: false,
+      back: false,
+      left: false,
+      right: false,
+      turn_left: false,
+      turn_right: false,
+      look_up: false,
+      look_down: false,
+      jump: false,
+    }
+  }
+
+  pub fn with_speed(mut self, speed: f32) -> Self {
+    self.speed = speed;
+    self
+  }
+
+  // Call from `App::input`. True if the key was one of ours.
+  pub fn handle_key(&mut self, event: &KeyEvent) -> bool {
+    let PhysicalKey::Code(code) = event.physical_key else {
+      return false;
+    };
+    let pressed = event.state.is_pressed();
+    let held = match code {
+      KeyCode::KeyW => &mut self.forward,
+      KeyCode::KeyS => &mut self.back,
+      KeyCode::KeyA => &mut self.left,
+      KeyCode::KeyD => &mut self.right,
+      KeyCode::ArrowLeft => &mut self.turn_left,
+      KeyCode::ArrowRight => &mut self.turn_right,
+      KeyCode::ArrowUp => &mut self.look_up,
+      KeyCode::ArrowDown => &mut self.look_down,
+      KeyCode::Space => {
+        // Holding space doesn't bunny hop.
+        if pressed && !event.repeat {
+          self.jump = true;
+        }
+        return true;
+      }
+      _ => return false,
+    };
+    *held = pressed;
+    true
+  }
+
+  pub fn turn(&mut self, yaw: f32, pitch: f32) {
+    self.yaw += yaw;
+    self.pitch = (self.pitch + pitch).clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
+  }
+
+  // Facing on the ground plane.
+  pub fn forward(&self) -> uv::Vec3 {
+    uv::Vec3::new(-self.yaw.sin(), 0.0, -self.yaw.cos())
+  }
+
+  // Where the camera looks, pitch included.
+  pub fn look(&self) -> uv::Vec3 {
+    let flat = self.forward() * self.pitch.cos();
+    uv::Vec3::new(flat.x, self.pitch.sin(), flat.z)
+  }
+
+  // Velocity wished for from the keys held, relative to the facing.
+  pub fn wish_velocity(&self) -> uv::Vec3 {
+    let axis = |positive: bool, negative: bool| positive as i32 as f32 - negative as i32 as f32;
+    let forward = self.forward();
+    let right = uv::Vec3::new(-forward.z, 0.0, forward.x);
+    let wish = forward * axis(self.forward, self.back) + right * axis(self.right, self.left);
+    if wish.mag_sq() > 0.0 {
+      wish.normalized() * self.speed
+    } else {
+      wish
+    }
+  }
+
+  // A jump pressed since last asked.
+  pub fn take_jump(&mut self) -> bool {
+    std::mem::take(&mut self.jump)
+  }
+
+  // Applies a frame of input to `controller`. Call from `App::update`.
+  pub fn drive(&mut self, controller: &mut CharacterController, world: &PhysicsWorld, dt: f32) {
+    let turn = |positive: bool, negative: bool| (positive as i32 - negative as i32) as f32;
+    self.turn(
+      turn(self.turn_left, self.turn_right) * self.turn_speed * dt,
+      turn(self.look_up, self.look_down) * self.turn_speed * dt,
+    );
+    if self.take_jump() {
+      controller.jump();
+    }
+    controller.update(world, self.wish_velocity(), dt);
+  }
+}
+
+// How a camera follows a character.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CameraFollow {
+  // From the eyes, `eye_height` above the feet.
+  FirstPerson { eye_height: f32 },
+  // From behind and above, looking at the head.
+  ThirdPerson { distance: f32, height: f32 },
+}
+
+impl CameraFollow {
+  pub fn apply(&self, camera: &mut Camera, controller: &CharacterController, input: &CharacterInput) {
+    let up = controller.up;
+    match *self {
+      CameraFollow::FirstPerson { eye_height } => {
+        camera.eye = controller.feet() + up * eye_height;
+        camera.target = camera.eye + input.look();
+      }
+      CameraFollow::ThirdPerson { distance, height } => {
+        let head = controller.position + up * (controller.half_height() + controller.radius());
+        camera.target = head;
+        camera.eye = head - input.look() * distance + up * height;
+      }
+    }
+    camera.up = up;
+  }
+}
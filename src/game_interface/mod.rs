@@ -1,2 +1,3 @@
 pub mod app;
-pub mod screen;
\ No newline at end of file
+pub mod screen;
+pub mod character_input;
\ No newline at end of file
@@ -1,6 +1,6 @@
 use winit::event::KeyEvent;
 
-use crate::{render::State, game_interface::app};
+use crate::{render::State, game_interface::app, audio::stream::MusicTrack};
 
 use app::App;
 
@@ -8,4 +8,10 @@ pub trait Screen<T: App> {
   fn resize(&mut self, game_state: &mut T, engine_state: &mut State, new_size: winit::dpi::PhysicalSize<u32>);
   fn input(&mut self, game_state: &mut T, engine_state: &mut State, event: &KeyEvent);
   fn update(&mut self, game_state: &mut T, engine_state: &mut State);
+
+  // Crossfaded to when the screen becomes current; None leaves the music
+  // alone.
+  fn music(&self) -> Option<MusicTrack> {
+    None
+  }
 }
\ No newline at end of file
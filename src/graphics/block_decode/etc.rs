@@ -0,0 +1,313 @@
+use super::Block;
+
+const ETC_MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+const ETC_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+fn bits(value: u64, high: u32, low: u32) -> i32 {
+    ((value >> low) & ((1 << (high - low + 1)) - 1)) as i32
+}
+
+fn extend(value: i32, from_bits: u32) -> i32 {
+    (value << (8 - from_bits)) | (value >> (2 * from_bits - 8))
+}
+
+fn clamp_rgb(r: i32, g: i32, b: i32) -> [u8; 4] {
+    [r.clamp(0, 255) as u8, g.clamp(0, 255) as u8, b.clamp(0, 255) as u8, 255]
+}
+
+// ETC texel indices run down the columns: texel (x, y) is index x * 4 + y.
+fn etc_index(block: u64, x: usize, y: usize) -> usize {
+    let i = x * 4 + y;
+    let msb = (block >> (16 + i)) & 1;
+    let lsb = (block >> i) & 1;
+    (msb << 1 | lsb) as usize
+}
+
+pub(super) fn etc2_rgb(data: &[u8]) -> Block {
+    etc2_color(data, false)
+}
+
+pub(super) fn etc2_rgb_a1(data: &[u8]) -> Block {
+    etc2_color(data, true)
+}
+
+// With punch-through alpha there's no individual mode: bit 33 says whether
+// the block is opaque, and if not, texels with index 2 are transparent black
+// (except in planar mode).
+fn etc2_color(data: &[u8], punchthrough: bool) -> Block {
+    let block = u64::from_be_bytes(data[..8].try_into().unwrap());
+    let mut out = [[0; 4]; 16];
+
+    let differential = punchthrough || bits(block, 33, 33) == 1;
+    let transparent = punchthrough && bits(block, 33, 33) == 0;
+    let (r, g, b) = (bits(block, 63, 59), bits(block, 55, 51), bits(block, 47, 43));
+    let signed = |v: i32| if v >= 4 { v - 8 } else { v };
+    let r2 = r + signed(bits(block, 58, 56));
+    let g2 = g + signed(bits(block, 50, 48));
+    let b2 = b + signed(bits(block, 42, 40));
+
+    if differential && !(0..32).contains(&r2) {
+        // T mode
+        let c1 = [
+            extend(bits(block, 60, 59) << 2 | bits(block, 57, 56), 4),
+            extend(bits(block, 55, 52), 4),
+            extend(bits(block, 51, 48), 4),
+        ];
+        let c2 = [
+            extend(bits(block, 47, 44), 4),
+            extend(bits(block, 43, 40), 4),
+            extend(bits(block, 39, 36), 4),
+        ];
+        let d = ETC_DISTANCES[(bits(block, 35, 34) << 1 | bits(block, 32, 32)) as usize];
+        let paint = [
+            clamp_rgb(c1[0], c1[1], c1[2]),
+            clamp_rgb(c2[0] + d, c2[1] + d, c2[2] + d),
+            clamp_rgb(c2[0], c2[1], c2[2]),
+            clamp_rgb(c2[0] - d, c2[1] - d, c2[2] - d),
+        ];
+        return paint_block(block, paint, transparent);
+    }
+
+    if differential && !(0..32).contains(&g2) {
+        // H mode
+        let c1 = [
+            bits(block, 62, 59),
+            bits(block, 58, 56) << 1 | bits(block, 52, 52),
+            bits(block, 51, 51) << 3 | bits(block, 49, 47),
+        ];
+        let c2 = [bits(block, 46, 43), bits(block, 42, 39), bits(block, 38, 35)];
+        let packed = |c: [i32; 3]| c[0] << 8 | c[1] << 4 | c[2];
+        let order = (packed(c1) >= packed(c2)) as i32;
+        let d = ETC_DISTANCES[(bits(block, 34, 34) << 2 | bits(block, 32, 32) << 1 | order) as usize];
+        let (c1, c2) = (c1.map(|v| extend(v, 4)), c2.map(|v| extend(v, 4)));
+        let paint = [
+            clamp_rgb(c1[0] + d, c1[1] + d, c1[2] + d),
+            clamp_rgb(c1[0] - d, c1[1] - d, c1[2] - d),
+            clamp_rgb(c2[0] + d, c2[1] + d, c2[2] + d),
+            clamp_rgb(c2[0] - d, c2[1] - d, c2[2] - d),
+        ];
+        return paint_block(block, paint, transparent);
+    }
+
+    if differential && !(0..32).contains(&b2) {
+        // Planar mode: a gradient through three corner colours
+        let o = [
+            extend(bits(block, 62, 57), 6),
+            extend(bits(block, 56, 56) << 6 | bits(block, 54, 49), 7),
+            extend(bits(block, 48, 48) << 5 | bits(block, 44, 43) << 3 | bits(block, 41, 39), 6),
+        ];
+        let h = [
+            extend(bits(block, 38, 34) << 1 | bits(block, 32, 32), 6),
+            extend(bits(block, 31, 25), 7),
+            extend(bits(block, 24, 19), 6),
+        ];
+        let v = [
+            extend(bits(block, 18, 13), 6),
+            extend(bits(block, 12, 6), 7),
+            extend(bits(block, 5, 0), 6),
+        ];
+        for (i, texel) in out.iter_mut().enumerate() {
+            let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+            let channel = |c: usize| (x * (h[c] - o[c]) + y * (v[c] - o[c]) + 4 * o[c] + 2) >> 2;
+            *texel = clamp_rgb(channel(0), channel(1), channel(2));
+        }
+        return out;
+    }
+
+    let (base1, base2) = if differential {
+        (
+            [extend(r, 5), extend(g, 5), extend(b, 5)],
+            [extend(r2, 5), extend(g2, 5), extend(b2, 5)],
+        )
+    } else {
+        (
+            [bits(block, 63, 60) * 17, bits(block, 55, 52) * 17, bits(block, 47, 44) * 17],
+            [bits(block, 59, 56) * 17, bits(block, 51, 48) * 17, bits(block, 43, 40) * 17],
+        )
+    };
+    let tables = [bits(block, 39, 37) as usize, bits(block, 36, 34) as usize];
+    let flip = bits(block, 32, 32) == 1;
+    for (i, texel) in out.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let second = if flip { y >= 2 } else { x >= 2 };
+        let (base, table) = if second { (base2, tables[1]) } else { (base1, tables[0]) };
+        let index = etc_index(block, x, y);
+        let modifier = match index {
+            // Punch-through blocks lose the small positive modifier.
+            0 if transparent => 0,
+            2 if transparent => {
+                *texel = [0; 4];
+                continue;
+            }
+            _ => ETC_MODIFIERS[table][index & 1] * if index >= 2 { -1 } else { 1 },
+        };
+        *texel = clamp_rgb(base[0] + modifier, base[1] + modifier, base[2] + modifier);
+    }
+    out
+}
+
+fn paint_block(block: u64, paint: [[u8; 4]; 4], transparent: bool) -> Block {
+    std::array::from_fn(|i| match etc_index(block, i % 4, i / 4) {
+        2 if transparent => [0; 4],
+        index => paint[index],
+    })
+}
+
+pub(super) fn etc2_rgba(data: &[u8]) -> Block {
+    let alpha = u64::from_be_bytes(data[..8].try_into().unwrap());
+    let base = bits(alpha, 63, 56);
+    let multiplier = bits(alpha, 55, 52);
+    let table = EAC_MODIFIERS[bits(alpha, 51, 48) as usize];
+
+    let mut block = etc2_rgb(&data[8..]);
+    for (i, texel) in block.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let shift = 45 - 3 * (x * 4 + y) as u32;
+        let index = bits(alpha, shift + 2, shift) as usize;
+        texel[3] = (base + table[index] * multiplier).clamp(0, 255) as u8;
+    }
+    block
+}
+
+pub(super) fn eac_r11(data: &[u8]) -> Block {
+    eac_channel(data, false).map(|r| [unorm11(r), 0, 0, 255])
+}
+
+pub(super) fn eac_r11_signed(data: &[u8]) -> Block {
+    eac_channel(data, true).map(|r| [snorm11(r), 0, 0, 127])
+}
+
+pub(super) fn eac_rg11(data: &[u8]) -> Block {
+    let red = eac_channel(&data[..8], false);
+    let green = eac_channel(&data[8..], false);
+    std::array::from_fn(|i| [unorm11(red[i]), unorm11(green[i]), 0, 255])
+}
+
+pub(super) fn eac_rg11_signed(data: &[u8]) -> Block {
+    let red = eac_channel(&data[..8], true);
+    let green = eac_channel(&data[8..], true);
+    std::array::from_fn(|i| [snorm11(red[i]), snorm11(green[i]), 0, 127])
+}
+
+// One EAC channel as 11-bit values: 0..=2047 unsigned, -1023..=1023 signed.
+fn eac_channel(data: &[u8], signed: bool) -> [i32; 16] {
+    let block = u64::from_be_bytes(data[..8].try_into().unwrap());
+    let base = if signed {
+        (bits(block, 63, 56) as u8 as i8).max(-127) as i32
+    } else {
+        bits(block, 63, 56)
+    };
+    let multiplier = bits(block, 55, 52);
+    let table = EAC_MODIFIERS[bits(block, 51, 48) as usize];
+    std::array::from_fn(|i| {
+        let (x, y) = (i % 4, i / 4);
+        let shift = 45 - 3 * (x * 4 + y) as u32;
+        let modifier = table[bits(block, shift + 2, shift) as usize];
+        // A zero multiplier still moves the value, by an eighth of a step.
+        let step = if multiplier == 0 { modifier } else { modifier * multiplier * 8 };
+        if signed {
+            (base * 8 + step).clamp(-1023, 1023)
+        } else {
+            (base * 8 + 4 + step).clamp(0, 2047)
+        }
+    })
+}
+
+fn unorm11(value: i32) -> u8 {
+    ((value * 255 + 1023) / 2047) as u8
+}
+
+fn snorm11(value: i32) -> u8 {
+    let rounding = if value < 0 { -511 } else { 511 };
+    ((value * 127 + rounding) / 1023) as i8 as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Blocks are random bytes; the expected texels were checked against a
+    // hardware decoder. Each test looks at texels 0, 9 and 15.
+    fn texels(block: Block) -> [[u8; 4]; 3] {
+        [block[0], block[9], block[15]]
+    }
+
+    fn snorm(texels: [[i8; 4]; 3]) -> [[u8; 4]; 3] {
+        texels.map(|texel| texel.map(|c| c as u8))
+    }
+
+    #[test]
+    fn decodes_etc2_rgb() {
+        let block = [0x76, 0x22, 0x44, 0xdf, 0x14, 0x38, 0x68, 0x05];
+        let expected = [[221, 139, 172, 255], [146, 96, 80, 255], [146, 96, 80, 255]];
+        assert_eq!(texels(etc2_rgb(&block)), expected);
+    }
+
+    #[test]
+    fn decodes_etc2_rgb_a1() {
+        let block = [0xa5, 0xcd, 0xba, 0xf7, 0x4d, 0x87, 0x86, 0x23];
+        let expected = [[0, 23, 6, 255], [164, 205, 230, 255], [220, 255, 255, 255]];
+        assert_eq!(texels(etc2_rgb_a1(&block)), expected);
+    }
+
+    #[test]
+    fn decodes_etc2_rgba() {
+        let block = [
+            0x44, 0x9a, 0x63, 0x10, 0x2f, 0x9c, 0x53, 0x54, 0xb3, 0x34, 0xa4, 0xc5, 0xfe, 0xc5, 0xde, 0xc7,
+        ];
+        let expected = [[81, 0, 64, 0], [34, 51, 51, 95], [34, 51, 51, 77]];
+        assert_eq!(texels(etc2_rgba(&block)), expected);
+    }
+
+    #[test]
+    fn decodes_eac_r11() {
+        let block = [0xe7, 0xfd, 0x23, 0x11, 0xf2, 0x6f, 0x5b, 0x5d];
+        assert_eq!(texels(eac_r11(&block)), [[201, 0, 0, 255], [255, 0, 0, 255], [246, 0, 0, 255]]);
+
+        let block = [0x85, 0x28, 0x4c, 0x87, 0x4c, 0x1a, 0xe7, 0x4b];
+        assert_eq!(texels(eac_r11_signed(&block)), snorm([[-127, 0, 0, 127]; 3]));
+    }
+
+    #[test]
+    fn decodes_eac_rg11() {
+        let block = [
+            0x47, 0x81, 0xa5, 0xb1, 0x1d, 0x76, 0x71, 0xb9, 0x15, 0x4b, 0x9d, 0xe7, 0x8d, 0x13, 0xad, 0xb5,
+        ];
+        assert_eq!(texels(eac_rg11(&block)), [[119, 25, 0, 255], [0, 1, 0, 255], [15, 37, 0, 255]]);
+
+        let block = [
+            0x70, 0x5c, 0x4b, 0xdb, 0xf7, 0xf8, 0x9a, 0x60, 0xf6, 0x6f, 0x5f, 0xe5, 0xac, 0x01, 0xad, 0xb2,
+        ];
+        let expected = snorm([[76, -52, 0, 127], [127, 14, 0, 127], [96, -52, 0, 127]]);
+        assert_eq!(texels(eac_rg11_signed(&block)), expected);
+    }
+}
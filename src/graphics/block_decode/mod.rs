@@ -0,0 +1,155 @@
+// CPU decoders for block-compressed formats, used when the device can't
+// sample them directly. BC, ETC2 and EAC blocks are 4x4 texels; ASTC blocks
+// are whatever size the format says. Every decoder writes a block's texels
+// in row-major order.
+
+mod astc;
+mod bc;
+mod etc;
+
+// The format `decode` produces for `format`: RGBA8 in the same colour space
+// for most, Rgba8Snorm for the signed BC4, BC5 and EAC formats and
+// Rgba16Float for BC6H. None for HDR ASTC, which needs the device, and for
+// formats that aren't block-compressed.
+pub fn decoded_format(format: wgpu::TextureFormat) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat as F;
+    let decoded = match format {
+        F::Bc4RSnorm | F::Bc5RgSnorm | F::EacR11Snorm | F::EacRg11Snorm => F::Rgba8Snorm,
+        F::Bc6hRgbUfloat | F::Bc6hRgbFloat => F::Rgba16Float,
+        F::Astc {
+            channel: wgpu::AstcChannel::Hdr,
+            ..
+        } => return None,
+        format if !format.is_compressed() => return None,
+        format if format.is_srgb() => F::Rgba8UnormSrgb,
+        _ => F::Rgba8Unorm,
+    };
+    Some(decoded)
+}
+
+// Decodes a whole image of `format` into tightly packed texels of
+// `decoded_format(format)`. Returns None for formats without a decoder and
+// when `data` is too short.
+pub fn decode(format: wgpu::TextureFormat, width: u32, height: u32, data: &[u8]) -> Option<Vec<u8>> {
+    use wgpu::TextureFormat as F;
+    let rgba8 = |block_size: usize, decode_block: fn(&[u8]) -> Block| {
+        decode_blocks(width, height, (4, 4), block_size, data, |block, out| {
+            out.copy_from_slice(&decode_block(block))
+        })
+    };
+    match format {
+        F::Bc1RgbaUnorm | F::Bc1RgbaUnormSrgb => rgba8(8, bc::bc1),
+        F::Bc2RgbaUnorm | F::Bc2RgbaUnormSrgb => rgba8(16, bc::bc2),
+        F::Bc3RgbaUnorm | F::Bc3RgbaUnormSrgb => rgba8(16, bc::bc3),
+        F::Bc4RUnorm => rgba8(8, bc::bc4),
+        F::Bc4RSnorm => rgba8(8, bc::bc4_signed),
+        F::Bc5RgUnorm => rgba8(16, bc::bc5),
+        F::Bc5RgSnorm => rgba8(16, bc::bc5_signed),
+        F::Bc6hRgbUfloat | F::Bc6hRgbFloat => {
+            let signed = format == F::Bc6hRgbFloat;
+            decode_blocks(width, height, (4, 4), 16, data, |block, out| {
+                out.copy_from_slice(&bc::bc6h(block, signed))
+            })
+        }
+        F::Bc7RgbaUnorm | F::Bc7RgbaUnormSrgb => rgba8(16, bc::bc7),
+        F::Etc2Rgb8Unorm | F::Etc2Rgb8UnormSrgb => rgba8(8, etc::etc2_rgb),
+        F::Etc2Rgb8A1Unorm | F::Etc2Rgb8A1UnormSrgb => rgba8(8, etc::etc2_rgb_a1),
+        F::Etc2Rgba8Unorm | F::Etc2Rgba8UnormSrgb => rgba8(16, etc::etc2_rgba),
+        F::EacR11Unorm => rgba8(8, etc::eac_r11),
+        F::EacR11Snorm => rgba8(8, etc::eac_r11_signed),
+        F::EacRg11Unorm => rgba8(16, etc::eac_rg11),
+        F::EacRg11Snorm => rgba8(16, etc::eac_rg11_signed),
+        F::Astc { channel, .. } if channel != wgpu::AstcChannel::Hdr => {
+            let (block_width, block_height) = format.block_dimensions();
+            let srgb = channel == wgpu::AstcChannel::UnormSrgb;
+            decode_blocks(width, height, (block_width, block_height), 16, data, |block, out| {
+                astc::decode_block(block, block_width as usize, block_height as usize, srgb, out)
+            })
+        }
+        _ => None,
+    }
+}
+
+pub type Block = [[u8; 4]; 16];
+
+// Runs `decode_block` over every block of the image, each filling a
+// row-major block of N-byte texels, and keeps the texels inside the image.
+fn decode_blocks<const N: usize>(
+    width: u32,
+    height: u32,
+    (block_width, block_height): (u32, u32),
+    block_size: usize,
+    data: &[u8],
+    decode_block: impl Fn(&[u8], &mut [[u8; N]]),
+) -> Option<Vec<u8>> {
+    let blocks_wide = width.div_ceil(block_width) as usize;
+    let blocks_high = height.div_ceil(block_height) as usize;
+    if data.len() < blocks_wide * blocks_high * block_size {
+        return None;
+    }
+
+    let (width, height) = (width as usize, height as usize);
+    let (block_width, block_height) = (block_width as usize, block_height as usize);
+    let mut texels = vec![0; width * height * N];
+    let mut block = vec![[0; N]; block_width * block_height];
+    for by in 0..blocks_high {
+        for bx in 0..blocks_wide {
+            let offset = (by * blocks_wide + bx) * block_size;
+            decode_block(&data[offset..offset + block_size], &mut block);
+            for (i, texel) in block.iter().enumerate() {
+                let (x, y) = (bx * block_width + i % block_width, by * block_height + i / block_width);
+                if x < width && y < height {
+                    let at = (y * width + x) * N;
+                    texels[at..at + N].copy_from_slice(texel);
+                }
+            }
+        }
+    }
+    Some(texels)
+}
+
+// Reads little-endian bit fields from a 128-bit block, BC6H, BC7 and ASTC
+// style. Reading past the end gives zeros.
+struct BitReader {
+    bits: u128,
+    position: u32,
+}
+
+impl BitReader {
+    fn new(data: &[u8]) -> Self {
+        Self {
+            bits: u128::from_le_bytes(data[..16].try_into().unwrap()),
+            position: 0,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let value = bit_range(self.bits, self.position, count);
+        self.position += count;
+        value
+    }
+}
+
+fn bit_range(bits: u128, start: u32, count: u32) -> u32 {
+    if count == 0 || start >= 128 {
+        return 0;
+    }
+    ((bits >> start) & ((1u128 << count.min(32)) - 1)) as u32
+}
+
+// Widens an n-bit value to 8 bits by repeating its top bits.
+fn expand(value: u32, bits: u32) -> u8 {
+    match bits {
+        0 => 0,
+        8.. => value as u8,
+        _ => {
+            let mut expanded = value << (8 - bits);
+            let mut filled = bits;
+            while filled < 8 {
+                expanded |= expanded >> filled;
+                filled *= 2;
+            }
+            expanded as u8
+        }
+    }
+}
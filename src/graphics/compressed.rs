@@ -0,0 +1,307 @@
+use std::io::Read;
+
+use anyhow::*;
+
+use super::block_decode;
+
+// A texture file's contents ready for upload: every mip level the file
+// provides, each holding all array layers (cube faces included, +X -X +Y -Y
+// +Z -Z) back to back in the order wgpu expects.
+pub struct TextureData {
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub layers: u32,
+    pub cube: bool,
+    pub levels: Vec<Vec<u8>>,
+}
+
+const KTX2_MAGIC: &[u8] = b"\xABKTX 20\xBB\r\n\x1A\n";
+const DDS_MAGIC: &[u8] = b"DDS ";
+
+impl TextureData {
+    // Picks the container from the file's magic bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.starts_with(KTX2_MAGIC) {
+            Self::from_ktx2(bytes)
+        } else if bytes.starts_with(DDS_MAGIC) {
+            Self::from_dds(bytes)
+        } else {
+            bail!("not a KTX2 or DDS file")
+        }
+    }
+
+    pub fn is_container(bytes: &[u8]) -> bool {
+        bytes.starts_with(KTX2_MAGIC) || bytes.starts_with(DDS_MAGIC)
+    }
+
+    pub fn from_ktx2(bytes: &[u8]) -> Result<Self> {
+        let reader = ktx2::Reader::new(bytes).map_err(|e| anyhow!("invalid KTX2 file: {:?}", e))?;
+        let header = reader.header();
+        let format = header
+            .format
+            .and_then(ktx2_format)
+            .ok_or_else(|| anyhow!("unsupported KTX2 format {:?}", header.format))?;
+        if header.pixel_depth > 1 {
+            bail!("3D KTX2 textures are not supported");
+        }
+
+        let levels = reader
+            .levels()
+            .map(|level| match header.supercompression_scheme {
+                None => Ok(level.to_vec()),
+                Some(ktx2::SupercompressionScheme::Zstandard) => {
+                    let mut decoder = ruzstd::StreamingDecoder::new(level)
+                        .map_err(|e| anyhow!("invalid zstd data: {:?}", e))?;
+                    let mut data = Vec::new();
+                    decoder.read_to_end(&mut data)?;
+                    Ok(data)
+                }
+                Some(scheme) => bail!("unsupported KTX2 supercompression {:?}", scheme),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let data = Self {
+            format,
+            width: header.pixel_width,
+            height: header.pixel_height.max(1),
+            layers: header.layer_count.max(1) * header.face_count,
+            cube: header.face_count == 6,
+            levels,
+        };
+        data.validate()?;
+        Ok(data)
+    }
+
+    pub fn from_dds(bytes: &[u8]) -> Result<Self> {
+        let dds = ddsfile::Dds::read(bytes).map_err(|e| anyhow!("invalid DDS file: {}", e))?;
+        let format = dds
+            .get_dxgi_format()
+            .and_then(dxgi_format)
+            .ok_or_else(|| anyhow!("unsupported DDS format {:?}", dds.get_dxgi_format()))?;
+        if dds.get_depth() > 1 {
+            bail!("3D DDS textures are not supported");
+        }
+
+        let cube = match &dds.header10 {
+            Some(header10) => header10.misc_flag.contains(ddsfile::MiscFlag::TEXTURECUBE),
+            None => dds.header.caps2.contains(ddsfile::Caps2::CUBEMAP),
+        };
+        let layers = match &dds.header10 {
+            Some(header10) => header10.array_size.max(1) * if cube { 6 } else { 1 },
+            None if cube => 6,
+            None => 1,
+        };
+        let (width, height) = (dds.get_width(), dds.get_height());
+        let level_count = dds.get_num_mipmap_levels().max(1);
+
+        // DDS stores each layer's whole mip chain in turn; regroup by level.
+        let mut levels = vec![Vec::new(); level_count as usize];
+        let mut offset = 0;
+        for _ in 0..layers {
+            for (level, data) in levels.iter_mut().enumerate() {
+                let size = level_size(format, width >> level, height >> level);
+                let bytes = dds
+                    .data
+                    .get(offset..offset + size)
+                    .ok_or_else(|| anyhow!("DDS file is truncated"))?;
+                data.extend_from_slice(bytes);
+                offset += size;
+            }
+        }
+
+        let data = Self {
+            format,
+            width,
+            height,
+            layers,
+            cube,
+            levels,
+        };
+        data.validate()?;
+        Ok(data)
+    }
+
+    pub fn view_dimension(&self) -> wgpu::TextureViewDimension {
+        match (self.cube, self.layers) {
+            (true, 6) => wgpu::TextureViewDimension::Cube,
+            (true, _) => wgpu::TextureViewDimension::CubeArray,
+            (false, 1) => wgpu::TextureViewDimension::D2,
+            (false, _) => wgpu::TextureViewDimension::D2Array,
+        }
+    }
+
+    // Whether the texture can be created as-is: the device has the format's
+    // feature and the size is whole blocks, as wgpu requires.
+    pub fn is_supported(&self, features: wgpu::Features) -> bool {
+        let (block_width, block_height) = self.format.block_dimensions();
+        features.contains(self.format.required_features())
+            && self.width.is_multiple_of(block_width)
+            && self.height.is_multiple_of(block_height)
+    }
+
+    // Expands a block-compressed texture to an uncompressed format the
+    // device can sample, keeping its mips and colour space. See
+    // `block_decode::decoded_format` for what each format becomes.
+    pub fn decompress(&self) -> Result<Self> {
+        let format = block_decode::decoded_format(self.format).ok_or_else(|| {
+            anyhow!(
+                "no CPU decoder for {:?}; it needs device features {:?}",
+                self.format,
+                self.format.required_features()
+            )
+        })?;
+        let texel_size = format.block_copy_size(None).unwrap_or(4) as usize;
+        let levels = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, data)| {
+                let (width, height) = self.level_dimensions(level as u32);
+                let layer_size = level_size(self.format, width, height);
+                let mut texels = Vec::with_capacity(width as usize * height as usize * texel_size * self.layers as usize);
+                for layer in data.chunks(layer_size).take(self.layers as usize) {
+                    let decoded = block_decode::decode(self.format, width, height, layer)
+                        .ok_or_else(|| anyhow!("{:?} level {} is truncated", self.format, level))?;
+                    texels.extend_from_slice(&decoded);
+                }
+                Ok(texels)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            format,
+            width: self.width,
+            height: self.height,
+            layers: self.layers,
+            cube: self.cube,
+            levels,
+        })
+    }
+
+    pub fn level_dimensions(&self, level: u32) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.levels.is_empty() {
+            bail!("texture has no image data");
+        }
+        for (level, data) in self.levels.iter().enumerate() {
+            let (width, height) = self.level_dimensions(level as u32);
+            let expected = level_size(self.format, width, height) * self.layers as usize;
+            if data.len() < expected {
+                bail!("mip level {} has {} bytes, expected {}", level, data.len(), expected);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Bytes in one layer of a `width` x `height` image, rounding up to whole
+// blocks for compressed formats.
+pub fn level_size(format: wgpu::TextureFormat, width: u32, height: u32) -> usize {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap_or(4);
+    let blocks_wide = width.max(1).div_ceil(block_width);
+    let blocks_high = height.max(1).div_ceil(block_height);
+    (blocks_wide * blocks_high * block_size) as usize
+}
+
+fn ktx2_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use ktx2::Format as K;
+    use wgpu::TextureFormat as F;
+    let format = match format {
+        K::R8G8B8A8_UNORM => F::Rgba8Unorm,
+        K::R8G8B8A8_SRGB => F::Rgba8UnormSrgb,
+        K::B8G8R8A8_UNORM => F::Bgra8Unorm,
+        K::B8G8R8A8_SRGB => F::Bgra8UnormSrgb,
+        K::R16G16B16A16_SFLOAT => F::Rgba16Float,
+        K::R32G32B32A32_SFLOAT => F::Rgba32Float,
+        K::BC1_RGB_UNORM_BLOCK | K::BC1_RGBA_UNORM_BLOCK => F::Bc1RgbaUnorm,
+        K::BC1_RGB_SRGB_BLOCK | K::BC1_RGBA_SRGB_BLOCK => F::Bc1RgbaUnormSrgb,
+        K::BC2_UNORM_BLOCK => F::Bc2RgbaUnorm,
+        K::BC2_SRGB_BLOCK => F::Bc2RgbaUnormSrgb,
+        K::BC3_UNORM_BLOCK => F::Bc3RgbaUnorm,
+        K::BC3_SRGB_BLOCK => F::Bc3RgbaUnormSrgb,
+        K::BC4_UNORM_BLOCK => F::Bc4RUnorm,
+        K::BC4_SNORM_BLOCK => F::Bc4RSnorm,
+        K::BC5_UNORM_BLOCK => F::Bc5RgUnorm,
+        K::BC5_SNORM_BLOCK => F::Bc5RgSnorm,
+        K::BC6H_UFLOAT_BLOCK => F::Bc6hRgbUfloat,
+        K::BC6H_SFLOAT_BLOCK => F::Bc6hRgbFloat,
+        K::BC7_UNORM_BLOCK => F::Bc7RgbaUnorm,
+        K::BC7_SRGB_BLOCK => F::Bc7RgbaUnormSrgb,
+        K::ETC2_R8G8B8_UNORM_BLOCK => F::Etc2Rgb8Unorm,
+        K::ETC2_R8G8B8_SRGB_BLOCK => F::Etc2Rgb8UnormSrgb,
+        K::ETC2_R8G8B8A1_UNORM_BLOCK => F::Etc2Rgb8A1Unorm,
+        K::ETC2_R8G8B8A1_SRGB_BLOCK => F::Etc2Rgb8A1UnormSrgb,
+        K::ETC2_R8G8B8A8_UNORM_BLOCK => F::Etc2Rgba8Unorm,
+        K::ETC2_R8G8B8A8_SRGB_BLOCK => F::Etc2Rgba8UnormSrgb,
+        K::EAC_R11_UNORM_BLOCK => F::EacR11Unorm,
+        K::EAC_R11_SNORM_BLOCK => F::EacR11Snorm,
+        K::EAC_R11G11_UNORM_BLOCK => F::EacRg11Unorm,
+        K::EAC_R11G11_SNORM_BLOCK => F::EacRg11Snorm,
+        _ => return astc_format(format.0.get()),
+    };
+    Some(format)
+}
+
+// ASTC formats are numbered in pairs, UNORM then SRGB, in the same block
+// order as `wgpu::AstcBlock`.
+fn astc_format(vk_format: u32) -> Option<wgpu::TextureFormat> {
+    use wgpu::AstcBlock as B;
+    const BLOCKS: [B; 14] = [
+        B::B4x4,
+        B::B5x4,
+        B::B5x5,
+        B::B6x5,
+        B::B6x6,
+        B::B8x5,
+        B::B8x6,
+        B::B8x8,
+        B::B10x5,
+        B::B10x6,
+        B::B10x8,
+        B::B10x10,
+        B::B12x10,
+        B::B12x12,
+    ];
+    let index = vk_format.checked_sub(ktx2::Format::ASTC_4x4_UNORM_BLOCK.0.get())?;
+    let block = *BLOCKS.get(index as usize / 2)?;
+    let channel = if index % 2 == 0 {
+        wgpu::AstcChannel::Unorm
+    } else {
+        wgpu::AstcChannel::UnormSrgb
+    };
+    Some(wgpu::TextureFormat::Astc { block, channel })
+}
+
+fn dxgi_format(format: ddsfile::DxgiFormat) -> Option<wgpu::TextureFormat> {
+    use ddsfile::DxgiFormat as D;
+    use wgpu::TextureFormat as F;
+    let format = match format {
+        D::R8G8B8A8_UNorm => F::Rgba8Unorm,
+        D::R8G8B8A8_UNorm_sRGB => F::Rgba8UnormSrgb,
+        D::B8G8R8A8_UNorm => F::Bgra8Unorm,
+        D::B8G8R8A8_UNorm_sRGB => F::Bgra8UnormSrgb,
+        D::R16G16B16A16_Float => F::Rgba16Float,
+        D::R32G32B32A32_Float => F::Rgba32Float,
+        D::BC1_Typeless | D::BC1_UNorm => F::Bc1RgbaUnorm,
+        D::BC1_UNorm_sRGB => F::Bc1RgbaUnormSrgb,
+        D::BC2_Typeless | D::BC2_UNorm => F::Bc2RgbaUnorm,
+        D::BC2_UNorm_sRGB => F::Bc2RgbaUnormSrgb,
+        D::BC3_Typeless | D::BC3_UNorm => F::Bc3RgbaUnorm,
+        D::BC3_UNorm_sRGB => F::Bc3RgbaUnormSrgb,
+        D::BC4_Typeless | D::BC4_UNorm => F::Bc4RUnorm,
+        D::BC4_SNorm => F::Bc4RSnorm,
+        D::BC5_Typeless | D::BC5_UNorm => F::Bc5RgUnorm,
+        D::BC5_SNorm => F::Bc5RgSnorm,
+        D::BC6H_Typeless | D::BC6H_UF16 => F::Bc6hRgbUfloat,
+        D::BC6H_SF16 => F::Bc6hRgbFloat,
+        D::BC7_Typeless | D::BC7_UNorm => F::Bc7RgbaUnorm,
+        D::BC7_UNorm_sRGB => F::Bc7RgbaUnormSrgb,
+        _ => return None,
+    };
+    Some(format)
+}
@@ -10,3 +10,31 @@ pub struct BufferContents<'a> {
     pub indices: &'a [u16],
     pub num_indices: u32,
 }
+
+// Vertex of a skinned mesh: up to four joints, indices into its skeleton,
+// and how much each moves it. Weights should add up to one.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkinnedVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub joints: [u16; 4],
+    pub weights: [f32; 4],
+}
+
+impl SkinnedVertex {
+    // Locations 0 and 1 match `Vertex`; joints and weights are 2 and 3.
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+            0 => Float32x3,
+            1 => Float32x2,
+            2 => Uint16x4,
+            3 => Float32x4,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SkinnedVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
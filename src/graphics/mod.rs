@@ -1,5 +1,17 @@
 pub mod texture;
+pub mod mipmap;
+pub mod cubemap;
+pub mod compressed;
+pub mod block_decode;
+pub mod shader;
+pub mod skybox;
+pub mod pipeline;
+pub mod render_graph;
+pub mod post;
+pub mod msaa;
+pub mod picking;
 pub mod text;
 pub mod light;
 pub mod gl;
-pub mod objects;
\ No newline at end of file
+pub mod objects;
+pub mod skinning;
\ No newline at end of file
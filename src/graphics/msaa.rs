@@ -0,0 +1,132 @@
+use super::texture::Texture;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Msaa {
+    Off,
+    X2,
+    #[default]
+    X4,
+    X8,
+}
+
+impl Msaa {
+    pub const ALL: [Msaa; 4] = [Msaa::Off, Msaa::X2, Msaa::X4, Msaa::X8];
+
+    pub fn sample_count(self) -> u32 {
+        match self {
+            Msaa::Off => 1,
+            Msaa::X2 => 2,
+            Msaa::X4 => 4,
+            Msaa::X8 => 8,
+        }
+    }
+
+    pub fn from_sample_count(sample_count: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|msaa| msaa.sample_count() == sample_count)
+    }
+
+    // Levels usable with every one of `formats` on this adapter. Without
+    // TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES on the device wgpu only allows
+    // the counts WebGPU guarantees, 1 and 4, whatever the adapter reports.
+    pub fn supported(
+        adapter: &wgpu::Adapter,
+        device_features: wgpu::Features,
+        formats: &[wgpu::TextureFormat],
+    ) -> Vec<Msaa> {
+        let adapter_specific =
+            device_features.contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+        Self::ALL
+            .into_iter()
+            .filter(|msaa| {
+                let count = msaa.sample_count();
+                if !adapter_specific {
+                    return count == 1 || count == 4;
+                }
+                formats.iter().all(|&format| {
+                    adapter
+                        .get_texture_format_features(format)
+                        .flags
+                        .sample_count_supported(count)
+                })
+            })
+            .collect()
+    }
+
+    // Highest supported level not above `self`, falling back to `Off`.
+    pub fn clamp_to(self, supported: &[Msaa]) -> Msaa {
+        supported
+            .iter()
+            .copied()
+            .filter(|&msaa| msaa <= self)
+            .max()
+            .unwrap_or(Msaa::Off)
+    }
+}
+
+// The scene's colour and depth attachments. With MSAA on, colour is drawn
+// into a multisampled texture and resolved into the caller's view at the end
+// of every pass; with it off the caller's view is drawn into directly.
+pub struct SceneTargets {
+    msaa: Msaa,
+    color: Option<Texture>,
+    depth: Texture,
+}
+
+impl SceneTargets {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        msaa: Msaa,
+    ) -> Self {
+        let sample_count = msaa.sample_count();
+        let color = (sample_count > 1).then(|| {
+            Texture::create_multisampled(
+                device,
+                config.width,
+                config.height,
+                format,
+                sample_count,
+                "msaa_color",
+            )
+        });
+        let depth = Texture::create_depth_texture(device, config, sample_count, "depth_texture");
+        Self { msaa, color, depth }
+    }
+
+    pub fn msaa(&self) -> Msaa {
+        self.msaa
+    }
+
+    pub fn color_attachment<'a>(
+        &'a self,
+        target: &'a wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        let (view, resolve_target) = match &self.color {
+            Some(color) => (&color.view, Some(target)),
+            None => (target, None),
+        };
+        wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+
+    // Cleared every pass: cameras sharing a target don't depth test against
+    // each other.
+    pub fn depth_attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.depth.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Discard,
+            }),
+            stencil_ops: None,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use wgpu::util::DeviceExt;
+
+use crate::graphics::gl::{SkinnedVertex, Vertex};
+
+// Mesh geometry on the CPU, e.g. as parsed from a model file.
+#[derive(Debug, Clone, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    // Merges every model in an OBJ file into one indexed triangle list.
+    // Materials are ignored.
+    pub fn from_obj(bytes: &[u8]) -> anyhow::Result<Self> {
+        let (models, _) = tobj::load_obj_buf(
+            &mut std::io::Cursor::new(bytes),
+            &tobj::LoadOptions {
+                single_index: true,
+                triangulate: true,
+                ..Default::default()
+            },
+            |_| Err(tobj::LoadError::OpenFileFailed),
+        )?;
+
+        let mut data = MeshData::default();
+        for model in models {
+            let mesh = model.mesh;
+            let base = data.vertices.len() as u32;
+            for (i, position) in mesh.positions.chunks_exact(3).enumerate() {
+                // OBJ puts v = 0 at the bottom, wgpu at the top.
+                let tex_coords = match mesh.texcoords.get(i * 2..i * 2 + 2) {
+                    Some(uv) => [uv[0], 1.0 - uv[1]],
+                    None => [0.0, 0.0],
+                };
+                data.vertices.push(Vertex {
+                    position: [position[0], position[1], position[2]],
+                    tex_coords,
+                });
+            }
+            data.indices.extend(mesh.indices.iter().map(|&i| base + i));
+        }
+        Ok(data)
+    }
+}
+
+// Geometry for the skinning pipeline, e.g. imported from glTF.
+#[derive(Debug, Clone, Default)]
+pub struct SkinnedMeshData {
+    pub vertices: Vec<SkinnedVertex>,
+    pub indices: Vec<u32>,
+}
+
+// Mesh uploaded to the GPU, drawn as a u32 indexed triangle list.
+pub struct Mesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_indices: u32,
+}
+
+impl Mesh {
+    pub fn from_data(device: &wgpu::Device, data: &MeshData, label: Option<&str>) -> Self {
+        Self::from_slices(device, &data.vertices, &data.indices, label)
+    }
+
+    // Drawn with `SkinnedVertex::desc()` instead of the scene's layout.
+    pub fn from_skinned_data(device: &wgpu::Device, data: &SkinnedMeshData, label: Option<&str>) -> Self {
+        Self::from_slices(device, &data.vertices, &data.indices, label)
+    }
+
+    fn from_slices<V: bytemuck::Pod>(
+        device: &wgpu::Device,
+        vertices: &[V],
+        indices: &[u32],
+        label: Option<&str>,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label,
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        Self {
+            vertex_buffer,
+            index_buffer,
+            num_indices: indices.len() as u32,
+        }
+    }
+}
@@ -0,0 +1,310 @@
+// GPU picking: scene instances write their entity ids into an R32Uint target
+// next to the colour pass, and pixels or rectangles of it are copied back a
+// frame or two later instead of stalling the frame that asked.
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, OnceLock},
+    task::{Context, Poll, Waker},
+};
+
+use crate::ecs::entities::Entity;
+
+use super::texture::Texture;
+
+pub const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+// What the ID buffer is cleared to; instances with it can't be picked.
+pub const NO_ENTITY: Entity = u32::MAX;
+
+struct Shared<T> {
+    result: Option<T>,
+    waker: Option<Waker>,
+}
+
+// The answer to a pick once it has been read back. Check it with `try_take`
+// every frame or `.await` it; either way the engine must keep rendering for
+// it to complete.
+pub struct PickRequest<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> PickRequest<T> {
+    fn pending() -> Self {
+        Self {
+            shared: Arc::new(Mutex::new(Shared {
+                result: None,
+                waker: None,
+            })),
+        }
+    }
+
+    pub(crate) fn ready(value: T) -> Self {
+        let request = Self::pending();
+        complete(&request.shared, value);
+        request
+    }
+
+    pub fn is_ready(&self) -> bool {
+        lock(&self.shared).result.is_some()
+    }
+
+    pub fn try_take(&mut self) -> Option<T> {
+        lock(&self.shared).result.take()
+    }
+}
+
+impl<T> Future for PickRequest<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut shared = lock(&self.shared);
+        match shared.result.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+fn lock<T>(shared: &Mutex<Shared<T>>) -> std::sync::MutexGuard<'_, Shared<T>> {
+    shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn complete<T>(shared: &Mutex<Shared<T>>, value: T) {
+    let mut shared = lock(shared);
+    shared.result = Some(value);
+    if let Some(waker) = shared.waker.take() {
+        waker.wake();
+    }
+}
+
+// Hands the ids of a read back region, row by row, to whoever asked.
+type Deliver = Box<dyn FnOnce(&[Entity])>;
+
+struct Region {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Region {
+    fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+struct Readback {
+    width: u32,
+    height: u32,
+    buffer: wgpu::Buffer,
+    padded_row: u32,
+    mapped: Arc<OnceLock<Result<(), wgpu::BufferAsyncError>>>,
+    deliver: Deliver,
+}
+
+pub struct IdBuffer {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    depth: Texture,
+    width: u32,
+    height: u32,
+    // Asked for since the last frame, copied after the next ID pass.
+    queued: Vec<(Region, Deliver)>,
+    // Copied this frame, mapped once the frame is submitted.
+    copied: Vec<Readback>,
+    in_flight: Vec<Readback>,
+}
+
+impl IdBuffer {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let (width, height) = (config.width.max(1), config.height.max(1));
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("entity_ids"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ID_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth = Texture::create_depth_texture(device, config, 1, "entity_id_depth");
+        Self {
+            texture,
+            view,
+            depth,
+            width,
+            height,
+            queued: Vec::new(),
+            copied: Vec::new(),
+            in_flight: Vec::new(),
+        }
+    }
+
+    // New targets for the new surface size; picks already asked for carry on.
+    pub fn resize(&mut self, device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) {
+        let resized = Self::new(device, config);
+        self.texture = resized.texture;
+        self.view = resized.view;
+        self.depth = resized.depth;
+        self.width = resized.width;
+        self.height = resized.height;
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn color_attachment(&self, load: wgpu::LoadOp<wgpu::Color>) -> wgpu::RenderPassColorAttachment<'_> {
+        wgpu::RenderPassColorAttachment {
+            view: &self.view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load,
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+
+    pub fn depth_attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.depth.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Discard,
+            }),
+            stencil_ops: None,
+        }
+    }
+
+    // The entity drawn at pixel (x, y), None for background.
+    pub fn pick(&mut self, x: u32, y: u32) -> PickRequest<Option<Entity>> {
+        let request = PickRequest::pending();
+        let shared = request.shared.clone();
+        let deliver = move |ids: &[Entity]| {
+            complete(&shared, ids.first().copied().filter(|&id| id != NO_ENTITY));
+        };
+        self.queued.push((Region::new(x, y, 1, 1), Box::new(deliver)));
+        request
+    }
+
+    // Every entity with a pixel in the rectangle, sorted and without repeats.
+    pub fn pick_rect(&mut self, x: u32, y: u32, width: u32, height: u32) -> PickRequest<Vec<Entity>> {
+        let request = PickRequest::pending();
+        let shared = request.shared.clone();
+        let deliver = move |ids: &[Entity]| {
+            let mut entities: Vec<Entity> = ids.iter().copied().filter(|&id| id != NO_ENTITY).collect();
+            entities.sort_unstable();
+            entities.dedup();
+            complete(&shared, entities);
+        };
+        self.queued.push((Region::new(x, y, width, height), Box::new(deliver)));
+        request
+    }
+
+    // Records copies of the regions asked for. Call after the ID pass.
+    pub fn record_readbacks(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder) {
+        for (region, deliver) in self.queued.drain(..) {
+            // Whatever lies outside the target is background.
+            let x = region.x.min(self.width);
+            let y = region.y.min(self.height);
+            let width = region.width.min(self.width - x);
+            let height = region.height.min(self.height - y);
+            if width == 0 || height == 0 {
+                deliver(&[]);
+                continue;
+            }
+            let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+            let padded_row = (width * 4).div_ceil(alignment) * alignment;
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("entity_id_readback"),
+                size: (padded_row * height) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+            encoder.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture: &self.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x, y, z: 0 },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_row),
+                        rows_per_image: Some(height),
+                    },
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+            self.copied.push(Readback {
+                width,
+                height,
+                buffer,
+                padded_row,
+                mapped: Arc::new(OnceLock::new()),
+                deliver,
+            });
+        }
+    }
+
+    // Starts mapping this frame's copies. Call once the frame is submitted.
+    pub fn map_readbacks(&mut self) {
+        for readback in self.copied.drain(..) {
+            let mapped = readback.mapped.clone();
+            readback.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+                let _ = mapped.set(result);
+            });
+            self.in_flight.push(readback);
+        }
+    }
+
+    // Hands out the readbacks that have finished, without waiting for the
+    // rest.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        if self.in_flight.is_empty() {
+            return;
+        }
+        device.poll(wgpu::Maintain::Poll);
+        let mut i = 0;
+        while i < self.in_flight.len() {
+            let Some(mapped) = self.in_flight[i].mapped.get().map(Result::is_ok) else {
+                i += 1;
+                continue;
+            };
+            let readback = self.in_flight.swap_remove(i);
+            let mut ids = Vec::new();
+            if mapped {
+                let (width, height) = (readback.width, readback.height);
+                let data = readback.buffer.slice(..).get_mapped_range();
+                for row in 0..height {
+                    let start = (row * readback.padded_row) as usize;
+                    let bytes = &data[start..start + (width * 4) as usize];
+                    ids.extend(bytes.chunks_exact(4).map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]])));
+                }
+                drop(data);
+                readback.buffer.unmap();
+            }
+            (readback.deliver)(&ids);
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use super::shader::{self, PreprocessedShader, ShaderError, ShaderLoader};
+use crate::assets::watcher::FileWatcher;
+
+// Everything that makes two scene pipelines different. Defines are kept
+// sorted so the same set in a different order hits the same entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub shader: String,
+    pub defines: Vec<String>,
+    pub vertex_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
+    pub format: wgpu::TextureFormat,
+    pub depth: Option<DepthTest>,
+    pub sample_count: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DepthTest {
+    pub format: wgpu::TextureFormat,
+    pub compare: wgpu::CompareFunction,
+    pub write: bool,
+}
+
+impl PipelineKey {
+    pub fn new(
+        shader: &str,
+        vertex_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        Self {
+            shader: shader.to_string(),
+            defines: Vec::new(),
+            vertex_layouts,
+            format,
+            depth: None,
+            sample_count: 1,
+        }
+    }
+
+    pub fn with_define(mut self, define: &str) -> Self {
+        self.defines.push(define.to_string());
+        self.defines.sort();
+        self.defines.dedup();
+        self
+    }
+
+    pub fn with_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    // Depth-tested with `Less`, writing depth.
+    pub fn with_depth(self, format: wgpu::TextureFormat) -> Self {
+        self.with_depth_test(DepthTest {
+            format,
+            compare: wgpu::CompareFunction::Less,
+            write: true,
+        })
+    }
+
+    pub fn with_depth_test(mut self, depth: DepthTest) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+}
+
+// Builds render pipeline variants on demand and keeps them around. Each
+// shader needs a pipeline layout registered with `set_layout` first.
+//
+// With hot reload enabled every file a pipeline was built from is watched,
+// and a change rebuilds all variants. A variant that fails to rebuild keeps
+// its previous pipeline; the error is logged and kept in `error()`.
+pub struct PipelineCache {
+    loader: Box<dyn ShaderLoader>,
+    fallback: Option<Box<dyn ShaderLoader>>,
+    sources: HashMap<String, String>,
+    layouts: HashMap<String, wgpu::PipelineLayout>,
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+    watcher: Option<FileWatcher>,
+    error: Option<ShaderError>,
+}
+
+impl PipelineCache {
+    pub fn new(loader: Box<dyn ShaderLoader>) -> Self {
+        Self {
+            loader,
+            fallback: None,
+            sources: HashMap::new(),
+            layouts: HashMap::new(),
+            pipelines: HashMap::new(),
+            watcher: None,
+            error: None,
+        }
+    }
+
+    // Loader used when a variant cannot be built from the main loader at all,
+    // e.g. the embedded shaders behind an on-disk copy being edited.
+    pub fn with_fallback(mut self, fallback: Box<dyn ShaderLoader>) -> Self {
+        self.fallback = Some(fallback);
+        self
+    }
+
+    pub fn with_hot_reload(mut self) -> Self {
+        self.watcher = Some(FileWatcher::new());
+        self
+    }
+
+    // Registers an in-memory shader under `path`, taking precedence over the
+    // loaders. Lets games add shaders without touching the engine's files;
+    // they can still `#include` the engine's shaders.
+    pub fn add_source(&mut self, path: &str, source: &str) {
+        self.sources.insert(path.to_string(), source.to_string());
+    }
+
+    pub fn set_layout(&mut self, shader: &str, layout: wgpu::PipelineLayout) {
+        self.layouts.insert(shader.to_string(), layout);
+    }
+
+    pub fn get(&self, key: &PipelineKey) -> Option<&wgpu::RenderPipeline> {
+        self.pipelines.get(key)
+    }
+
+    pub fn get_or_create(
+        &mut self,
+        device: &wgpu::Device,
+        key: &PipelineKey,
+    ) -> Result<&wgpu::RenderPipeline, ShaderError> {
+        if !self.pipelines.contains_key(key) {
+            let (result, files) = self.build(self.loader.as_ref(), device, key);
+            self.watch(files);
+            let pipeline = match result {
+                Ok(pipeline) => pipeline,
+                Err(e) => {
+                    let Some(fallback) = self.fallback.as_deref() else {
+                        return Err(e);
+                    };
+                    log::error!("{}", e);
+                    let pipeline = self.build(fallback, device, key).0?;
+                    self.error = Some(e);
+                    pipeline
+                }
+            };
+            self.pipelines.insert(key.clone(), pipeline);
+        }
+        Ok(&self.pipelines[key])
+    }
+
+    // Last error from building a variant; cleared once everything rebuilds.
+    pub fn error(&self) -> Option<&ShaderError> {
+        self.error.as_ref()
+    }
+
+    // Rebuilds every variant if a watched file changed. Returns true when
+    // something was rebuilt or the error state changed.
+    pub fn reload(&mut self, device: &wgpu::Device) -> bool {
+        let Some(watcher) = self.watcher.as_mut() else {
+            return false;
+        };
+        if !watcher.poll() {
+            return false;
+        }
+
+        let keys: Vec<PipelineKey> = self.pipelines.keys().cloned().collect();
+        let mut error = None;
+        for key in keys {
+            let (result, files) = self.build(self.loader.as_ref(), device, &key);
+            self.watch(files);
+            match result {
+                Ok(pipeline) => {
+                    self.pipelines.insert(key, pipeline);
+                }
+                Err(e) => {
+                    log::error!("failed to reload {}, keeping its previous pipeline: {}", key.shader, e);
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        if error.is_none() {
+            log::info!("reloaded shaders");
+        }
+        self.error = error;
+        true
+    }
+
+    fn watch(&mut self, files: Vec<PathBuf>) {
+        if let Some(watcher) = self.watcher.as_mut() {
+            for file in files {
+                watcher.watch(file);
+            }
+        }
+    }
+
+    // Also returns the on-disk files the variant was built from, even when
+    // the build fails, so fixing a broken include triggers another reload.
+    fn build(
+        &self,
+        loader: &dyn ShaderLoader,
+        device: &wgpu::Device,
+        key: &PipelineKey,
+    ) -> (Result<wgpu::RenderPipeline, ShaderError>, Vec<PathBuf>) {
+        let loader = &SourceOverrides {
+            sources: &self.sources,
+            loader,
+        };
+        let mut files = loader.disk_path(&key.shader).into_iter().collect::<Vec<_>>();
+        let result = shader::preprocess(loader, &key.shader, &key.defines).and_then(|preprocessed| {
+            files = preprocessed
+                .files
+                .iter()
+                .filter_map(|file| loader.disk_path(file))
+                .collect();
+            self.compile(device, key, preprocessed)
+        });
+        (result, files)
+    }
+
+    fn compile(
+        &self,
+        device: &wgpu::Device,
+        key: &PipelineKey,
+        preprocessed: PreprocessedShader,
+    ) -> Result<wgpu::RenderPipeline, ShaderError> {
+        let layout = self
+            .layouts
+            .get(&key.shader)
+            .ok_or_else(|| ShaderError::new(&key.shader, "no pipeline layout registered"))?;
+
+        shader::validate_wgsl(&preprocessed.source, &key.shader)
+            .map_err(|e| preprocessed.remap_error(e))?;
+
+        // naga catches most mistakes, but the shader can still disagree with
+        // the pipeline layout, so catch wgpu's validation errors too instead
+        // of letting them panic.
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&key.shader),
+            source: wgpu::ShaderSource::Wgsl(preprocessed.source.as_str().into()),
+        });
+        let pipeline = create_render_pipeline(device, layout, &module, key);
+        if let Some(e) = pollster::block_on(device.pop_error_scope()) {
+            return Err(locate_stage_error(&preprocessed, &key.shader, e.to_string()));
+        }
+        Ok(pipeline)
+    }
+}
+
+// wgpu's errors have no source location. Those naming a stage are put on
+// the line of its entry point so the file and line can still be reported.
+fn locate_stage_error(preprocessed: &PreprocessedShader, path: &str, message: String) -> ShaderError {
+    let entry_point = if message.contains("ShaderStages(VERTEX)") {
+        "fn vs_main("
+    } else if message.contains("ShaderStages(FRAGMENT)") {
+        "fn fs_main("
+    } else {
+        return ShaderError::new(path, message);
+    };
+    let Some(line) = preprocessed
+        .source
+        .lines()
+        .position(|line| line.trim_start().starts_with(entry_point))
+    else {
+        return ShaderError::new(path, message);
+    };
+    preprocessed.remap_error(ShaderError {
+        line: line as u32 + 1,
+        column: 1,
+        ..ShaderError::new(path, message)
+    })
+}
+
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    key: &PipelineKey,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&key.shader),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &key.vertex_layouts,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: "fs_main",
+            targets: &[Some(wgpu::ColorTargetState {
+                format: key.format,
+                // Integer targets, like entity ids, can't blend.
+                blend: matches!(key.format.sample_type(None, None), Some(wgpu::TextureSampleType::Float { .. }))
+                    .then_some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        depth_stencil: key.depth.map(|depth| wgpu::DepthStencilState {
+            format: depth.format,
+            depth_write_enabled: depth.write,
+            depth_compare: depth.compare,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: key.sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        multiview: None,
+    })
+}
+
+struct SourceOverrides<'a> {
+    sources: &'a HashMap<String, String>,
+    loader: &'a dyn ShaderLoader,
+}
+
+impl ShaderLoader for SourceOverrides<'_> {
+    fn load(&self, path: &str) -> std::io::Result<String> {
+        match self.sources.get(path) {
+            Some(source) => Ok(source.clone()),
+            None => self.loader.load(path),
+        }
+    }
+
+    fn disk_path(&self, path: &str) -> Option<PathBuf> {
+        if self.sources.contains_key(path) {
+            return None;
+        }
+        self.loader.disk_path(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::shader::EmbeddedShaderLoader;
+
+    const FILES: EmbeddedShaderLoader = EmbeddedShaderLoader::new(&[
+        ("scene.wgsl", "#include \"common.wgsl\"\n\n@fragment\nfn fs_main() {}\n"),
+        ("common.wgsl", "struct Out { a: f32 }\n@vertex\n  fn vs_main() {}\n"),
+    ]);
+
+    #[test]
+    fn stage_errors_point_at_their_entry_point() {
+        let preprocessed = shader::preprocess(&FILES, "scene.wgsl", &[]).unwrap();
+        let locate = |message: &str| {
+            let e = locate_stage_error(&preprocessed, "scene.wgsl", message.to_string());
+            (e.path, e.line)
+        };
+        let vertex = "Error matching ShaderStages(VERTEX) shader requirements against the pipeline";
+        assert_eq!(locate(vertex), ("common.wgsl".to_string(), 3));
+        let fragment = "Error matching ShaderStages(FRAGMENT) shader requirements against the pipeline";
+        assert_eq!(locate(fragment), ("scene.wgsl".to_string(), 4));
+        assert_eq!(locate("Device is invalid"), ("scene.wgsl".to_string(), 0));
+    }
+}
@@ -0,0 +1,587 @@
+use std::{sync::Arc, time::Instant};
+
+use anyhow::bail;
+use image::GenericImageView;
+
+use super::pipeline::{PipelineCache, PipelineKey};
+use super::shader::ShaderError;
+use super::texture::Texture;
+
+// The scene is rendered into this and only brought into display range by
+// the tonemapping stage.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+const BLIT_SHADER: &str = "post/blit.wgsl";
+const BLOOM_THRESHOLD_SHADER: &str = "post/bloom_threshold.wgsl";
+const BLOOM_BLUR_SHADER: &str = "post/bloom_blur.wgsl";
+const BLOOM_COMPOSITE_SHADER: &str = "post/bloom_composite.wgsl";
+const COLOR_GRADING_SHADER: &str = "post/color_grading.wgsl";
+const IDENTITY_LUT_SIZE: u32 = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Tonemapper {
+    Clamp,
+    Reinhard,
+    Aces,
+    AgX,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum StageKind {
+    Simple,
+    Bloom,
+    ColorGrading,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostParamsUniform {
+    values: [f32; 8],
+    texel_size: [f32; 2],
+    time: f32,
+    _padding: f32,
+}
+
+// One fullscreen effect. `params` fill `values` in the stage's shader, see
+// the comment at the top of each file in `shaders/post` for their meaning.
+pub struct PostStage {
+    name: String,
+    pub enabled: bool,
+    pub params: [f32; 8],
+    shader: String,
+    kind: StageKind,
+    buffer: wgpu::Buffer,
+    // Bloom's threshold and blur passes draw into the half-size targets, so
+    // they get the same values with those targets' texel size.
+    bloom_buffer: Option<wgpu::Buffer>,
+}
+
+impl PostStage {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+// Ordered chain of fullscreen effects from the HDR scene target to the
+// surface. Each enabled stage reads the previous stage's output; the last
+// one writes the surface.
+pub struct PostStack {
+    stages: Vec<PostStage>,
+    surface_format: wgpu::TextureFormat,
+    hdr: Texture,
+    ping: [Texture; 2],
+    bloom: [Texture; 2],
+    lut: Texture,
+    sampler: wgpu::Sampler,
+    layout: wgpu::BindGroupLayout,
+    lut_layout: wgpu::BindGroupLayout,
+    blit_buffer: wgpu::Buffer,
+    start: Instant,
+}
+
+impl PostStack {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pipelines: &mut PipelineCache,
+        width: u32,
+        height: u32,
+        surface_format: wgpu::TextureFormat,
+    ) -> Self {
+        let layout = create_layout(device, wgpu::TextureViewDimension::D2, "post_bind_group_layout");
+        let lut_layout = create_layout(device, wgpu::TextureViewDimension::D3, "post_lut_bind_group_layout");
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let (hdr, ping, bloom) = create_targets(device, width, height);
+        let identity: Vec<u8> = (0..IDENTITY_LUT_SIZE.pow(3))
+            .flat_map(|i| {
+                let scale = |v: u32| (v * 255 / (IDENTITY_LUT_SIZE - 1)) as u8;
+                let r = i % IDENTITY_LUT_SIZE;
+                let g = (i / IDENTITY_LUT_SIZE) % IDENTITY_LUT_SIZE;
+                let b = i / (IDENTITY_LUT_SIZE * IDENTITY_LUT_SIZE);
+                [scale(r), scale(g), scale(b), 255]
+            })
+            .collect();
+        let lut = create_lut(device, queue, IDENTITY_LUT_SIZE, &identity);
+
+        let mut stack = Self {
+            stages: Vec::new(),
+            surface_format,
+            hdr,
+            ping,
+            bloom,
+            lut,
+            sampler,
+            layout,
+            lut_layout,
+            blit_buffer: create_params_buffer(device, "blit"),
+            start: Instant::now(),
+        };
+
+        for shader in [
+            BLIT_SHADER,
+            BLOOM_THRESHOLD_SHADER,
+            BLOOM_BLUR_SHADER,
+            BLOOM_COMPOSITE_SHADER,
+            "post/tonemap.wgsl",
+            "post/chromatic_aberration.wgsl",
+            "post/vignette.wgsl",
+            "post/film_grain.wgsl",
+            "post/fxaa.wgsl",
+        ] {
+            stack.register_layout(device, pipelines, shader, false);
+        }
+        stack.register_layout(device, pipelines, COLOR_GRADING_SHADER, true);
+
+        let defaults: [(&str, &str, StageKind, bool, [f32; 8]); 7] = [
+            ("bloom", BLOOM_COMPOSITE_SHADER, StageKind::Bloom, true, [1.0, 0.5, 0.3, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            ("tonemap", "post/tonemap.wgsl", StageKind::Simple, true, [0.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            ("color_grading", COLOR_GRADING_SHADER, StageKind::ColorGrading, false, [1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            ("chromatic_aberration", "post/chromatic_aberration.wgsl", StageKind::Simple, false, [0.005, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            ("vignette", "post/vignette.wgsl", StageKind::Simple, false, [0.4, 0.6, 0.5, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            ("film_grain", "post/film_grain.wgsl", StageKind::Simple, false, [0.05, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+            ("fxaa", "post/fxaa.wgsl", StageKind::Simple, true, [0.0; 8]),
+        ];
+        for (name, shader, kind, enabled, params) in defaults {
+            stack.stages.push(PostStage {
+                name: name.to_string(),
+                enabled,
+                params,
+                shader: shader.to_string(),
+                kind,
+                buffer: create_params_buffer(device, name),
+                bloom_buffer: (kind == StageKind::Bloom).then(|| create_params_buffer(device, "bloom_half")),
+            });
+        }
+        stack.create_pipelines(device, pipelines);
+        stack
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        let (hdr, ping, bloom) = create_targets(device, width, height);
+        self.hdr = hdr;
+        self.ping = ping;
+        self.bloom = bloom;
+    }
+
+    // Target the scene is rendered into.
+    pub fn hdr_view(&self) -> &wgpu::TextureView {
+        &self.hdr.view
+    }
+
+    pub fn stages(&self) -> &[PostStage] {
+        &self.stages
+    }
+
+    pub fn stage_mut(&mut self, name: &str) -> Option<&mut PostStage> {
+        self.stages.iter_mut().find(|stage| stage.name == name)
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        if let Some(stage) = self.stage_mut(name) {
+            stage.enabled = enabled;
+        }
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.stages.iter().any(|stage| stage.name == name && stage.enabled)
+    }
+
+    // Exposure in EV stops, 0 leaves the scene as rendered.
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.set_param("tonemap", 0, exposure);
+    }
+
+    pub fn set_tonemapper(&mut self, tonemapper: Tonemapper) {
+        let value = match tonemapper {
+            Tonemapper::Clamp => 0.0,
+            Tonemapper::Reinhard => 1.0,
+            Tonemapper::Aces => 2.0,
+            Tonemapper::AgX => 3.0,
+        };
+        self.set_param("tonemap", 1, value);
+    }
+
+    pub fn set_bloom(&mut self, threshold: f32, knee: f32, intensity: f32) {
+        self.set_param("bloom", 0, threshold);
+        self.set_param("bloom", 1, knee);
+        self.set_param("bloom", 2, intensity);
+    }
+
+    pub fn set_vignette(&mut self, intensity: f32, radius: f32, smoothness: f32) {
+        self.set_param("vignette", 0, intensity);
+        self.set_param("vignette", 1, radius);
+        self.set_param("vignette", 2, smoothness);
+    }
+
+    pub fn set_chromatic_aberration(&mut self, amount: f32) {
+        self.set_param("chromatic_aberration", 0, amount);
+    }
+
+    pub fn set_film_grain(&mut self, intensity: f32) {
+        self.set_param("film_grain", 0, intensity);
+    }
+
+    pub fn set_color_grading_strength(&mut self, strength: f32) {
+        self.set_param("color_grading", 0, strength);
+    }
+
+    // Loads a LUT laid out as a horizontal strip of `size` slices, each
+    // `size` x `size` pixels (so the image is size² x size), with blue
+    // increasing from slice to slice.
+    pub fn set_color_grading_lut(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+    ) -> anyhow::Result<()> {
+        let (width, height) = img.dimensions();
+        if height < 2 || width != height * height {
+            bail!("LUT strip must be size² x size pixels, got {}x{}", width, height);
+        }
+        let rgba = img.to_rgba8();
+        let size = height;
+        let mut data = Vec::with_capacity((size * size * size * 4) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.extend_from_slice(&rgba.get_pixel(b * size + r, g).0);
+                }
+            }
+        }
+        self.lut = create_lut(device, queue, size, &data);
+        Ok(())
+    }
+
+    // Inserts a game-defined stage at `index` (clamped to the end). `source`
+    // is WGSL that can `#include "post/common.wgsl"` for the fullscreen
+    // vertex shader, input bindings and `params`.
+    pub fn add_stage(
+        &mut self,
+        device: &wgpu::Device,
+        pipelines: &mut PipelineCache,
+        index: usize,
+        name: &str,
+        source: &str,
+    ) -> Result<(), ShaderError> {
+        let shader = format!("post/custom/{}.wgsl", name);
+        pipelines.add_source(&shader, source);
+        self.register_layout(device, pipelines, &shader, false);
+        for format in [HDR_FORMAT, self.surface_format] {
+            pipelines.get_or_create(device, &stage_key(&shader, format))?;
+        }
+
+        let index = index.min(self.stages.len());
+        self.stages.insert(
+            index,
+            PostStage {
+                name: name.to_string(),
+                enabled: true,
+                params: [0.0; 8],
+                shader,
+                kind: StageKind::Simple,
+                buffer: create_params_buffer(device, name),
+                bloom_buffer: None,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn remove_stage(&mut self, name: &str) {
+        self.stages.retain(|stage| stage.name != name);
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue) {
+        let texel_size = |texture: &Texture| {
+            let size = texture.texture.size();
+            [1.0 / size.width as f32, 1.0 / size.height as f32]
+        };
+        let (full, half) = (texel_size(&self.hdr), texel_size(&self.bloom[0]));
+        let time = self.start.elapsed().as_secs_f32();
+        let upload = |buffer: &wgpu::Buffer, values: [f32; 8], texel_size: [f32; 2]| {
+            let uniform = PostParamsUniform {
+                values,
+                texel_size,
+                time,
+                _padding: 0.0,
+            };
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[uniform]));
+        };
+        upload(&self.blit_buffer, [0.0; 8], full);
+        for stage in &self.stages {
+            upload(&stage.buffer, stage.params, full);
+            if let Some(buffer) = &stage.bloom_buffer {
+                upload(buffer, stage.params, half);
+            }
+        }
+    }
+
+    // Runs the enabled stages from the HDR target into `output`.
+    pub fn execute(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &PipelineCache,
+        output: &wgpu::TextureView,
+    ) {
+        // Stages whose pipelines failed to build are skipped, not drawn from
+        // stale inputs.
+        let stages: Vec<&PostStage> = self
+            .stages
+            .iter()
+            .filter(|stage| stage.enabled)
+            .filter(|stage| pipelines.get(&stage_key(&stage.shader, self.surface_format)).is_some())
+            .collect();
+
+        if stages.is_empty() {
+            self.draw(device, encoder, pipelines, BLIT_SHADER, &[], self.surface_format, &self.hdr.view, None, &self.blit_buffer, output);
+            return;
+        }
+
+        let mut input = &self.hdr.view;
+        for (i, stage) in stages.iter().enumerate() {
+            let last = i + 1 == stages.len();
+            let (target, format) = if last {
+                (output, self.surface_format)
+            } else {
+                (&self.ping[i % 2].view, HDR_FORMAT)
+            };
+
+            match stage.kind {
+                StageKind::Simple => {
+                    self.draw(device, encoder, pipelines, &stage.shader, &[], format, input, None, &stage.buffer, target);
+                }
+                StageKind::ColorGrading => {
+                    self.draw(device, encoder, pipelines, &stage.shader, &[], format, input, Some(&self.lut.view), &stage.buffer, target);
+                }
+                StageKind::Bloom => {
+                    let [a, b] = &self.bloom;
+                    let half = stage.bloom_buffer.as_ref().unwrap_or(&stage.buffer);
+                    self.draw(device, encoder, pipelines, BLOOM_THRESHOLD_SHADER, &[], HDR_FORMAT, input, None, half, &a.view);
+                    self.draw(device, encoder, pipelines, BLOOM_BLUR_SHADER, &["HORIZONTAL"], HDR_FORMAT, &a.view, None, half, &b.view);
+                    self.draw(device, encoder, pipelines, BLOOM_BLUR_SHADER, &[], HDR_FORMAT, &b.view, None, half, &a.view);
+                    self.draw(device, encoder, pipelines, &stage.shader, &[], format, input, Some(&a.view), &stage.buffer, target);
+                }
+            }
+            input = target;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pipelines: &PipelineCache,
+        shader: &str,
+        defines: &[&str],
+        format: wgpu::TextureFormat,
+        input: &wgpu::TextureView,
+        extra: Option<&wgpu::TextureView>,
+        params: &wgpu::Buffer,
+        target: &wgpu::TextureView,
+    ) {
+        let key = defines
+            .iter()
+            .fold(stage_key(shader, format), |key, define| key.with_define(define));
+        let Some(pipeline) = pipelines.get(&key) else {
+            return;
+        };
+        let layout = if shader == COLOR_GRADING_SHADER {
+            &self.lut_layout
+        } else {
+            &self.layout
+        };
+        // Stages without an extra texture bind their input again, the
+        // layout is shared by all of them.
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(shader),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(extra.unwrap_or(input)),
+                },
+            ],
+        });
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(shader),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    fn set_param(&mut self, stage: &str, index: usize, value: f32) {
+        if let Some(stage) = self.stage_mut(stage) {
+            stage.params[index] = value;
+        }
+    }
+
+    fn register_layout(&self, device: &wgpu::Device, pipelines: &mut PipelineCache, shader: &str, lut: bool) {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(shader),
+            bind_group_layouts: &[if lut { &self.lut_layout } else { &self.layout }],
+            push_constant_ranges: &[],
+        });
+        pipelines.set_layout(shader, layout);
+    }
+
+    fn create_pipelines(&self, device: &wgpu::Device, pipelines: &mut PipelineCache) {
+        let mut keys = vec![
+            stage_key(BLIT_SHADER, self.surface_format),
+            stage_key(BLOOM_THRESHOLD_SHADER, HDR_FORMAT),
+            stage_key(BLOOM_BLUR_SHADER, HDR_FORMAT),
+            stage_key(BLOOM_BLUR_SHADER, HDR_FORMAT).with_define("HORIZONTAL"),
+        ];
+        for stage in &self.stages {
+            keys.push(stage_key(&stage.shader, HDR_FORMAT));
+            keys.push(stage_key(&stage.shader, self.surface_format));
+        }
+        for key in keys {
+            if let Err(e) = pipelines.get_or_create(device, &key) {
+                log::error!("{}", e);
+            }
+        }
+    }
+}
+
+fn stage_key(shader: &str, format: wgpu::TextureFormat) -> PipelineKey {
+    PipelineKey::new(shader, Vec::new(), format)
+}
+
+fn create_layout(
+    device: &wgpu::Device,
+    extra_dimension: wgpu::TextureViewDimension,
+    label: &str,
+) -> wgpu::BindGroupLayout {
+    let texture = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            multisampled: false,
+            view_dimension,
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+        },
+        count: None,
+    };
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            texture(0, wgpu::TextureViewDimension::D2),
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            texture(3, extra_dimension),
+        ],
+        label: Some(label),
+    })
+}
+
+fn create_params_buffer(device: &wgpu::Device, label: &str) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: std::mem::size_of::<PostParamsUniform>() as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+fn create_targets(device: &wgpu::Device, width: u32, height: u32) -> (Texture, [Texture; 2], [Texture; 2]) {
+    let target = |w: u32, h: u32, label| Texture::create_render_target(device, w, h, HDR_FORMAT, label);
+    let (half_width, half_height) = ((width / 2).max(1), (height / 2).max(1));
+    (
+        target(width, height, "hdr_scene"),
+        [target(width, height, "post_ping"), target(width, height, "post_pong")],
+        [target(half_width, half_height, "bloom_a"), target(half_width, half_height, "bloom_b")],
+    )
+}
+
+fn create_lut(device: &wgpu::Device, queue: &wgpu::Queue, size: u32, rgba: &[u8]) -> Texture {
+    let extent = wgpu::Extent3d {
+        width: size,
+        height: size,
+        depth_or_array_layers: size,
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("color_grading_lut"),
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D3,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            aspect: wgpu::TextureAspect::All,
+            texture: &texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        rgba,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * size),
+            rows_per_image: Some(size),
+        },
+        extent,
+    );
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        ..Default::default()
+    });
+    Texture {
+        texture,
+        view,
+        sampler: Arc::new(sampler),
+    }
+}
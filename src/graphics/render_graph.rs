@@ -0,0 +1,827 @@
+use std::{collections::HashMap, fmt};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ResourceId(usize);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PassId(usize);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TextureSize {
+    // Follows the surface, so it is reallocated on resize.
+    Surface,
+    Fixed { width: u32, height: u32 },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TextureDesc {
+    pub size: TextureSize,
+    pub format: wgpu::TextureFormat,
+    pub sample_count: u32,
+    pub usage: wgpu::TextureUsages,
+}
+
+impl TextureDesc {
+    // Surface-sized single-sample attachment that later passes can sample.
+    pub fn attachment(format: wgpu::TextureFormat) -> Self {
+        Self {
+            size: TextureSize::Surface,
+            format,
+            sample_count: 1,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BufferDesc {
+    pub size: u64,
+    pub usage: wgpu::BufferUsages,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ResourceKind {
+    // Owned outside the graph (the surface, or a texture the engine keeps
+    // around); its view is handed to `execute` every frame.
+    Imported,
+    Texture(TextureDesc),
+    Buffer(BufferDesc),
+}
+
+struct Resource {
+    name: String,
+    kind: ResourceKind,
+    // Set for the single-sample copies the graph creates to resolve MSAA
+    // textures that are sampled later.
+    resolve_of: Option<ResourceId>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ColorAttachment {
+    pub resource: ResourceId,
+    pub load: wgpu::LoadOp<wgpu::Color>,
+    pub resolve_target: Option<ResourceId>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct DepthAttachment {
+    pub resource: ResourceId,
+    pub load: wgpu::LoadOp<f32>,
+}
+
+// What a pass touches. Colour/depth attachments and `write`s make the pass a
+// writer of those resources; `read`s are sampled textures or read-only
+// buffers. Writers of a resource run in the order they were added, and
+// readers run after all of them.
+#[derive(Debug, Clone)]
+pub struct PassDesc {
+    name: String,
+    colors: Vec<ColorAttachment>,
+    depth: Option<DepthAttachment>,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    side_effects: bool,
+}
+
+impl PassDesc {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            colors: Vec::new(),
+            depth: None,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            side_effects: false,
+        }
+    }
+
+    pub fn color(mut self, resource: ResourceId, load: wgpu::LoadOp<wgpu::Color>) -> Self {
+        self.colors.push(ColorAttachment {
+            resource,
+            load,
+            resolve_target: None,
+        });
+        self
+    }
+
+    pub fn color_resolve(
+        mut self,
+        resource: ResourceId,
+        load: wgpu::LoadOp<wgpu::Color>,
+        resolve_target: ResourceId,
+    ) -> Self {
+        self.colors.push(ColorAttachment {
+            resource,
+            load,
+            resolve_target: Some(resolve_target),
+        });
+        self
+    }
+
+    pub fn depth(mut self, resource: ResourceId, load: wgpu::LoadOp<f32>) -> Self {
+        self.depth = Some(DepthAttachment { resource, load });
+        self
+    }
+
+    pub fn read(mut self, resource: ResourceId) -> Self {
+        self.reads.push(resource);
+        self
+    }
+
+    pub fn write(mut self, resource: ResourceId) -> Self {
+        self.writes.push(resource);
+        self
+    }
+
+    // Keeps the pass even when nothing reads its outputs (readbacks, queries).
+    pub fn side_effects(mut self) -> Self {
+        self.side_effects = true;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn written(&self) -> impl Iterator<Item = ResourceId> + '_ {
+        self.colors
+            .iter()
+            .flat_map(|c| std::iter::once(c.resource).chain(c.resolve_target))
+            .chain(self.depth.map(|d| d.resource))
+            .chain(self.writes.iter().copied())
+    }
+
+    fn used(&self) -> impl Iterator<Item = ResourceId> + '_ {
+        self.written().chain(self.reads.iter().copied())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderGraphError {
+    Cycle(Vec<String>),
+    UnknownResource { pass: String },
+    // A pass writes a buffer as a colour/depth attachment or similar.
+    InvalidUsage { pass: String, resource: String },
+}
+
+impl fmt::Display for RenderGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderGraphError::Cycle(passes) => {
+                write!(f, "render graph has a cycle between passes: {}", passes.join(", "))
+            }
+            RenderGraphError::UnknownResource { pass } => {
+                write!(f, "pass `{}` uses a resource from another graph", pass)
+            }
+            RenderGraphError::InvalidUsage { pass, resource } => {
+                write!(f, "pass `{}` cannot use `{}` that way", pass, resource)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RenderGraphError {}
+
+// Result of `RenderGraph::compile`. Holds no GPU objects, so ordering and
+// aliasing can be checked without a device.
+#[derive(Debug, Clone)]
+pub struct CompiledGraph {
+    // Passes to run, in order. Passes that contribute nothing are left out.
+    pub order: Vec<PassId>,
+    // Per resource, the first and last position in `order` using it.
+    pub lifetimes: Vec<Option<(usize, usize)>>,
+    // Per resource, the physical allocation backing it. Transient resources
+    // whose lifetimes don't overlap share one when their descriptions match.
+    // None for imported and unused resources.
+    pub physical: Vec<Option<usize>>,
+    pub physical_count: usize,
+    passes: Vec<PassDesc>,
+}
+
+impl CompiledGraph {
+    pub fn pass_position(&self, pass: PassId) -> Option<usize> {
+        self.order.iter().position(|p| *p == pass)
+    }
+}
+
+// Something recorded by a pass. `C` is whatever the owner of the graph hands
+// to every pass when executing it (the engine passes its `State`).
+pub trait RenderGraphPass<C: ?Sized> {
+    fn execute(&mut self, ctx: &mut PassContext, data: &C);
+}
+
+impl<C: ?Sized, F: FnMut(&mut PassContext, &C)> RenderGraphPass<C> for F {
+    fn execute(&mut self, ctx: &mut PassContext, data: &C) {
+        self(ctx, data)
+    }
+}
+
+enum PhysicalResource {
+    Texture(wgpu::Texture, wgpu::TextureView),
+    Buffer(wgpu::Buffer),
+}
+
+pub struct PassContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    pass: &'a PassDesc,
+    surface: ResourceId,
+    imports: &'a [(ResourceId, &'a wgpu::TextureView)],
+    resources: &'a [Resource],
+    physical: &'a [PhysicalResource],
+    assignment: &'a [Option<usize>],
+}
+
+impl<'a> PassContext<'a> {
+    pub fn view(&self, id: ResourceId) -> Option<&'a wgpu::TextureView> {
+        if self.resources.get(id.0)?.kind == ResourceKind::Imported {
+            return self.imports.iter().find(|(i, _)| *i == id).map(|(_, view)| *view);
+        }
+        match self.physical.get((*self.assignment.get(id.0)?)?)? {
+            PhysicalResource::Texture(_, view) => Some(view),
+            PhysicalResource::Buffer(_) => None,
+        }
+    }
+
+    pub fn texture(&self, id: ResourceId) -> Option<&'a wgpu::Texture> {
+        match self.physical.get((*self.assignment.get(id.0)?)?)? {
+            PhysicalResource::Texture(texture, _) => Some(texture),
+            PhysicalResource::Buffer(_) => None,
+        }
+    }
+
+    pub fn buffer(&self, id: ResourceId) -> Option<&'a wgpu::Buffer> {
+        match self.physical.get((*self.assignment.get(id.0)?)?)? {
+            PhysicalResource::Buffer(buffer) => Some(buffer),
+            PhysicalResource::Texture(..) => None,
+        }
+    }
+
+    // Surface view for the frame being rendered.
+    pub fn surface(&self) -> Option<&'a wgpu::TextureView> {
+        self.view(self.surface)
+    }
+
+    // Begins a render pass on the attachments the pass declared.
+    pub fn begin_render_pass(&mut self) -> wgpu::RenderPass<'_> {
+        let colors: Vec<Option<wgpu::RenderPassColorAttachment>> = self
+            .pass
+            .colors
+            .iter()
+            .map(|c| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view: self.view(c.resource)?,
+                    resolve_target: c.resolve_target.and_then(|r| self.view(r)),
+                    ops: wgpu::Operations {
+                        load: c.load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })
+            })
+            .collect();
+        let depth = self.pass.depth.and_then(|d| {
+            Some(wgpu::RenderPassDepthStencilAttachment {
+                view: self.view(d.resource)?,
+                depth_ops: Some(wgpu::Operations {
+                    load: d.load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            })
+        });
+        self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.pass.name),
+            color_attachments: &colors,
+            depth_stencil_attachment: depth,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        })
+    }
+}
+
+struct PassEntry<C: ?Sized> {
+    desc: PassDesc,
+    pass: Option<Box<dyn RenderGraphPass<C>>>,
+}
+
+// Declares frame resources and the passes using them, then works out pass
+// order, culls passes nobody needs, aliases transient textures and buffers,
+// and resolves multisampled textures before they are sampled.
+pub struct RenderGraph<C: ?Sized> {
+    resources: Vec<Resource>,
+    passes: Vec<PassEntry<C>>,
+    surface: ResourceId,
+    compiled: Option<CompiledGraph>,
+    physical: Vec<PhysicalResource>,
+    physical_size: (u32, u32),
+}
+
+impl<C: ?Sized> RenderGraph<C> {
+    pub fn new() -> Self {
+        Self {
+            resources: vec![Resource {
+                name: "surface".to_string(),
+                kind: ResourceKind::Imported,
+                resolve_of: None,
+            }],
+            passes: Vec::new(),
+            surface: ResourceId(0),
+            compiled: None,
+            physical: Vec::new(),
+            physical_size: (0, 0),
+        }
+    }
+
+    // The swapchain texture of the current frame.
+    pub fn surface(&self) -> ResourceId {
+        self.surface
+    }
+
+    pub fn import_texture(&mut self, name: &str) -> ResourceId {
+        self.add_resource(name, ResourceKind::Imported)
+    }
+
+    pub fn create_texture(&mut self, name: &str, desc: TextureDesc) -> ResourceId {
+        self.add_resource(name, ResourceKind::Texture(desc))
+    }
+
+    pub fn create_buffer(&mut self, name: &str, desc: BufferDesc) -> ResourceId {
+        self.add_resource(name, ResourceKind::Buffer(desc))
+    }
+
+    pub fn resource_name(&self, id: ResourceId) -> Option<&str> {
+        self.resources.get(id.0).map(|r| r.name.as_str())
+    }
+
+    pub fn add_pass(&mut self, desc: PassDesc, pass: impl RenderGraphPass<C> + 'static) -> PassId {
+        self.compiled = None;
+        self.passes.push(PassEntry {
+            desc,
+            pass: Some(Box::new(pass)),
+        });
+        PassId(self.passes.len() - 1)
+    }
+
+    // Passes keep their ids; a removed pass is simply never scheduled.
+    pub fn remove_pass(&mut self, id: PassId) {
+        if let Some(entry) = self.passes.get_mut(id.0) {
+            self.compiled = None;
+            entry.pass = None;
+        }
+    }
+
+    pub fn find_pass(&self, name: &str) -> Option<PassId> {
+        self.passes
+            .iter()
+            .position(|p| p.pass.is_some() && p.desc.name == name)
+            .map(PassId)
+    }
+
+    pub fn compile(&mut self) -> Result<&CompiledGraph, RenderGraphError> {
+        if self.compiled.is_none() {
+            let compiled = self.build()?;
+            self.compiled = Some(compiled);
+            // New layout, new allocations.
+            self.physical.clear();
+        }
+        Ok(self.compiled.as_ref().unwrap())
+    }
+
+    fn add_resource(&mut self, name: &str, kind: ResourceKind) -> ResourceId {
+        self.compiled = None;
+        self.resources.push(Resource {
+            name: name.to_string(),
+            kind,
+            resolve_of: None,
+        });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    fn build(&mut self) -> Result<CompiledGraph, RenderGraphError> {
+        let mut passes: Vec<PassDesc> = self
+            .passes
+            .iter()
+            .map(|p| p.desc.clone())
+            .collect();
+        let active: Vec<bool> = self.passes.iter().map(|p| p.pass.is_some()).collect();
+
+        for (i, pass) in passes.iter().enumerate() {
+            if !active[i] {
+                continue;
+            }
+            for id in pass.used() {
+                if id.0 >= self.resources.len() {
+                    return Err(RenderGraphError::UnknownResource {
+                        pass: pass.name.clone(),
+                    });
+                }
+            }
+            let attachments = pass
+                .colors
+                .iter()
+                .flat_map(|c| std::iter::once(c.resource).chain(c.resolve_target))
+                .chain(pass.depth.map(|d| d.resource));
+            for id in attachments {
+                if let ResourceKind::Buffer(_) = self.resources[id.0].kind {
+                    return Err(RenderGraphError::InvalidUsage {
+                        pass: pass.name.clone(),
+                        resource: self.resources[id.0].name.clone(),
+                    });
+                }
+            }
+        }
+
+        self.insert_resolves(&mut passes, &active);
+
+        // Dependency edges: each writer of a resource depends on the previous
+        // writer, and readers depend on the last writer.
+        let mut writers: Vec<Vec<usize>> = vec![Vec::new(); self.resources.len()];
+        for (i, pass) in passes.iter().enumerate() {
+            if !active[i] {
+                continue;
+            }
+            for id in pass.written() {
+                if writers[id.0].last() != Some(&i) {
+                    writers[id.0].push(i);
+                }
+            }
+        }
+        let mut deps: Vec<Vec<usize>> = vec![Vec::new(); passes.len()];
+        for chain in &writers {
+            for pair in chain.windows(2) {
+                deps[pair[1]].push(pair[0]);
+            }
+        }
+        for (i, pass) in passes.iter().enumerate() {
+            if !active[i] {
+                continue;
+            }
+            for id in &pass.reads {
+                if let Some(&last) = writers[id.0].last() {
+                    if last != i {
+                        deps[i].push(last);
+                    }
+                }
+            }
+        }
+
+        // Keep passes that write the surface or have side effects, and
+        // everything they depend on.
+        let mut live = vec![false; passes.len()];
+        let mut stack: Vec<usize> = (0..passes.len())
+            .filter(|&i| {
+                active[i]
+                    && (passes[i].side_effects
+                        || passes[i].written().any(|id| id == self.surface))
+            })
+            .collect();
+        while let Some(i) = stack.pop() {
+            if !live[i] {
+                live[i] = true;
+                stack.extend(deps[i].iter().copied());
+            }
+        }
+
+        // Kahn's algorithm, picking the earliest added pass when several are
+        // ready so the order stays stable.
+        let mut remaining: Vec<usize> = deps.iter().map(|d| d.iter().filter(|&&p| live[p]).count()).collect();
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); passes.len()];
+        for (i, d) in deps.iter().enumerate() {
+            for &p in d {
+                dependents[p].push(i);
+            }
+        }
+        let mut ready: Vec<usize> = (0..passes.len())
+            .filter(|&i| live[i] && remaining[i] == 0)
+            .collect();
+        let mut order = Vec::new();
+        while !ready.is_empty() {
+            let (slot, &next) = ready.iter().enumerate().min_by_key(|(_, &p)| p).unwrap();
+            ready.swap_remove(slot);
+            order.push(PassId(next));
+            for &d in &dependents[next] {
+                if live[d] {
+                    remaining[d] -= 1;
+                    if remaining[d] == 0 {
+                        ready.push(d);
+                    }
+                }
+            }
+        }
+        let live_count = live.iter().filter(|&&l| l).count();
+        if order.len() != live_count {
+            let stuck = (0..passes.len())
+                .filter(|&i| live[i] && !order.contains(&PassId(i)))
+                .map(|i| passes[i].name.clone())
+                .collect();
+            return Err(RenderGraphError::Cycle(stuck));
+        }
+
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.resources.len()];
+        for (position, pass) in order.iter().enumerate() {
+            for id in passes[pass.0].used() {
+                let lifetime = lifetimes[id.0].get_or_insert((position, position));
+                lifetime.1 = position;
+            }
+        }
+
+        // Greedy aliasing: walk the passes in order, reusing an allocation
+        // that is free by then and has the same description.
+        let mut physical: Vec<Option<usize>> = vec![None; self.resources.len()];
+        let mut physical_kinds: Vec<ResourceKind> = Vec::new();
+        let mut free_at: Vec<usize> = Vec::new();
+        let mut by_start: Vec<usize> = (0..self.resources.len())
+            .filter(|&r| lifetimes[r].is_some() && self.resources[r].kind != ResourceKind::Imported)
+            .collect();
+        by_start.sort_by_key(|&r| (lifetimes[r].unwrap().0, r));
+        for r in by_start {
+            let (start, end) = lifetimes[r].unwrap();
+            let kind = &self.resources[r].kind;
+            let reuse = (0..physical_kinds.len())
+                .find(|&p| free_at[p] < start && compatible(&physical_kinds[p], kind));
+            let p = match reuse {
+                Some(p) => {
+                    if let (ResourceKind::Buffer(existing), ResourceKind::Buffer(wanted)) =
+                        (&mut physical_kinds[p], kind)
+                    {
+                        existing.size = existing.size.max(wanted.size);
+                    }
+                    p
+                }
+                None => {
+                    physical_kinds.push(kind.clone());
+                    free_at.push(0);
+                    physical_kinds.len() - 1
+                }
+            };
+            free_at[p] = end;
+            physical[r] = Some(p);
+        }
+
+        Ok(CompiledGraph {
+            order,
+            lifetimes,
+            physical,
+            physical_count: physical_kinds.len(),
+            passes,
+        })
+    }
+
+    // Sampling a multisampled texture isn't possible with a filtering
+    // sampler, so reads of one are redirected to a single-sample copy that
+    // the last writer before the read resolves into.
+    fn insert_resolves(&mut self, passes: &mut [PassDesc], active: &[bool]) {
+        for reader in 0..passes.len() {
+            if !active[reader] {
+                continue;
+            }
+            for slot in 0..passes[reader].reads.len() {
+                let id = passes[reader].reads[slot];
+                let ResourceKind::Texture(desc) = self.resources[id.0].kind else {
+                    continue;
+                };
+                if desc.sample_count <= 1 {
+                    continue;
+                }
+                let resolved = self.resolve_resource(id, desc);
+                passes[reader].reads[slot] = resolved;
+
+                let writer = (0..passes.len())
+                    .filter(|&w| active[w] && w != reader)
+                    .rfind(|&w| passes[w].colors.iter().any(|c| c.resource == id));
+                if let Some(writer) = writer {
+                    for color in passes[writer].colors.iter_mut() {
+                        if color.resource == id && color.resolve_target.is_none() {
+                            color.resolve_target = Some(resolved);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn resolve_resource(&mut self, id: ResourceId, desc: TextureDesc) -> ResourceId {
+        if let Some(i) = self.resources.iter().position(|r| r.resolve_of == Some(id)) {
+            return ResourceId(i);
+        }
+        self.resources.push(Resource {
+            name: format!("{} (resolved)", self.resources[id.0].name),
+            kind: ResourceKind::Texture(TextureDesc {
+                sample_count: 1,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+                ..desc
+            }),
+            resolve_of: Some(id),
+        });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    // Compiles if needed, (re)allocates transient resources for the surface
+    // size and records every scheduled pass into `encoder`. `imports` gives
+    // the views of imported textures, including the surface, for this frame.
+    pub fn execute(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        imports: &[(ResourceId, &wgpu::TextureView)],
+        surface_size: (u32, u32),
+        data: &C,
+    ) -> Result<(), RenderGraphError> {
+        self.compile()?;
+        if self.physical_size != surface_size {
+            self.physical.clear();
+            self.physical_size = surface_size;
+        }
+        if self.physical.is_empty() {
+            self.allocate(device);
+        }
+
+        let compiled = self.compiled.as_ref().unwrap();
+        for pass_id in &compiled.order {
+            let Some(pass) = self.passes[pass_id.0].pass.as_mut() else {
+                continue;
+            };
+            let mut ctx = PassContext {
+                device,
+                queue,
+                encoder: &mut *encoder,
+                pass: &compiled.passes[pass_id.0],
+                surface: self.surface,
+                imports,
+                resources: &self.resources,
+                physical: &self.physical,
+                assignment: &compiled.physical,
+            };
+            pass.execute(&mut ctx, data);
+        }
+        Ok(())
+    }
+
+    fn allocate(&mut self, device: &wgpu::Device) {
+        let Some(compiled) = self.compiled.as_ref() else {
+            return;
+        };
+        let mut kinds: HashMap<usize, (ResourceKind, String)> = HashMap::new();
+        for (r, p) in compiled.physical.iter().enumerate() {
+            let Some(p) = *p else {
+                continue;
+            };
+            let entry = kinds
+                .entry(p)
+                .or_insert_with(|| (self.resources[r].kind.clone(), self.resources[r].name.clone()));
+            if let (ResourceKind::Buffer(existing), ResourceKind::Buffer(wanted)) =
+                (&mut entry.0, &self.resources[r].kind)
+            {
+                existing.size = existing.size.max(wanted.size);
+            }
+        }
+
+        let (surface_width, surface_height) = self.physical_size;
+        self.physical = (0..compiled.physical_count)
+            .map(|p| {
+                let (kind, name) = &kinds[&p];
+                match kind {
+                    ResourceKind::Texture(desc) => {
+                        let (width, height) = match desc.size {
+                            TextureSize::Surface => (surface_width, surface_height),
+                            TextureSize::Fixed { width, height } => (width, height),
+                        };
+                        let texture = device.create_texture(&wgpu::TextureDescriptor {
+                            label: Some(name),
+                            size: wgpu::Extent3d {
+                                width: width.max(1),
+                                height: height.max(1),
+                                depth_or_array_layers: 1,
+                            },
+                            mip_level_count: 1,
+                            sample_count: desc.sample_count,
+                            dimension: wgpu::TextureDimension::D2,
+                            format: desc.format,
+                            usage: desc.usage,
+                            view_formats: &[],
+                        });
+                        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                        PhysicalResource::Texture(texture, view)
+                    }
+                    ResourceKind::Buffer(desc) => PhysicalResource::Buffer(device.create_buffer(
+                        &wgpu::BufferDescriptor {
+                            label: Some(name),
+                            size: desc.size,
+                            usage: desc.usage,
+                            mapped_at_creation: false,
+                        },
+                    )),
+                    ResourceKind::Imported => unreachable!("imported resources are never allocated"),
+                }
+            })
+            .collect();
+    }
+}
+
+impl<C: ?Sized> Default for RenderGraph<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn compatible(a: &ResourceKind, b: &ResourceKind) -> bool {
+    match (a, b) {
+        (ResourceKind::Texture(a), ResourceKind::Texture(b)) => a == b,
+        (ResourceKind::Buffer(a), ResourceKind::Buffer(b)) => a.usage == b.usage,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color() -> TextureDesc {
+        TextureDesc::attachment(wgpu::TextureFormat::Rgba8Unorm)
+    }
+
+    fn add(graph: &mut RenderGraph<()>, desc: PassDesc) -> PassId {
+        graph.add_pass(desc, |_: &mut PassContext, _: &()| {})
+    }
+
+    #[test]
+    fn orders_passes_by_dependency() {
+        let mut graph = RenderGraph::new();
+        let surface = graph.surface();
+        let shadow = graph.create_texture("shadow", color());
+        let scene = graph.create_texture("scene", color());
+        let debug = graph.create_texture("debug", color());
+        // Added out of order: readers before the passes writing their inputs.
+        let post = add(&mut graph, PassDesc::new("post").read(scene).color(surface, wgpu::LoadOp::Load));
+        let main = add(&mut graph, PassDesc::new("main").read(shadow).color(scene, wgpu::LoadOp::Load));
+        let shadows = add(&mut graph, PassDesc::new("shadows").color(shadow, wgpu::LoadOp::Load));
+        let unused = add(&mut graph, PassDesc::new("unused").read(scene).color(debug, wgpu::LoadOp::Load));
+        let ui = add(&mut graph, PassDesc::new("ui").color(surface, wgpu::LoadOp::Load));
+
+        let compiled = graph.compile().unwrap();
+        assert_eq!(compiled.order, vec![shadows, main, post, ui]);
+        // Nothing reads `debug`, so its pass is culled and it isn't allocated.
+        assert_eq!(compiled.pass_position(unused), None);
+        assert_eq!(compiled.lifetimes[debug.0], None);
+        assert_eq!(compiled.physical[debug.0], None);
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let mut graph = RenderGraph::new();
+        let surface = graph.surface();
+        let a = graph.create_texture("a", color());
+        let b = graph.create_texture("b", color());
+        let load = wgpu::LoadOp::Load;
+        add(&mut graph, PassDesc::new("first").read(b).color(a, load).color(surface, load));
+        add(&mut graph, PassDesc::new("second").read(a).color(b, load).color(surface, load));
+
+        match graph.compile() {
+            Err(RenderGraphError::Cycle(passes)) => assert_eq!(passes, vec!["first", "second"]),
+            other => panic!("expected a cycle, got {:?}", other.map(|c| c.order.clone())),
+        }
+    }
+
+    #[test]
+    fn aliases_only_disjoint_lifetimes() {
+        let mut graph = RenderGraph::new();
+        let surface = graph.surface();
+        let a = graph.create_texture("a", color());
+        let b = graph.create_texture("b", color());
+        let c = graph.create_texture("c", color());
+        let hdr = graph.create_texture("hdr", TextureDesc::attachment(wgpu::TextureFormat::Rgba16Float));
+        add(&mut graph, PassDesc::new("write a").color(a, wgpu::LoadOp::Load));
+        add(&mut graph, PassDesc::new("a to b").read(a).color(b, wgpu::LoadOp::Load));
+        add(&mut graph, PassDesc::new("b to c").read(b).color(c, wgpu::LoadOp::Load));
+        add(&mut graph, PassDesc::new("c to hdr").read(c).color(hdr, wgpu::LoadOp::Load));
+        add(&mut graph, PassDesc::new("present").read(hdr).color(surface, wgpu::LoadOp::Load));
+
+        let compiled = graph.compile().unwrap();
+        assert_eq!(compiled.lifetimes[a.0], Some((0, 1)));
+        assert_eq!(compiled.lifetimes[b.0], Some((1, 2)));
+        assert_eq!(compiled.lifetimes[c.0], Some((2, 3)));
+        assert_eq!(compiled.physical[surface.0], None);
+
+        // a is done before c starts, so they share; b overlaps both.
+        assert_eq!(compiled.physical[a.0], compiled.physical[c.0]);
+        assert_ne!(compiled.physical[a.0], compiled.physical[b.0]);
+        assert_ne!(compiled.physical[b.0], compiled.physical[c.0]);
+        // Different formats never share, whatever their lifetimes.
+        assert_ne!(compiled.physical[hdr.0], compiled.physical[a.0]);
+        assert_eq!(compiled.physical_count, 3);
+
+        for x in [a, b, c, hdr] {
+            for y in [a, b, c, hdr] {
+                let lifetimes = (compiled.lifetimes[x.0], compiled.lifetimes[y.0]);
+                let (Some((xs, xe)), Some((ys, ye))) = lifetimes else {
+                    continue;
+                };
+                if x != y && xs <= ye && ys <= xe {
+                    assert_ne!(compiled.physical[x.0], compiled.physical[y.0]);
+                }
+            }
+        }
+    }
+}
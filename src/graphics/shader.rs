@@ -0,0 +1,435 @@
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+};
+
+#[derive(Debug, Clone)]
+pub struct ShaderError {
+    pub path: String,
+    // 1-based, 0 when the error has no source location (e.g. IO errors)
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+}
+
+impl ShaderError {
+    pub fn new(path: &str, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            line: 0,
+            column: 0,
+            message: message.into(),
+        }
+    }
+
+    fn at(path: &str, location: Option<naga::SourceLocation>, message: impl Into<String>) -> Self {
+        let (line, column) = location.map_or((0, 0), |loc| (loc.line_number, loc.line_position));
+        Self {
+            path: path.to_string(),
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn on_line(path: &str, line: u32, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            line,
+            column: 1,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line > 0 {
+            write!(f, "{}:{}:{}: {}", self.path, self.line, self.column, self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+// Parses and validates WGSL with naga so a broken shader is reported instead
+// of reaching pipeline creation.
+pub fn validate_wgsl(source: &str, path: &str) -> Result<naga::Module, ShaderError> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|e| ShaderError::at(path, e.location(source), e.message()))?;
+
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|e| ShaderError::at(path, e.location(source), e.as_inner().to_string()))?;
+
+    Ok(module)
+}
+
+// Preprocesses, validates and creates a module for a shader that builds its
+// own pipelines outside `PipelineCache`, such as the mipmap and cubemap ones.
+pub fn create_module(
+    device: &wgpu::Device,
+    loader: &dyn ShaderLoader,
+    path: &str,
+) -> Result<wgpu::ShaderModule, ShaderError> {
+    let preprocessed = preprocess(loader, path, &[])?;
+    validate_wgsl(&preprocessed.source, path).map_err(|e| preprocessed.remap_error(e))?;
+    Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(path),
+        source: wgpu::ShaderSource::Wgsl(preprocessed.source.into()),
+    }))
+}
+
+// WGSL source loaded through the asset server. `#include`s are left for the
+// preprocessor, so register it with `PipelineCache::add_source` to build
+// pipelines from it.
+#[derive(Debug, Clone)]
+pub struct Shader {
+    pub path: String,
+    pub source: String,
+}
+
+// Where shader files come from. Paths are '/'-separated and relative to the
+// loader's root, e.g. "shader.wgsl" or "common/camera.wgsl".
+pub trait ShaderLoader {
+    fn load(&self, path: &str) -> std::io::Result<String>;
+
+    // File on disk backing `path`, if any, so it can be watched for changes.
+    fn disk_path(&self, _path: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+pub struct DiskShaderLoader {
+    root: PathBuf,
+}
+
+impl DiskShaderLoader {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl ShaderLoader for DiskShaderLoader {
+    fn load(&self, path: &str) -> std::io::Result<String> {
+        fs::read_to_string(self.root.join(path))
+    }
+
+    fn disk_path(&self, path: &str) -> Option<PathBuf> {
+        Some(self.root.join(path))
+    }
+}
+
+// Shaders compiled into the binary with `include_str!`.
+pub struct EmbeddedShaderLoader {
+    files: &'static [(&'static str, &'static str)],
+}
+
+impl EmbeddedShaderLoader {
+    pub const fn new(files: &'static [(&'static str, &'static str)]) -> Self {
+        Self { files }
+    }
+}
+
+impl ShaderLoader for EmbeddedShaderLoader {
+    fn load(&self, path: &str) -> std::io::Result<String> {
+        self.files
+            .iter()
+            .find(|(name, _)| *name == path)
+            .map(|(_, source)| source.to_string())
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, path.to_string()))
+    }
+}
+
+// Output of `preprocess`: the flattened WGSL plus where each of its lines
+// came from, so naga errors can be reported against the original files.
+pub struct PreprocessedShader {
+    pub source: String,
+    // Every file that contributed, starting with the root shader.
+    pub files: Vec<String>,
+    line_origins: Vec<(usize, u32)>,
+}
+
+impl PreprocessedShader {
+    // Maps an error located in `source` back to the file and line it came from.
+    pub fn remap_error(&self, mut error: ShaderError) -> ShaderError {
+        if error.line == 0 {
+            return error;
+        }
+        if let Some(&(file, line)) = self.line_origins.get(error.line as usize - 1) {
+            error.path = self.files[file].clone();
+            error.line = line;
+        }
+        error
+    }
+}
+
+// Expands a small C-like directive set:
+//
+//   #include "path.wgsl"   relative to the including file, then to the
+//                          loader root; each file is included at most once
+//   #define NAME / #undef NAME
+//   #ifdef NAME / #ifndef NAME / #else / #endif
+//
+// `defines` are set before the root file is processed.
+pub fn preprocess(
+    loader: &dyn ShaderLoader,
+    path: &str,
+    defines: &[String],
+) -> Result<PreprocessedShader, ShaderError> {
+    let mut state = PreprocessState {
+        loader,
+        defines: defines.iter().cloned().collect(),
+        stack: Vec::new(),
+        included: HashSet::new(),
+        output: PreprocessedShader {
+            source: String::new(),
+            files: Vec::new(),
+            line_origins: Vec::new(),
+        },
+    };
+    let source = loader
+        .load(path)
+        .map_err(|e| ShaderError::new(path, format!("failed to read shader: {}", e)))?;
+    state.process(path, &source)?;
+    Ok(state.output)
+}
+
+struct PreprocessState<'a> {
+    loader: &'a dyn ShaderLoader,
+    defines: HashSet<String>,
+    stack: Vec<String>,
+    included: HashSet<String>,
+    output: PreprocessedShader,
+}
+
+struct Conditional {
+    active: bool,
+    parent_active: bool,
+    seen_else: bool,
+}
+
+impl PreprocessState<'_> {
+    fn process(&mut self, path: &str, source: &str) -> Result<(), ShaderError> {
+        self.stack.push(path.to_string());
+        self.included.insert(path.to_string());
+        let file_index = self.output.files.len();
+        self.output.files.push(path.to_string());
+
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i as u32 + 1;
+            let active = conditionals.last().is_none_or(|c| c.active);
+            let trimmed = line.trim();
+
+            let Some(directive) = trimmed.strip_prefix('#') else {
+                if active {
+                    self.output.source.push_str(line);
+                    self.output.source.push('\n');
+                    self.output.line_origins.push((file_index, line_number));
+                }
+                continue;
+            };
+            let mut parts = directive.split_whitespace();
+            let name = parts.next().unwrap_or("");
+            let argument = parts.next();
+            let error = |message: String| ShaderError::on_line(path, line_number, message);
+
+            match name {
+                "ifdef" | "ifndef" => {
+                    let flag = argument.ok_or_else(|| error(format!("#{} needs a name", name)))?;
+                    let defined = self.defines.contains(flag);
+                    conditionals.push(Conditional {
+                        active: active && (defined == (name == "ifdef")),
+                        parent_active: active,
+                        seen_else: false,
+                    });
+                }
+                "else" => {
+                    let c = conditionals
+                        .last_mut()
+                        .filter(|c| !c.seen_else)
+                        .ok_or_else(|| error("#else without matching #ifdef".to_string()))?;
+                    c.seen_else = true;
+                    c.active = c.parent_active && !c.active;
+                }
+                "endif" => {
+                    conditionals
+                        .pop()
+                        .ok_or_else(|| error("#endif without matching #ifdef".to_string()))?;
+                }
+                _ if !active => {}
+                "define" => {
+                    let flag = argument.ok_or_else(|| error("#define needs a name".to_string()))?;
+                    self.defines.insert(flag.to_string());
+                }
+                "undef" => {
+                    let flag = argument.ok_or_else(|| error("#undef needs a name".to_string()))?;
+                    self.defines.remove(flag);
+                }
+                "include" => {
+                    let target = directive["include".len()..].trim();
+                    let target = target
+                        .strip_prefix('"')
+                        .and_then(|t| t.strip_suffix('"'))
+                        .ok_or_else(|| error(format!("expected #include \"file\", found `{}`", trimmed)))?;
+                    let mut resolved = resolve_include(path, target);
+                    let mut included = self.loader.load(&resolved);
+                    if included.is_err() {
+                        let from_root = resolve_include("", target);
+                        if let Ok(source) = self.loader.load(&from_root) {
+                            resolved = from_root;
+                            included = Ok(source);
+                        }
+                    }
+
+                    if self.stack.contains(&resolved) {
+                        let mut cycle = self.stack.clone();
+                        cycle.push(resolved);
+                        return Err(error(format!("include cycle: {}", cycle.join(" -> "))));
+                    }
+                    if self.included.contains(&resolved) {
+                        continue;
+                    }
+                    let included =
+                        included.map_err(|e| error(format!("cannot include `{}`: {}", resolved, e)))?;
+                    self.process(&resolved, &included)?;
+                }
+                _ => return Err(error(format!("unknown directive `#{}`", name))),
+            }
+        }
+
+        if !conditionals.is_empty() {
+            return Err(ShaderError::new(path, "unterminated #ifdef at end of file"));
+        }
+        self.stack.pop();
+        Ok(())
+    }
+}
+
+// Resolves `target` relative to the directory of `from`, collapsing `.` and
+// `..` so the same file always gets the same name.
+fn resolve_include(from: &str, target: &str) -> String {
+    let base = Path::new(from).parent().unwrap_or(Path::new(""));
+    let mut parts: Vec<String> = Vec::new();
+    for component in base.join(target).components() {
+        match component {
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part.to_string_lossy().into_owned()),
+            _ => {}
+        }
+    }
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILES: EmbeddedShaderLoader = EmbeddedShaderLoader::new(&[
+        ("main.wgsl", "#include \"common/camera.wgsl\"\n#include \"lights.wgsl\"\nfn main() {}\n"),
+        // "math.wgsl" isn't next to it, so it comes from the root.
+        ("common/camera.wgsl", "#include \"math.wgsl\"\nstruct Camera { fov: f32 }\n"),
+        ("lights.wgsl", "#include \"common/../common/camera.wgsl\"\nstruct Light { power: f32 }\n"),
+        ("math.wgsl", "const PI: f32 = 3.14159;\n"),
+        ("cycle_a.wgsl", "#include \"cycle_b.wgsl\"\n"),
+        ("cycle_b.wgsl", "// b\n#include \"cycle_a.wgsl\"\n"),
+        ("missing.wgsl", "fn f() {}\n#include \"nowhere.wgsl\"\n"),
+        (
+            "flags.wgsl",
+            "#ifdef A\na\n#ifdef B\nab\n#else\na_not_b\n#endif\n#else\nnot_a\n#ifndef B\nnot_a_not_b\n#endif\n#endif\n\
+             #ifdef UNSET\n#define HIDDEN\n#endif\n#ifdef HIDDEN\nhidden\n#endif\n",
+        ),
+        ("broken_main.wgsl", "// header\n\n#include \"broken.wgsl\"\nfn main() {}\nfn after() -> f32 { return z; }\n"),
+        ("broken.wgsl", "fn broken() -> f32 {\n    let x = 1.0;\n    return y;\n}\n"),
+        ("stray_else.wgsl", "a\n#else\n"),
+        ("double_else.wgsl", "#ifdef A\n#else\n#else\n#endif\n"),
+        ("stray_endif.wgsl", "#endif\n"),
+        ("open.wgsl", "#ifdef A\n"),
+        ("unknown.wgsl", "\n#pragma once\n"),
+    ]);
+
+    fn source(path: &str, defines: &[&str]) -> String {
+        let defines: Vec<String> = defines.iter().map(|flag| flag.to_string()).collect();
+        preprocess(&FILES, path, &defines).unwrap().source
+    }
+
+    fn fails(path: &str) -> ShaderError {
+        preprocess(&FILES, path, &[]).err().unwrap()
+    }
+
+    #[test]
+    fn includes_each_file_once() {
+        let shader = preprocess(&FILES, "main.wgsl", &[]).unwrap();
+        assert_eq!(
+            shader.source,
+            "const PI: f32 = 3.14159;\nstruct Camera { fov: f32 }\nstruct Light { power: f32 }\nfn main() {}\n"
+        );
+        assert_eq!(shader.files, ["main.wgsl", "common/camera.wgsl", "math.wgsl", "lights.wgsl"]);
+        validate_wgsl(&shader.source, "main.wgsl").unwrap();
+    }
+
+    #[test]
+    fn reports_include_cycles() {
+        let error = fails("cycle_a.wgsl");
+        assert_eq!((error.path.as_str(), error.line), ("cycle_b.wgsl", 2));
+        assert!(error.message.contains("cycle_a.wgsl -> cycle_b.wgsl -> cycle_a.wgsl"), "{}", error);
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        let error = fails("missing.wgsl");
+        assert_eq!((error.path.as_str(), error.line), ("missing.wgsl", 2));
+        assert!(error.message.contains("nowhere.wgsl"), "{}", error);
+
+        let error = fails("absent.wgsl");
+        assert_eq!((error.path.as_str(), error.line), ("absent.wgsl", 0));
+    }
+
+    #[test]
+    fn nests_conditionals() {
+        assert_eq!(source("flags.wgsl", &[]), "not_a\nnot_a_not_b\n");
+        assert_eq!(source("flags.wgsl", &["B"]), "not_a\n");
+        assert_eq!(source("flags.wgsl", &["A"]), "a\na_not_b\n");
+        assert_eq!(source("flags.wgsl", &["A", "B"]), "a\nab\n");
+        // Only the define in the taken branch counts.
+        assert_eq!(source("flags.wgsl", &["UNSET"]), "not_a\nnot_a_not_b\nhidden\n");
+    }
+
+    #[test]
+    fn rejects_unbalanced_conditionals() {
+        assert_eq!(fails("stray_else.wgsl").line, 2);
+        assert_eq!(fails("double_else.wgsl").line, 3);
+        assert_eq!(fails("stray_endif.wgsl").line, 1);
+        assert!(fails("open.wgsl").message.contains("unterminated"));
+        assert_eq!(fails("unknown.wgsl").line, 2);
+    }
+
+    #[test]
+    fn remaps_errors_to_the_original_lines() {
+        let shader = preprocess(&FILES, "broken_main.wgsl", &[]).unwrap();
+        let error = validate_wgsl(&shader.source, "broken_main.wgsl").err().unwrap();
+        // Two lines of header come before the included file.
+        assert_eq!(error.line, 5);
+        let error = shader.remap_error(error);
+        assert_eq!((error.path.as_str(), error.line), ("broken.wgsl", 3));
+
+        // Lines after the include map back to the including file.
+        let fixed = shader.source.replace("return y;", "return x;");
+        let error = validate_wgsl(&fixed, "broken_main.wgsl").err().unwrap();
+        let error = shader.remap_error(error);
+        assert_eq!((error.path.as_str(), error.line), ("broken_main.wgsl", 5));
+
+        // Errors without a line are left alone.
+        let error = shader.remap_error(ShaderError::new("broken_main.wgsl", "failed"));
+        assert_eq!((error.path.as_str(), error.line), ("broken_main.wgsl", 0));
+    }
+}
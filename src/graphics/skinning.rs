@@ -0,0 +1,89 @@
+use ultraviolet as uv;
+
+// A skinned mesh added to the renderer with `State::add_skinned_mesh`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SkinnedMeshId(pub(crate) usize);
+
+// One matrix per joint, read by skinned.wgsl's vertex shader to move each
+// vertex by its weighted joints. See `Pose::joint_matrices`.
+pub struct JointBuffer {
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    capacity: usize,
+    label: String,
+}
+
+// The joint matrices as a read-only storage buffer, visible to vertex
+// shaders only.
+pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("joint_bind_group_layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+    })
+}
+
+impl JointBuffer {
+    // Starts with identities, i.e. the bind pose.
+    pub fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, joint_count: usize, label: &str) -> Self {
+        let capacity = joint_count.max(1);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (capacity * std::mem::size_of::<[[f32; 4]; 4]>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: true,
+        });
+        let identity: [[f32; 4]; 4] = uv::Mat4::identity().into();
+        buffer
+            .slice(..)
+            .get_mapped_range_mut()
+            .copy_from_slice(bytemuck::cast_slice(&vec![identity; capacity]));
+        buffer.unmap();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        Self {
+            buffer,
+            bind_group,
+            capacity,
+            label: label.to_string(),
+        }
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Grows the buffer, and with it the bind group, when there are more
+    // matrices than fit.
+    pub fn write(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        matrices: &[uv::Mat4],
+    ) {
+        if matrices.len() > self.capacity {
+            *self = Self::new(device, layout, matrices.len(), &self.label);
+        }
+        let data: Vec<[[f32; 4]; 4]> = matrices.iter().map(|&matrix| matrix.into()).collect();
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&data));
+    }
+}
@@ -1,102 +1,616 @@
+use std::{collections::HashMap, sync::Arc};
+
 use anyhow::*;
 use image::GenericImageView;
 
+use super::compressed::TextureData;
+use crate::assets::vfs::Vfs;
+use super::cubemap::{self, EquirectConverter};
+use super::mipmap::{self, MipmapGenerator};
+use super::shader::ShaderLoader;
+
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
+    pub sampler: Arc<wgpu::Sampler>,
 }
 
-impl Texture {
-    pub fn from_bytes(
+// How a texture is sampled. wgpu samplers have no LOD bias; raise
+// `lod_min_clamp` to force blurrier mips instead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerConfig {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub address_mode_w: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    // 1 disables anisotropic filtering. Clamped to 1..=16, and only applied
+    // when every filter is Linear as wgpu requires.
+    pub anisotropy: u16,
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+}
+
+impl SamplerConfig {
+    // Repeating, trilinear.
+    pub const REPEAT: Self = Self {
+        address_mode_u: wgpu::AddressMode::Repeat,
+        address_mode_v: wgpu::AddressMode::Repeat,
+        address_mode_w: wgpu::AddressMode::Repeat,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Linear,
+        mipmap_filter: wgpu::FilterMode::Linear,
+        anisotropy: 1,
+        lod_min_clamp: 0.0,
+        lod_max_clamp: 32.0,
+    };
+
+    pub const CLAMP: Self = Self {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        ..Self::REPEAT
+    };
+
+    // Nearest everything, for pixel art.
+    pub const PIXELATED: Self = Self {
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Self::CLAMP
+    };
+
+    pub fn with_address_mode(mut self, mode: wgpu::AddressMode) -> Self {
+        self.address_mode_u = mode;
+        self.address_mode_v = mode;
+        self.address_mode_w = mode;
+        self
+    }
+
+    pub fn with_anisotropy(mut self, anisotropy: u16) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    pub fn with_lod_clamp(mut self, min: f32, max: f32) -> Self {
+        self.lod_min_clamp = min;
+        self.lod_max_clamp = max;
+        self
+    }
+
+    pub fn descriptor(&self) -> wgpu::SamplerDescriptor<'static> {
+        let all_linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|&filter| filter == wgpu::FilterMode::Linear);
+        wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: self.address_mode_w,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            lod_min_clamp: self.lod_min_clamp,
+            lod_max_clamp: self.lod_max_clamp,
+            compare: None,
+            anisotropy_clamp: if all_linear { self.anisotropy.clamp(1, 16) } else { 1 },
+            border_color: None,
+        }
+    }
+
+    fn key(&self) -> SamplerKey {
+        SamplerKey {
+            address_modes: [self.address_mode_u, self.address_mode_v, self.address_mode_w],
+            filters: [self.mag_filter, self.min_filter, self.mipmap_filter],
+            anisotropy: self.anisotropy,
+            lod_clamp: [self.lod_min_clamp.to_bits(), self.lod_max_clamp.to_bits()],
+        }
+    }
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self::REPEAT
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct SamplerKey {
+    address_modes: [wgpu::AddressMode; 3],
+    filters: [wgpu::FilterMode; 3],
+    anisotropy: u16,
+    lod_clamp: [u32; 2],
+}
+
+// Hands out one shared sampler per distinct `SamplerConfig`.
+#[derive(Default)]
+pub struct SamplerCache {
+    samplers: HashMap<SamplerKey, Arc<wgpu::Sampler>>,
+}
+
+impl SamplerCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&mut self, device: &wgpu::Device, config: &SamplerConfig) -> Arc<wgpu::Sampler> {
+        self.samplers
+            .entry(config.key())
+            .or_insert_with(|| Arc::new(device.create_sampler(&config.descriptor())))
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.is_empty()
+    }
+}
+
+// Colour textures are stored as sRGB so they are linearised when sampled;
+// data such as normal or roughness maps must be read back unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn rgba8_format(self) -> wgpu::TextureFormat {
+        match self {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureOptions {
+    pub color_space: ColorSpace,
+    pub mipmaps: bool,
+    pub sampler: SamplerConfig,
+}
+
+impl TextureOptions {
+    // For normal maps and other non-colour data.
+    pub fn data() -> Self {
+        Self {
+            color_space: ColorSpace::Linear,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            color_space: ColorSpace::Srgb,
+            mipmaps: true,
+            sampler: SamplerConfig::default(),
+        }
+    }
+}
+
+// Loads textures sharing one sampler cache and mipmap generator. Keep one
+// around (`State` has one, see `State::textures_mut`) rather than making a
+// new one per texture.
+pub struct TextureLoader {
+    samplers: SamplerCache,
+    // Source of the mipmap and equirect shaders.
+    shaders: Box<dyn ShaderLoader>,
+    mipmaps: MipmapGenerator,
+    // Built on the first `load_equirect`.
+    equirect: Option<EquirectConverter>,
+}
+
+impl TextureLoader {
+    pub fn new(device: &wgpu::Device, shaders: Box<dyn ShaderLoader>) -> Result<Self> {
+        let mipmaps = MipmapGenerator::new(device, shaders.as_ref())?;
+        Ok(Self {
+            samplers: SamplerCache::new(),
+            shaders,
+            mipmaps,
+            equirect: None,
+        })
+    }
+
+    // KTX2 and DDS files are uploaded with their own format and mips, so
+    // only `options.sampler` applies to them.
+    pub fn load_bytes(
+        &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         bytes: &[u8],
         label: &str,
-    ) -> Result<Self> {
+        options: &TextureOptions,
+    ) -> Result<Texture> {
+        if TextureData::is_container(bytes) {
+            let data = TextureData::from_bytes(bytes)?;
+            return self.load_data(device, queue, &data, Some(label), &options.sampler);
+        }
         let img = image::load_from_memory(bytes)?;
-        Self::from_image(device, queue, &img, Some(label))
+        self.load_image(device, queue, &img, Some(label), options)
     }
 
-    pub fn from_image(
+    // Reads `path` through the VFS, see `load_bytes`.
+    pub fn load_file(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        vfs: &Vfs,
+        path: &str,
+        options: &TextureOptions,
+    ) -> Result<Texture> {
+        let bytes = vfs.read(path).with_context(|| format!("failed to read {}", path))?;
+        self.load_bytes(device, queue, &bytes, path, options)
+    }
+
+    pub fn load_image(
+        &mut self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
-    ) -> Result<Self> {
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+        options: &TextureOptions,
+    ) -> Result<Texture> {
+        let texture = create_texture(device, queue, img, label, options);
+        if options.mipmaps {
+            self.mipmaps.generate(device, queue, &texture);
+        }
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = self.samplers.get(device, &options.sampler);
 
-        let size = wgpu::Extent3d {
-            width: dimensions.0,
-            height: dimensions.1,
-            depth_or_array_layers: 1,
+        Ok(Texture {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    // Writes `img` into a texture `load_image` created with the same options,
+    // keeping its views and every bind group using them valid. Returns false,
+    // leaving the texture untouched, when the size or format would change.
+    pub fn reload_image(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texture: &Texture,
+        img: &image::DynamicImage,
+        options: &TextureOptions,
+    ) -> bool {
+        let (width, height) = img.dimensions();
+        let mip_level_count = if options.mipmaps {
+            mipmap::mip_level_count(width, height)
+        } else {
+            1
         };
+        let texture = &texture.texture;
+        let compatible = texture.width() == width
+            && texture.height() == height
+            && texture.depth_or_array_layers() == 1
+            && texture.mip_level_count() == mip_level_count
+            && texture.format() == options.color_space.rgba8_format()
+            && texture.usage().contains(wgpu::TextureUsages::COPY_DST);
+        if !compatible {
+            return false;
+        }
+        write_image(queue, texture, &img.to_rgba8());
+        if options.mipmaps {
+            self.mipmaps.generate(device, queue, texture);
+        }
+        true
+    }
+
+    // Uploads compressed data directly when the device supports its format,
+    // otherwise decompresses it on the CPU first.
+    pub fn load_data(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &TextureData,
+        label: Option<&str>,
+        sampler: &SamplerConfig,
+    ) -> Result<Texture> {
+        let decompressed;
+        let data = if data.is_supported(device.features()) {
+            data
+        } else {
+            log::warn!(
+                "{:?} is not supported by this device, decompressing {}",
+                data.format,
+                label.unwrap_or("texture")
+            );
+            decompressed = data.decompress()?;
+            &decompressed
+        };
+        let sampler = self.samplers.get(device, sampler);
+        Ok(Texture::from_data(device, queue, data, label, sampler))
+    }
+
+    // Faces are square, all the same size and in wgpu's layer order: +X, -X,
+    // +Y, -Y, +Z, -Z.
+    pub fn load_cubemap(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces: &[image::DynamicImage; 6],
+        label: Option<&str>,
+        options: &TextureOptions,
+    ) -> Result<Texture> {
+        let (size, height) = faces[0].dimensions();
+        if size != height || faces.iter().any(|face| face.dimensions() != (size, size)) {
+            bail!(
+                "cubemap faces of {} must be square and the same size",
+                label.unwrap_or("texture")
+            );
+        }
+        let mip_level_count = if options.mipmaps {
+            mipmap::mip_level_count(size, size)
+        } else {
+            1
+        };
+        let sampler = self.samplers.get(device, &options.sampler);
+        let texture = Texture::create_cubemap(
+            device,
+            size,
+            options.color_space.rgba8_format(),
+            mip_level_count,
+            label,
+            sampler,
+        );
+        for (layer, face) in faces.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                },
+                &face.to_rgba8(),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * size),
+                    rows_per_image: Some(size),
+                },
+                wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        if options.mipmaps {
+            self.mipmaps.generate(device, queue, &texture.texture);
+        }
+        Ok(texture)
+    }
+
+    // Converts an equirectangular panorama, usually an HDR image, into a
+    // `cubemap::HDR_CUBE_FORMAT` cubemap with `face_size` pixel faces. The
+    // data stays linear, so `options.color_space` is ignored.
+    pub fn load_equirect(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        face_size: u32,
+        label: Option<&str>,
+        options: &TextureOptions,
+    ) -> Result<Texture> {
+        let face_size = face_size.max(1);
+        let mip_level_count = if options.mipmaps {
+            mipmap::mip_level_count(face_size, face_size)
+        } else {
+            1
+        };
+        let sampler = self.samplers.get(device, &options.sampler);
+        let texture = Texture::create_cubemap(
+            device,
+            face_size,
+            cubemap::HDR_CUBE_FORMAT,
+            mip_level_count,
+            label,
+            sampler,
+        );
+        let equirect = match &self.equirect {
+            Some(equirect) => equirect,
+            None => self.equirect.insert(EquirectConverter::new(device, self.shaders.as_ref())?),
+        };
+        equirect.convert(device, queue, img, &texture.texture);
+        if options.mipmaps {
+            self.mipmaps.generate(device, queue, &texture.texture);
+        }
+        Ok(texture)
+    }
+
+    pub fn sampler(&mut self, device: &wgpu::Device, config: &SamplerConfig) -> Arc<wgpu::Sampler> {
+        self.samplers.get(device, config)
+    }
+
+    pub fn samplers(&self) -> &SamplerCache {
+        &self.samplers
+    }
+
+    pub fn mipmaps_mut(&mut self) -> &mut MipmapGenerator {
+        &mut self.mipmaps
+    }
+}
+
+impl Texture {
+    // Uses `TextureOptions::default()`; see `TextureLoader::load_bytes`.
+    pub fn from_bytes(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        loader: &mut TextureLoader,
+        bytes: &[u8],
+        label: &str,
+    ) -> Result<Self> {
+        loader.load_bytes(device, queue, bytes, label, &TextureOptions::default())
+    }
+
+    // Creates the texture in `data`'s own format, which the device must
+    // support; see `TextureLoader::load_data` for the fallback.
+    pub fn from_data(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &TextureData,
+        label: Option<&str>,
+        sampler: Arc<wgpu::Sampler>,
+    ) -> Self {
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
-            size,
-            mip_level_count: 1,
+            size: wgpu::Extent3d {
+                width: data.width,
+                height: data.height,
+                depth_or_array_layers: data.layers,
+            },
+            mip_level_count: data.levels.len() as u32,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format: data.format,
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
 
-        queue.write_texture(
-            wgpu::ImageCopyTexture {
-                aspect: wgpu::TextureAspect::All,
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-            },
-            &rgba,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            size,
-        );
+        let (block_width, block_height) = data.format.block_dimensions();
+        let block_size = data.format.block_copy_size(None).unwrap_or(4);
+        for (level, bytes) in data.levels.iter().enumerate() {
+            let (width, height) = data.level_dimensions(level as u32);
+            let (blocks_wide, blocks_high) = (width.div_ceil(block_width), height.div_ceil(block_height));
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                bytes,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(blocks_wide * block_size),
+                    rows_per_image: Some(blocks_high),
+                },
+                // Compressed mips are copied as whole blocks.
+                wgpu::Extent3d {
+                    width: blocks_wide * block_width,
+                    height: blocks_high * block_height,
+                    depth_or_array_layers: data.layers,
+                },
+            );
+        }
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(data.view_dimension()),
             ..Default::default()
         });
-
-        Ok(Self {
+        Self {
             texture,
             view,
             sampler,
-        })
+        }
+    }
+
+    // Uses `TextureOptions::default()`; see `TextureLoader` for the rest.
+    pub fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        loader: &mut TextureLoader,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        loader.load_image(device, queue, img, label, &TextureOptions::default())
     }
 }
 
+fn create_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    img: &image::DynamicImage,
+    label: Option<&str>,
+    options: &TextureOptions,
+) -> wgpu::Texture {
+    let rgba = img.to_rgba8();
+    let dimensions = img.dimensions();
+
+    let size = wgpu::Extent3d {
+        width: dimensions.0,
+        height: dimensions.1,
+        depth_or_array_layers: 1,
+    };
+    let (mip_level_count, usage) = if options.mipmaps {
+        (
+            mipmap::mip_level_count(dimensions.0, dimensions.1),
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        )
+    } else {
+        (1, wgpu::TextureUsages::empty())
+    };
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label,
+        size,
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: options.color_space.rgba8_format(),
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | usage,
+        view_formats: &[],
+    });
+    write_image(queue, &texture, &rgba);
+    texture
+}
+
+// Writes mip 0 of an RGBA8 texture of the image's size.
+fn write_image(queue: &wgpu::Queue, texture: &wgpu::Texture, rgba: &image::RgbaImage) {
+    let (width, height) = rgba.dimensions();
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            aspect: wgpu::TextureAspect::All,
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        rgba,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * width),
+            rows_per_image: Some(height),
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float; // 1.
 
     pub fn create_depth_texture(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
         label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
             // 2.
-            width: config.width,
-            height: config.height,
+            width: config.width.max(1),
+            height: config.height.max(1),
             depth_or_array_layers: 1,
         };
         let desc = wgpu::TextureDescriptor {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT // 3.
@@ -120,10 +634,127 @@ impl Texture {
             ..Default::default()
         });
 
+        Self {
+            texture,
+            view,
+            sampler: Arc::new(sampler),
+        }
+    }
+
+    // Colour texture that can be both rendered into by a camera and sampled
+    // by materials.
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            sampler: Arc::new(sampler),
+        }
+    }
+
+    // Six square layers viewed as a cube. It can be rendered into so faces
+    // can be generated and mipmapped on the GPU.
+    pub fn create_cubemap(
+        device: &wgpu::Device,
+        size: u32,
+        format: wgpu::TextureFormat,
+        mip_level_count: u32,
+        label: Option<&str>,
+        sampler: Arc<wgpu::Sampler>,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width: size.max(1),
+                height: size.max(1),
+                depth_or_array_layers: 6,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
         Self {
             texture,
             view,
             sampler,
         }
     }
-}
\ No newline at end of file
+
+    // Multisampled colour attachment. It can only be rendered into and
+    // resolved, so the sampler is never used.
+    pub fn create_multisampled(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: &str,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+
+        Self {
+            texture,
+            view,
+            sampler: Arc::new(sampler),
+        }
+    }
+}
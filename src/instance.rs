@@ -1,15 +1,20 @@
 use ultraviolet as uv;
 
+use crate::ecs::entities::Entity;
+
 pub struct Instance {
     pub position: uv::Vec3,
     pub rotation: uv::Rotor3,
     pub scale: f32,
+    // Written to the ID buffer for GPU picking, `NO_ENTITY` for none.
+    pub entity: Entity,
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct InstanceRaw {
     model: [[f32; 4]; 4],
+    entity: u32,
 }
 
 impl Instance {
@@ -21,6 +26,7 @@ impl Instance {
         };
         InstanceRaw {
             model: similarity.into_homogeneous_matrix().into(),
+            entity: self.entity,
         }
     }
 }
@@ -67,6 +73,11 @@ impl InstanceRaw {
                     shader_location: 8,
                     format: wgpu::VertexFormat::Float32x4,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 16]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Uint32,
+                },
             ],
         }
     }
@@ -3,4 +3,10 @@ pub mod graphics;
 pub mod game_interface;
 pub mod render;
 pub mod instance;
-pub mod ecs;
\ No newline at end of file
+pub mod ecs;
+pub mod assets;
+pub mod audio;
+pub mod physics;
+pub mod physics2d;
+pub mod time;
+pub mod animation;
\ No newline at end of file
@@ -0,0 +1,279 @@
+use ultraviolet as uv;
+
+use super::ray::Layers;
+use super::shape::{Aabb, Shape};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BodyKind {
+    // Moved by forces and contacts.
+    Dynamic,
+    // Moved only by its velocity or kinematic target; pushes dynamic bodies
+    // without being pushed back.
+    Kinematic,
+    Static,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BodyHandle(pub(crate) usize);
+
+#[derive(Debug, Clone)]
+pub struct RigidBody {
+    pub kind: BodyKind,
+    pub shape: Shape,
+    pub position: uv::Vec3,
+    pub rotation: uv::Rotor3,
+    pub linear_velocity: uv::Vec3,
+    pub angular_velocity: uv::Vec3,
+    pub friction: f32,
+    pub restitution: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub gravity_scale: f32,
+    // What queries like `PhysicsWorld::raycast` and other bodies see it as.
+    pub layers: Layers,
+    // Layers it collides with. A pair only collides, and only reports
+    // events, when each is on a layer in the other's mask.
+    pub collision_mask: Layers,
+    // Reports bodies entering and leaving it instead of colliding.
+    pub sensor: bool,
+    mass: f32,
+    inv_mass: f32,
+    inv_inertia: uv::Vec3,
+    force: uv::Vec3,
+    torque: uv::Vec3,
+    kinematic_target: Option<(uv::Vec3, uv::Rotor3)>,
+}
+
+impl RigidBody {
+    pub fn new(kind: BodyKind, shape: Shape) -> Self {
+        let mut body = Self {
+            kind,
+            shape,
+            position: uv::Vec3::zero(),
+            rotation: uv::Rotor3::identity(),
+            linear_velocity: uv::Vec3::zero(),
+            angular_velocity: uv::Vec3::zero(),
+            friction: 0.5,
+            restitution: 0.0,
+            linear_damping: 0.01,
+            angular_damping: 0.05,
+            gravity_scale: 1.0,
+            layers: Layers::ALL,
+            collision_mask: Layers::ALL,
+            sensor: false,
+            mass: 0.0,
+            inv_mass: 0.0,
+            inv_inertia: uv::Vec3::zero(),
+            force: uv::Vec3::zero(),
+            torque: uv::Vec3::zero(),
+            kinematic_target: None,
+        };
+        body.set_density(1.0);
+        body
+    }
+
+    pub fn dynamic(shape: Shape) -> Self {
+        Self::new(BodyKind::Dynamic, shape)
+    }
+
+    pub fn kinematic(shape: Shape) -> Self {
+        Self::new(BodyKind::Kinematic, shape)
+    }
+
+    pub fn fixed(shape: Shape) -> Self {
+        Self::new(BodyKind::Static, shape)
+    }
+
+    pub fn with_position(mut self, position: uv::Vec3) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: uv::Rotor3) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_velocity(mut self, velocity: uv::Vec3) -> Self {
+        self.linear_velocity = velocity;
+        self
+    }
+
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.set_density(density);
+        self
+    }
+
+    pub fn with_mass(mut self, mass: f32) -> Self {
+        self.set_mass(mass);
+        self
+    }
+
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+
+    pub fn with_restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn with_gravity_scale(mut self, gravity_scale: f32) -> Self {
+        self.gravity_scale = gravity_scale;
+        self
+    }
+
+    pub fn with_layers(mut self, layers: Layers) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    pub fn with_collision_mask(mut self, mask: Layers) -> Self {
+        self.collision_mask = mask;
+        self
+    }
+
+    pub fn with_sensor(mut self, sensor: bool) -> Self {
+        self.sensor = sensor;
+        self
+    }
+
+    pub fn collides_with(&self, other: &RigidBody) -> bool {
+        self.layers.intersects(other.collision_mask) && other.layers.intersects(self.collision_mask)
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.kind == BodyKind::Dynamic
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.mass
+    }
+
+    // Zero for kinematic and static bodies.
+    pub fn inv_mass(&self) -> f32 {
+        if self.is_dynamic() {
+            self.inv_mass
+        } else {
+            0.0
+        }
+    }
+
+    pub fn set_density(&mut self, density: f32) {
+        let (mass, inertia) = self.shape.mass_properties(density);
+        self.set_mass_properties(mass, inertia);
+    }
+
+    // Keeps the shape's mass distribution.
+    pub fn set_mass(&mut self, mass: f32) {
+        let (shape_mass, inertia) = self.shape.mass_properties(1.0);
+        self.set_mass_properties(mass, inertia * (mass / shape_mass.max(f32::EPSILON)));
+    }
+
+    fn set_mass_properties(&mut self, mass: f32, inertia: uv::Vec3) {
+        let invert = |x: f32| if x > 0.0 { 1.0 / x } else { 0.0 };
+        self.mass = mass;
+        self.inv_mass = invert(mass);
+        self.inv_inertia = uv::Vec3::new(invert(inertia.x), invert(inertia.y), invert(inertia.z));
+    }
+
+    // World-space inverse inertia tensor, zero unless dynamic.
+    pub fn inv_inertia_world(&self) -> uv::Mat3 {
+        if !self.is_dynamic() {
+            return uv::Mat3::from_scale(0.0);
+        }
+        let rotation = self.rotation.into_matrix();
+        rotation * uv::Mat3::from_nonuniform_scale(self.inv_inertia) * rotation.transposed()
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        self.shape.aabb(self.position, self.rotation)
+    }
+
+    pub fn velocity_at(&self, point: uv::Vec3) -> uv::Vec3 {
+        self.linear_velocity + self.angular_velocity.cross(point - self.position)
+    }
+
+    // Forces and torques last until the next step.
+    pub fn apply_force(&mut self, force: uv::Vec3) {
+        self.force += force;
+    }
+
+    pub fn apply_force_at(&mut self, force: uv::Vec3, point: uv::Vec3) {
+        self.force += force;
+        self.torque += (point - self.position).cross(force);
+    }
+
+    pub fn apply_torque(&mut self, torque: uv::Vec3) {
+        self.torque += torque;
+    }
+
+    pub fn apply_impulse(&mut self, impulse: uv::Vec3) {
+        self.linear_velocity += impulse * self.inv_mass();
+    }
+
+    pub fn apply_impulse_at(&mut self, impulse: uv::Vec3, point: uv::Vec3) {
+        self.linear_velocity += impulse * self.inv_mass();
+        self.angular_velocity += self.inv_inertia_world() * (point - self.position).cross(impulse);
+    }
+
+    // Where a kinematic body should be after the next step; its velocity is
+    // set to get it there so contacts see the motion.
+    pub fn set_kinematic_target(&mut self, position: uv::Vec3, rotation: uv::Rotor3) {
+        self.kinematic_target = Some((position, rotation));
+    }
+
+    pub(crate) fn integrate_velocity(&mut self, gravity: uv::Vec3, dt: f32) {
+        match self.kind {
+            BodyKind::Dynamic => {
+                let acceleration = self.force * self.inv_mass + gravity * self.gravity_scale;
+                self.linear_velocity += acceleration * dt;
+                self.angular_velocity += self.inv_inertia_world() * self.torque * dt;
+                self.linear_velocity *= 1.0 / (1.0 + dt * self.linear_damping);
+                self.angular_velocity *= 1.0 / (1.0 + dt * self.angular_damping);
+            }
+            BodyKind::Kinematic => {
+                if let Some((position, rotation)) = self.kinematic_target.take() {
+                    self.linear_velocity = (position - self.position) / dt;
+                    self.angular_velocity = angular_velocity_between(self.rotation, rotation, dt);
+                }
+            }
+            BodyKind::Static => {
+                self.linear_velocity = uv::Vec3::zero();
+                self.angular_velocity = uv::Vec3::zero();
+            }
+        }
+        self.force = uv::Vec3::zero();
+        self.torque = uv::Vec3::zero();
+    }
+
+    pub(crate) fn integrate_position(&mut self, dt: f32) {
+        if self.kind == BodyKind::Static {
+            return;
+        }
+        self.position += self.linear_velocity * dt;
+        let angle = self.angular_velocity.mag() * dt;
+        if angle > 0.0 {
+            let axis = self.angular_velocity.normalized();
+            let delta = uv::Rotor3::from_angle_plane(angle, uv::Bivec3::from_normalized_axis(axis));
+            self.rotation = (delta * self.rotation).normalized();
+        }
+    }
+}
+
+// Angular velocity turning `from` into `to` over `dt`.
+fn angular_velocity_between(from: uv::Rotor3, to: uv::Rotor3, dt: f32) -> uv::Vec3 {
+    let mut delta = to * from.reversed();
+    // Take the short way round.
+    if delta.s < 0.0 {
+        delta *= -1.0;
+    }
+    let axis = uv::Vec3::new(-delta.bv.yz, delta.bv.xz, -delta.bv.xy);
+    let sin = axis.mag();
+    if sin <= f32::EPSILON {
+        return uv::Vec3::zero();
+    }
+    let angle = 2.0 * sin.atan2(delta.s);
+    axis / sin * (angle / dt)
+}
@@ -0,0 +1,562 @@
+// Dynamic AABB tree in the style of Box2D's: leaves hold fat boxes so small
+// movements don't touch the tree, and inserts keep it balanced with AVL
+// rotations.
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use ultraviolet as uv;
+
+use super::ray::Ray;
+use super::shape::Aabb;
+
+const NULL: usize = usize::MAX;
+pub const DEFAULT_MARGIN: f32 = 0.1;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProxyId(usize);
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    aabb: Aabb,
+    parent: usize,
+    left: usize,
+    right: usize,
+    // 0 for leaves, -1 for free nodes.
+    height: i32,
+    data: Option<T>,
+}
+
+impl<T> Node<T> {
+    fn is_leaf(&self) -> bool {
+        self.left == NULL
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DynamicBvh<T> {
+    nodes: Vec<Node<T>>,
+    root: usize,
+    free: Vec<usize>,
+    margin: f32,
+    len: usize,
+}
+
+impl<T> Default for DynamicBvh<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> DynamicBvh<T> {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            root: NULL,
+            free: Vec::new(),
+            margin: DEFAULT_MARGIN,
+            len: 0,
+        }
+    }
+
+    // How far leaf boxes reach past what they were given.
+    pub fn with_margin(mut self, margin: f32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+        self.root = NULL;
+        self.len = 0;
+    }
+
+    pub fn insert(&mut self, aabb: Aabb, data: T) -> ProxyId {
+        let leaf = self.allocate(aabb.expanded(self.margin), Some(data));
+        self.insert_leaf(leaf);
+        self.len += 1;
+        ProxyId(leaf)
+    }
+
+    pub fn remove(&mut self, id: ProxyId) -> Option<T> {
+        self.leaf(id)?;
+        self.remove_leaf(id.0);
+        self.len -= 1;
+        let data = self.nodes[id.0].data.take();
+        self.release(id.0);
+        data
+    }
+
+    // Moves a leaf, only restructuring the tree once `aabb` leaves its fat
+    // box. Returns whether it did.
+    pub fn update(&mut self, id: ProxyId, aabb: Aabb) -> bool {
+        let Some(node) = self.leaf(id) else {
+            return false;
+        };
+        if node.aabb.contains(&aabb) {
+            return false;
+        }
+        self.remove_leaf(id.0);
+        self.nodes[id.0].aabb = aabb.expanded(self.margin);
+        self.insert_leaf(id.0);
+        true
+    }
+
+    pub fn get(&self, id: ProxyId) -> Option<&T> {
+        self.leaf(id)?.data.as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: ProxyId) -> Option<&mut T> {
+        let node = self.nodes.get_mut(id.0).filter(|node| node.height == 0)?;
+        node.data.as_mut()
+    }
+
+    pub fn fat_aabb(&self, id: ProxyId) -> Option<Aabb> {
+        Some(self.leaf(id)?.aabb)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ProxyId, &T)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, node)| Some((ProxyId(i), node.data.as_ref()?)))
+    }
+
+    // Calls `f` for every leaf whose fat box overlaps `aabb`.
+    pub fn query(&self, aabb: &Aabb, mut f: impl FnMut(ProxyId, &T)) {
+        if self.root == NULL {
+            return;
+        }
+        let mut stack = vec![self.root];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.aabb.overlaps(aabb) {
+                continue;
+            }
+            match &node.data {
+                Some(data) => f(ProxyId(index), data),
+                None => stack.extend([node.right, node.left]),
+            }
+        }
+    }
+
+    // Every pair of leaves with overlapping fat boxes, lower id first, sorted.
+    pub fn overlap_pairs(&self) -> Vec<(ProxyId, ProxyId)> {
+        let mut pairs = Vec::new();
+        for (id, _) in self.iter() {
+            let aabb = self.nodes[id.0].aabb;
+            self.query(&aabb, |other, _| {
+                if other > id {
+                    pairs.push((id, other));
+                }
+            });
+        }
+        pairs.sort();
+        pairs
+    }
+
+    // Nearest hit along `ray`. `hit` does the exact test against a leaf whose
+    // box the ray crosses, returning the distance if it hits.
+    pub fn raycast(
+        &self,
+        ray: &Ray,
+        max_distance: f32,
+        mut hit: impl FnMut(ProxyId, &T) -> Option<f32>,
+    ) -> Option<(ProxyId, f32)> {
+        let mut best: Option<(ProxyId, f32)> = None;
+        if self.root == NULL {
+            return None;
+        }
+        let mut stack = vec![self.root];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let limit = best.map_or(max_distance, |(_, distance)| distance);
+            if node.aabb.ray_distance(ray, limit).is_none() {
+                continue;
+            }
+            match &node.data {
+                Some(data) => {
+                    if let Some(distance) = hit(ProxyId(index), data).filter(|d| *d <= limit) {
+                        best = Some((ProxyId(index), distance));
+                    }
+                }
+                None => stack.extend([node.right, node.left]),
+            }
+        }
+        best
+    }
+
+    // Closest leaf to `point` by `distance`, which must be no less than the
+    // distance to the leaf's box. Visits boxes nearest first.
+    pub fn nearest(
+        &self,
+        point: uv::Vec3,
+        max_distance: f32,
+        mut distance: impl FnMut(ProxyId, &T) -> f32,
+    ) -> Option<(ProxyId, f32)> {
+        if self.root == NULL {
+            return None;
+        }
+        let mut best: Option<(ProxyId, f32)> = None;
+        let mut heap = BinaryHeap::new();
+        heap.push(Candidate {
+            distance: self.nodes[self.root].aabb.distance_squared(point).sqrt(),
+            index: self.root,
+        });
+        while let Some(Candidate { distance: bound, index }) = heap.pop() {
+            let limit = best.map_or(max_distance, |(_, d)| d);
+            if bound > limit {
+                break;
+            }
+            let node = &self.nodes[index];
+            match &node.data {
+                Some(data) => {
+                    let d = distance(ProxyId(index), data);
+                    if d <= limit && best.is_none_or(|(_, b)| d < b) {
+                        best = Some((ProxyId(index), d));
+                    }
+                }
+                None => {
+                    for child in [node.left, node.right] {
+                        heap.push(Candidate {
+                            distance: self.nodes[child].aabb.distance_squared(point).sqrt(),
+                            index: child,
+                        });
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    fn leaf(&self, id: ProxyId) -> Option<&Node<T>> {
+        self.nodes.get(id.0).filter(|node| node.height == 0 && node.data.is_some())
+    }
+
+    fn allocate(&mut self, aabb: Aabb, data: Option<T>) -> usize {
+        let node = Node {
+            aabb,
+            parent: NULL,
+            left: NULL,
+            right: NULL,
+            height: 0,
+            data,
+        };
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn release(&mut self, index: usize) {
+        let node = &mut self.nodes[index];
+        node.height = -1;
+        node.data = None;
+        node.left = NULL;
+        node.right = NULL;
+        node.parent = NULL;
+        self.free.push(index);
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        if self.root == NULL {
+            self.root = leaf;
+            self.nodes[leaf].parent = NULL;
+            return;
+        }
+
+        // Walk down to the sibling that grows the tree's area the least.
+        let leaf_aabb = self.nodes[leaf].aabb;
+        let mut index = self.root;
+        while !self.nodes[index].is_leaf() {
+            let node = &self.nodes[index];
+            let area = node.aabb.area();
+            let combined = node.aabb.union(&leaf_aabb).area();
+            let cost = 2.0 * combined;
+            let inheritance = 2.0 * (combined - area);
+            let child_cost = |child: usize| {
+                let child = &self.nodes[child];
+                let grown = leaf_aabb.union(&child.aabb).area();
+                if child.is_leaf() {
+                    grown + inheritance
+                } else {
+                    grown - child.aabb.area() + inheritance
+                }
+            };
+            let (left_cost, right_cost) = (child_cost(node.left), child_cost(node.right));
+            if cost < left_cost && cost < right_cost {
+                break;
+            }
+            index = if left_cost < right_cost { node.left } else { node.right };
+        }
+
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let new_parent = self.allocate(leaf_aabb.union(&self.nodes[sibling].aabb), None);
+        self.nodes[new_parent].parent = old_parent;
+        self.nodes[new_parent].height = self.nodes[sibling].height + 1;
+        self.nodes[new_parent].left = sibling;
+        self.nodes[new_parent].right = leaf;
+        self.nodes[sibling].parent = new_parent;
+        self.nodes[leaf].parent = new_parent;
+        if old_parent == NULL {
+            self.root = new_parent;
+        } else if self.nodes[old_parent].left == sibling {
+            self.nodes[old_parent].left = new_parent;
+        } else {
+            self.nodes[old_parent].right = new_parent;
+        }
+
+        self.refit(self.nodes[leaf].parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        if leaf == self.root {
+            self.root = NULL;
+            return;
+        }
+        let parent = self.nodes[leaf].parent;
+        let grandparent = self.nodes[parent].parent;
+        let sibling = if self.nodes[parent].left == leaf {
+            self.nodes[parent].right
+        } else {
+            self.nodes[parent].left
+        };
+        self.nodes[leaf].parent = NULL;
+        if grandparent == NULL {
+            self.root = sibling;
+            self.nodes[sibling].parent = NULL;
+            self.release(parent);
+            return;
+        }
+        if self.nodes[grandparent].left == parent {
+            self.nodes[grandparent].left = sibling;
+        } else {
+            self.nodes[grandparent].right = sibling;
+        }
+        self.nodes[sibling].parent = grandparent;
+        self.release(parent);
+        self.refit(grandparent);
+    }
+
+    // Rebalances and refits every node from `index` up to the root.
+    fn refit(&mut self, mut index: usize) {
+        while index != NULL {
+            index = self.balance(index);
+            let (left, right) = (self.nodes[index].left, self.nodes[index].right);
+            self.nodes[index].height = 1 + self.nodes[left].height.max(self.nodes[right].height);
+            self.nodes[index].aabb = self.nodes[left].aabb.union(&self.nodes[right].aabb);
+            index = self.nodes[index].parent;
+        }
+    }
+
+    // Rotates the taller grandchild up if `a`'s subtrees differ in height by
+    // more than one. Returns the node now in `a`'s place.
+    fn balance(&mut self, a: usize) -> usize {
+        if self.nodes[a].is_leaf() || self.nodes[a].height < 2 {
+            return a;
+        }
+        let (b, c) = (self.nodes[a].left, self.nodes[a].right);
+        let balance = self.nodes[c].height - self.nodes[b].height;
+        if balance > 1 {
+            self.rotate_up(a, c, b, true)
+        } else if balance < -1 {
+            self.rotate_up(a, b, c, false)
+        } else {
+            a
+        }
+    }
+
+    // Puts `up` (a child of `a`) in `a`'s place; `a` keeps `other` and takes
+    // the shorter of `up`'s children.
+    fn rotate_up(&mut self, a: usize, up: usize, other: usize, up_was_right: bool) -> usize {
+        let (f, g) = (self.nodes[up].left, self.nodes[up].right);
+        let parent = self.nodes[a].parent;
+        self.nodes[up].left = a;
+        self.nodes[up].parent = parent;
+        self.nodes[a].parent = up;
+        if parent == NULL {
+            self.root = up;
+        } else if self.nodes[parent].left == a {
+            self.nodes[parent].left = up;
+        } else {
+            self.nodes[parent].right = up;
+        }
+
+        let (keep, give) = if self.nodes[f].height > self.nodes[g].height {
+            (f, g)
+        } else {
+            (g, f)
+        };
+        self.nodes[up].right = keep;
+        if up_was_right {
+            self.nodes[a].right = give;
+        } else {
+            self.nodes[a].left = give;
+        }
+        self.nodes[give].parent = a;
+        self.nodes[a].aabb = self.nodes[other].aabb.union(&self.nodes[give].aabb);
+        self.nodes[a].height = 1 + self.nodes[other].height.max(self.nodes[give].height);
+        self.nodes[up].aabb = self.nodes[a].aabb.union(&self.nodes[keep].aabb);
+        self.nodes[up].height = 1 + self.nodes[a].height.max(self.nodes[keep].height);
+        up
+    }
+}
+
+// Min-heap entry for nearest-first traversal.
+#[derive(Copy, Clone, PartialEq)]
+struct Candidate {
+    distance: f32,
+    index: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then(other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn random_box(rng: &mut fastrand::Rng) -> Aabb {
+        let min = uv::Vec3::new(rng.f32(), rng.f32(), rng.f32()) * 20.0;
+        min_box(min, rng)
+    }
+
+    fn min_box(min: uv::Vec3, rng: &mut fastrand::Rng) -> Aabb {
+        Aabb::new(min, min + uv::Vec3::new(rng.f32(), rng.f32(), rng.f32()) * 2.0)
+    }
+
+    // Checks links, heights, bounds and AVL balance below `index` and returns
+    // its height and leaf count.
+    fn check<T>(tree: &DynamicBvh<T>, index: usize, parent: usize) -> (i32, usize) {
+        let node = &tree.nodes[index];
+        assert_eq!(node.parent, parent, "node {} has the wrong parent", index);
+        if node.is_leaf() {
+            assert_eq!(node.height, 0);
+            assert!(node.data.is_some());
+            return (0, 1);
+        }
+        let (left, right) = (&tree.nodes[node.left], &tree.nodes[node.right]);
+        assert!(node.aabb.contains(&left.aabb) && node.aabb.contains(&right.aabb));
+        let (left_height, left_leaves) = check(tree, node.left, index);
+        let (right_height, right_leaves) = check(tree, node.right, index);
+        assert_eq!(node.height, 1 + left_height.max(right_height));
+        assert!((left_height - right_height).abs() <= 1, "node {} is unbalanced", index);
+        (node.height, left_leaves + right_leaves)
+    }
+
+    fn check_tree<T>(tree: &DynamicBvh<T>) {
+        let leaves = if tree.root == NULL { 0 } else { check(tree, tree.root, NULL).1 };
+        assert_eq!(leaves, tree.len());
+    }
+
+    #[test]
+    fn matches_brute_force_through_random_edits() {
+        let mut rng = fastrand::Rng::with_seed(42);
+        let mut tree = DynamicBvh::new();
+        // What each leaf was last given.
+        let mut boxes: BTreeMap<ProxyId, Aabb> = BTreeMap::new();
+        for round in 0..400 {
+            match rng.usize(0..10) {
+                0..=4 => {
+                    let aabb = random_box(&mut rng);
+                    let id = tree.insert(aabb, round);
+                    boxes.insert(id, aabb);
+                }
+                5..=7 if !boxes.is_empty() => {
+                    let id = *boxes.keys().nth(rng.usize(0..boxes.len())).unwrap();
+                    // Mostly nudges that stay inside the fat box.
+                    let aabb = if rng.bool() {
+                        let nudge = uv::Vec3::broadcast(DEFAULT_MARGIN * 0.5 * rng.f32());
+                        Aabb::new(boxes[&id].min + nudge, boxes[&id].max + nudge)
+                    } else {
+                        random_box(&mut rng)
+                    };
+                    tree.update(id, aabb);
+                    boxes.insert(id, aabb);
+                }
+                _ if !boxes.is_empty() => {
+                    let id = *boxes.keys().nth(rng.usize(0..boxes.len())).unwrap();
+                    assert!(tree.remove(id).is_some());
+                    boxes.remove(&id);
+                }
+                _ => {}
+            }
+            check_tree(&tree);
+            for (id, aabb) in &boxes {
+                assert!(tree.fat_aabb(*id).unwrap().contains(aabb));
+            }
+
+            let fat: BTreeMap<ProxyId, Aabb> = boxes.keys().map(|id| (*id, tree.fat_aabb(*id).unwrap())).collect();
+            let area = random_box(&mut rng);
+            let mut found = Vec::new();
+            tree.query(&area, |id, _| found.push(id));
+            found.sort();
+            let expected: Vec<_> = fat.iter().filter(|(_, b)| b.overlaps(&area)).map(|(id, _)| *id).collect();
+            assert_eq!(found, expected);
+
+            let mut pairs = Vec::new();
+            for (a, box_a) in &fat {
+                for (b, box_b) in fat.range(*a..).skip(1) {
+                    if box_a.overlaps(box_b) {
+                        pairs.push((*a, *b));
+                    }
+                }
+            }
+            assert_eq!(tree.overlap_pairs(), pairs);
+
+            let point = uv::Vec3::new(rng.f32(), rng.f32(), rng.f32()) * 20.0;
+            let distance = |id: ProxyId| boxes[&id].distance_squared(point).sqrt();
+            let nearest = tree.nearest(point, f32::INFINITY, |id, _| distance(id)).map(|(_, d)| d);
+            let expected = boxes.keys().map(|id| distance(*id)).min_by(f32::total_cmp);
+            assert_eq!(nearest, expected);
+        }
+    }
+
+    #[test]
+    fn small_moves_keep_the_fat_box() {
+        let mut rng = fastrand::Rng::with_seed(7);
+        let mut tree = DynamicBvh::new().with_margin(0.5);
+        let aabb = min_box(uv::Vec3::zero(), &mut rng);
+        let id = tree.insert(aabb, ());
+        let fat = tree.fat_aabb(id).unwrap();
+        assert_eq!(fat.min, aabb.min - uv::Vec3::broadcast(0.5));
+
+        let nudged = Aabb::new(aabb.min + uv::Vec3::broadcast(0.4), aabb.max + uv::Vec3::broadcast(0.4));
+        assert!(!tree.update(id, nudged));
+        assert_eq!(tree.fat_aabb(id).unwrap().min, fat.min);
+
+        let moved = Aabb::new(aabb.min + uv::Vec3::broadcast(0.6), aabb.max + uv::Vec3::broadcast(0.6));
+        assert!(tree.update(id, moved));
+        assert_eq!(tree.fat_aabb(id).unwrap().min, moved.min - uv::Vec3::broadcast(0.5));
+    }
+}
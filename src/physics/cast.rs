@@ -0,0 +1,127 @@
+// Sweeps of convex shapes, for things that move through the world without
+// being simulated, like character controllers.
+use ultraviolet as uv;
+
+use super::gjk::{self, Convex, Gjk};
+use super::shape::{Aabb, Shape};
+
+const CAST_ITERATIONS: usize = 32;
+const CAST_TOLERANCE: f32 = 1e-4;
+// Shapes touching only block moves heading into each other by more than
+// this, so sliding along a surface doesn't catch on noise in its normal.
+const GRAZE_TOLERANCE: f32 = 1e-3;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShapeHit {
+    // How far the shape can move before touching.
+    pub distance: f32,
+    pub point: uv::Vec3,
+    // Unit surface normal of what was hit, facing the moving shape.
+    pub normal: uv::Vec3,
+    // Index of the triangle hit, for triangle meshes.
+    pub triangle: Option<usize>,
+}
+
+// Conservative advancement: the plane between the closest points keeps the
+// shapes apart until the moving one has covered the gap along its normal.
+// Shapes already touching only hit when moving further in.
+fn cast_convex(moving: &Convex, direction: uv::Vec3, max_distance: f32, obstacle: &Convex) -> Option<ShapeHit> {
+    let margin = moving.margin + obstacle.margin;
+    let mut shape = *moving;
+    let mut distance = 0.0;
+    let mut normal = -direction;
+    let mut point = moving.position;
+    for _ in 0..CAST_ITERATIONS {
+        shape.position = moving.position + direction * distance;
+        let closest = match gjk::gjk(&shape, obstacle) {
+            Gjk::Separated(closest) if closest.distance > CAST_TOLERANCE => closest,
+            // The cores overlap, which only happens when starting deep inside.
+            overlap => {
+                let simplex = match overlap {
+                    Gjk::Overlapping(simplex) => simplex,
+                    Gjk::Separated(_) => Vec::new(),
+                };
+                let penetration = gjk::epa(&shape, obstacle, simplex)?;
+                let normal = -penetration.normal;
+                return (normal.dot(direction) < 0.0).then_some(ShapeHit {
+                    distance,
+                    point: penetration.point_b,
+                    normal,
+                    triangle: None,
+                });
+            }
+        };
+        normal = (closest.point_a - closest.point_b) / closest.distance;
+        point = closest.point_b + normal * obstacle.margin;
+        let closing = -normal.dot(direction);
+        if closest.distance - margin <= CAST_TOLERANCE {
+            return (closing > GRAZE_TOLERANCE).then_some(ShapeHit {
+                distance,
+                point,
+                normal,
+                triangle: None,
+            });
+        }
+        if closing <= 1e-6 {
+            return None;
+        }
+        distance += (closest.distance - margin) / closing;
+        if distance > max_distance {
+            return None;
+        }
+    }
+    // Still apart where it stopped, so stopping there is safe.
+    Some(ShapeHit {
+        distance,
+        point,
+        normal,
+        triangle: None,
+    })
+}
+
+impl Shape {
+    // Sweeps this shape from `position` along unit `direction` and returns
+    // where it first touches `target`. Only convex shapes can be swept.
+    #[allow(clippy::too_many_arguments)]
+    pub fn cast(
+        &self,
+        position: uv::Vec3,
+        rotation: uv::Rotor3,
+        direction: uv::Vec3,
+        max_distance: f32,
+        target: &Shape,
+        target_position: uv::Vec3,
+        target_rotation: uv::Rotor3,
+    ) -> Option<ShapeHit> {
+        let moving = Convex::new(self, position, rotation)?;
+        if let Some(obstacle) = Convex::new(target, target_position, target_rotation) {
+            return cast_convex(&moving, direction, max_distance, &obstacle);
+        }
+        let Shape::TriangleMesh(mesh) = target else {
+            return None;
+        };
+        // Only triangles near the swept bounds, found in the mesh's space.
+        let start = self.aabb(position, rotation);
+        let end = self.aabb(position + direction * max_distance, rotation);
+        let inverse = target_rotation.reversed();
+        let local_bounds = start.union(&end).transformed(inverse * -target_position, inverse);
+        let mut best: Option<ShapeHit> = None;
+        for index in 0..mesh.triangles.len() {
+            let local = mesh.triangle(index);
+            if !Aabb::from_points(local).overlaps(&local_bounds) {
+                continue;
+            }
+            let world = local.map(|v| target_rotation * v + target_position);
+            let limit = best.map_or(max_distance, |hit| hit.distance);
+            if let Some(hit) = cast_convex(&moving, direction, limit, &Convex::triangle(world)) {
+                if best.is_none_or(|best| hit.distance < best.distance) {
+                    best = Some(ShapeHit {
+                        triangle: Some(index),
+                        ..hit
+                    });
+                }
+            }
+        }
+        best
+    }
+}
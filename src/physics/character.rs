@@ -0,0 +1,509 @@
+// Kinematic character: a capsule that sweeps through the world instead of
+// being simulated. It slides along walls, climbs steps and walkable slopes,
+// follows the ground downhill and can still jump shortly after running off
+// a ledge. Bodies block it but aren't pushed.
+use ultraviolet as uv;
+
+use super::body::BodyHandle;
+use super::cast::ShapeHit;
+use super::ray::{Layers, Ray};
+use super::shape::Shape;
+use super::world::PhysicsWorld;
+
+const MAX_SLIDES: usize = 4;
+const MAX_DEPENETRATION: usize = 4;
+
+#[derive(Debug, Clone)]
+pub struct CharacterController {
+    // Centre of the capsule.
+    pub position: uv::Vec3,
+    // Unit length; the capsule stands along it.
+    pub up: uv::Vec3,
+    // Steepest slope walked on rather than slid down, in radians.
+    pub max_slope: f32,
+    // Tallest ledge climbed without jumping.
+    pub step_height: f32,
+    // How far down the ground is followed, e.g. down stairs and slopes.
+    pub snap_distance: f32,
+    // Gap kept between the capsule and the world.
+    pub skin: f32,
+    pub jump_speed: f32,
+    // How long after leaving the ground a jump still works.
+    pub coyote_time: f32,
+    // How long a jump asked for in the air waits for landing.
+    pub jump_buffer: f32,
+    pub gravity_scale: f32,
+    // Bodies on these layers block the character.
+    pub mask: Layers,
+    half_height: f32,
+    radius: f32,
+    vertical_speed: f32,
+    velocity: uv::Vec3,
+    ground: Option<(BodyHandle, uv::Vec3)>,
+    since_grounded: f32,
+    // Time since a jump was asked for, until it happens or expires.
+    jump_requested: Option<f32>,
+    hits: Vec<(BodyHandle, ShapeHit)>,
+}
+
+impl CharacterController {
+    // Capsule with a cylinder `2 * half_height` tall between its caps.
+    pub fn new(half_height: f32, radius: f32) -> Self {
+        Self {
+            position: uv::Vec3::zero(),
+            up: uv::Vec3::unit_y(),
+            max_slope: 45f32.to_radians(),
+            step_height: 0.3,
+            snap_distance: 0.2,
+            skin: 0.02,
+            jump_speed: 5.0,
+            coyote_time: 0.1,
+            jump_buffer: 0.1,
+            gravity_scale: 1.0,
+            mask: Layers::ALL,
+            half_height,
+            radius,
+            vertical_speed: 0.0,
+            velocity: uv::Vec3::zero(),
+            ground: None,
+            since_grounded: f32::INFINITY,
+            jump_requested: None,
+            hits: Vec::new(),
+        }
+    }
+
+    pub fn with_position(mut self, position: uv::Vec3) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_max_slope(mut self, max_slope: f32) -> Self {
+        self.max_slope = max_slope;
+        self
+    }
+
+    pub fn with_step_height(mut self, step_height: f32) -> Self {
+        self.step_height = step_height;
+        self
+    }
+
+    pub fn with_jump_speed(mut self, jump_speed: f32) -> Self {
+        self.jump_speed = jump_speed;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: Layers) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    pub fn half_height(&self) -> f32 {
+        self.half_height
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn shape(&self) -> Shape {
+        Shape::capsule(self.half_height, self.radius)
+    }
+
+    // Turns the capsule's local y onto `up`.
+    pub fn rotation(&self) -> uv::Rotor3 {
+        uv::Rotor3::from_rotation_between(uv::Vec3::unit_y(), self.up)
+    }
+
+    // Bottom of the capsule.
+    pub fn feet(&self) -> uv::Vec3 {
+        self.position - self.up * (self.half_height + self.radius)
+    }
+
+    pub fn is_grounded(&self) -> bool {
+        self.ground.is_some()
+    }
+
+    // What the character stands on and its normal.
+    pub fn ground(&self) -> Option<(BodyHandle, uv::Vec3)> {
+        self.ground
+    }
+
+    // How fast it actually moved over the last update.
+    pub fn velocity(&self) -> uv::Vec3 {
+        self.velocity
+    }
+
+    pub fn vertical_speed(&self) -> f32 {
+        self.vertical_speed
+    }
+
+    // Everything touched during the last update.
+    pub fn hits(&self) -> &[(BodyHandle, ShapeHit)] {
+        &self.hits
+    }
+
+    // Jumps on the next update that finds the character on the ground, or
+    // just off it.
+    pub fn jump(&mut self) {
+        self.jump_requested = Some(0.0);
+    }
+
+    pub fn is_walkable(&self, normal: uv::Vec3) -> bool {
+        normal.dot(self.up) >= self.max_slope.cos() - 1e-4
+    }
+
+    // Moves by `movement` (a velocity, only its part across `up` is used),
+    // then falls, lands and snaps to the ground.
+    pub fn update(&mut self, world: &PhysicsWorld, movement: uv::Vec3, dt: f32) {
+        if dt <= 0.0 {
+            return;
+        }
+        let start = self.position;
+        self.hits.clear();
+        self.depenetrate(world);
+
+        self.since_grounded = if self.is_grounded() { 0.0 } else { self.since_grounded + dt };
+        let mut jumped = false;
+        if let Some(waited) = self.jump_requested {
+            if self.since_grounded <= self.coyote_time {
+                self.vertical_speed = self.jump_speed;
+                self.ground = None;
+                self.since_grounded = f32::INFINITY;
+                self.jump_requested = None;
+                jumped = true;
+            } else if waited + dt > self.jump_buffer {
+                self.jump_requested = None;
+            } else {
+                self.jump_requested = Some(waited + dt);
+            }
+        }
+        let was_grounded = self.is_grounded();
+        if was_grounded {
+            self.vertical_speed = 0.0;
+        } else {
+            self.vertical_speed += world.gravity.dot(self.up) * self.gravity_scale * dt;
+        }
+
+        let mut step = (movement - self.up * movement.dot(self.up)) * dt;
+        if let Some((_, normal)) = self.ground {
+            // Along the slope, keeping the speed across it.
+            step -= self.up * (step.dot(normal) / normal.dot(self.up));
+        }
+        self.move_across(world, step, was_grounded);
+
+        if self.vertical_speed != 0.0 {
+            let rising = self.vertical_speed > 0.0;
+            let fall = self.up * self.vertical_speed * dt;
+            for (_, hit) in self.slide(world, fall, false) {
+                if rising && hit.normal.dot(self.up) < 0.0 {
+                    self.vertical_speed = 0.0;
+                }
+            }
+        }
+
+        let probe = if was_grounded && !jumped {
+            self.snap_distance
+        } else {
+            self.skin
+        };
+        self.find_ground(world, probe);
+        self.velocity = (self.position - start) / dt;
+    }
+
+    // Sweeps by `displacement`, sliding along whatever is in the way. Returns
+    // what it hit.
+    pub fn move_and_slide(&mut self, world: &PhysicsWorld, displacement: uv::Vec3) -> Vec<(BodyHandle, ShapeHit)> {
+        self.slide(world, displacement, false)
+    }
+
+    // A sideways move that climbs steps when on the ground. The climb is
+    // only kept if it gets further than sliding along the obstacle would.
+    fn move_across(&mut self, world: &PhysicsWorld, step: uv::Vec3, grounded: bool) {
+        let start = self.position;
+        let hits = self.slide(world, step, true);
+        let blocked = hits.iter().any(|(_, hit)| !self.is_walkable(hit.normal));
+        if !grounded || !blocked || self.step_height <= 0.0 {
+            return;
+        }
+        let slid = self.position;
+        let hits_before = self.hits.len();
+        self.position = start;
+        let rise = self.cast(world, self.up, self.step_height).map_or(self.step_height, |(_, hit)| hit.distance);
+        self.position += self.up * rise;
+        self.slide(world, step, true);
+        let landing = self.cast(world, -self.up, rise + self.skin);
+        let feet = start - self.up * (self.half_height + self.radius);
+        let landed = landing
+            .and_then(|(_, hit)| self.support(world, &hit))
+            .is_some_and(|(_, point)| (point - feet).dot(self.up) <= self.step_height);
+        let across = |p: uv::Vec3| {
+            let d = p - start;
+            (d - self.up * d.dot(self.up)).mag()
+        };
+        if landed && across(self.position) > across(slid) + 1e-4 {
+            let (_, hit) = landing.unwrap();
+            self.position -= self.up * hit.distance;
+        } else {
+            self.position = slid;
+            self.hits.truncate(hits_before);
+        }
+    }
+
+    // Sliding keeps going along each surface hit. With `across`, slopes too
+    // steep to walk are treated as walls rather than ramps to climb.
+    fn slide(&mut self, world: &PhysicsWorld, displacement: uv::Vec3, across: bool) -> Vec<(BodyHandle, ShapeHit)> {
+        let mut hits = Vec::new();
+        let mut remaining = displacement;
+        for _ in 0..MAX_SLIDES {
+            let length = remaining.mag();
+            if length < 1e-6 {
+                break;
+            }
+            let direction = remaining / length;
+            let Some((body, hit)) = self.cast(world, direction, length) else {
+                self.position += remaining;
+                break;
+            };
+            self.position += direction * hit.distance;
+            hits.push((body, hit));
+            let mut normal = hit.normal;
+            if across && !self.is_walkable(normal) {
+                let flat = normal - self.up * normal.dot(self.up);
+                if flat.mag_sq() > 1e-8 {
+                    normal = flat.normalized();
+                }
+            }
+            remaining = direction * (length - hit.distance);
+            remaining -= normal * remaining.dot(normal);
+            // Don't turn back on ourselves in a corner.
+            if remaining.dot(displacement) <= 0.0 {
+                break;
+            }
+        }
+        self.hits.extend(hits.iter().copied());
+        hits
+    }
+
+    fn find_ground(&mut self, world: &PhysicsWorld, probe: f32) {
+        self.ground = None;
+        if self.vertical_speed > 0.0 {
+            return;
+        }
+        let Some((body, hit)) = self.cast(world, -self.up, probe) else {
+            return;
+        };
+        let Some((normal, _)) = self.support(world, &hit) else {
+            return;
+        };
+        self.position -= self.up * hit.distance;
+        self.vertical_speed = 0.0;
+        self.ground = Some((body, normal));
+        if let Some(requested) = self.jump_requested {
+            // Landed with a jump buffered: it goes off next update.
+            self.jump_requested = Some(requested.min(self.jump_buffer));
+        }
+    }
+
+    // The walkable surface under a hit from above, if any, as its normal and
+    // a point on it. The rounded bottom touches ledges and step edges at an
+    // angle, so those are judged by the surface just inside the edge instead.
+    fn support(&self, world: &PhysicsWorld, hit: &ShapeHit) -> Option<(uv::Vec3, uv::Vec3)> {
+        if self.is_walkable(hit.normal) {
+            return Some((hit.normal, hit.point));
+        }
+        let across = hit.normal - self.up * hit.normal.dot(self.up);
+        if hit.normal.dot(self.up) <= 0.0 || across.mag_sq() < 1e-8 {
+            return None;
+        }
+        let inward = -across.normalized() * (self.skin * 0.5);
+        let ray = Ray::new(hit.point + inward + self.up * self.skin, -self.up);
+        // Starting inside means it's a slope rising inward, not a ledge.
+        let (_, below) = world.raycast_solid(&ray, self.skin * 2.0, self.mask)?;
+        (below.distance > 0.0 && self.is_walkable(below.normal)).then_some((below.normal, below.point))
+    }
+
+    // Pushes the capsule out of anything it ended up inside, e.g. a body
+    // that moved into it.
+    fn depenetrate(&mut self, world: &PhysicsWorld) {
+        let shape = self.shape();
+        for _ in 0..MAX_DEPENETRATION {
+            let contacts = world.shape_contacts(&shape, self.position, self.rotation(), self.mask);
+            let deepest = contacts
+                .iter()
+                .filter(|(_, contact)| contact.depth > 0.0)
+                .max_by(|(_, a), (_, b)| a.depth.total_cmp(&b.depth));
+            let Some((_, contact)) = deepest else {
+                break;
+            };
+            self.position -= contact.normal * (contact.depth + self.skin);
+        }
+    }
+
+    // Sweeps the capsule grown by the skin, so stopping where it touches
+    // leaves the skin as a gap.
+    fn cast(&self, world: &PhysicsWorld, direction: uv::Vec3, distance: f32) -> Option<(BodyHandle, ShapeHit)> {
+        let shape = Shape::capsule(self.half_height, self.radius + self.skin);
+        world.shape_cast(&shape, self.position, self.rotation(), direction, distance, self.mask)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::body::RigidBody;
+    use crate::physics::world::DEFAULT_TIMESTEP;
+
+    const DT: f32 = DEFAULT_TIMESTEP;
+    // Centre height of `character()` standing on y = 0.
+    const STANDING: f32 = 0.5 + 0.3 + 0.02;
+
+    fn block(center: uv::Vec3, half_extents: uv::Vec3) -> RigidBody {
+        RigidBody::fixed(Shape::cuboid(half_extents)).with_position(center)
+    }
+
+    // A floor with its top at y = 0 from x = -10 to `end`.
+    fn floor(end: f32) -> RigidBody {
+        let half = (end + 10.0) / 2.0;
+        block(uv::Vec3::new(end - half, -0.5, 0.0), uv::Vec3::new(half, 0.5, 10.0))
+    }
+
+    fn world(bodies: impl IntoIterator<Item = RigidBody>) -> PhysicsWorld {
+        let mut world = PhysicsWorld::new();
+        for body in bodies {
+            world.add_body(body);
+        }
+        world
+    }
+
+    fn character(x: f32) -> CharacterController {
+        CharacterController::new(0.5, 0.3).with_position(uv::Vec3::new(x, STANDING, 0.0))
+    }
+
+    fn run(controller: &mut CharacterController, world: &PhysicsWorld, movement: uv::Vec3, seconds: f32) {
+        for _ in 0..(seconds / DT).round() as usize {
+            controller.update(world, movement, DT);
+        }
+    }
+
+    fn walk() -> uv::Vec3 {
+        uv::Vec3::new(2.0, 0.0, 0.0)
+    }
+
+    #[test]
+    fn climbs_steps_but_not_walls() {
+        let step = world([floor(10.0), block(uv::Vec3::new(3.0, 0.1, 0.0), uv::Vec3::new(1.5, 0.1, 2.0))]);
+        let mut controller = character(0.0);
+        run(&mut controller, &step, walk(), 1.5);
+        assert!(controller.position.x > 2.0, "stuck at {:?}", controller.position);
+        assert!((controller.feet().y - 0.2).abs() < 0.03, "feet at {:?}", controller.feet());
+        assert!(controller.is_grounded());
+
+        let wall = world([floor(10.0), block(uv::Vec3::new(3.0, 0.5, 0.0), uv::Vec3::new(1.5, 0.5, 2.0))]);
+        let mut controller = character(0.0);
+        run(&mut controller, &wall, walk(), 1.5);
+        assert!(controller.position.x < 1.5 - 0.3, "walked into {:?}", controller.position);
+        assert!(controller.feet().y.abs() < 0.03, "feet at {:?}", controller.feet());
+    }
+
+    #[test]
+    fn walks_up_walkable_slopes_only() {
+        // A plank rising from x = 1 towards +x.
+        let ramp = |angle: f32| {
+            let rotation = uv::Rotor3::from_rotation_xy(angle);
+            let center = uv::Vec3::new(1.0, 0.0, 0.0) + uv::Vec3::new(3.0, -0.1, 0.0).rotated_by(rotation);
+            block(center, uv::Vec3::new(3.0, 0.1, 2.0)).with_rotation(rotation)
+        };
+
+        let gentle = world([floor(10.0), ramp(30f32.to_radians())]);
+        let mut controller = character(0.0);
+        run(&mut controller, &gentle, walk(), 1.5);
+        assert!(controller.feet().y > 0.5, "feet at {:?}", controller.feet());
+        let (_, normal) = controller.ground().expect("on the ramp");
+        assert!((normal.dot(uv::Vec3::unit_y()) - 30f32.to_radians().cos()).abs() < 1e-3);
+
+        let steep = world([floor(10.0), ramp(60f32.to_radians())]);
+        let mut controller = character(0.0);
+        run(&mut controller, &steep, walk(), 1.5);
+        assert!(controller.feet().y < 0.1, "climbed to {:?}", controller.feet());
+        assert!(controller.position.x < 1.5, "walked into {:?}", controller.position);
+    }
+
+    #[test]
+    fn follows_small_drops_and_leaves_ledges() {
+        let lower = block(uv::Vec3::new(6.0, -0.65, 0.0), uv::Vec3::new(5.0, 0.5, 10.0));
+        let drop = world([floor(1.0), lower]);
+        let mut controller = character(0.0);
+        for _ in 0..60 {
+            controller.update(&drop, walk(), DT);
+            assert!(controller.is_grounded(), "lost the ground at {:?}", controller.position);
+        }
+        assert!((controller.feet().y + 0.15).abs() < 0.03, "feet at {:?}", controller.feet());
+
+        let ledge = world([floor(1.0)]);
+        let mut controller = character(0.0);
+        run(&mut controller, &ledge, walk(), 1.0);
+        assert!(!controller.is_grounded());
+        assert!(controller.vertical_speed() < 0.0);
+    }
+
+    // Walks off the ledge of `floor(1.0)` and updates until the first
+    // frame in the air.
+    fn walk_off(world: &PhysicsWorld) -> CharacterController {
+        let mut controller = character(0.0);
+        while controller.is_grounded() || controller.position.x < 1.0 {
+            controller.update(world, walk(), DT);
+            assert!(controller.position.x < 3.0, "never left the ledge");
+        }
+        controller
+    }
+
+    #[test]
+    fn jumps_shortly_after_leaving_the_ground() {
+        let ledge = world([floor(1.0)]);
+        let mut controller = walk_off(&ledge);
+        controller.jump();
+        controller.update(&ledge, walk(), DT);
+        assert!(controller.vertical_speed() > 4.0, "{}", controller.vertical_speed());
+
+        let mut controller = walk_off(&ledge);
+        run(&mut controller, &ledge, walk(), 0.2);
+        controller.jump();
+        controller.update(&ledge, walk(), DT);
+        assert!(controller.vertical_speed() < 0.0, "{}", controller.vertical_speed());
+    }
+
+    #[test]
+    fn buffers_jumps_asked_for_just_before_landing() {
+        let ground = world([floor(10.0)]);
+        // Lands 0.2 s after a drop of g * 0.2^2 / 2.
+        let drop = |jump_at: f32| {
+            let mut controller = character(0.0);
+            controller.position.y += 9.81 * 0.2 * 0.2 / 2.0;
+            let mut highest = 0.0f32;
+            for frame in 0..30 {
+                if frame == (jump_at / DT).round() as usize {
+                    controller.jump();
+                }
+                controller.update(&ground, uv::Vec3::zero(), DT);
+                highest = highest.max(controller.vertical_speed());
+            }
+            highest
+        };
+        assert!(drop(0.15) > 4.0);
+        assert_eq!(drop(0.0), 0.0);
+    }
+
+    #[test]
+    fn stands_on_ledges_covered_by_sensors() {
+        // Far enough past the edge that the capsule touches it at an angle
+        // too steep to walk, so the surface inside the edge is checked.
+        let trigger = RigidBody::fixed(Shape::cuboid(uv::Vec3::new(0.5, 0.5, 1.0)))
+            .with_position(uv::Vec3::new(0.5, 0.4, 0.0))
+            .with_sensor(true);
+        let ledge = world([floor(1.0), trigger]);
+        let mut controller = character(1.25);
+        run(&mut controller, &ledge, uv::Vec3::zero(), 0.5);
+        assert!(controller.is_grounded(), "fell to {:?}", controller.position);
+        assert!(controller.position.y > 0.0, "fell to {:?}", controller.position);
+    }
+}
@@ -0,0 +1,271 @@
+use ultraviolet as uv;
+
+use super::body::{BodyHandle, RigidBody};
+use super::gjk::{self, Convex, Gjk};
+use super::shape::Shape;
+
+pub const MAX_MANIFOLD_POINTS: usize = 4;
+// Points further apart than this, or separated by more, are dropped.
+const BREAKING_DISTANCE: f32 = 0.02;
+// Contacts are kept a little before the shapes touch, so resting bodies
+// don't flicker in and out of contact.
+pub(crate) const CONTACT_MARGIN: f32 = 0.01;
+
+#[derive(Debug, Copy, Clone)]
+pub struct ContactPoint {
+    // On each body, in its local space, so the point can be tracked as the
+    // bodies move.
+    pub local_a: uv::Vec3,
+    pub local_b: uv::Vec3,
+    // World space, halfway between the surfaces.
+    pub point: uv::Vec3,
+    // Positive when the shapes overlap.
+    pub depth: f32,
+    // Impulses from the last step, reused to warm start the solver.
+    pub normal_impulse: f32,
+    pub tangent_impulse: [f32; 2],
+}
+
+// Up to four points where two bodies touch, kept from step to step. The
+// normal points from `body_a` to `body_b`.
+#[derive(Debug, Clone)]
+pub struct Manifold {
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+    pub normal: uv::Vec3,
+    pub points: Vec<ContactPoint>,
+    pub friction: f32,
+    pub restitution: f32,
+}
+
+impl Manifold {
+    pub(crate) fn new(body_a: BodyHandle, body_b: BodyHandle, a: &RigidBody, b: &RigidBody) -> Self {
+        Self {
+            body_a,
+            body_b,
+            normal: uv::Vec3::zero(),
+            points: Vec::new(),
+            // The usual geometric mean, and the bouncier of the two.
+            friction: (a.friction * b.friction).sqrt(),
+            restitution: a.restitution.max(b.restitution),
+        }
+    }
+
+    // Moves the points with their bodies and drops the ones that came apart.
+    pub(crate) fn refresh(&mut self, a: &RigidBody, b: &RigidBody) {
+        let normal = self.normal;
+        self.points.retain_mut(|point| {
+            let world_a = a.rotation * point.local_a + a.position;
+            let world_b = b.rotation * point.local_b + b.position;
+            point.depth = (world_a - world_b).dot(normal);
+            point.point = (world_a + world_b) * 0.5;
+            let drift = (world_a - world_b) - normal * point.depth;
+            point.depth > -BREAKING_DISTANCE && drift.mag_sq() < BREAKING_DISTANCE * BREAKING_DISTANCE
+        });
+    }
+
+    // Adds a fresh contact, replacing a nearby old one and keeping the four
+    // points spanning the largest area.
+    pub(crate) fn add(&mut self, contact: Contact, a: &RigidBody, b: &RigidBody) {
+        if self.normal.dot(contact.normal) < 0.95 {
+            self.points.clear();
+        }
+        self.normal = contact.normal;
+        let mut point = ContactPoint {
+            local_a: a.rotation.reversed() * (contact.point_a - a.position),
+            local_b: b.rotation.reversed() * (contact.point_b - b.position),
+            point: (contact.point_a + contact.point_b) * 0.5,
+            depth: contact.depth,
+            normal_impulse: 0.0,
+            tangent_impulse: [0.0; 2],
+        };
+        let nearest = self
+            .points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (i, (p.local_a - point.local_a).mag_sq()))
+            .filter(|(_, d)| *d < BREAKING_DISTANCE * BREAKING_DISTANCE)
+            .min_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((i, _)) = nearest {
+            point.normal_impulse = self.points[i].normal_impulse;
+            point.tangent_impulse = self.points[i].tangent_impulse;
+            self.points[i] = point;
+            return;
+        }
+        self.points.push(point);
+        if self.points.len() > MAX_MANIFOLD_POINTS {
+            let drop = self.least_useful();
+            self.points.remove(drop);
+        }
+    }
+
+    // Of five points, the one whose removal keeps the deepest point and the
+    // largest area.
+    fn least_useful(&self) -> usize {
+        let deepest = (0..self.points.len())
+            .max_by(|&x, &y| self.points[x].depth.total_cmp(&self.points[y].depth))
+            .unwrap_or(0);
+        let mut best = (f32::MIN, 0);
+        for drop in 0..self.points.len() {
+            if drop == deepest {
+                continue;
+            }
+            let kept: Vec<uv::Vec3> = (0..self.points.len())
+                .filter(|&i| i != drop)
+                .map(|i| self.points[i].local_a)
+                .collect();
+            let area = quad_area(&kept);
+            if area > best.0 {
+                best = (area, drop);
+            }
+        }
+        best.1
+    }
+}
+
+fn quad_area(points: &[uv::Vec3]) -> f32 {
+    let [a, b, c, d] = [points[0], points[1], points[2], points[3]];
+    // The largest of the three ways to pair up the diagonals.
+    [(a - b).cross(c - d), (a - c).cross(b - d), (a - d).cross(b - c)]
+        .iter()
+        .map(|v| v.mag_sq())
+        .fold(0.0, f32::max)
+}
+
+// A single contact found this step, normal from a to b.
+#[derive(Debug, Copy, Clone)]
+pub struct Contact {
+    pub point_a: uv::Vec3,
+    pub point_b: uv::Vec3,
+    pub normal: uv::Vec3,
+    pub depth: f32,
+}
+
+impl Contact {
+    fn flipped(self) -> Self {
+        Contact {
+            point_a: self.point_b,
+            point_b: self.point_a,
+            normal: -self.normal,
+            depth: self.depth,
+        }
+    }
+}
+
+// Contact between two convex shapes within `CONTACT_MARGIN` of touching.
+pub(crate) fn convex_contact(a: &Convex, b: &Convex) -> Option<Contact> {
+    let margin = a.margin + b.margin;
+    match gjk::gjk(a, b) {
+        Gjk::Separated(closest) if closest.distance > 1e-4 => {
+            if closest.distance > margin + CONTACT_MARGIN {
+                return None;
+            }
+            let normal = (closest.point_b - closest.point_a) / closest.distance;
+            Some(Contact {
+                point_a: closest.point_a + normal * a.margin,
+                point_b: closest.point_b - normal * b.margin,
+                normal,
+                depth: margin - closest.distance,
+            })
+        }
+        Gjk::Separated(_) => overlapping(a, b, Vec::new()),
+        Gjk::Overlapping(simplex) => overlapping(a, b, simplex),
+    }
+}
+
+fn overlapping(a: &Convex, b: &Convex, simplex: Vec<gjk::Vertex>) -> Option<Contact> {
+    let penetration = gjk::epa(a, b, simplex)?;
+    Some(Contact {
+        point_a: penetration.point_a,
+        point_b: penetration.point_b,
+        normal: penetration.normal,
+        depth: penetration.depth,
+    })
+}
+
+// Contacts between two bodies, each tagged with the mesh triangle it came
+// from (0 for convex pairs) so the manifolds can be kept apart.
+pub(crate) fn contacts(a: &RigidBody, b: &RigidBody) -> Vec<(u32, Contact)> {
+    match (&a.shape, &b.shape) {
+        (Shape::TriangleMesh(_), Shape::TriangleMesh(_)) => Vec::new(),
+        (Shape::TriangleMesh(_), _) => mesh_contacts(b, a)
+            .into_iter()
+            .map(|(triangle, contact)| (triangle, contact.flipped()))
+            .collect(),
+        (_, Shape::TriangleMesh(_)) => mesh_contacts(a, b),
+        _ => {
+            let convex_a = Convex::new(&a.shape, a.position, a.rotation);
+            let convex_b = Convex::new(&b.shape, b.position, b.rotation);
+            match (convex_a, convex_b) {
+                (Some(convex_a), Some(convex_b)) => convex_contact(&convex_a, &convex_b)
+                    .map(|contact| vec![(0, contact)])
+                    .unwrap_or_default(),
+                _ => Vec::new(),
+            }
+        }
+    }
+}
+
+fn mesh_contacts(body: &RigidBody, mesh_body: &RigidBody) -> Vec<(u32, Contact)> {
+    let Some(convex) = Convex::new(&body.shape, body.position, body.rotation) else {
+        return Vec::new();
+    };
+    near_triangles(body, mesh_body)
+        .into_iter()
+        .filter_map(|(index, world)| Some((index, convex_contact(&convex, &Convex::triangle(world))?)))
+        .collect()
+}
+
+// Triangles of `mesh_body`'s mesh, in world space, that may be within
+// `CONTACT_MARGIN` of `body`.
+fn near_triangles(body: &RigidBody, mesh_body: &RigidBody) -> Vec<(u32, [uv::Vec3; 3])> {
+    let Shape::TriangleMesh(mesh) = &mesh_body.shape else {
+        return Vec::new();
+    };
+    // Work in the mesh's space to test the triangles' bounds cheaply.
+    let inverse = mesh_body.rotation.reversed();
+    let local_bounds = body
+        .aabb()
+        .expanded(CONTACT_MARGIN)
+        .transformed(inverse * -mesh_body.position, inverse);
+    let mut triangles = Vec::new();
+    for (index, triangle) in mesh.triangles.iter().enumerate() {
+        let local = triangle.map(|i| mesh.vertices[i as usize]);
+        let bounds = super::shape::Aabb::from_points(local);
+        if !bounds.overlaps(&local_bounds) {
+            continue;
+        }
+        triangles.push((index as u32, local.map(|v| mesh_body.rotation * v + mesh_body.position)));
+    }
+    triangles
+}
+
+// Whether two bodies overlap at all, however deeply, without working out
+// contacts. Meshes are only surfaces, so a shape wholly inside one doesn't
+// count.
+pub(crate) fn overlaps(a: &RigidBody, b: &RigidBody) -> bool {
+    let convex_overlap = |x: &Convex, y: &Convex| match gjk::gjk(x, y) {
+        Gjk::Separated(closest) => closest.distance < x.margin + y.margin,
+        Gjk::Overlapping(_) => true,
+    };
+    let (body, mesh_body) = match (&a.shape, &b.shape) {
+        (Shape::TriangleMesh(_), Shape::TriangleMesh(_)) => return false,
+        (Shape::TriangleMesh(_), _) => (b, a),
+        (_, Shape::TriangleMesh(_)) => (a, b),
+        _ => {
+            return match (
+                Convex::new(&a.shape, a.position, a.rotation),
+                Convex::new(&b.shape, b.position, b.rotation),
+            ) {
+                (Some(convex_a), Some(convex_b)) => convex_overlap(&convex_a, &convex_b),
+                _ => false,
+            };
+        }
+    };
+    let Some(convex) = Convex::new(&body.shape, body.position, body.rotation) else {
+        return false;
+    };
+    near_triangles(body, mesh_body)
+        .into_iter()
+        .any(|(_, world)| convex_overlap(&convex, &Convex::triangle(world)))
+}
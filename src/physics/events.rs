@@ -0,0 +1,323 @@
+// What gameplay hears about contacts: solid bodies starting, staying and
+// ending in contact, and bodies entering and leaving sensors.
+use std::collections::{BTreeMap, BTreeSet};
+
+use ultraviolet as uv;
+
+use super::body::BodyHandle;
+use super::contact::Manifold;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ContactReport {
+    pub point: uv::Vec3,
+    // From the first body to the second.
+    pub normal: uv::Vec3,
+    // Positive when the shapes overlap.
+    pub depth: f32,
+    // What the solver applied here in the last step, along the normal and
+    // across it.
+    pub normal_impulse: f32,
+    pub friction_impulse: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collision<T = BodyHandle> {
+    pub a: T,
+    pub b: T,
+    pub contacts: Vec<ContactReport>,
+}
+
+impl Collision {
+    pub(crate) fn new(a: BodyHandle, b: BodyHandle) -> Self {
+        Self {
+            a,
+            b,
+            contacts: Vec::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, manifold: &Manifold) {
+        self.contacts.extend(manifold.points.iter().map(|point| ContactReport {
+            point: point.point,
+            normal: manifold.normal,
+            depth: point.depth,
+            normal_impulse: point.normal_impulse,
+            friction_impulse: uv::Vec2::from(point.tangent_impulse).mag(),
+        }));
+    }
+}
+
+impl<T> Collision<T> {
+    // Push `a` gave `b` along the normals in the last step; `b` gave `a`
+    // the opposite. Friction isn't included.
+    pub fn impulse(&self) -> uv::Vec3 {
+        self.contacts
+            .iter()
+            .fold(uv::Vec3::zero(), |sum, contact| sum + contact.normal * contact.normal_impulse)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CollisionEvent<T = BodyHandle> {
+    Started(Collision<T>),
+    // Still touching at the end of the frame, with the latest contacts.
+    Stayed(Collision<T>),
+    Ended { a: T, b: T },
+    TriggerEntered { sensor: T, other: T },
+    TriggerExited { sensor: T, other: T },
+}
+
+impl<T> CollisionEvent<T> {
+    // The two sides, in the order the event names them.
+    pub fn pair(&self) -> (&T, &T) {
+        match self {
+            CollisionEvent::Started(collision) | CollisionEvent::Stayed(collision) => (&collision.a, &collision.b),
+            CollisionEvent::Ended { a, b } => (a, b),
+            CollisionEvent::TriggerEntered { sensor, other } | CollisionEvent::TriggerExited { sensor, other } => {
+                (sensor, other)
+            }
+        }
+    }
+
+    pub fn involves(&self, id: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        let (a, b) = self.pair();
+        a == id || b == id
+    }
+
+    // Swaps the ids for others, e.g. body handles for entities. None if
+    // either side has no counterpart.
+    pub fn map<U>(self, mut f: impl FnMut(T) -> Option<U>) -> Option<CollisionEvent<U>> {
+        let collision = |collision: Collision<T>, f: &mut dyn FnMut(T) -> Option<U>| {
+            Some(Collision {
+                a: f(collision.a)?,
+                b: f(collision.b)?,
+                contacts: collision.contacts,
+            })
+        };
+        Some(match self {
+            CollisionEvent::Started(c) => CollisionEvent::Started(collision(c, &mut f)?),
+            CollisionEvent::Stayed(c) => CollisionEvent::Stayed(collision(c, &mut f)?),
+            CollisionEvent::Ended { a, b } => CollisionEvent::Ended { a: f(a)?, b: f(b)? },
+            CollisionEvent::TriggerEntered { sensor, other } => CollisionEvent::TriggerEntered {
+                sensor: f(sensor)?,
+                other: f(other)?,
+            },
+            CollisionEvent::TriggerExited { sensor, other } => CollisionEvent::TriggerExited {
+                sensor: f(sensor)?,
+                other: f(other)?,
+            },
+        })
+    }
+}
+
+type Pair = (BodyHandle, BodyHandle);
+
+// Turns what touches after each step into events, kept by both worlds.
+#[derive(Debug, Default)]
+pub(crate) struct EventQueue {
+    // Solid pairs in contact and sensors overlapping, as of the last step.
+    touching: BTreeSet<Pair>,
+    overlapping: BTreeSet<Pair>,
+    events: Vec<CollisionEvent>,
+    // Pairs that went on touching this frame, reported as it ends.
+    stayed: BTreeMap<Pair, Collision>,
+    // Ends caused by removing bodies, reported with the next frame.
+    pending: Vec<CollisionEvent>,
+}
+
+impl EventQueue {
+    pub(crate) fn events(&self) -> &[CollisionEvent] {
+        &self.events
+    }
+
+    // Before the steps of a frame.
+    pub(crate) fn begin(&mut self) {
+        self.events.clear();
+        self.stayed.clear();
+        self.events.append(&mut self.pending);
+    }
+
+    // After them.
+    pub(crate) fn finish(&mut self) {
+        let stayed = std::mem::take(&mut self.stayed);
+        self.events.extend(stayed.into_values().map(CollisionEvent::Stayed));
+    }
+
+    // Compares what touches after a step's solve, so impulses are known,
+    // with what touched after the last one. `sensor_first` orders a trigger
+    // pair sensor first.
+    pub(crate) fn record(
+        &mut self,
+        touching: BTreeMap<Pair, Collision>,
+        overlapping: BTreeSet<Pair>,
+        sensor_first: impl Fn(Pair) -> Pair,
+    ) {
+        for &(a, b) in &self.touching {
+            if !touching.contains_key(&(a, b)) {
+                self.stayed.remove(&(a, b));
+                self.events.push(CollisionEvent::Ended { a, b });
+            }
+        }
+        let pairs = touching.keys().copied().collect();
+        for (pair, collision) in touching {
+            if self.touching.contains(&pair) {
+                self.stayed.insert(pair, collision);
+            } else {
+                self.events.push(CollisionEvent::Started(collision));
+            }
+        }
+        self.touching = pairs;
+
+        for &pair in self.overlapping.difference(&overlapping) {
+            let (sensor, other) = sensor_first(pair);
+            self.events.push(CollisionEvent::TriggerExited { sensor, other });
+        }
+        for &pair in overlapping.difference(&self.overlapping) {
+            let (sensor, other) = sensor_first(pair);
+            self.events.push(CollisionEvent::TriggerEntered { sensor, other });
+        }
+        self.overlapping = overlapping;
+    }
+
+    // Ends whatever `handle` was touching, reported with the next frame.
+    pub(crate) fn remove_body(&mut self, handle: BodyHandle, sensor_first: impl Fn(Pair) -> Pair) {
+        let involved = |(a, b): &Pair| *a == handle || *b == handle;
+        for (a, b) in self.touching.iter().copied().filter(involved) {
+            self.stayed.remove(&(a, b));
+            self.pending.push(CollisionEvent::Ended { a, b });
+        }
+        for pair in self.overlapping.iter().copied().filter(involved) {
+            let (sensor, other) = sensor_first(pair);
+            self.pending.push(CollisionEvent::TriggerExited { sensor, other });
+        }
+        self.touching.retain(|pair| !involved(pair));
+        self.overlapping.retain(|pair| !involved(pair));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::body::RigidBody;
+    use crate::physics::ray::Layers;
+    use crate::physics::shape::Shape;
+    use crate::physics::world::PhysicsWorld;
+
+    // A floor with its top at y = 0, added first so it is handle 0.
+    fn world() -> (PhysicsWorld, BodyHandle) {
+        let mut world = PhysicsWorld::new();
+        let floor = RigidBody::fixed(Shape::cuboid(uv::Vec3::new(5.0, 0.5, 5.0)));
+        let floor = world.add_body(floor.with_position(uv::Vec3::new(0.0, -0.5, 0.0)));
+        (world, floor)
+    }
+
+    fn ball(height: f32) -> RigidBody {
+        RigidBody::dynamic(Shape::sphere(0.25)).with_position(uv::Vec3::new(0.0, height, 0.0))
+    }
+
+    // A sensor box spanning y = 1 to 2.
+    fn sensor() -> RigidBody {
+        RigidBody::fixed(Shape::cuboid(uv::Vec3::new(1.0, 0.5, 1.0)))
+            .with_position(uv::Vec3::new(0.0, 1.5, 0.0))
+            .with_sensor(true)
+    }
+
+    // The last frame's events, without their contacts.
+    fn names(world: &PhysicsWorld) -> Vec<String> {
+        world
+            .events()
+            .iter()
+            .map(|event| {
+                let name = match event {
+                    CollisionEvent::Started(_) => "started",
+                    CollisionEvent::Stayed(_) => "stayed",
+                    CollisionEvent::Ended { .. } => "ended",
+                    CollisionEvent::TriggerEntered { .. } => "entered",
+                    CollisionEvent::TriggerExited { .. } => "exited",
+                };
+                let (a, b) = event.pair();
+                format!("{} {} {}", name, a.0, b.0)
+            })
+            .collect()
+    }
+
+    // Steps one frame at a time for `frames`, returning every frame's events
+    // that had any.
+    fn run(world: &mut PhysicsWorld, frames: usize) -> Vec<Vec<String>> {
+        (0..frames)
+            .filter_map(|_| {
+                world.step();
+                Some(names(world)).filter(|names| !names.is_empty())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn contacts_start_stay_and_end() {
+        let (mut world, _) = world();
+        let ball = world.add_body(ball(1.0));
+        let frames = run(&mut world, 60);
+        assert_eq!(frames[0], ["started 0 1"]);
+        assert!(frames[1..].iter().all(|frame| frame == &["stayed 0 1"]), "{:?}", frames);
+        let stayed = &world.events()[0];
+        assert!(matches!(stayed, CollisionEvent::Stayed(collision) if !collision.contacts.is_empty()));
+
+        // Lifted clear, the contact ends on the next step and then goes quiet.
+        world.body_mut(ball).unwrap().position.y = 3.0;
+        world.body_mut(ball).unwrap().linear_velocity = uv::Vec3::zero();
+        assert_eq!(run(&mut world, 1), [["ended 0 1"]]);
+        assert!(run(&mut world, 5).is_empty());
+    }
+
+    #[test]
+    fn stayed_events_come_last_in_a_frame() {
+        let (mut world, _) = world();
+        world.add_body(ball(0.25));
+        // Several steps in one frame: it lands in the first and stays in the
+        // rest.
+        world.update(world.timestep * 4.5);
+        assert_eq!(names(&world), ["started 0 1", "stayed 0 1"]);
+    }
+
+    #[test]
+    fn sensors_report_bodies_passing_through() {
+        let (mut world, _) = world();
+        world.add_body(sensor());
+        world.add_body(ball(2.5));
+        let frames = run(&mut world, 90);
+        let events: Vec<_> = frames.concat().into_iter().filter(|name| !name.starts_with("stayed")).collect();
+        assert_eq!(events, ["entered 1 2", "exited 1 2", "started 0 2"]);
+    }
+
+    #[test]
+    fn removing_a_body_ends_what_it_touched() {
+        let (mut world, _) = world();
+        // Tall enough that the ball rests inside it on the floor.
+        let sensor = RigidBody::fixed(Shape::cuboid(uv::Vec3::broadcast(1.0))).with_sensor(true);
+        world.add_body(sensor);
+        let ball = world.add_body(ball(0.25));
+        world.step();
+        assert_eq!(names(&world), ["started 0 2", "entered 1 2"]);
+
+        world.remove_body(ball);
+        assert_eq!(names(&world), ["started 0 2", "entered 1 2"]);
+        world.step();
+        assert_eq!(names(&world), ["ended 0 2", "exited 1 2"]);
+        world.step();
+        assert!(names(&world).is_empty());
+    }
+
+    #[test]
+    fn masked_out_bodies_report_nothing() {
+        let (mut world, floor) = world();
+        world.body_mut(floor).unwrap().collision_mask = Layers::layer(0);
+        world.add_body(sensor().with_collision_mask(Layers::layer(0)));
+        let ball = world.add_body(ball(2.5).with_layers(Layers::layer(1)));
+        assert!(run(&mut world, 60).is_empty());
+        // It fell through both.
+        assert!(world.body(ball).unwrap().position.y < -1.0);
+    }
+}
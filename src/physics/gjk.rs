@@ -0,0 +1,507 @@
+// GJK for the distance between convex shapes and EPA for their penetration
+// once they overlap. Spheres and capsules are run as a point or segment
+// with a margin, which keeps the common shallow contacts out of EPA.
+use ultraviolet as uv;
+
+use super::shape::Shape;
+
+const MAX_ITERATIONS: usize = 64;
+const TOLERANCE: f32 = 1e-6;
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum Core<'a> {
+    Point,
+    // Half height along local y.
+    Segment(f32),
+    Box(uv::Vec3),
+    Hull(&'a [uv::Vec3]),
+    // World space, the transform is ignored.
+    Triangle([uv::Vec3; 3]),
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Convex<'a> {
+    pub core: Core<'a>,
+    pub margin: f32,
+    pub position: uv::Vec3,
+    pub rotation: uv::Rotor3,
+}
+
+impl<'a> Convex<'a> {
+    // None for triangle meshes, which are collided triangle by triangle.
+    pub fn new(shape: &'a Shape, position: uv::Vec3, rotation: uv::Rotor3) -> Option<Self> {
+        let (core, margin) = match shape {
+            Shape::Sphere { radius } => (Core::Point, *radius),
+            Shape::Capsule { half_height, radius } => (Core::Segment(*half_height), *radius),
+            Shape::Box { half_extents } => (Core::Box(*half_extents), 0.0),
+            Shape::ConvexHull { points } => (Core::Hull(points), 0.0),
+            Shape::TriangleMesh(_) => return None,
+        };
+        Some(Self {
+            core,
+            margin,
+            position,
+            rotation,
+        })
+    }
+
+    pub fn triangle(vertices: [uv::Vec3; 3]) -> Self {
+        Self {
+            core: Core::Triangle(vertices),
+            margin: 0.0,
+            position: uv::Vec3::zero(),
+            rotation: uv::Rotor3::identity(),
+        }
+    }
+
+    pub fn core_support(&self, direction: uv::Vec3) -> uv::Vec3 {
+        if let Core::Triangle(vertices) = self.core {
+            return furthest(&vertices, direction);
+        }
+        let local = self.rotation.reversed() * direction;
+        let point = match self.core {
+            Core::Point => uv::Vec3::zero(),
+            Core::Segment(half_height) => uv::Vec3::new(0.0, half_height.copysign(local.y), 0.0),
+            Core::Box(half) => uv::Vec3::new(half.x.copysign(local.x), half.y.copysign(local.y), half.z.copysign(local.z)),
+            Core::Hull(points) => furthest(points, local),
+            Core::Triangle(_) => unreachable!(),
+        };
+        self.rotation * point + self.position
+    }
+
+    pub fn support(&self, direction: uv::Vec3) -> uv::Vec3 {
+        let point = self.core_support(direction);
+        if self.margin > 0.0 && direction.mag_sq() > 0.0 {
+            point + direction.normalized() * self.margin
+        } else {
+            point
+        }
+    }
+
+    fn center(&self) -> uv::Vec3 {
+        match self.core {
+            Core::Triangle([a, b, c]) => (a + b + c) / 3.0,
+            _ => self.position,
+        }
+    }
+}
+
+fn furthest(points: &[uv::Vec3], direction: uv::Vec3) -> uv::Vec3 {
+    points
+        .iter()
+        .copied()
+        .fold((f32::MIN, uv::Vec3::zero()), |best, p| {
+            let d = p.dot(direction);
+            if d > best.0 {
+                (d, p)
+            } else {
+                best
+            }
+        })
+        .1
+}
+
+// A vertex of the Minkowski difference a - b, remembering where it came from.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Vertex {
+    w: uv::Vec3,
+    a: uv::Vec3,
+    b: uv::Vec3,
+}
+
+fn support(a: &Convex, b: &Convex, direction: uv::Vec3, margins: bool) -> Vertex {
+    let (pa, pb) = if margins {
+        (a.support(direction), b.support(-direction))
+    } else {
+        (a.core_support(direction), b.core_support(-direction))
+    };
+    Vertex { w: pa - pb, a: pa, b: pb }
+}
+
+// Closest points between the cores of `a` and `b`.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Closest {
+    pub point_a: uv::Vec3,
+    pub point_b: uv::Vec3,
+    pub distance: f32,
+}
+
+pub(crate) enum Gjk {
+    Separated(Closest),
+    Overlapping(Vec<Vertex>),
+}
+
+pub(crate) fn gjk(a: &Convex, b: &Convex) -> Gjk {
+    let mut direction = a.center() - b.center();
+    if direction.mag_sq() < TOLERANCE {
+        direction = uv::Vec3::unit_x();
+    }
+    let mut simplex = vec![support(a, b, direction, false)];
+    let mut weights = vec![1.0];
+    let mut v = simplex[0].w;
+
+    for _ in 0..MAX_ITERATIONS {
+        let vv = v.mag_sq();
+        if vv <= TOLERANCE * TOLERANCE {
+            return Gjk::Overlapping(simplex);
+        }
+        let next = support(a, b, -v, false);
+        // No point further towards the origin, v is as close as it gets.
+        if vv - v.dot(next.w) <= 1e-6 * vv.max(1.0)
+            || simplex.iter().any(|s| (s.w - next.w).mag_sq() < TOLERANCE * TOLERANCE)
+        {
+            break;
+        }
+        simplex.push(next);
+        let (closest, new_weights) = closest_to_origin(&simplex);
+        if simplex.len() == 4 && new_weights.iter().all(|&w| w > 0.0) {
+            return Gjk::Overlapping(simplex);
+        }
+        // Drop the vertices that no longer support the closest point.
+        let mut kept = Vec::with_capacity(4);
+        weights.clear();
+        for (vertex, weight) in simplex.iter().zip(new_weights) {
+            if weight > 0.0 {
+                kept.push(*vertex);
+                weights.push(weight);
+            }
+        }
+        simplex = kept;
+        v = closest;
+    }
+
+    let point_a = simplex.iter().zip(&weights).fold(uv::Vec3::zero(), |p, (s, w)| p + s.a * *w);
+    let point_b = simplex.iter().zip(&weights).fold(uv::Vec3::zero(), |p, (s, w)| p + s.b * *w);
+    Gjk::Separated(Closest {
+        point_a,
+        point_b,
+        distance: v.mag(),
+    })
+}
+
+// Closest point of the simplex to the origin and its barycentric weights.
+fn closest_to_origin(simplex: &[Vertex]) -> (uv::Vec3, Vec<f32>) {
+    match simplex {
+        [a] => (a.w, vec![1.0]),
+        [a, b] => {
+            let (weights, point) = closest_on_segment(a.w, b.w);
+            (point, weights.to_vec())
+        }
+        [a, b, c] => {
+            let (weights, point) = closest_on_triangle(a.w, b.w, c.w);
+            (point, weights.to_vec())
+        }
+        [a, b, c, d] => closest_on_tetrahedron([a.w, b.w, c.w, d.w]),
+        _ => unreachable!("simplex of {} vertices", simplex.len()),
+    }
+}
+
+fn closest_on_segment(a: uv::Vec3, b: uv::Vec3) -> ([f32; 2], uv::Vec3) {
+    let ab = b - a;
+    let len = ab.mag_sq();
+    if len <= f32::EPSILON {
+        return ([1.0, 0.0], a);
+    }
+    let t = (-a.dot(ab) / len).clamp(0.0, 1.0);
+    ([1.0 - t, t], a + ab * t)
+}
+
+// Ericson, Real-Time Collision Detection 5.1.5, with the origin as the query
+// point.
+fn closest_on_triangle(a: uv::Vec3, b: uv::Vec3, c: uv::Vec3) -> ([f32; 3], uv::Vec3) {
+    let (ab, ac, ap) = (b - a, c - a, -a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return ([1.0, 0.0, 0.0], a);
+    }
+    let bp = -b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return ([0.0, 1.0, 0.0], b);
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return ([1.0 - v, v, 0.0], a + ab * v);
+    }
+    let cp = -c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return ([0.0, 0.0, 1.0], c);
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return ([1.0 - w, 0.0, w], a + ac * w);
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return ([0.0, 1.0 - w, w], b + (c - b) * w);
+    }
+    let denom = 1.0 / (va + vb + vc);
+    let (v, w) = (vb * denom, vc * denom);
+    ([1.0 - v - w, v, w], a + ab * v + ac * w)
+}
+
+fn closest_on_tetrahedron(points: [uv::Vec3; 4]) -> (uv::Vec3, Vec<f32>) {
+    const FACES: [[usize; 4]; 4] = [[0, 1, 2, 3], [0, 2, 3, 1], [0, 3, 1, 2], [1, 3, 2, 0]];
+    let mut best: Option<(f32, uv::Vec3, Vec<f32>)> = None;
+    let mut inside = true;
+    for [i, j, k, opposite] in FACES {
+        let (a, b, c) = (points[i], points[j], points[k]);
+        let normal = (b - a).cross(c - a);
+        let origin_side = -a.dot(normal);
+        let opposite_side = (points[opposite] - a).dot(normal);
+        // The origin is outside this face if it is on the other side from
+        // the fourth vertex. A flat tetrahedron has no inside.
+        if origin_side * opposite_side >= 0.0 && opposite_side.abs() > TOLERANCE {
+            continue;
+        }
+        inside = false;
+        let (face_weights, point) = closest_on_triangle(a, b, c);
+        let distance = point.mag_sq();
+        if best.as_ref().is_none_or(|(d, _, _)| distance < *d) {
+            let mut weights = vec![0.0; 4];
+            weights[i] = face_weights[0];
+            weights[j] = face_weights[1];
+            weights[k] = face_weights[2];
+            best = Some((distance, point, weights));
+        }
+    }
+    match best {
+        Some((_, point, weights)) if !inside => (point, weights),
+        _ => (uv::Vec3::zero(), vec![0.25; 4]),
+    }
+}
+
+// Penetration of overlapping shapes, margins included. `normal` points from
+// a to b and moving b by `normal * depth` separates them.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Penetration {
+    pub point_a: uv::Vec3,
+    pub point_b: uv::Vec3,
+    pub normal: uv::Vec3,
+    pub depth: f32,
+}
+
+pub(crate) fn epa(a: &Convex, b: &Convex, simplex: Vec<Vertex>) -> Option<Penetration> {
+    let mut vertices = simplex;
+    // Grow whatever GJK stopped with into a tetrahedron.
+    const AXES: [uv::Vec3; 6] = [
+        uv::Vec3::new(1.0, 0.0, 0.0),
+        uv::Vec3::new(-1.0, 0.0, 0.0),
+        uv::Vec3::new(0.0, 1.0, 0.0),
+        uv::Vec3::new(0.0, -1.0, 0.0),
+        uv::Vec3::new(0.0, 0.0, 1.0),
+        uv::Vec3::new(0.0, 0.0, -1.0),
+    ];
+    for axis in AXES {
+        if vertices.len() == 4 {
+            break;
+        }
+        let candidate = support(a, b, axis, true);
+        let independent = match vertices.as_slice() {
+            [] => true,
+            [p] => (candidate.w - p.w).mag_sq() > TOLERANCE,
+            [p, q] => (q.w - p.w).cross(candidate.w - p.w).mag_sq() > TOLERANCE,
+            [p, q, r] => (q.w - p.w).cross(r.w - p.w).dot(candidate.w - p.w).abs() > TOLERANCE,
+            _ => false,
+        };
+        if independent {
+            vertices.push(candidate);
+        }
+    }
+    if vertices.len() < 4 {
+        return None;
+    }
+
+    let mut faces: Vec<Face> = Vec::new();
+    for [i, j, k] in [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]] {
+        faces.push(Face::new(&vertices, i, j, k)?);
+    }
+
+    for _ in 0..MAX_ITERATIONS {
+        let closest = *faces.iter().min_by(|x, y| x.distance.total_cmp(&y.distance))?;
+        let next = support(a, b, closest.normal, true);
+        if next.w.dot(closest.normal) - closest.distance < 1e-4 {
+            return Some(penetration(&vertices, &closest));
+        }
+
+        // Replace every face the new vertex can see, stitching the hole
+        // along its horizon.
+        let mut horizon: Vec<(usize, usize)> = Vec::new();
+        faces.retain(|face| {
+            if face.normal.dot(next.w - vertices[face.indices[0]].w) <= 0.0 {
+                return true;
+            }
+            for edge in [
+                (face.indices[0], face.indices[1]),
+                (face.indices[1], face.indices[2]),
+                (face.indices[2], face.indices[0]),
+            ] {
+                match horizon.iter().position(|&(p, q)| p == edge.1 && q == edge.0) {
+                    Some(shared) => {
+                        horizon.swap_remove(shared);
+                    }
+                    None => horizon.push(edge),
+                }
+            }
+            false
+        });
+        vertices.push(next);
+        let index = vertices.len() - 1;
+        for (p, q) in horizon {
+            faces.push(Face::new(&vertices, p, q, index)?);
+        }
+        if faces.is_empty() {
+            return None;
+        }
+    }
+    let closest = *faces.iter().min_by(|x, y| x.distance.total_cmp(&y.distance))?;
+    Some(penetration(&vertices, &closest))
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Face {
+    indices: [usize; 3],
+    normal: uv::Vec3,
+    distance: f32,
+}
+
+impl Face {
+    // Winds the face so its normal points away from the origin.
+    fn new(vertices: &[Vertex], i: usize, j: usize, k: usize) -> Option<Face> {
+        let (a, b, c) = (vertices[i].w, vertices[j].w, vertices[k].w);
+        let normal = (b - a).cross(c - a);
+        let len = normal.mag();
+        if len <= f32::EPSILON {
+            return None;
+        }
+        let mut face = Face {
+            indices: [i, j, k],
+            normal: normal / len,
+            distance: a.dot(normal) / len,
+        };
+        if face.distance < 0.0 {
+            face.indices.swap(1, 2);
+            face.normal = -face.normal;
+            face.distance = -face.distance;
+        }
+        Some(face)
+    }
+}
+
+fn penetration(vertices: &[Vertex], face: &Face) -> Penetration {
+    let [i, j, k] = face.indices;
+    let (a, b, c) = (vertices[i].w, vertices[j].w, vertices[k].w);
+    let (weights, _) = closest_on_triangle(a - face.normal * face.distance, b - face.normal * face.distance, c - face.normal * face.distance);
+    let blend = |f: fn(&Vertex) -> uv::Vec3| f(&vertices[i]) * weights[0] + f(&vertices[j]) * weights[1] + f(&vertices[k]) * weights[2];
+    Penetration {
+        point_a: blend(|v| v.a),
+        point_b: blend(|v| v.b),
+        normal: face.normal,
+        depth: face.distance,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::contact::convex_contact;
+
+    fn cube() -> Shape {
+        Shape::cuboid(uv::Vec3::broadcast(0.5))
+    }
+
+    fn at(shape: &Shape, position: uv::Vec3) -> Convex<'_> {
+        Convex::new(shape, position, uv::Rotor3::identity()).unwrap()
+    }
+
+    fn separated(a: &Convex, b: &Convex) -> Closest {
+        match gjk(a, b) {
+            Gjk::Separated(closest) => closest,
+            Gjk::Overlapping(_) => panic!("expected a gap"),
+        }
+    }
+
+    fn penetrating(a: &Convex, b: &Convex) -> Penetration {
+        match gjk(a, b) {
+            Gjk::Overlapping(simplex) => epa(a, b, simplex).expect("a penetration"),
+            Gjk::Separated(closest) => panic!("expected an overlap, got {:?}", closest),
+        }
+    }
+
+    fn assert_close(actual: uv::Vec3, expected: uv::Vec3) {
+        assert!((actual - expected).mag() < 1e-4, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn boxes_apart_report_the_gap_between_faces() {
+        let shape = cube();
+        let (a, b) = (at(&shape, uv::Vec3::zero()), at(&shape, uv::Vec3::new(1.5, 0.2, 0.0)));
+        let closest = separated(&a, &b);
+        assert!((closest.distance - 0.5).abs() < 1e-5, "{}", closest.distance);
+        assert!((closest.point_a.x - 0.5).abs() < 1e-5 && (closest.point_b.x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn rotated_boxes_meet_at_their_corners() {
+        let shape = cube();
+        let turned = Convex::new(&shape, uv::Vec3::zero(), uv::Rotor3::from_rotation_xy(45f32.to_radians())).unwrap();
+        let closest = separated(&turned, &at(&shape, uv::Vec3::new(2.0, 0.0, 0.0)));
+        assert!((closest.distance - (1.5 - 0.5f32.sqrt())).abs() < 1e-5, "{}", closest.distance);
+        assert!((closest.point_a.x - 0.5f32.sqrt()).abs() < 1e-5 && closest.point_a.y.abs() < 1e-5);
+    }
+
+    #[test]
+    fn capsule_and_sphere_cores_are_a_segment_and_a_point() {
+        let capsule = Shape::capsule(1.0, 0.25);
+        let sphere = Shape::sphere(0.5);
+        let closest = separated(&at(&capsule, uv::Vec3::zero()), &at(&sphere, uv::Vec3::new(2.0, 3.0, 0.0)));
+        assert!((closest.distance - 8f32.sqrt()).abs() < 1e-5, "{}", closest.distance);
+        assert_close(closest.point_a, uv::Vec3::new(0.0, 1.0, 0.0));
+
+        // Beside the segment the closest point is across from it.
+        let closest = separated(&at(&capsule, uv::Vec3::zero()), &at(&sphere, uv::Vec3::new(2.0, 0.5, 0.0)));
+        assert_close(closest.point_a, uv::Vec3::new(0.0, 0.5, 0.0));
+
+        // Margins included, they overlap by 0.25.
+        let (a, b) = (at(&capsule, uv::Vec3::zero()), at(&sphere, uv::Vec3::new(0.5, 0.0, 0.0)));
+        let contact = convex_contact(&a, &b).unwrap();
+        assert!((contact.depth - 0.25).abs() < 1e-5, "{}", contact.depth);
+        assert_close(contact.normal, uv::Vec3::unit_x());
+    }
+
+    #[test]
+    fn overlapping_boxes_push_apart_along_the_shallowest_axis() {
+        let shape = cube();
+        let penetration = penetrating(&at(&shape, uv::Vec3::zero()), &at(&shape, uv::Vec3::new(0.9, 0.3, 0.1)));
+        assert!((penetration.depth - 0.1).abs() < 1e-4, "{}", penetration.depth);
+        assert_close(penetration.normal, uv::Vec3::unit_x());
+
+        let penetration = penetrating(&at(&shape, uv::Vec3::zero()), &at(&shape, uv::Vec3::new(0.2, -0.7, 0.0)));
+        assert!((penetration.depth - 0.3).abs() < 1e-4, "{}", penetration.depth);
+        assert_close(penetration.normal, -uv::Vec3::unit_y());
+    }
+
+    #[test]
+    fn hulls_sink_into_boxes_point_first() {
+        // A pyramid pointing down, its tip 0.2 into the top of the cube.
+        let pyramid = Shape::convex_hull(vec![
+            uv::Vec3::new(0.0, -0.5, 0.0),
+            uv::Vec3::new(-0.5, 0.5, -0.5),
+            uv::Vec3::new(0.5, 0.5, -0.5),
+            uv::Vec3::new(0.5, 0.5, 0.5),
+            uv::Vec3::new(-0.5, 0.5, 0.5),
+        ]);
+        let shape = cube();
+        let penetration = penetrating(&at(&shape, uv::Vec3::zero()), &at(&pyramid, uv::Vec3::new(0.1, 0.8, 0.0)));
+        assert!((penetration.depth - 0.2).abs() < 1e-4, "{}", penetration.depth);
+        assert_close(penetration.normal, uv::Vec3::unit_y());
+        assert_close(penetration.point_b, uv::Vec3::new(0.1, 0.3, 0.0));
+
+        let closest = separated(&at(&shape, uv::Vec3::zero()), &at(&pyramid, uv::Vec3::new(0.1, 1.25, 0.0)));
+        assert!((closest.distance - 0.25).abs() < 1e-5, "{}", closest.distance);
+    }
+}
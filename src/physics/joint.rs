@@ -0,0 +1,622 @@
+// Joints hold two bodies together: rigidly, at a shared point, about a
+// hinge, along a slider or a set distance apart. They're solved alongside
+// the contacts with the same sequential impulses, one row per degree of
+// freedom taken away.
+use std::f32::consts::TAU;
+
+use ultraviolet as uv;
+
+use super::body::{BodyHandle, RigidBody};
+use super::solver::{self, SolverBody};
+
+// Fraction of a joint's drift corrected per step.
+const JOINT_BAUMGARTE: f32 = 0.2;
+// Impulse slots kept between steps: three linear, three angular, the lower
+// and upper limits and the motor.
+const ROWS: usize = 9;
+const LOWER: usize = 6;
+const UPPER: usize = 7;
+const MOTOR: usize = 8;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JointHandle(pub(crate) usize);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Motor {
+    // Relative speed driven towards, in radians or units per second.
+    pub speed: f32,
+    // Strongest torque or force it uses to get there.
+    pub max_force: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Spring {
+    // Oscillations per second.
+    pub frequency: f32,
+    // 1 settles as fast as possible without overshooting, 0 never settles.
+    pub damping_ratio: f32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum JointKind {
+    Fixed,
+    BallSocket,
+    // Turns about the axis only, between `limits` in radians if given.
+    Hinge {
+        limits: Option<(f32, f32)>,
+        motor: Option<Motor>,
+    },
+    // Slides along the axis only, without turning.
+    Slider {
+        limits: Option<(f32, f32)>,
+        motor: Option<Motor>,
+    },
+    // Keeps the anchors `length` apart, rigidly or with a spring.
+    Distance {
+        length: f32,
+        spring: Option<Spring>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Joint {
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+    pub kind: JointKind,
+    // Breaks once holding against more than these, in newtons and
+    // newton-metres.
+    pub break_force: f32,
+    pub break_torque: f32,
+    // Whether the joined bodies still collide with each other.
+    pub collide_connected: bool,
+    // World space as the joint is added; by default `body_b`'s position.
+    anchor_a: Option<uv::Vec3>,
+    anchor_b: Option<uv::Vec3>,
+    axis: uv::Vec3,
+    // The same, in each body's space, from when the joint was added.
+    local_anchor_a: uv::Vec3,
+    local_anchor_b: uv::Vec3,
+    local_axis_a: uv::Vec3,
+    local_axis_b: uv::Vec3,
+    // Across the axis, for measuring hinge angles.
+    local_normal_a: uv::Vec3,
+    local_normal_b: uv::Vec3,
+    // `body_b`'s rotation relative to `body_a`'s that is held.
+    reference: uv::Rotor3,
+    impulses: [f32; ROWS],
+    broken: bool,
+}
+
+impl Joint {
+    pub fn new(kind: JointKind, body_a: BodyHandle, body_b: BodyHandle) -> Self {
+        Self {
+            body_a,
+            body_b,
+            kind,
+            break_force: f32::INFINITY,
+            break_torque: f32::INFINITY,
+            collide_connected: false,
+            anchor_a: None,
+            anchor_b: None,
+            axis: uv::Vec3::unit_y(),
+            local_anchor_a: uv::Vec3::zero(),
+            local_anchor_b: uv::Vec3::zero(),
+            local_axis_a: uv::Vec3::unit_y(),
+            local_axis_b: uv::Vec3::unit_y(),
+            local_normal_a: uv::Vec3::unit_x(),
+            local_normal_b: uv::Vec3::unit_x(),
+            reference: uv::Rotor3::identity(),
+            impulses: [0.0; ROWS],
+            broken: false,
+        }
+    }
+
+    pub fn fixed(body_a: BodyHandle, body_b: BodyHandle) -> Self {
+        Self::new(JointKind::Fixed, body_a, body_b)
+    }
+
+    pub fn ball_socket(body_a: BodyHandle, body_b: BodyHandle) -> Self {
+        Self::new(JointKind::BallSocket, body_a, body_b)
+    }
+
+    pub fn hinge(body_a: BodyHandle, body_b: BodyHandle) -> Self {
+        Self::new(
+            JointKind::Hinge {
+                limits: None,
+                motor: None,
+            },
+            body_a,
+            body_b,
+        )
+    }
+
+    pub fn slider(body_a: BodyHandle, body_b: BodyHandle) -> Self {
+        Self::new(
+            JointKind::Slider {
+                limits: None,
+                motor: None,
+            },
+            body_a,
+            body_b,
+        )
+    }
+
+    pub fn distance(body_a: BodyHandle, body_b: BodyHandle, length: f32) -> Self {
+        Self::new(JointKind::Distance { length, spring: None }, body_a, body_b)
+    }
+
+    // Where the bodies are joined, in world space as the joint is added.
+    pub fn with_anchor(mut self, anchor: uv::Vec3) -> Self {
+        self.anchor_a = Some(anchor);
+        self.anchor_b = Some(anchor);
+        self
+    }
+
+    // Separate anchors on each body, for distance joints.
+    pub fn with_anchors(mut self, anchor_a: uv::Vec3, anchor_b: uv::Vec3) -> Self {
+        self.anchor_a = Some(anchor_a);
+        self.anchor_b = Some(anchor_b);
+        self
+    }
+
+    // Hinge or slider axis, in world space as the joint is added.
+    pub fn with_axis(mut self, axis: uv::Vec3) -> Self {
+        self.axis = axis.normalized();
+        self
+    }
+
+    // Hinge angles or slider offsets allowed. Ignored by other joints.
+    pub fn with_limits(mut self, lower: f32, upper: f32) -> Self {
+        if let JointKind::Hinge { limits, .. } | JointKind::Slider { limits, .. } = &mut self.kind {
+            *limits = Some((lower, upper));
+        }
+        self
+    }
+
+    // Drives a hinge or slider. Ignored by other joints.
+    pub fn with_motor(mut self, speed: f32, max_force: f32) -> Self {
+        if let JointKind::Hinge { motor, .. } | JointKind::Slider { motor, .. } = &mut self.kind {
+            *motor = Some(Motor { speed, max_force });
+        }
+        self
+    }
+
+    // Makes a distance joint springy. Ignored by other joints.
+    pub fn with_spring(mut self, frequency: f32, damping_ratio: f32) -> Self {
+        if let JointKind::Distance { spring, .. } = &mut self.kind {
+            *spring = Some(Spring {
+                frequency,
+                damping_ratio,
+            });
+        }
+        self
+    }
+
+    pub fn with_break_force(mut self, break_force: f32) -> Self {
+        self.break_force = break_force;
+        self
+    }
+
+    pub fn with_break_torque(mut self, break_torque: f32) -> Self {
+        self.break_torque = break_torque;
+        self
+    }
+
+    pub fn with_collide_connected(mut self, collide_connected: bool) -> Self {
+        self.collide_connected = collide_connected;
+        self
+    }
+
+    // A broken joint no longer holds; it stays until removed.
+    pub fn is_broken(&self) -> bool {
+        self.broken
+    }
+
+    // Makes a broken joint hold again, pulling the bodies back into place.
+    pub fn repair(&mut self) {
+        self.broken = false;
+        self.impulses = [0.0; ROWS];
+    }
+
+    // Takes the joint's frame from where the bodies are now.
+    pub(crate) fn attach(&mut self, a: &RigidBody, b: &RigidBody) {
+        let to_local = |body: &RigidBody, point: uv::Vec3| body.rotation.reversed() * (point - body.position);
+        self.local_anchor_a = to_local(a, self.anchor_a.unwrap_or(b.position));
+        self.local_anchor_b = to_local(b, self.anchor_b.unwrap_or(b.position));
+        self.local_axis_a = a.rotation.reversed() * self.axis;
+        self.local_axis_b = b.rotation.reversed() * self.axis;
+        let normal = solver::tangents(self.axis)[0];
+        self.local_normal_a = a.rotation.reversed() * normal;
+        self.local_normal_b = b.rotation.reversed() * normal;
+        self.reference = a.rotation.reversed() * b.rotation;
+    }
+
+    pub fn world_anchors(&self, a: &RigidBody, b: &RigidBody) -> (uv::Vec3, uv::Vec3) {
+        (
+            a.rotation * self.local_anchor_a + a.position,
+            b.rotation * self.local_anchor_b + b.position,
+        )
+    }
+
+    // Hinge angle in radians, slider offset or distance between the
+    // anchors; None for joints without one.
+    pub fn position(&self, a: &RigidBody, b: &RigidBody) -> Option<f32> {
+        let (pa, pb) = self.world_anchors(a, b);
+        match self.kind {
+            JointKind::Hinge { .. } => Some(self.angle(a, b)),
+            JointKind::Slider { .. } => Some((pb - pa).dot(a.rotation * self.local_axis_a)),
+            JointKind::Distance { .. } => Some((pb - pa).mag()),
+            JointKind::Fixed | JointKind::BallSocket => None,
+        }
+    }
+
+    fn angle(&self, a: &RigidBody, b: &RigidBody) -> f32 {
+        let axis = a.rotation * self.local_axis_a;
+        let (na, nb) = (a.rotation * self.local_normal_a, b.rotation * self.local_normal_b);
+        na.cross(nb).dot(axis).atan2(na.dot(nb))
+    }
+
+    // The small rotation taking where `body_b` should be turned to where it
+    // is, as an axis scaled by the angle.
+    fn rotation_error(&self, a: &RigidBody, b: &RigidBody) -> uv::Vec3 {
+        let target = a.rotation * self.reference;
+        let axes = [uv::Vec3::unit_x(), uv::Vec3::unit_y(), uv::Vec3::unit_z()];
+        axes.iter()
+            .fold(uv::Vec3::zero(), |sum, &axis| sum + (target * axis).cross(b.rotation * axis))
+            * 0.5
+    }
+}
+
+// One degree of freedom, driving `linear . (v_b - v_a) + angular_b . w_b -
+// angular_a . w_a + bias` to zero with the accumulated impulse kept within
+// `lower..upper`.
+#[derive(Debug, Copy, Clone)]
+struct Row {
+    slot: usize,
+    linear: uv::Vec3,
+    angular_a: uv::Vec3,
+    angular_b: uv::Vec3,
+    mass: f32,
+    bias: f32,
+    // A spring holds the row with its error in `bias` until it's softened
+    // for the mass it moves.
+    spring: Option<Spring>,
+    gamma: f32,
+    lower: f32,
+    upper: f32,
+}
+
+impl Row {
+    fn new(slot: usize, linear: uv::Vec3, angular_a: uv::Vec3, angular_b: uv::Vec3) -> Self {
+        Self {
+            slot,
+            linear,
+            angular_a,
+            angular_b,
+            mass: 0.0,
+            bias: 0.0,
+            spring: None,
+            gamma: 0.0,
+            lower: f32::NEG_INFINITY,
+            upper: f32::INFINITY,
+        }
+    }
+
+    fn point(slot: usize, direction: uv::Vec3, r_a: uv::Vec3, r_b: uv::Vec3) -> Self {
+        Self::new(slot, direction, r_a.cross(direction), r_b.cross(direction))
+    }
+
+    fn angular(slot: usize, axis: uv::Vec3) -> Self {
+        Self::new(slot, uv::Vec3::zero(), axis, axis)
+    }
+
+    fn negated(self) -> Self {
+        Self {
+            linear: -self.linear,
+            angular_a: -self.angular_a,
+            angular_b: -self.angular_b,
+            ..self
+        }
+    }
+
+    fn inv_mass(&self, a: &SolverBody, b: &SolverBody) -> f32 {
+        (a.inv_mass + b.inv_mass) * self.linear.mag_sq()
+            + (a.inv_inertia * self.angular_a).dot(self.angular_a)
+            + (b.inv_inertia * self.angular_b).dot(self.angular_b)
+    }
+
+    // Holds the error `error` rigidly, correcting a fraction per step.
+    fn rigid(mut self, error: f32, dt: f32) -> Self {
+        self.bias = JOINT_BAUMGARTE / dt * error;
+        self
+    }
+
+    // Keeps `distance` from going below zero: it may close the gap but no
+    // more, and is pushed back out once past it.
+    fn limit(mut self, distance: f32, dt: f32) -> Self {
+        self.bias = if distance > 0.0 {
+            distance / dt
+        } else {
+            JOINT_BAUMGARTE / dt * distance
+        };
+        self.lower = 0.0;
+        self
+    }
+
+    fn motor(mut self, motor: Motor, dt: f32) -> Self {
+        self.bias = -motor.speed;
+        self.upper = motor.max_force * dt;
+        self.lower = -self.upper;
+        self
+    }
+
+    fn velocity(&self, a: &SolverBody, b: &SolverBody) -> f32 {
+        self.linear.dot(b.linear - a.linear) + self.angular_b.dot(b.angular) - self.angular_a.dot(a.angular)
+    }
+
+    fn apply(&self, impulse: f32, a: &mut SolverBody, b: &mut SolverBody) {
+        a.linear -= self.linear * (impulse * a.inv_mass);
+        a.angular -= a.inv_inertia * self.angular_a * impulse;
+        b.linear += self.linear * (impulse * b.inv_mass);
+        b.angular += b.inv_inertia * self.angular_b * impulse;
+    }
+}
+
+fn rows(joint: &Joint, a: &RigidBody, b: &RigidBody, dt: f32) -> Vec<Row> {
+    let (pa, pb) = joint.world_anchors(a, b);
+    let (r_a, r_b) = (pa - a.position, pb - b.position);
+    let d = pb - pa;
+    let world_axes = [uv::Vec3::unit_x(), uv::Vec3::unit_y(), uv::Vec3::unit_z()];
+    let mut rows = Vec::new();
+    let point = |rows: &mut Vec<Row>| {
+        for (slot, axis) in world_axes.into_iter().enumerate() {
+            rows.push(Row::point(slot, axis, r_a, r_b).rigid(d.dot(axis), dt));
+        }
+    };
+    let orientation = |rows: &mut Vec<Row>| {
+        let error = joint.rotation_error(a, b);
+        for (slot, axis) in world_axes.into_iter().enumerate() {
+            rows.push(Row::angular(3 + slot, axis).rigid(error.dot(axis), dt));
+        }
+    };
+    // Limits and motor along one row, with its current position.
+    let ranged = |rows: &mut Vec<Row>, row: Row, position: f32, limits: Option<(f32, f32)>, motor: Option<Motor>| {
+        if let Some((lower, upper)) = limits {
+            rows.push(Row { slot: LOWER, ..row }.limit(position - lower, dt));
+            rows.push(Row { slot: UPPER, ..row }.negated().limit(upper - position, dt));
+        }
+        if let Some(motor) = motor {
+            rows.push(Row { slot: MOTOR, ..row }.motor(motor, dt));
+        }
+    };
+    match joint.kind {
+        JointKind::Fixed => {
+            point(&mut rows);
+            orientation(&mut rows);
+        }
+        JointKind::BallSocket => point(&mut rows),
+        JointKind::Hinge { limits, motor } => {
+            point(&mut rows);
+            let axis_a = a.rotation * joint.local_axis_a;
+            let axis_b = b.rotation * joint.local_axis_b;
+            let error = axis_a.cross(axis_b);
+            for (slot, across) in solver::tangents(axis_a).into_iter().enumerate() {
+                rows.push(Row::angular(3 + slot, across).rigid(error.dot(across), dt));
+            }
+            ranged(&mut rows, Row::angular(0, axis_a), joint.angle(a, b), limits, motor);
+        }
+        JointKind::Slider { limits, motor } => {
+            orientation(&mut rows);
+            let axis = a.rotation * joint.local_axis_a;
+            // Measured from `body_a` to the anchor on `body_b`, so turning
+            // `body_a` sweeps the slot along.
+            let r_a = pb - a.position;
+            for (slot, across) in solver::tangents(axis).into_iter().enumerate() {
+                rows.push(Row::point(slot, across, r_a, r_b).rigid(d.dot(across), dt));
+            }
+            ranged(&mut rows, Row::point(0, axis, r_a, r_b), d.dot(axis), limits, motor);
+        }
+        JointKind::Distance { length, spring } => {
+            let current = d.mag();
+            let direction = if current > 1e-6 {
+                d / current
+            } else {
+                a.rotation * joint.local_axis_a
+            };
+            let row = Row::point(0, direction, r_a, r_b);
+            rows.push(match spring {
+                Some(spring) => Row {
+                    bias: current - length,
+                    spring: Some(spring),
+                    ..row
+                },
+                None => row.rigid(current - length, dt),
+            });
+        }
+    }
+    rows
+}
+
+struct JointRows {
+    joint: usize,
+    a: usize,
+    b: usize,
+    rows: Vec<Row>,
+    impulses: Vec<f32>,
+}
+
+pub(crate) struct JointSolver {
+    joints: Vec<JointRows>,
+}
+
+impl JointSolver {
+    // `joints` are (slot, joint, body a slot, body b slot). Applies last
+    // step's impulses as a starting guess.
+    pub fn new(
+        joints: &[(usize, &Joint, usize, usize)],
+        rigid_bodies: &[Option<RigidBody>],
+        bodies: &mut [SolverBody],
+        dt: f32,
+    ) -> Self {
+        let mut solved = Vec::with_capacity(joints.len());
+        for &(index, joint, a, b) in joints {
+            let (Some(body_a), Some(body_b)) = (&rigid_bodies[a], &rigid_bodies[b]) else {
+                continue;
+            };
+            let mut rows = rows(joint, body_a, body_b, dt);
+            let mut impulses = Vec::with_capacity(rows.len());
+            for row in &mut rows {
+                let k = row.inv_mass(&bodies[a], &bodies[b]);
+                if let Some(spring) = row.spring {
+                    // The spring's stiffness and damping for the mass it
+                    // moves, folded into a bias and softness.
+                    let mass = if k > 0.0 { 1.0 / k } else { 0.0 };
+                    let omega = TAU * spring.frequency;
+                    let damping = 2.0 * mass * spring.damping_ratio * omega;
+                    let stiffness = mass * omega * omega;
+                    let denominator = dt * (damping + dt * stiffness);
+                    row.gamma = if denominator > 0.0 { 1.0 / denominator } else { 0.0 };
+                    row.bias *= dt * stiffness * row.gamma;
+                }
+                let k = k + row.gamma;
+                row.mass = if k > 0.0 { 1.0 / k } else { 0.0 };
+                let impulse = joint.impulses[row.slot].clamp(row.lower, row.upper);
+                let (body_a, body_b) = pair(bodies, a, b);
+                row.apply(impulse, body_a, body_b);
+                impulses.push(impulse);
+            }
+            solved.push(JointRows {
+                joint: index,
+                a,
+                b,
+                rows,
+                impulses,
+            });
+        }
+        Self { joints: solved }
+    }
+
+    pub fn solve(&mut self, bodies: &mut [SolverBody]) {
+        for joint in &mut self.joints {
+            let (a, b) = pair(bodies, joint.a, joint.b);
+            for (row, accumulated) in joint.rows.iter().zip(&mut joint.impulses) {
+                let lambda = -row.mass * (row.velocity(a, b) + row.bias + row.gamma * *accumulated);
+                let total = (*accumulated + lambda).clamp(row.lower, row.upper);
+                row.apply(total - *accumulated, a, b);
+                *accumulated = total;
+            }
+        }
+    }
+
+    // Keeps the impulses for warm starting and breaks joints pushed too
+    // hard.
+    pub fn finish(self, joints: &mut [Option<Joint>], dt: f32) {
+        for solved in self.joints {
+            let Some(joint) = joints[solved.joint].as_mut() else {
+                continue;
+            };
+            joint.impulses = [0.0; ROWS];
+            let (mut force, mut torque) = (uv::Vec3::zero(), uv::Vec3::zero());
+            for (row, impulse) in solved.rows.iter().zip(solved.impulses) {
+                joint.impulses[row.slot] = impulse;
+                if row.linear == uv::Vec3::zero() {
+                    torque += row.angular_b * impulse;
+                } else {
+                    force += row.linear * impulse;
+                }
+            }
+            if force.mag() > joint.break_force * dt || torque.mag() > joint.break_torque * dt {
+                joint.broken = true;
+                joint.impulses = [0.0; ROWS];
+            }
+        }
+    }
+}
+
+// Both bodies of a pair, mutably. Joints between a body and itself are
+// refused when added, so the slots differ.
+fn pair(bodies: &mut [SolverBody], a: usize, b: usize) -> (&mut SolverBody, &mut SolverBody) {
+    if a < b {
+        let (low, high) = bodies.split_at_mut(b);
+        (&mut low[a], &mut high[0])
+    } else {
+        let (low, high) = bodies.split_at_mut(a);
+        (&mut high[0], &mut low[b])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::shape::Shape;
+    use crate::physics::world::{PhysicsWorld, DEFAULT_TIMESTEP};
+
+    const GRAVITY: f32 = 9.81;
+
+    // A small ball hanging `length` below a fixed pivot at the origin,
+    // pulled `angle` radians to the side.
+    fn pendulum(length: f32, angle: f32) -> (PhysicsWorld, BodyHandle, Joint) {
+        let mut world = PhysicsWorld::new().with_gravity(uv::Vec3::new(0.0, -GRAVITY, 0.0));
+        let pivot = world.add_body(RigidBody::fixed(Shape::sphere(0.05)));
+        let position = uv::Vec3::new(length * angle.sin(), -length * angle.cos(), 0.0);
+        let mut bob = RigidBody::dynamic(Shape::sphere(0.05)).with_position(position);
+        bob.linear_damping = 0.0;
+        bob.angular_damping = 0.0;
+        let bob = world.add_body(bob);
+        let joint = Joint::distance(pivot, bob, length).with_anchors(uv::Vec3::zero(), position);
+        (world, bob, joint)
+    }
+
+    #[test]
+    fn pendulum_swings_with_the_small_angle_period() {
+        let length = 1.0;
+        let (mut world, bob, joint) = pendulum(length, 0.1);
+        world.add_joint(joint).unwrap();
+
+        // Times the bob crosses under the pivot, between steps.
+        let mut crossings = Vec::new();
+        let mut last_x = world.body(bob).unwrap().position.x;
+        for step in 1..=600 {
+            world.step();
+            let position = world.body(bob).unwrap().position;
+            assert!((position.mag() - length).abs() < 0.01, "length drifted to {}", position.mag());
+            if last_x.signum() != position.x.signum() {
+                let t = last_x / (last_x - position.x);
+                crossings.push((step as f32 - 1.0 + t) * DEFAULT_TIMESTEP);
+            }
+            last_x = position.x;
+        }
+
+        // Every other crossing is a full swing.
+        assert!(crossings.len() >= 8, "{:?}", crossings);
+        let period = (crossings[crossings.len() - 1] - crossings[0]) / (crossings.len() - 1) as f32 * 2.0;
+        let expected = TAU * (length / GRAVITY).sqrt();
+        assert!((period - expected).abs() < expected * 0.03, "period {} != {}", period, expected);
+    }
+
+    #[test]
+    fn joint_breaks_under_more_than_its_break_force() {
+        let (mut world, bob, joint) = pendulum(1.0, 0.0);
+        let weight = world.body(bob).unwrap().mass() * GRAVITY;
+        let handle = world.add_joint(joint.with_break_force(weight * 2.0)).unwrap();
+
+        // Hanging still it only carries its own weight.
+        for _ in 0..60 {
+            world.step();
+        }
+        assert!(!world.joint(handle).unwrap().is_broken());
+        assert!((world.body(bob).unwrap().position.y + 1.0).abs() < 0.01);
+
+        // Pulled down with three times its weight on top, it lets go and
+        // the bob falls away.
+        for _ in 0..5 {
+            world.body_mut(bob).unwrap().apply_force(uv::Vec3::new(0.0, -weight * 3.0, 0.0));
+            world.step();
+        }
+        assert!(world.joint(handle).unwrap().is_broken());
+        for _ in 0..30 {
+            world.step();
+        }
+        assert!(world.body(bob).unwrap().position.y < -1.5);
+    }
+}
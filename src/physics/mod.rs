@@ -0,0 +1,14 @@
+pub mod shape;
+pub mod body;
+pub mod gjk;
+pub mod contact;
+pub mod solver;
+pub mod world;
+pub mod ray;
+pub mod bvh;
+pub mod spatial_hash;
+pub mod picking;
+pub mod cast;
+pub mod character;
+pub mod events;
+pub mod joint;
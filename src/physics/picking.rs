@@ -0,0 +1,131 @@
+// Shapes for picking with rays, kept apart from the physics world so things
+// can be clickable without colliding. Each target carries its own data, such
+// as the entity it stands for.
+use ultraviolet as uv;
+
+use super::bvh::{DynamicBvh, ProxyId};
+use super::ray::{Layers, Ray, RayHit};
+use super::shape::{Aabb, Shape};
+
+#[derive(Debug, Clone)]
+pub struct PickTarget<T> {
+    pub shape: Shape,
+    pub position: uv::Vec3,
+    pub rotation: uv::Rotor3,
+    pub layers: Layers,
+    pub data: T,
+}
+
+impl<T> PickTarget<T> {
+    pub fn new(shape: Shape, data: T) -> Self {
+        Self {
+            shape,
+            position: uv::Vec3::zero(),
+            rotation: uv::Rotor3::identity(),
+            layers: Layers::ALL,
+            data,
+        }
+    }
+
+    pub fn with_position(mut self, position: uv::Vec3) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: uv::Rotor3) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_layers(mut self, layers: Layers) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        self.shape.aabb(self.position, self.rotation)
+    }
+
+    pub fn raycast(&self, ray: &Ray, max_distance: f32) -> Option<RayHit> {
+        self.shape.raycast(self.position, self.rotation, ray, max_distance)
+    }
+}
+
+pub struct PickScene<T> {
+    targets: DynamicBvh<PickTarget<T>>,
+}
+
+impl<T> Default for PickScene<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> PickScene<T> {
+    pub fn new() -> Self {
+        Self {
+            targets: DynamicBvh::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.targets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    pub fn insert(&mut self, target: PickTarget<T>) -> ProxyId {
+        self.targets.insert(target.aabb(), target)
+    }
+
+    pub fn remove(&mut self, id: ProxyId) -> Option<PickTarget<T>> {
+        self.targets.remove(id)
+    }
+
+    // Swaps in a new shape, placement or data. False if `id` is gone.
+    pub fn set(&mut self, id: ProxyId, target: PickTarget<T>) -> bool {
+        let aabb = target.aabb();
+        let Some(slot) = self.targets.get_mut(id) else {
+            return false;
+        };
+        *slot = target;
+        self.targets.update(id, aabb);
+        true
+    }
+
+    pub fn set_transform(&mut self, id: ProxyId, position: uv::Vec3, rotation: uv::Rotor3) -> bool {
+        let Some(target) = self.targets.get_mut(id) else {
+            return false;
+        };
+        target.position = position;
+        target.rotation = rotation;
+        let aabb = target.aabb();
+        self.targets.update(id, aabb);
+        true
+    }
+
+    pub fn get(&self, id: ProxyId) -> Option<&PickTarget<T>> {
+        self.targets.get(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ProxyId, &PickTarget<T>)> {
+        self.targets.iter()
+    }
+
+    // Nearest target on `mask` that `ray` hits.
+    pub fn raycast(&self, ray: &Ray, max_distance: f32, mask: Layers) -> Option<(ProxyId, RayHit)> {
+        let hit = |target: &PickTarget<T>| {
+            if target.layers.intersects(mask) {
+                target.raycast(ray, max_distance)
+            } else {
+                None
+            }
+        };
+        let (id, _) = self
+            .targets
+            .raycast(ray, max_distance, |_, target| hit(target).map(|hit| hit.distance))?;
+        Some((id, hit(self.targets.get(id)?)?))
+    }
+}
@@ -0,0 +1,474 @@
+// Ray queries against shapes. Solids (everything but triangle meshes) hit a
+// ray that starts inside them at distance 0, with the normal facing back
+// along the ray; meshes are surfaces and only hit where a triangle is crossed.
+use std::ops::BitOr;
+
+use ultraviolet as uv;
+
+use super::gjk::{self, Convex, Core, Gjk};
+use super::shape::{Aabb, Shape, TriMesh};
+
+const HULL_TOLERANCE: f32 = 1e-4;
+const HULL_ITERATIONS: usize = 64;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: uv::Vec3,
+    // Unit length, so distances along the ray are in world units.
+    pub direction: uv::Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: uv::Vec3, direction: uv::Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalized(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> uv::Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    // The same ray in the space of a body at `position` and `rotation`.
+    pub fn to_local(&self, position: uv::Vec3, rotation: uv::Rotor3) -> Ray {
+        let inverse = rotation.reversed();
+        Ray {
+            origin: inverse * (self.origin - position),
+            direction: inverse * self.direction,
+        }
+    }
+}
+
+// Bit set of up to 32 layers. Targets are on some layers and queries see
+// the targets whose layers intersect their mask.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Layers(pub u32);
+
+impl Layers {
+    pub const ALL: Layers = Layers(u32::MAX);
+    pub const NONE: Layers = Layers(0);
+
+    // Just layer `index`, 0 to 31.
+    pub fn layer(index: u32) -> Self {
+        Layers(1 << index)
+    }
+
+    pub fn intersects(self, other: Layers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl BitOr for Layers {
+    type Output = Layers;
+
+    fn bitor(self, other: Layers) -> Layers {
+        Layers(self.0 | other.0)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RayHit {
+    pub distance: f32,
+    pub point: uv::Vec3,
+    // Unit surface normal facing the ray.
+    pub normal: uv::Vec3,
+    // Index of the triangle hit, for triangle meshes.
+    pub triangle: Option<usize>,
+}
+
+impl RayHit {
+    fn new(ray: &Ray, distance: f32, normal: uv::Vec3) -> Self {
+        Self {
+            distance,
+            point: ray.at(distance),
+            normal,
+            triangle: None,
+        }
+    }
+
+    fn inside(ray: &Ray) -> Self {
+        Self::new(ray, 0.0, -ray.direction)
+    }
+
+    // Back from the space `Ray::to_local` moved the ray into.
+    fn to_world(self, position: uv::Vec3, rotation: uv::Rotor3) -> Self {
+        Self {
+            point: rotation * self.point + position,
+            normal: rotation * self.normal,
+            ..self
+        }
+    }
+}
+
+impl Aabb {
+    // Distance along `ray` to where it enters the box, 0 if it starts inside.
+    pub fn ray_distance(&self, ray: &Ray, max_distance: f32) -> Option<f32> {
+        self.ray_entry(ray, max_distance).map(|(distance, _)| distance)
+    }
+
+    pub fn ray_hit(&self, ray: &Ray, max_distance: f32) -> Option<RayHit> {
+        match self.ray_entry(ray, max_distance)? {
+            (distance, Some(axis)) => {
+                let mut normal = uv::Vec3::zero();
+                normal[axis] = -ray.direction[axis].signum();
+                Some(RayHit::new(ray, distance, normal))
+            }
+            (_, None) => Some(RayHit::inside(ray)),
+        }
+    }
+
+    // Slab test, also giving the axis of the face the ray enters through,
+    // None when it starts inside.
+    fn ray_entry(&self, ray: &Ray, max_distance: f32) -> Option<(f32, Option<usize>)> {
+        let (mut near, mut far) = (0.0f32, max_distance);
+        let mut entry = None;
+        for axis in 0..3 {
+            let (origin, direction) = (ray.origin[axis], ray.direction[axis]);
+            let (min, max) = (self.min[axis], self.max[axis]);
+            if direction.abs() < f32::EPSILON {
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+            let inv = 1.0 / direction;
+            let (t0, t1) = ((min - origin) * inv, (max - origin) * inv);
+            if t0.min(t1) > near {
+                near = t0.min(t1);
+                entry = Some(axis);
+            }
+            far = far.min(t0.max(t1));
+            if near > far {
+                return None;
+            }
+        }
+        Some((near, entry))
+    }
+}
+
+pub fn ray_sphere(ray: &Ray, center: uv::Vec3, radius: f32, max_distance: f32) -> Option<RayHit> {
+    let offset = ray.origin - center;
+    let c = offset.mag_sq() - radius * radius;
+    if c <= 0.0 {
+        return Some(RayHit::inside(ray));
+    }
+    let b = offset.dot(ray.direction);
+    let discriminant = b * b - c;
+    if b > 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    let distance = -b - discriminant.sqrt();
+    if distance > max_distance {
+        return None;
+    }
+    let normal = (ray.at(distance) - center).normalized();
+    Some(RayHit::new(ray, distance, normal))
+}
+
+// Hits either side of the triangle; the normal faces the ray either way.
+pub fn ray_triangle(ray: &Ray, [a, b, c]: [uv::Vec3; 3], max_distance: f32) -> Option<RayHit> {
+    let (ab, ac) = (b - a, c - a);
+    let p = ray.direction.cross(ac);
+    let det = ab.dot(p);
+    if det.abs() < 1e-8 {
+        return None;
+    }
+    let inv = 1.0 / det;
+    let t = ray.origin - a;
+    let u = t.dot(p) * inv;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = t.cross(ab);
+    let v = ray.direction.dot(q) * inv;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = ac.dot(q) * inv;
+    if distance < 0.0 || distance > max_distance {
+        return None;
+    }
+    let normal = ab.cross(ac).normalized();
+    let normal = if normal.dot(ray.direction) > 0.0 { -normal } else { normal };
+    Some(RayHit::new(ray, distance, normal))
+}
+
+// Capsule along y in its own space.
+fn ray_capsule(ray: &Ray, half_height: f32, radius: f32, max_distance: f32) -> Option<RayHit> {
+    let axis = ray.origin.y.clamp(-half_height, half_height);
+    if (ray.origin - uv::Vec3::new(0.0, axis, 0.0)).mag_sq() <= radius * radius {
+        return Some(RayHit::inside(ray));
+    }
+    // The side, an infinite cylinder cut to the segment, then the end caps.
+    let (o, d) = (ray.origin, ray.direction);
+    let a = d.x * d.x + d.z * d.z;
+    let mut best: Option<RayHit> = None;
+    if a > 1e-8 {
+        let b = o.x * d.x + o.z * d.z;
+        let c = o.x * o.x + o.z * o.z - radius * radius;
+        let discriminant = b * b - a * c;
+        if discriminant >= 0.0 {
+            let distance = (-b - discriminant.sqrt()) / a;
+            let point = ray.at(distance);
+            if distance >= 0.0 && distance <= max_distance && point.y.abs() <= half_height {
+                let normal = uv::Vec3::new(point.x, 0.0, point.z).normalized();
+                best = Some(RayHit::new(ray, distance, normal));
+            }
+        }
+    }
+    for cap in [-half_height, half_height] {
+        let limit = best.map_or(max_distance, |hit| hit.distance);
+        if let Some(hit) = ray_sphere(ray, uv::Vec3::new(0.0, cap, 0.0), radius, limit) {
+            best = Some(hit);
+        }
+    }
+    best
+}
+
+// Conservative advancement: step along the ray by the distance to the hull,
+// which can't overshoot it, until touching or heading away.
+fn ray_hull(ray: &Ray, points: &[uv::Vec3], max_distance: f32) -> Option<RayHit> {
+    let hull = Convex {
+        core: Core::Hull(points),
+        margin: 0.0,
+        position: uv::Vec3::zero(),
+        rotation: uv::Rotor3::identity(),
+    };
+    let mut distance = 0.0;
+    let mut normal = -ray.direction;
+    for _ in 0..HULL_ITERATIONS {
+        let point = Convex {
+            core: Core::Point,
+            margin: 0.0,
+            position: ray.at(distance),
+            rotation: uv::Rotor3::identity(),
+        };
+        let closest = match gjk::gjk(&point, &hull) {
+            Gjk::Overlapping(_) => return Some(RayHit::new(ray, distance, normal)),
+            Gjk::Separated(closest) => closest,
+        };
+        if closest.distance < HULL_TOLERANCE {
+            return Some(RayHit::new(ray, distance, normal));
+        }
+        normal = (closest.point_a - closest.point_b) / closest.distance;
+        if normal.dot(ray.direction) >= 0.0 {
+            return None;
+        }
+        distance += closest.distance;
+        if distance > max_distance {
+            return None;
+        }
+    }
+    None
+}
+
+impl TriMesh {
+    // Nearest triangle crossed by `ray`, in the mesh's space.
+    pub fn raycast(&self, ray: &Ray, max_distance: f32) -> Option<RayHit> {
+        self.aabb().ray_distance(ray, max_distance)?;
+        let mut best: Option<RayHit> = None;
+        for index in 0..self.triangles.len() {
+            let limit = best.map_or(max_distance, |hit| hit.distance);
+            if let Some(hit) = ray_triangle(ray, self.triangle(index), limit) {
+                best = Some(RayHit {
+                    triangle: Some(index),
+                    ..hit
+                });
+            }
+        }
+        best
+    }
+}
+
+impl Shape {
+    // Where `ray` first hits the shape placed at `position` and `rotation`.
+    pub fn raycast(
+        &self,
+        position: uv::Vec3,
+        rotation: uv::Rotor3,
+        ray: &Ray,
+        max_distance: f32,
+    ) -> Option<RayHit> {
+        let local = ray.to_local(position, rotation);
+        let hit = match self {
+            Shape::Sphere { radius } => ray_sphere(&local, uv::Vec3::zero(), *radius, max_distance),
+            Shape::Box { half_extents } => Aabb::new(-*half_extents, *half_extents).ray_hit(&local, max_distance),
+            Shape::Capsule { half_height, radius } => ray_capsule(&local, *half_height, *radius, max_distance),
+            Shape::ConvexHull { points } => ray_hull(&local, points, max_distance),
+            Shape::TriangleMesh(mesh) => mesh.raycast(&local, max_distance),
+        }?;
+        Some(hit.to_world(position, rotation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::body::RigidBody;
+    use crate::physics::world::PhysicsWorld;
+
+    fn v(x: f32, y: f32, z: f32) -> uv::Vec3 {
+        uv::Vec3::new(x, y, z)
+    }
+
+    fn unit_cube() -> Aabb {
+        Aabb::new(v(-1.0, -1.0, -1.0), v(1.0, 1.0, 1.0))
+    }
+
+    fn cube_points() -> Vec<uv::Vec3> {
+        let sign = |i: u32, bit: u32| if i & bit == 0 { -1.0 } else { 1.0 };
+        (0..8).map(|i| v(sign(i, 1), sign(i, 2), sign(i, 4))).collect()
+    }
+
+    fn triangle() -> [uv::Vec3; 3] {
+        [v(-1.0, -1.0, 0.0), v(1.0, -1.0, 0.0), v(0.0, 1.0, 0.0)]
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "{} != {}", actual, expected);
+    }
+
+    fn assert_inside(hit: Option<RayHit>, ray: &Ray) {
+        let hit = hit.expect("no hit from inside");
+        assert_eq!(hit.distance, 0.0);
+        assert_eq!(hit.point, ray.origin);
+        assert_eq!(hit.normal, -ray.direction);
+    }
+
+    #[test]
+    fn solids_hit_rays_starting_inside_at_zero() {
+        let ray = Ray::new(v(0.2, 0.3, -0.1), v(1.0, 2.0, 0.5));
+        assert_inside(ray_sphere(&ray, uv::Vec3::zero(), 1.0, 10.0), &ray);
+        assert_inside(unit_cube().ray_hit(&ray, 10.0), &ray);
+        assert_inside(ray_capsule(&ray, 1.0, 0.5, 10.0), &ray);
+        assert_inside(ray_hull(&ray, &cube_points(), 10.0), &ray);
+        assert_eq!(unit_cube().ray_distance(&ray, 10.0), Some(0.0));
+
+        // A triangle is a surface: a ray starting on it hits at 0, facing back.
+        let ray = Ray::new(v(0.0, -0.5, 0.0), v(0.0, 0.0, 1.0));
+        let hit = ray_triangle(&ray, triangle(), 10.0).unwrap();
+        assert_eq!(hit.distance, 0.0);
+        assert_eq!(hit.normal, v(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn hits_from_outside_face_the_ray() {
+        let ray = Ray::new(v(0.0, 0.0, 5.0), v(0.0, 0.0, -1.0));
+        let expect = |hit: Option<RayHit>, distance: f32| {
+            let hit = hit.unwrap();
+            assert_close(hit.distance, distance);
+            assert!((hit.normal - v(0.0, 0.0, 1.0)).mag() < 1e-3, "{:?}", hit.normal);
+        };
+        expect(ray_sphere(&ray, uv::Vec3::zero(), 1.0, 10.0), 4.0);
+        expect(unit_cube().ray_hit(&ray, 10.0), 4.0);
+        expect(ray_capsule(&ray, 1.0, 0.5, 10.0), 4.5);
+        expect(ray_hull(&ray, &cube_points(), 10.0), 4.0);
+        expect(ray_triangle(&ray, triangle(), 10.0), 5.0);
+
+        // Out of reach.
+        assert_eq!(ray_sphere(&ray, uv::Vec3::zero(), 1.0, 3.0), None);
+        assert_eq!(unit_cube().ray_hit(&ray, 3.0), None);
+        assert_eq!(ray_capsule(&ray, 1.0, 0.5, 4.0), None);
+        assert_eq!(ray_hull(&ray, &cube_points(), 3.0), None);
+        assert_eq!(ray_triangle(&ray, triangle(), 4.0), None);
+    }
+
+    #[test]
+    fn shapes_behind_the_origin_are_missed() {
+        let ray = Ray::new(v(0.0, 0.0, 5.0), v(0.0, 0.0, 1.0));
+        assert_eq!(ray_sphere(&ray, uv::Vec3::zero(), 1.0, 100.0), None);
+        assert_eq!(unit_cube().ray_hit(&ray, 100.0), None);
+        assert_eq!(ray_capsule(&ray, 1.0, 0.5, 100.0), None);
+        assert_eq!(ray_hull(&ray, &cube_points(), 100.0), None);
+        assert_eq!(ray_triangle(&ray, triangle(), 100.0), None);
+    }
+
+    #[test]
+    fn slab_test_handles_parallel_and_grazing_rays() {
+        let cube = unit_cube();
+        // Parallel to the y slabs, inside them and just outside.
+        let along = Ray::new(v(-5.0, 0.5, 0.0), v(1.0, 0.0, 0.0));
+        assert_eq!(cube.ray_distance(&along, 10.0), Some(4.0));
+        let above = Ray::new(v(-5.0, 1.01, 0.0), v(1.0, 0.0, 0.0));
+        assert_eq!(cube.ray_distance(&above, 10.0), None);
+
+        // Sliding along a face and along an edge still touches the box.
+        let face = Ray::new(v(-5.0, 1.0, 0.0), v(1.0, 0.0, 0.0));
+        let hit = cube.ray_hit(&face, 10.0).unwrap();
+        assert_eq!(hit.distance, 4.0);
+        assert_eq!(hit.normal, v(-1.0, 0.0, 0.0));
+        let edge = Ray::new(v(-5.0, 1.0, 1.0), v(1.0, 0.0, 0.0));
+        assert_eq!(cube.ray_distance(&edge, 10.0), Some(4.0));
+
+        // Diagonally across an edge, entering and leaving at once.
+        let corner = Ray::new(v(-2.0, 0.0, 0.0), v(1.0, 0.0, 1.0));
+        assert_close(cube.ray_distance(&corner, 10.0).unwrap(), 2.0f32.sqrt());
+        let past = Ray::new(v(-2.01, 0.0, 0.0), v(1.0, 0.0, 1.0));
+        assert_eq!(cube.ray_distance(&past, 10.0), None);
+    }
+
+    #[test]
+    fn mesh_hits_report_the_nearest_triangle() {
+        // Two quads facing +z, the far one listed first.
+        let mut vertices = Vec::new();
+        for z in [-5.0, -2.0] {
+            vertices.extend([v(-1.0, -1.0, z), v(1.0, -1.0, z), v(1.0, 1.0, z), v(-1.0, 1.0, z)]);
+        }
+        let mesh = TriMesh::new(vertices, vec![[0, 1, 2], [0, 2, 3], [4, 5, 6], [4, 6, 7]]);
+
+        let hit = mesh.raycast(&Ray::new(v(0.5, -0.5, 0.0), v(0.0, 0.0, -1.0)), 10.0).unwrap();
+        assert_eq!(hit.triangle, Some(2));
+        assert_close(hit.distance, 2.0);
+        let hit = mesh.raycast(&Ray::new(v(-0.5, 0.5, 0.0), v(0.0, 0.0, -1.0)), 10.0).unwrap();
+        assert_eq!(hit.triangle, Some(3));
+
+        // From between the quads only the far one is ahead.
+        let hit = mesh.raycast(&Ray::new(v(0.5, -0.5, -3.0), v(0.0, 0.0, -1.0)), 10.0).unwrap();
+        assert_eq!(hit.triangle, Some(0));
+        assert_close(hit.distance, 2.0);
+
+        // Through the shape, placed and turned, the index comes along.
+        let shape = Shape::triangle_mesh(mesh);
+        let rotation = uv::Rotor3::from_rotation_xz(std::f32::consts::FRAC_PI_2);
+        let position = v(10.0, 0.0, 0.0);
+        let ray = Ray::new(rotation * v(0.5, -0.5, 0.0) + position, rotation * v(0.0, 0.0, -1.0));
+        let hit = shape.raycast(position, rotation, &ray, 10.0).unwrap();
+        assert_eq!(hit.triangle, Some(2));
+        assert_close(hit.distance, 2.0);
+    }
+
+    #[test]
+    fn world_raycast_filters_by_layer() {
+        let mut world = PhysicsWorld::new();
+        let near = world.add_body(
+            RigidBody::fixed(Shape::sphere(1.0))
+                .with_position(v(0.0, 0.0, -5.0))
+                .with_layers(Layers::layer(1)),
+        );
+        let far = world.add_body(
+            RigidBody::fixed(Shape::cuboid(v(1.0, 1.0, 1.0)))
+                .with_position(v(0.0, 0.0, -10.0))
+                .with_layers(Layers::layer(2)),
+        );
+        let ray = Ray::new(uv::Vec3::zero(), v(0.0, 0.0, -1.0));
+
+        let (handle, hit) = world.raycast(&ray, 100.0, Layers::ALL).unwrap();
+        assert_eq!(handle, near);
+        assert_close(hit.distance, 4.0);
+        let (handle, hit) = world.raycast(&ray, 100.0, Layers::layer(2)).unwrap();
+        assert_eq!(handle, far);
+        assert_close(hit.distance, 9.0);
+        let both = Layers::layer(1) | Layers::layer(2);
+        assert_eq!(world.raycast(&ray, 100.0, both).map(|(handle, _)| handle), Some(near));
+        assert_eq!(world.raycast(&ray, 100.0, Layers::layer(3)), None);
+        assert_eq!(world.raycast(&ray, 100.0, Layers::NONE), None);
+        assert_eq!(world.raycast(&ray, 8.0, Layers::layer(2)), None);
+    }
+}
@@ -0,0 +1,214 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use ultraviolet as uv;
+
+use crate::graphics::objects::mesh::MeshData;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: uv::Vec3,
+    pub max: uv::Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: uv::Vec3, max: uv::Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = uv::Vec3>) -> Self {
+        let mut aabb = Aabb::new(uv::Vec3::broadcast(f32::MAX), uv::Vec3::broadcast(f32::MIN));
+        for point in points {
+            aabb.min = aabb.min.min_by_component(point);
+            aabb.max = aabb.max.max_by_component(point);
+        }
+        aabb
+    }
+
+    pub fn center(&self) -> uv::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> uv::Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn expanded(&self, margin: f32) -> Self {
+        Aabb::new(self.min - uv::Vec3::broadcast(margin), self.max + uv::Vec3::broadcast(margin))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Aabb::new(self.min.min_by_component(other.min), self.max.max_by_component(other.max))
+    }
+
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && self.min.z <= other.min.z
+            && self.max.x >= other.max.x
+            && self.max.y >= other.max.y
+            && self.max.z >= other.max.z
+    }
+
+    // Surface area, the cost tree building tries to keep low.
+    pub fn area(&self) -> f32 {
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    pub fn distance_squared(&self, point: uv::Vec3) -> f32 {
+        let clamped = point.clamped(self.min, self.max);
+        (point - clamped).mag_sq()
+    }
+
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    // The box around this one after a rotation and translation.
+    pub fn transformed(&self, position: uv::Vec3, rotation: uv::Rotor3) -> Self {
+        let matrix = rotation.into_matrix();
+        let half = self.half_extents();
+        let extent = uv::Vec3::new(
+            matrix.cols[0].x.abs() * half.x + matrix.cols[1].x.abs() * half.y + matrix.cols[2].x.abs() * half.z,
+            matrix.cols[0].y.abs() * half.x + matrix.cols[1].y.abs() * half.y + matrix.cols[2].y.abs() * half.z,
+            matrix.cols[0].z.abs() * half.x + matrix.cols[1].z.abs() * half.y + matrix.cols[2].z.abs() * half.z,
+        );
+        let center = rotation * self.center() + position;
+        Aabb::new(center - extent, center + extent)
+    }
+}
+
+// Triangles for static level geometry. Collides with the convex shapes but
+// not with other meshes.
+#[derive(Debug, Clone)]
+pub struct TriMesh {
+    pub vertices: Vec<uv::Vec3>,
+    pub triangles: Vec<[u32; 3]>,
+    aabb: Aabb,
+}
+
+impl TriMesh {
+    pub fn new(vertices: Vec<uv::Vec3>, triangles: Vec<[u32; 3]>) -> Self {
+        let aabb = Aabb::from_points(vertices.iter().copied());
+        Self {
+            vertices,
+            triangles,
+            aabb,
+        }
+    }
+
+    pub fn from_mesh_data(data: &MeshData) -> Self {
+        let vertices = data.vertices.iter().map(|v| uv::Vec3::from(v.position)).collect();
+        let triangles = data
+            .indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        Self::new(vertices, triangles)
+    }
+
+    pub fn triangle(&self, index: usize) -> [uv::Vec3; 3] {
+        self.triangles[index].map(|i| self.vertices[i as usize])
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        self.aabb
+    }
+}
+
+// Collision geometry in the body's local space. Capsules run along y.
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Sphere { radius: f32 },
+    Box { half_extents: uv::Vec3 },
+    Capsule { half_height: f32, radius: f32 },
+    ConvexHull { points: Arc<[uv::Vec3]> },
+    TriangleMesh(Arc<TriMesh>),
+}
+
+impl Shape {
+    pub fn sphere(radius: f32) -> Self {
+        Shape::Sphere { radius }
+    }
+
+    pub fn cuboid(half_extents: uv::Vec3) -> Self {
+        Shape::Box { half_extents }
+    }
+
+    pub fn capsule(half_height: f32, radius: f32) -> Self {
+        Shape::Capsule { half_height, radius }
+    }
+
+    // Any point cloud; only its convex hull matters.
+    pub fn convex_hull(points: Vec<uv::Vec3>) -> Self {
+        Shape::ConvexHull { points: points.into() }
+    }
+
+    pub fn triangle_mesh(mesh: TriMesh) -> Self {
+        Shape::TriangleMesh(Arc::new(mesh))
+    }
+
+    pub fn local_aabb(&self) -> Aabb {
+        match self {
+            Shape::Sphere { radius } => Aabb::new(-uv::Vec3::broadcast(*radius), uv::Vec3::broadcast(*radius)),
+            Shape::Box { half_extents } => Aabb::new(-*half_extents, *half_extents),
+            Shape::Capsule { half_height, radius } => {
+                let extent = uv::Vec3::new(*radius, half_height + radius, *radius);
+                Aabb::new(-extent, extent)
+            }
+            Shape::ConvexHull { points } => Aabb::from_points(points.iter().copied()),
+            Shape::TriangleMesh(mesh) => mesh.aabb(),
+        }
+    }
+
+    pub fn aabb(&self, position: uv::Vec3, rotation: uv::Rotor3) -> Aabb {
+        match self {
+            Shape::Sphere { radius } => {
+                Aabb::new(position - uv::Vec3::broadcast(*radius), position + uv::Vec3::broadcast(*radius))
+            }
+            Shape::Capsule { half_height, radius } => {
+                let axis = rotation * uv::Vec3::new(0.0, *half_height, 0.0);
+                Aabb::from_points([position + axis, position - axis]).expanded(*radius)
+            }
+            _ => self.local_aabb().transformed(position, rotation),
+        }
+    }
+
+    // Mass and the diagonal of the inertia tensor about the centre. Hulls and
+    // meshes are treated as their bounding box.
+    pub fn mass_properties(&self, density: f32) -> (f32, uv::Vec3) {
+        match self {
+            Shape::Sphere { radius } => {
+                let mass = density * 4.0 / 3.0 * PI * radius.powi(3);
+                (mass, uv::Vec3::broadcast(0.4 * mass * radius * radius))
+            }
+            Shape::Capsule { half_height, radius } => {
+                let (r2, height) = (radius * radius, 2.0 * half_height);
+                let cylinder = density * PI * r2 * height;
+                let hemisphere = density * 2.0 / 3.0 * PI * r2 * radius;
+                let axial = cylinder * r2 * 0.5 + 2.0 * hemisphere * 0.4 * r2;
+                let lateral = cylinder * (height * height / 12.0 + r2 * 0.25)
+                    + 2.0 * hemisphere * (0.4 * r2 + height * height * 0.25 + 0.375 * height * radius);
+                (cylinder + 2.0 * hemisphere, uv::Vec3::new(lateral, axial, lateral))
+            }
+            _ => {
+                let half = match self {
+                    Shape::Box { half_extents } => *half_extents,
+                    _ => self.local_aabb().half_extents(),
+                };
+                let mass = density * 8.0 * half.x * half.y * half.z;
+                let (x2, y2, z2) = (half.x * half.x, half.y * half.y, half.z * half.z);
+                (mass, uv::Vec3::new(y2 + z2, x2 + z2, x2 + y2) * (mass / 3.0))
+            }
+        }
+    }
+
+    pub fn is_convex(&self) -> bool {
+        !matches!(self, Shape::TriangleMesh(_))
+    }
+}
@@ -0,0 +1,178 @@
+// Sequential impulses: each contact point is solved in turn, clamping the
+// accumulated impulse, and the pass is repeated until it settles.
+use ultraviolet as uv;
+
+use super::body::RigidBody;
+use super::contact::Manifold;
+
+// Penetration left alone so resting contacts stay touching.
+const SLOP: f32 = 0.005;
+// Fraction of the remaining penetration pushed out per step.
+const BAUMGARTE: f32 = 0.2;
+// Slower impacts don't bounce, which keeps resting bodies from jittering.
+const RESTITUTION_THRESHOLD: f32 = 1.0;
+
+struct PointConstraint {
+    manifold: usize,
+    point: usize,
+    r_a: uv::Vec3,
+    r_b: uv::Vec3,
+    normal_mass: f32,
+    tangent_mass: [f32; 2],
+    bias: f32,
+}
+
+struct Pair {
+    a: usize,
+    b: usize,
+    normal: uv::Vec3,
+    tangents: [uv::Vec3; 2],
+    friction: f32,
+}
+
+// Velocity state of one body while solving.
+#[derive(Copy, Clone)]
+pub(crate) struct SolverBody {
+    pub linear: uv::Vec3,
+    pub angular: uv::Vec3,
+    pub inv_mass: f32,
+    pub inv_inertia: uv::Mat3,
+    pub position: uv::Vec3,
+}
+
+impl SolverBody {
+    pub fn new(body: &RigidBody) -> Self {
+        Self {
+            linear: body.linear_velocity,
+            angular: body.angular_velocity,
+            inv_mass: body.inv_mass(),
+            inv_inertia: body.inv_inertia_world(),
+            position: body.position,
+        }
+    }
+
+    pub fn apply(&mut self, impulse: uv::Vec3, r: uv::Vec3) {
+        self.linear += impulse * self.inv_mass;
+        self.angular += self.inv_inertia * r.cross(impulse);
+    }
+
+    pub fn velocity_at(&self, r: uv::Vec3) -> uv::Vec3 {
+        self.linear + self.angular.cross(r)
+    }
+
+    // Inverse effective mass along `direction` at offset `r`.
+    pub fn inv_mass_along(&self, r: uv::Vec3, direction: uv::Vec3) -> f32 {
+        let rn = r.cross(direction);
+        self.inv_mass + (self.inv_inertia * rn).cross(r).dot(direction)
+    }
+}
+
+pub(crate) fn tangents(normal: uv::Vec3) -> [uv::Vec3; 2] {
+    let helper = if normal.x.abs() < 0.57 {
+        uv::Vec3::unit_x()
+    } else {
+        uv::Vec3::unit_y()
+    };
+    let t1 = normal.cross(helper).normalized();
+    [t1, normal.cross(t1)]
+}
+
+pub(crate) struct ContactSolver {
+    pairs: Vec<Pair>,
+    points: Vec<(usize, PointConstraint)>,
+}
+
+impl ContactSolver {
+    // `index` maps a manifold's bodies to slots in `bodies`. Applies last
+    // step's impulses as a starting guess.
+    pub fn new(
+        manifolds: &[&mut Manifold],
+        index: impl Fn(&Manifold) -> (usize, usize),
+        bodies: &mut [SolverBody],
+        dt: f32,
+    ) -> Self {
+        let mut pairs = Vec::with_capacity(manifolds.len());
+        let mut points = Vec::new();
+        for (m, manifold) in manifolds.iter().enumerate() {
+            let (a, b) = index(manifold);
+            let pair = Pair {
+                a,
+                b,
+                normal: manifold.normal,
+                tangents: tangents(manifold.normal),
+                friction: manifold.friction,
+            };
+            for (p, contact) in manifold.points.iter().enumerate() {
+                let (body_a, body_b) = (bodies[a], bodies[b]);
+                let r_a = contact.point - body_a.position;
+                let r_b = contact.point - body_b.position;
+                let mass = |direction: uv::Vec3| {
+                    let k = body_a.inv_mass_along(r_a, direction) + body_b.inv_mass_along(r_b, direction);
+                    if k > 0.0 {
+                        1.0 / k
+                    } else {
+                        0.0
+                    }
+                };
+                let relative = body_b.velocity_at(r_b) - body_a.velocity_at(r_a);
+                let approach = relative.dot(pair.normal);
+                let mut bias = BAUMGARTE / dt * (contact.depth - SLOP).max(0.0);
+                // Speculative contacts that haven't touched yet may close
+                // the gap but no more.
+                if contact.depth < 0.0 {
+                    bias = contact.depth / dt;
+                }
+                if approach < -RESTITUTION_THRESHOLD {
+                    bias = bias.max(-manifold.restitution * approach);
+                }
+                let constraint = PointConstraint {
+                    manifold: m,
+                    point: p,
+                    r_a,
+                    r_b,
+                    normal_mass: mass(pair.normal),
+                    tangent_mass: [mass(pair.tangents[0]), mass(pair.tangents[1])],
+                    bias,
+                };
+                let impulse = pair.normal * contact.normal_impulse
+                    + pair.tangents[0] * contact.tangent_impulse[0]
+                    + pair.tangents[1] * contact.tangent_impulse[1];
+                bodies[a].apply(-impulse, r_a);
+                bodies[b].apply(impulse, r_b);
+                points.push((pairs.len(), constraint));
+            }
+            pairs.push(pair);
+        }
+        Self { pairs, points }
+    }
+
+    pub fn solve(&self, manifolds: &mut [&mut Manifold], bodies: &mut [SolverBody]) {
+        for (pair_index, constraint) in &self.points {
+            let pair = &self.pairs[*pair_index];
+            let contact = &mut manifolds[constraint.manifold].points[constraint.point];
+            let (a, b) = (pair.a, pair.b);
+
+            // Friction first, bounded by the normal impulse of the last pass.
+            let limit = pair.friction * contact.normal_impulse;
+            for i in 0..2 {
+                let relative = bodies[b].velocity_at(constraint.r_b) - bodies[a].velocity_at(constraint.r_a);
+                let lambda = -relative.dot(pair.tangents[i]) * constraint.tangent_mass[i];
+                let total = (contact.tangent_impulse[i] + lambda).clamp(-limit, limit);
+                let applied = total - contact.tangent_impulse[i];
+                contact.tangent_impulse[i] = total;
+                let impulse = pair.tangents[i] * applied;
+                bodies[a].apply(-impulse, constraint.r_a);
+                bodies[b].apply(impulse, constraint.r_b);
+            }
+
+            let relative = bodies[b].velocity_at(constraint.r_b) - bodies[a].velocity_at(constraint.r_a);
+            let lambda = (constraint.bias - relative.dot(pair.normal)) * constraint.normal_mass;
+            let total = (contact.normal_impulse + lambda).max(0.0);
+            let applied = total - contact.normal_impulse;
+            contact.normal_impulse = total;
+            let impulse = pair.normal * applied;
+            bodies[a].apply(-impulse, constraint.r_a);
+            bodies[b].apply(impulse, constraint.r_b);
+        }
+    }
+}
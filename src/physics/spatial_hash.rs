@@ -0,0 +1,462 @@
+// Uniform grid for 2D: each entry is listed in every cell its rectangle
+// touches. Suits many similar-sized objects, e.g. units for AI queries.
+use std::collections::{HashMap, HashSet};
+
+use ultraviolet as uv;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Rect {
+    pub min: uv::Vec2,
+    pub max: uv::Vec2,
+}
+
+impl Rect {
+    pub fn new(min: uv::Vec2, max: uv::Vec2) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_center(center: uv::Vec2, half_extents: uv::Vec2) -> Self {
+        Self::new(center - half_extents, center + half_extents)
+    }
+
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x && self.min.y <= other.max.y && self.max.y >= other.min.y
+    }
+
+    pub fn distance_squared(&self, point: uv::Vec2) -> f32 {
+        (point - point.clamped(self.min, self.max)).mag_sq()
+    }
+
+    // Distance along the unit `direction` to where the ray enters, 0 if it
+    // starts inside.
+    pub fn ray_distance(&self, origin: uv::Vec2, direction: uv::Vec2, max_distance: f32) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, max_distance);
+        for axis in 0..2 {
+            let (o, d) = (origin[axis], direction[axis]);
+            if d.abs() < f32::EPSILON {
+                if o < self.min[axis] || o > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let (t0, t1) = ((self.min[axis] - o) / d, (self.max[axis] - o) / d);
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GridId(usize);
+
+type Cell = (i32, i32);
+
+#[derive(Debug, Clone)]
+struct Entry<T> {
+    rect: Rect,
+    data: T,
+    // Inclusive cell range the entry is listed in.
+    cells: (Cell, Cell),
+}
+
+#[derive(Debug, Clone)]
+pub struct SpatialHash<T> {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<GridId>>,
+    entries: Vec<Option<Entry<T>>>,
+}
+
+impl<T> SpatialHash<T> {
+    // Around the size of a typical entry works best.
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(Option::is_none)
+    }
+
+    pub fn insert(&mut self, rect: Rect, data: T) -> GridId {
+        let cells = self.cell_range(&rect);
+        let entry = Entry { rect, data, cells };
+        let id = match self.entries.iter().position(Option::is_none) {
+            Some(i) => {
+                self.entries[i] = Some(entry);
+                GridId(i)
+            }
+            None => {
+                self.entries.push(Some(entry));
+                GridId(self.entries.len() - 1)
+            }
+        };
+        self.link(id, cells);
+        id
+    }
+
+    pub fn remove(&mut self, id: GridId) -> Option<T> {
+        let entry = self.entries.get_mut(id.0)?.take()?;
+        self.unlink(id, entry.cells);
+        Some(entry.data)
+    }
+
+    // Only touches the grid when the entry crosses into other cells.
+    pub fn update(&mut self, id: GridId, rect: Rect) {
+        let cells = self.cell_range(&rect);
+        let Some(entry) = self.entries.get_mut(id.0).and_then(Option::as_mut) else {
+            return;
+        };
+        entry.rect = rect;
+        let old = std::mem::replace(&mut entry.cells, cells);
+        if old != cells {
+            self.unlink(id, old);
+            self.link(id, cells);
+        }
+    }
+
+    pub fn get(&self, id: GridId) -> Option<&T> {
+        Some(&self.entries.get(id.0)?.as_ref()?.data)
+    }
+
+    pub fn rect(&self, id: GridId) -> Option<Rect> {
+        Some(self.entries.get(id.0)?.as_ref()?.rect)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (GridId, &T)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| Some((GridId(i), &entry.as_ref()?.data)))
+    }
+
+    // Calls `f` for every entry overlapping `rect`, in id order.
+    pub fn query(&self, rect: &Rect, mut f: impl FnMut(GridId, &T)) {
+        let ((x0, y0), (x1, y1)) = self.cell_range(rect);
+        let mut found = Vec::new();
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                found.extend(self.cells.get(&(x, y)).into_iter().flatten().copied());
+            }
+        }
+        found.sort();
+        found.dedup();
+        for id in found {
+            let entry = self.entries[id.0].as_ref().unwrap();
+            if entry.rect.overlaps(rect) {
+                f(id, &entry.data);
+            }
+        }
+    }
+
+    // Every pair of overlapping entries, lower id first, sorted.
+    pub fn overlap_pairs(&self) -> Vec<(GridId, GridId)> {
+        let mut pairs = Vec::new();
+        for ids in self.cells.values() {
+            for (i, &a) in ids.iter().enumerate() {
+                for &b in &ids[i + 1..] {
+                    let (a, b) = (a.min(b), a.max(b));
+                    if self.entries[a.0].as_ref().unwrap().rect.overlaps(&self.entries[b.0].as_ref().unwrap().rect) {
+                        pairs.push((a, b));
+                    }
+                }
+            }
+        }
+        pairs.sort();
+        pairs.dedup();
+        pairs
+    }
+
+    // Nearest hit along the ray, walking the cells it crosses in order.
+    // `hit` does the exact test, returning the distance if it hits.
+    pub fn raycast(
+        &self,
+        origin: uv::Vec2,
+        direction: uv::Vec2,
+        max_distance: f32,
+        mut hit: impl FnMut(GridId, &T) -> Option<f32>,
+    ) -> Option<(GridId, f32)> {
+        let direction = direction.normalized();
+        // An endless ray stops once it is past everything.
+        let max_distance = if max_distance.is_finite() {
+            max_distance
+        } else {
+            self.entries
+                .iter()
+                .flatten()
+                .flat_map(|entry| [entry.rect.min, entry.rect.max])
+                .map(|corner| (corner - origin).mag() + self.cell_size)
+                .fold(0.0, f32::max)
+        };
+        let mut cell = self.cell(origin);
+        let step = (
+            if direction.x >= 0.0 { 1 } else { -1 },
+            if direction.y >= 0.0 { 1 } else { -1 },
+        );
+        // Distance along the ray to the next cell boundary on each axis, and
+        // between boundaries.
+        let boundary = |c: i32, s: i32, o: f32, d: f32| {
+            if d.abs() < f32::EPSILON {
+                return (f32::INFINITY, f32::INFINITY);
+            }
+            let edge = (c + if s > 0 { 1 } else { 0 }) as f32 * self.cell_size;
+            ((edge - o) / d, self.cell_size / d.abs())
+        };
+        let (mut next_x, delta_x) = boundary(cell.0, step.0, origin.x, direction.x);
+        let (mut next_y, delta_y) = boundary(cell.1, step.1, origin.y, direction.y);
+
+        let mut best: Option<(GridId, f32)> = None;
+        let mut tested = HashSet::new();
+        let mut entered = 0.0;
+        loop {
+            if let Some(ids) = self.cells.get(&cell) {
+                for &id in ids {
+                    if !tested.insert(id) {
+                        continue;
+                    }
+                    let entry = self.entries[id.0].as_ref().unwrap();
+                    let limit = best.map_or(max_distance, |(_, d)| d);
+                    if entry.rect.ray_distance(origin, direction, limit).is_none() {
+                        continue;
+                    }
+                    if let Some(distance) = hit(id, &entry.data).filter(|d| *d <= limit) {
+                        if best.is_none_or(|(b, d)| distance < d || distance == d && id < b) {
+                            best = Some((id, distance));
+                        }
+                    }
+                }
+            }
+            // Hits in later cells can't be closer than where they start.
+            if entered > best.map_or(max_distance, |(_, d)| d) {
+                break;
+            }
+            if next_x < next_y {
+                cell.0 += step.0;
+                entered = next_x;
+                next_x += delta_x;
+            } else {
+                cell.1 += step.1;
+                entered = next_y;
+                next_y += delta_y;
+            }
+            if entered > max_distance {
+                break;
+            }
+        }
+        best
+    }
+
+    // Closest entry to `point` by `distance`, which must be no less than the
+    // distance to the entry's rectangle. Searches rings of cells outwards.
+    pub fn nearest(
+        &self,
+        point: uv::Vec2,
+        max_distance: f32,
+        mut distance: impl FnMut(GridId, &T) -> f32,
+    ) -> Option<(GridId, f32)> {
+        let center = self.cell(point);
+        // No point searching past the farthest occupied cell.
+        let farthest = self
+            .cells
+            .keys()
+            .map(|cell| (cell.0 - center.0).abs().max((cell.1 - center.1).abs()))
+            .max()
+            .unwrap_or(0);
+        let rings = ((max_distance / self.cell_size).ceil().min(i32::MAX as f32) as i32)
+            .saturating_add(1)
+            .min(farthest);
+        let mut best: Option<(GridId, f32)> = None;
+        let mut tested = HashSet::new();
+        for ring in 0..=rings {
+            for cell in ring_cells(center, ring) {
+                for &id in self.cells.get(&cell).into_iter().flatten() {
+                    if !tested.insert(id) {
+                        continue;
+                    }
+                    let entry = self.entries[id.0].as_ref().unwrap();
+                    let limit = best.map_or(max_distance, |(_, d)| d);
+                    if entry.rect.distance_squared(point) > limit * limit {
+                        continue;
+                    }
+                    let d = distance(id, &entry.data);
+                    if d <= limit && best.is_none_or(|(b, bd)| d < bd || d == bd && id < b) {
+                        best = Some((id, d));
+                    }
+                }
+            }
+            // Anything in the next ring is at least this far away.
+            if best.is_some_and(|(_, d)| d <= ring as f32 * self.cell_size) {
+                break;
+            }
+        }
+        best
+    }
+
+    fn cell(&self, point: uv::Vec2) -> Cell {
+        (
+            (point.x / self.cell_size).floor() as i32,
+            (point.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn cell_range(&self, rect: &Rect) -> (Cell, Cell) {
+        (self.cell(rect.min), self.cell(rect.max))
+    }
+
+    fn link(&mut self, id: GridId, ((x0, y0), (x1, y1)): (Cell, Cell)) {
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                self.cells.entry((x, y)).or_default().push(id);
+            }
+        }
+    }
+
+    fn unlink(&mut self, id: GridId, ((x0, y0), (x1, y1)): (Cell, Cell)) {
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                if let Some(ids) = self.cells.get_mut(&(x, y)) {
+                    ids.retain(|other| *other != id);
+                    if ids.is_empty() {
+                        self.cells.remove(&(x, y));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// The cells at Chebyshev distance `ring` from `center`.
+fn ring_cells(center: Cell, ring: i32) -> Vec<Cell> {
+    if ring == 0 {
+        return vec![center];
+    }
+    let mut cells = Vec::with_capacity(8 * ring as usize);
+    for i in -ring..=ring {
+        cells.push((center.0 + i, center.1 - ring));
+        cells.push((center.0 + i, center.1 + ring));
+    }
+    for i in -ring + 1..ring {
+        cells.push((center.0 - ring, center.1 + i));
+        cells.push((center.0 + ring, center.1 + i));
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn random_rect(rng: &mut fastrand::Rng) -> Rect {
+        let min = uv::Vec2::new(rng.f32(), rng.f32()) * 40.0 - uv::Vec2::broadcast(20.0);
+        Rect::new(min, min + uv::Vec2::new(rng.f32(), rng.f32()) * 4.0)
+    }
+
+    // Every entry is listed in exactly the cells its rectangle touches.
+    fn check_grid<T>(grid: &SpatialHash<T>) {
+        let mut expected: HashMap<Cell, Vec<GridId>> = HashMap::new();
+        for (i, entry) in grid.entries.iter().enumerate() {
+            let Some(entry) = entry else {
+                continue;
+            };
+            assert_eq!(entry.cells, grid.cell_range(&entry.rect));
+            let ((x0, y0), (x1, y1)) = entry.cells;
+            for x in x0..=x1 {
+                for y in y0..=y1 {
+                    expected.entry((x, y)).or_default().push(GridId(i));
+                }
+            }
+        }
+        let mut cells = grid.cells.clone();
+        for ids in cells.values_mut() {
+            ids.sort();
+        }
+        assert_eq!(cells, expected);
+    }
+
+    #[test]
+    fn matches_brute_force_through_random_edits() {
+        let mut rng = fastrand::Rng::with_seed(42);
+        let mut grid = SpatialHash::new(2.0);
+        let mut rects: BTreeMap<GridId, Rect> = BTreeMap::new();
+        for round in 0..400 {
+            match rng.usize(0..10) {
+                0..=4 => {
+                    let rect = random_rect(&mut rng);
+                    rects.insert(grid.insert(rect, round), rect);
+                }
+                5..=7 if !rects.is_empty() => {
+                    let id = *rects.keys().nth(rng.usize(0..rects.len())).unwrap();
+                    // Mostly small moves, some within their cells.
+                    let rect = if rng.bool() {
+                        let nudge = uv::Vec2::new(rng.f32(), rng.f32()) * 0.5;
+                        Rect::new(rects[&id].min + nudge, rects[&id].max + nudge)
+                    } else {
+                        random_rect(&mut rng)
+                    };
+                    grid.update(id, rect);
+                    rects.insert(id, rect);
+                }
+                _ if !rects.is_empty() => {
+                    let id = *rects.keys().nth(rng.usize(0..rects.len())).unwrap();
+                    assert!(grid.remove(id).is_some());
+                    rects.remove(&id);
+                }
+                _ => {}
+            }
+            check_grid(&grid);
+            assert_eq!(grid.len(), rects.len());
+
+            let area = random_rect(&mut rng);
+            let mut found = Vec::new();
+            grid.query(&area, |id, _| found.push(id));
+            let expected: Vec<_> = rects.iter().filter(|(_, r)| r.overlaps(&area)).map(|(id, _)| *id).collect();
+            assert_eq!(found, expected);
+
+            let mut pairs = Vec::new();
+            for (a, rect_a) in &rects {
+                for (b, rect_b) in rects.range(*a..).skip(1) {
+                    if rect_a.overlaps(rect_b) {
+                        pairs.push((*a, *b));
+                    }
+                }
+            }
+            assert_eq!(grid.overlap_pairs(), pairs);
+
+            let point = uv::Vec2::new(rng.f32(), rng.f32()) * 50.0 - uv::Vec2::broadcast(25.0);
+            let distance = |id: GridId| rects[&id].distance_squared(point).sqrt();
+            let nearest = grid.nearest(point, f32::INFINITY, |id, _| distance(id)).map(|(_, d)| d);
+            let expected = rects.keys().map(|id| distance(*id)).min_by(f32::total_cmp);
+            assert_eq!(nearest, expected);
+        }
+    }
+
+    #[test]
+    fn moves_within_a_cell_leave_the_grid_alone() {
+        let mut grid = SpatialHash::new(1.0);
+        let id = grid.insert(Rect::new(uv::Vec2::new(0.1, 0.1), uv::Vec2::new(0.3, 0.3)), ());
+        let cells = grid.cells.clone();
+        grid.update(id, Rect::new(uv::Vec2::new(0.5, 0.5), uv::Vec2::new(0.9, 0.9)));
+        assert_eq!(grid.cells, cells);
+        assert_eq!(grid.rect(id).unwrap().min, uv::Vec2::new(0.5, 0.5));
+
+        grid.update(id, Rect::new(uv::Vec2::new(0.5, 0.5), uv::Vec2::new(1.5, 0.9)));
+        assert_eq!(grid.cells.len(), 2);
+        check_grid(&grid);
+    }
+}
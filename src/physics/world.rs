@@ -0,0 +1,646 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use ultraviolet as uv;
+
+use super::body::{BodyHandle, BodyKind, RigidBody};
+use super::bvh::{DynamicBvh, ProxyId};
+use super::cast::ShapeHit;
+use super::contact::{self, Contact, Manifold, CONTACT_MARGIN};
+use super::events::{Collision, CollisionEvent, EventQueue};
+use super::joint::{Joint, JointHandle, JointSolver};
+use super::ray::{Layers, Ray, RayHit};
+use super::shape::{Aabb, Shape};
+use super::solver::{ContactSolver, SolverBody};
+
+pub const DEFAULT_TIMESTEP: f32 = 1.0 / 60.0;
+
+// Keyed by body pair and mesh triangle, ordered so stepping never depends
+// on hashing.
+type ManifoldKey = (BodyHandle, BodyHandle, u32);
+pub(crate) type Pair = (BodyHandle, BodyHandle);
+
+// Rigid bodies stepped at a fixed rate. The same bodies and the same calls
+// give the same results.
+pub struct PhysicsWorld {
+    bodies: Vec<Option<RigidBody>>,
+    // Each body's leaf in `broadphase`, by handle.
+    proxies: Vec<Option<ProxyId>>,
+    broadphase: DynamicBvh<BodyHandle>,
+    manifolds: BTreeMap<ManifoldKey, Manifold>,
+    joints: Vec<Option<Joint>>,
+    events: EventQueue,
+    pub gravity: uv::Vec3,
+    pub timestep: f32,
+    pub iterations: usize,
+    // Steps taken per `update` at most, so a long frame can't snowball.
+    pub max_steps: u32,
+    clock: StepClock,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PhysicsWorld {
+    pub fn new() -> Self {
+        Self {
+            bodies: Vec::new(),
+            proxies: Vec::new(),
+            broadphase: DynamicBvh::new(),
+            manifolds: BTreeMap::new(),
+            joints: Vec::new(),
+            events: EventQueue::default(),
+            gravity: uv::Vec3::new(0.0, -9.81, 0.0),
+            timestep: DEFAULT_TIMESTEP,
+            iterations: 10,
+            max_steps: 5,
+            clock: StepClock::default(),
+        }
+    }
+
+    pub fn with_gravity(mut self, gravity: uv::Vec3) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_timestep(mut self, timestep: f32) -> Self {
+        self.timestep = timestep;
+        self
+    }
+
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn add_body(&mut self, body: RigidBody) -> BodyHandle {
+        let aabb = body.aabb().expanded(CONTACT_MARGIN);
+        let handle = match self.bodies.iter().position(Option::is_none) {
+            Some(i) => {
+                self.bodies[i] = Some(body);
+                BodyHandle(i)
+            }
+            None => {
+                self.bodies.push(Some(body));
+                self.proxies.push(None);
+                BodyHandle(self.bodies.len() - 1)
+            }
+        };
+        self.proxies[handle.0] = Some(self.broadphase.insert(aabb, handle));
+        handle
+    }
+
+    // Also removes the joints holding it.
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<RigidBody> {
+        self.manifolds.retain(|(a, b, _), _| *a != handle && *b != handle);
+        for slot in &mut self.joints {
+            if slot.as_ref().is_some_and(|joint| joint.body_a == handle || joint.body_b == handle) {
+                *slot = None;
+            }
+        }
+        self.events.remove_body(handle, |pair| sensor_first(&self.bodies, pair));
+        if let Some(proxy) = self.proxies.get_mut(handle.0).and_then(Option::take) {
+            self.broadphase.remove(proxy);
+        }
+        self.bodies.get_mut(handle.0)?.take()
+    }
+
+    pub fn body(&self, handle: BodyHandle) -> Option<&RigidBody> {
+        self.bodies.get(handle.0)?.as_ref()
+    }
+
+    pub fn body_mut(&mut self, handle: BodyHandle) -> Option<&mut RigidBody> {
+        self.bodies.get_mut(handle.0)?.as_mut()
+    }
+
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyHandle, &RigidBody)> {
+        self.bodies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, body)| Some((BodyHandle(i), body.as_ref()?)))
+    }
+
+    // Joins two bodies as they are now. None if either is missing or they're
+    // the same body.
+    pub fn add_joint(&mut self, mut joint: Joint) -> Option<JointHandle> {
+        if joint.body_a == joint.body_b {
+            return None;
+        }
+        joint.attach(self.body(joint.body_a)?, self.body(joint.body_b)?);
+        let handle = match self.joints.iter().position(Option::is_none) {
+            Some(i) => {
+                self.joints[i] = Some(joint);
+                JointHandle(i)
+            }
+            None => {
+                self.joints.push(Some(joint));
+                JointHandle(self.joints.len() - 1)
+            }
+        };
+        Some(handle)
+    }
+
+    pub fn remove_joint(&mut self, handle: JointHandle) -> Option<Joint> {
+        self.joints.get_mut(handle.0)?.take()
+    }
+
+    pub fn joint(&self, handle: JointHandle) -> Option<&Joint> {
+        self.joints.get(handle.0)?.as_ref()
+    }
+
+    pub fn joint_mut(&mut self, handle: JointHandle) -> Option<&mut Joint> {
+        self.joints.get_mut(handle.0)?.as_mut()
+    }
+
+    pub fn joints(&self) -> impl Iterator<Item = (JointHandle, &Joint)> {
+        self.joints
+            .iter()
+            .enumerate()
+            .filter_map(|(i, joint)| Some((JointHandle(i), joint.as_ref()?)))
+    }
+
+    // Hinge angle, slider offset or anchor distance of a joint right now.
+    pub fn joint_position(&self, handle: JointHandle) -> Option<f32> {
+        let joint = self.joint(handle)?;
+        joint.position(self.body(joint.body_a)?, self.body(joint.body_b)?)
+    }
+
+    // Fat bounds of every body as of the last step, for queries.
+    pub fn broadphase(&self) -> &DynamicBvh<BodyHandle> {
+        &self.broadphase
+    }
+
+    // Contacts as of the last step.
+    pub fn manifolds(&self) -> impl Iterator<Item = &Manifold> {
+        self.manifolds.values().filter(|manifold| !manifold.points.is_empty())
+    }
+
+    // Collision and trigger events from the last `update` or `step`, in the
+    // order they happened, with `Stayed` ones last.
+    pub fn events(&self) -> &[CollisionEvent] {
+        self.events.events()
+    }
+
+    // Runs as many fixed steps as `dt` seconds cover and returns how many.
+    pub fn update(&mut self, dt: f32) -> u32 {
+        let steps = self.clock.steps(dt, self.timestep, self.max_steps);
+        self.events.begin();
+        for _ in 0..steps {
+            self.advance();
+        }
+        self.events.finish();
+        steps
+    }
+
+    // How far into the next step the accumulated time is, 0..1, for
+    // interpolating what is drawn.
+    pub fn interpolation(&self) -> f32 {
+        self.clock.interpolation(self.timestep)
+    }
+
+    pub fn step(&mut self) {
+        self.events.begin();
+        self.advance();
+        self.events.finish();
+    }
+
+    fn advance(&mut self) {
+        let dt = self.timestep;
+        let overlapping = self.collide();
+        for body in self.bodies.iter_mut().flatten() {
+            body.integrate_velocity(self.gravity, dt);
+        }
+        self.solve(dt);
+        self.record_events(overlapping);
+        for body in self.bodies.iter_mut().flatten() {
+            body.integrate_position(dt);
+        }
+        self.update_broadphase();
+    }
+
+    // Nearest body on `mask` that `ray` hits. Bodies are found through the
+    // broadphase, so one moved by hand is only seen where it was after the
+    // last step.
+    pub fn raycast(&self, ray: &Ray, max_distance: f32, mask: Layers) -> Option<(BodyHandle, RayHit)> {
+        self.raycast_where(ray, max_distance, |body| body.layers.intersects(mask))
+    }
+
+    // `raycast` passing through sensors, like `shape_cast`.
+    pub fn raycast_solid(&self, ray: &Ray, max_distance: f32, mask: Layers) -> Option<(BodyHandle, RayHit)> {
+        self.raycast_where(ray, max_distance, |body| !body.sensor && body.layers.intersects(mask))
+    }
+
+    fn raycast_where(
+        &self,
+        ray: &Ray,
+        max_distance: f32,
+        filter: impl Fn(&RigidBody) -> bool,
+    ) -> Option<(BodyHandle, RayHit)> {
+        let hit = |handle: BodyHandle| {
+            let body = self.body(handle).filter(|body| filter(body))?;
+            body.shape.raycast(body.position, body.rotation, ray, max_distance)
+        };
+        let (proxy, _) = self
+            .broadphase
+            .raycast(ray, max_distance, |_, handle| hit(*handle).map(|hit| hit.distance))?;
+        let handle = *self.broadphase.get(proxy)?;
+        Some((handle, hit(handle)?))
+    }
+
+    // Nearest body on `mask` that `shape` touches when swept from `position`
+    // along unit `direction`. Seen through the broadphase like `raycast`;
+    // sensors are passed through.
+    pub fn shape_cast(
+        &self,
+        shape: &Shape,
+        position: uv::Vec3,
+        rotation: uv::Rotor3,
+        direction: uv::Vec3,
+        max_distance: f32,
+        mask: Layers,
+    ) -> Option<(BodyHandle, ShapeHit)> {
+        let end = position + direction * max_distance;
+        let swept = shape.aabb(position, rotation).union(&shape.aabb(end, rotation));
+        let mut best: Option<(BodyHandle, ShapeHit)> = None;
+        self.broadphase.query(&swept, |_, handle| {
+            let Some(body) = self.body(*handle).filter(|body| !body.sensor && body.layers.intersects(mask)) else {
+                return;
+            };
+            let limit = best.map_or(max_distance, |(_, hit)| hit.distance);
+            let Some(hit) = shape.cast(position, rotation, direction, limit, &body.shape, body.position, body.rotation)
+            else {
+                return;
+            };
+            // Ties go to the lower handle so the query order doesn't matter.
+            if best.is_none_or(|(b, h)| hit.distance < h.distance || hit.distance == h.distance && *handle < b) {
+                best = Some((*handle, hit));
+            }
+        });
+        best
+    }
+
+    // Contacts between `shape` and the bodies on `mask` it touches or is
+    // within `CONTACT_MARGIN` of, normals pointing from the shape to the
+    // body, sensors aside. For pushing things that aren't bodies out of the
+    // world.
+    pub fn shape_contacts(
+        &self,
+        shape: &Shape,
+        position: uv::Vec3,
+        rotation: uv::Rotor3,
+        mask: Layers,
+    ) -> Vec<(BodyHandle, Contact)> {
+        let probe = RigidBody::kinematic(shape.clone())
+            .with_position(position)
+            .with_rotation(rotation);
+        let mut found = Vec::new();
+        self.broadphase.query(&probe.aabb().expanded(CONTACT_MARGIN), |_, handle| {
+            if let Some(body) = self.body(*handle).filter(|body| !body.sensor && body.layers.intersects(mask)) {
+                found.extend(contact::contacts(&probe, body).into_iter().map(|(_, contact)| (*handle, contact)));
+            }
+        });
+        found.sort_by_key(|(handle, _)| *handle);
+        found
+    }
+
+    fn update_broadphase(&mut self) {
+        update_broadphase(&mut self.broadphase, &self.bodies, &self.proxies);
+    }
+
+    // Pairs of bodies whose bounds overlap and that can affect each other.
+    fn candidate_pairs(&mut self) -> Vec<Pair> {
+        // Bodies may have been moved by hand since the last step.
+        self.update_broadphase();
+        let joined: BTreeSet<Pair> = self
+            .joints
+            .iter()
+            .flatten()
+            .filter(|joint| !joint.collide_connected && !joint.is_broken())
+            .map(|joint| (joint.body_a.min(joint.body_b), joint.body_a.max(joint.body_b)))
+            .collect();
+        let mut pairs = candidate_pairs(&self.broadphase, &self.bodies);
+        pairs.retain(|pair| !joined.contains(pair));
+        pairs
+    }
+
+    // Updates the contact manifolds and returns the sensor pairs that
+    // overlap, which get no manifolds.
+    fn collide(&mut self) -> BTreeSet<Pair> {
+        let pairs = self.candidate_pairs();
+        let mut previous = std::mem::take(&mut self.manifolds);
+        let mut manifolds = BTreeMap::new();
+        let mut overlapping = BTreeSet::new();
+        for (handle_a, handle_b) in pairs {
+            let (Some(a), Some(b)) = (self.body(handle_a), self.body(handle_b)) else {
+                continue;
+            };
+            if a.sensor || b.sensor {
+                if contact::overlaps(a, b) {
+                    overlapping.insert((handle_a, handle_b));
+                }
+                continue;
+            }
+            let mut touched = Vec::new();
+            for (feature, found) in contact::contacts(a, b) {
+                let key = (handle_a, handle_b, feature);
+                let mut manifold = previous
+                    .remove(&key)
+                    .unwrap_or_else(|| Manifold::new(handle_a, handle_b, a, b));
+                manifold.refresh(a, b);
+                manifold.add(found, a, b);
+                touched.push(feature);
+                manifolds.insert(key, manifold);
+            }
+            // Manifolds that found no new contact keep their points while
+            // they still hold.
+            let stale: Vec<ManifoldKey> = previous
+                .range((handle_a, handle_b, 0)..=(handle_a, handle_b, u32::MAX))
+                .map(|(key, _)| *key)
+                .filter(|key| !touched.contains(&key.2))
+                .collect();
+            for key in stale {
+                let mut manifold = previous.remove(&key).unwrap();
+                manifold.refresh(a, b);
+                if !manifold.points.is_empty() {
+                    manifolds.insert(key, manifold);
+                }
+            }
+        }
+        self.manifolds = manifolds;
+        overlapping
+    }
+
+    // Hands what touches after this step's solve to the event queue.
+    fn record_events(&mut self, overlapping: BTreeSet<Pair>) {
+        let mut touching: BTreeMap<Pair, Collision> = BTreeMap::new();
+        for manifold in self.manifolds() {
+            touching
+                .entry((manifold.body_a, manifold.body_b))
+                .or_insert_with(|| Collision::new(manifold.body_a, manifold.body_b))
+                .add(manifold);
+        }
+        self.events.record(touching, overlapping, |pair| sensor_first(&self.bodies, pair));
+    }
+
+    fn solve(&mut self, dt: f32) {
+        let mut solver_bodies: Vec<SolverBody> = self
+            .bodies
+            .iter()
+            .map(|body| match body {
+                Some(body) => SolverBody::new(body),
+                None => SolverBody::new(&RigidBody::fixed(super::shape::Shape::sphere(0.0))),
+            })
+            .collect();
+        let mut manifolds: Vec<&mut Manifold> = self.manifolds.values_mut().collect();
+        let solver = ContactSolver::new(
+            &manifolds,
+            |manifold| (manifold.body_a.0, manifold.body_b.0),
+            &mut solver_bodies,
+            dt,
+        );
+        let joints: Vec<_> = self
+            .joints
+            .iter()
+            .enumerate()
+            .filter_map(|(i, joint)| {
+                let joint = joint.as_ref().filter(|joint| !joint.is_broken())?;
+                Some((i, joint, joint.body_a.0, joint.body_b.0))
+            })
+            .collect();
+        let mut joint_solver = JointSolver::new(&joints, &self.bodies, &mut solver_bodies, dt);
+        for _ in 0..self.iterations {
+            joint_solver.solve(&mut solver_bodies);
+            solver.solve(&mut manifolds, &mut solver_bodies);
+        }
+        joint_solver.finish(&mut self.joints, dt);
+        for (body, solved) in self.bodies.iter_mut().zip(&solver_bodies) {
+            if let Some(body) = body.as_mut().filter(|body| body.is_dynamic()) {
+                body.linear_velocity = solved.linear;
+                body.angular_velocity = solved.angular;
+            }
+        }
+    }
+}
+
+// A body as far as the stepping shared with `physics2d::world` cares.
+pub(crate) trait WorldBody {
+    fn kind(&self) -> BodyKind;
+    fn is_sensor(&self) -> bool;
+    // Whether their layers and masks let the two touch.
+    fn can_collide(&self, other: &Self) -> bool;
+    // Bounds grown by the contact margin, as the broadphase holds them.
+    fn fat_aabb(&self) -> Aabb;
+}
+
+impl WorldBody for RigidBody {
+    fn kind(&self) -> BodyKind {
+        self.kind
+    }
+
+    fn is_sensor(&self) -> bool {
+        self.sensor
+    }
+
+    fn can_collide(&self, other: &Self) -> bool {
+        self.collides_with(other)
+    }
+
+    fn fat_aabb(&self) -> Aabb {
+        self.aabb().expanded(CONTACT_MARGIN)
+    }
+}
+
+// Time carried over between `update`s of a fixed-step world.
+#[derive(Debug, Default)]
+pub(crate) struct StepClock {
+    accumulator: f32,
+}
+
+impl StepClock {
+    // Adds `dt` seconds and takes out the steps now due, at most
+    // `max_steps`. Time past those is dropped, so a long frame can't
+    // snowball.
+    pub(crate) fn steps(&mut self, dt: f32, timestep: f32, max_steps: u32) -> u32 {
+        self.accumulator += dt;
+        let mut steps = 0;
+        while self.accumulator >= timestep && steps < max_steps {
+            self.accumulator -= timestep;
+            steps += 1;
+        }
+        if steps == max_steps {
+            self.accumulator = self.accumulator.min(timestep);
+        }
+        steps
+    }
+
+    pub(crate) fn interpolation(&self, timestep: f32) -> f32 {
+        (self.accumulator / timestep).clamp(0.0, 1.0)
+    }
+}
+
+pub(crate) fn update_broadphase<B: WorldBody>(
+    broadphase: &mut DynamicBvh<BodyHandle>,
+    bodies: &[Option<B>],
+    proxies: &[Option<ProxyId>],
+) {
+    for (body, proxy) in bodies.iter().zip(proxies) {
+        if let (Some(body), Some(proxy)) = (body, proxy) {
+            broadphase.update(*proxy, body.fat_aabb());
+        }
+    }
+}
+
+// Pairs the broadphase finds whose bounds still overlap and that can affect
+// each other, sorted.
+pub(crate) fn candidate_pairs<B: WorldBody>(broadphase: &DynamicBvh<BodyHandle>, bodies: &[Option<B>]) -> Vec<Pair> {
+    let body = |handle: BodyHandle| bodies.get(handle.0)?.as_ref();
+    let mut pairs: Vec<_> = broadphase
+        .overlap_pairs()
+        .into_iter()
+        .filter_map(|(x, y)| {
+            let (a, b) = (*broadphase.get(x)?, *broadphase.get(y)?);
+            let (body_a, body_b) = (body(a)?, body(b)?);
+            let touching = body_a.fat_aabb().overlaps(&body_b.fat_aabb());
+            // Sensors notice anything that can move, solid bodies only what
+            // they can push.
+            let dynamic = |body: &B| body.kind() == BodyKind::Dynamic;
+            let moving = |body: &B| body.kind() != BodyKind::Static;
+            let interacts = dynamic(body_a)
+                || dynamic(body_b)
+                || (body_a.is_sensor() || body_b.is_sensor()) && (moving(body_a) || moving(body_b));
+            (interacts && body_a.can_collide(body_b) && touching).then_some((a.min(b), a.max(b)))
+        })
+        .collect();
+    pairs.sort();
+    pairs
+}
+
+// Orders a trigger pair sensor first.
+pub(crate) fn sensor_first<B: WorldBody>(bodies: &[Option<B>], (a, b): Pair) -> Pair {
+    let sensor = |handle: BodyHandle| bodies.get(handle.0).and_then(Option::as_ref).is_some_and(B::is_sensor);
+    if sensor(b) && !sensor(a) {
+        (b, a)
+    } else {
+        (a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Ground with its top at y = 0.
+    fn ground(world: &mut PhysicsWorld) -> BodyHandle {
+        let ground = RigidBody::fixed(Shape::cuboid(uv::Vec3::new(10.0, 0.5, 10.0)));
+        world.add_body(ground.with_position(uv::Vec3::new(0.0, -0.5, 0.0)))
+    }
+
+    fn undamped(mut body: RigidBody) -> RigidBody {
+        body.linear_damping = 0.0;
+        body.angular_damping = 0.0;
+        body
+    }
+
+    // Boxes and balls tumbling onto the ground and each other.
+    fn pile() -> PhysicsWorld {
+        let mut world = PhysicsWorld::new();
+        ground(&mut world);
+        for i in 0..12 {
+            let shape = if i % 3 == 0 {
+                Shape::sphere(0.3)
+            } else {
+                Shape::cuboid(uv::Vec3::new(0.3, 0.2, 0.25))
+            };
+            let position = uv::Vec3::new((i % 4) as f32 * 0.35 - 0.5, 0.5 + i as f32 * 0.45, (i % 3) as f32 * 0.2);
+            let rotation = uv::Rotor3::from_euler_angles(0.1 * i as f32, 0.3 * i as f32, 0.2);
+            world.add_body(RigidBody::dynamic(shape).with_position(position).with_rotation(rotation));
+        }
+        world
+    }
+
+    #[test]
+    fn identical_worlds_step_identically() {
+        let (mut first, mut second) = (pile(), pile());
+        for _ in 0..300 {
+            first.step();
+            second.step();
+        }
+        let bits = |world: &PhysicsWorld| {
+            world
+                .bodies()
+                .flat_map(|(_, body)| {
+                    let r = body.rotation;
+                    let values = [r.s, r.bv.xy, r.bv.xz, r.bv.yz];
+                    [body.position, body.linear_velocity, body.angular_velocity]
+                        .into_iter()
+                        .flat_map(|v| [v.x, v.y, v.z])
+                        .chain(values)
+                        .map(f32::to_bits)
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(bits(&first), bits(&second));
+        // Something actually happened.
+        assert!(first.manifolds().count() > 3);
+    }
+
+    #[test]
+    fn stacked_boxes_come_to_rest() {
+        let mut world = PhysicsWorld::new();
+        ground(&mut world);
+        let boxes: Vec<_> = (0..3)
+            .map(|i| {
+                let body = RigidBody::dynamic(Shape::cuboid(uv::Vec3::broadcast(0.5)))
+                    .with_position(uv::Vec3::new(0.0, 0.5 + i as f32 * 1.01, 0.0));
+                world.add_body(body)
+            })
+            .collect();
+        for _ in 0..120 {
+            world.step();
+        }
+        let settled: Vec<_> = boxes.iter().map(|&handle| world.body(handle).unwrap().position).collect();
+        for _ in 0..120 {
+            world.step();
+        }
+        for (i, (&handle, before)) in boxes.iter().zip(settled).enumerate() {
+            let body = world.body(handle).unwrap();
+            let drift = (body.position - before).mag();
+            assert!(drift < 1e-3, "box {} drifted from {:?} to {:?}", i, before, body.position);
+            assert!(body.linear_velocity.mag() < 0.02, "box {} still moving {:?}", i, body.linear_velocity);
+            // Sunk no further than the solver's slop per contact.
+            let expected = 0.5 + i as f32;
+            assert!((body.position.y - expected).abs() < 0.03, "box {} at {}", i, body.position.y);
+        }
+    }
+
+    #[test]
+    fn restitution_sets_the_bounce_height() {
+        // Peak height after the first bounce of a ball dropped from 1 m.
+        let bounce = |restitution: f32| {
+            let mut world = PhysicsWorld::new();
+            ground(&mut world);
+            let ball = undamped(RigidBody::dynamic(Shape::sphere(0.25)))
+                .with_position(uv::Vec3::new(0.0, 1.25, 0.0))
+                .with_restitution(restitution);
+            let ball = world.add_body(ball);
+            let mut bounced = false;
+            let mut peak = 0.0f32;
+            for _ in 0..120 {
+                world.step();
+                let body = world.body(ball).unwrap();
+                bounced |= body.linear_velocity.y > 0.0;
+                if bounced {
+                    peak = peak.max(body.position.y - 0.25);
+                }
+            }
+            peak
+        };
+        let elastic = bounce(0.8);
+        assert!((elastic - 0.64).abs() < 0.08, "bounced {}", elastic);
+        let dead = bounce(0.0);
+        assert!(dead < 0.01, "bounced {}", dead);
+    }
+}
@@ -0,0 +1,284 @@
+use ultraviolet as uv;
+
+use crate::physics::body::BodyKind;
+use crate::physics::ray::Layers;
+use crate::physics::spatial_hash::Rect;
+
+use super::shape::{cross, rotate, Shape};
+
+#[derive(Debug, Clone)]
+pub struct RigidBody {
+    pub kind: BodyKind,
+    pub shape: Shape,
+    pub position: uv::Vec2,
+    // Radians, counter-clockwise.
+    pub rotation: f32,
+    pub linear_velocity: uv::Vec2,
+    pub angular_velocity: f32,
+    pub friction: f32,
+    pub restitution: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub gravity_scale: f32,
+    // What queries like `PhysicsWorld::raycast` and other bodies see it as.
+    pub layers: Layers,
+    // Layers it collides with. A pair only collides, and only reports
+    // events, when each is on a layer in the other's mask.
+    pub collision_mask: Layers,
+    // Reports bodies entering and leaving it instead of colliding.
+    pub sensor: bool,
+    // Swept against what it passes on the way each step, so it can't skip
+    // through thin walls however fast it goes. For small fast bodies like
+    // bullets; it costs more than ordinary bodies.
+    pub bullet: bool,
+    // Makes it a one-way platform: bodies pass through moving along this
+    // unit direction (in local space, so it turns with the body) and land
+    // on it coming the other way.
+    pub one_way: Option<uv::Vec2>,
+    mass: f32,
+    inv_mass: f32,
+    inv_inertia: f32,
+    force: uv::Vec2,
+    torque: f32,
+    kinematic_target: Option<(uv::Vec2, f32)>,
+}
+
+impl RigidBody {
+    pub fn new(kind: BodyKind, shape: Shape) -> Self {
+        let mut body = Self {
+            kind,
+            shape,
+            position: uv::Vec2::zero(),
+            rotation: 0.0,
+            linear_velocity: uv::Vec2::zero(),
+            angular_velocity: 0.0,
+            friction: 0.5,
+            restitution: 0.0,
+            linear_damping: 0.01,
+            angular_damping: 0.05,
+            gravity_scale: 1.0,
+            layers: Layers::ALL,
+            collision_mask: Layers::ALL,
+            sensor: false,
+            bullet: false,
+            one_way: None,
+            mass: 0.0,
+            inv_mass: 0.0,
+            inv_inertia: 0.0,
+            force: uv::Vec2::zero(),
+            torque: 0.0,
+            kinematic_target: None,
+        };
+        body.set_density(1.0);
+        body
+    }
+
+    pub fn dynamic(shape: Shape) -> Self {
+        Self::new(BodyKind::Dynamic, shape)
+    }
+
+    pub fn kinematic(shape: Shape) -> Self {
+        Self::new(BodyKind::Kinematic, shape)
+    }
+
+    pub fn fixed(shape: Shape) -> Self {
+        Self::new(BodyKind::Static, shape)
+    }
+
+    pub fn with_position(mut self, position: uv::Vec2) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_velocity(mut self, velocity: uv::Vec2) -> Self {
+        self.linear_velocity = velocity;
+        self
+    }
+
+    pub fn with_angular_velocity(mut self, angular_velocity: f32) -> Self {
+        self.angular_velocity = angular_velocity;
+        self
+    }
+
+    pub fn with_density(mut self, density: f32) -> Self {
+        self.set_density(density);
+        self
+    }
+
+    pub fn with_mass(mut self, mass: f32) -> Self {
+        self.set_mass(mass);
+        self
+    }
+
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+
+    pub fn with_restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn with_gravity_scale(mut self, gravity_scale: f32) -> Self {
+        self.gravity_scale = gravity_scale;
+        self
+    }
+
+    pub fn with_layers(mut self, layers: Layers) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    pub fn with_collision_mask(mut self, mask: Layers) -> Self {
+        self.collision_mask = mask;
+        self
+    }
+
+    pub fn with_sensor(mut self, sensor: bool) -> Self {
+        self.sensor = sensor;
+        self
+    }
+
+    pub fn with_bullet(mut self, bullet: bool) -> Self {
+        self.bullet = bullet;
+        self
+    }
+
+    // Passable along `direction`, e.g. up for a platform jumped onto from
+    // below.
+    pub fn with_one_way(mut self, direction: uv::Vec2) -> Self {
+        self.one_way = Some(direction.normalized());
+        self
+    }
+
+    pub fn collides_with(&self, other: &RigidBody) -> bool {
+        self.layers.intersects(other.collision_mask) && other.layers.intersects(self.collision_mask)
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.kind == BodyKind::Dynamic
+    }
+
+    pub fn mass(&self) -> f32 {
+        self.mass
+    }
+
+    // Zero for kinematic and static bodies.
+    pub fn inv_mass(&self) -> f32 {
+        if self.is_dynamic() {
+            self.inv_mass
+        } else {
+            0.0
+        }
+    }
+
+    pub fn inv_inertia(&self) -> f32 {
+        if self.is_dynamic() {
+            self.inv_inertia
+        } else {
+            0.0
+        }
+    }
+
+    pub fn set_density(&mut self, density: f32) {
+        let (mass, inertia) = self.shape.mass_properties(density);
+        self.set_mass_properties(mass, inertia);
+    }
+
+    // Keeps the shape's mass distribution.
+    pub fn set_mass(&mut self, mass: f32) {
+        let (shape_mass, inertia) = self.shape.mass_properties(1.0);
+        self.set_mass_properties(mass, inertia * (mass / shape_mass.max(f32::EPSILON)));
+    }
+
+    fn set_mass_properties(&mut self, mass: f32, inertia: f32) {
+        let invert = |x: f32| if x > 0.0 { 1.0 / x } else { 0.0 };
+        self.mass = mass;
+        self.inv_mass = invert(mass);
+        self.inv_inertia = invert(inertia);
+    }
+
+    pub fn aabb(&self) -> Rect {
+        self.shape.aabb(self.position, self.rotation)
+    }
+
+    // The one-way direction in world space.
+    pub fn one_way_direction(&self) -> Option<uv::Vec2> {
+        self.one_way.map(|direction| rotate(direction, self.rotation))
+    }
+
+    pub fn velocity_at(&self, point: uv::Vec2) -> uv::Vec2 {
+        let r = point - self.position;
+        self.linear_velocity + uv::Vec2::new(-r.y, r.x) * self.angular_velocity
+    }
+
+    // Forces and torques last until the next step.
+    pub fn apply_force(&mut self, force: uv::Vec2) {
+        self.force += force;
+    }
+
+    pub fn apply_force_at(&mut self, force: uv::Vec2, point: uv::Vec2) {
+        self.force += force;
+        self.torque += cross(point - self.position, force);
+    }
+
+    pub fn apply_torque(&mut self, torque: f32) {
+        self.torque += torque;
+    }
+
+    pub fn apply_impulse(&mut self, impulse: uv::Vec2) {
+        self.linear_velocity += impulse * self.inv_mass();
+    }
+
+    pub fn apply_impulse_at(&mut self, impulse: uv::Vec2, point: uv::Vec2) {
+        self.linear_velocity += impulse * self.inv_mass();
+        self.angular_velocity += self.inv_inertia() * cross(point - self.position, impulse);
+    }
+
+    // Where a kinematic body should be after the next step; its velocity is
+    // set to get it there so contacts see the motion.
+    pub fn set_kinematic_target(&mut self, position: uv::Vec2, rotation: f32) {
+        self.kinematic_target = Some((position, rotation));
+    }
+
+    pub(crate) fn integrate_velocity(&mut self, gravity: uv::Vec2, dt: f32) {
+        match self.kind {
+            BodyKind::Dynamic => {
+                let acceleration = self.force * self.inv_mass + gravity * self.gravity_scale;
+                self.linear_velocity += acceleration * dt;
+                self.angular_velocity += self.inv_inertia * self.torque * dt;
+                self.linear_velocity *= 1.0 / (1.0 + dt * self.linear_damping);
+                self.angular_velocity *= 1.0 / (1.0 + dt * self.angular_damping);
+            }
+            BodyKind::Kinematic => {
+                if let Some((position, rotation)) = self.kinematic_target.take() {
+                    self.linear_velocity = (position - self.position) / dt;
+                    // The short way round.
+                    let turn = (rotation - self.rotation + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU)
+                        - std::f32::consts::PI;
+                    self.angular_velocity = turn / dt;
+                }
+            }
+            BodyKind::Static => {
+                self.linear_velocity = uv::Vec2::zero();
+                self.angular_velocity = 0.0;
+            }
+        }
+        self.force = uv::Vec2::zero();
+        self.torque = 0.0;
+    }
+
+    pub(crate) fn integrate_position(&mut self, dt: f32) {
+        if self.kind == BodyKind::Static {
+            return;
+        }
+        self.position += self.linear_velocity * dt;
+        self.rotation += self.angular_velocity * dt;
+    }
+}
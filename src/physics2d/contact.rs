@@ -0,0 +1,425 @@
+// Contacts between 2D shapes. Each step finds the whole manifold afresh (up
+// to two points, by clipping the faces against each other), and points are
+// matched to last step's by the features that met to carry their impulses
+// over.
+use ultraviolet as uv;
+
+use crate::physics::body::BodyHandle;
+use crate::physics::spatial_hash::Rect;
+
+use super::body::RigidBody;
+use super::shape::{Convex, Part};
+
+// Contacts are kept a little before the shapes touch, so resting bodies
+// don't flicker in and out of contact.
+pub(crate) const CONTACT_MARGIN: f32 = 0.01;
+// The first shape's face is preferred as the reference by this much, so
+// near ties don't flip from step to step.
+const REFERENCE_TOLERANCE: f32 = 0.0005;
+// A contact at a chain join only counts if its normal points away from the
+// next segment by at least this much; otherwise that segment covers it.
+const JOIN_TOLERANCE: f32 = 0.05;
+
+#[derive(Debug, Copy, Clone)]
+pub struct ContactPoint {
+    // World space, halfway between the surfaces.
+    pub point: uv::Vec2,
+    // Positive when the shapes overlap.
+    pub depth: f32,
+    // Which features met, to match the point up with last step's.
+    pub id: u32,
+    // Impulses from the last step, reused to warm start the solver.
+    pub normal_impulse: f32,
+    pub tangent_impulse: f32,
+}
+
+// Up to two points where two bodies touch. The normal points from `body_a`
+// to `body_b`.
+#[derive(Debug, Clone)]
+pub struct Manifold {
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+    pub normal: uv::Vec2,
+    pub points: Vec<ContactPoint>,
+    pub friction: f32,
+    pub restitution: f32,
+}
+
+impl Manifold {
+    pub(crate) fn new(body_a: BodyHandle, body_b: BodyHandle, a: &RigidBody, b: &RigidBody, contact: Contact) -> Self {
+        Self {
+            body_a,
+            body_b,
+            normal: contact.normal,
+            points: contact.points,
+            // The usual geometric mean, and the bouncier of the two.
+            friction: (a.friction * b.friction).sqrt(),
+            restitution: a.restitution.max(b.restitution),
+        }
+    }
+
+    // Starts points that met the same way last step with their impulses.
+    pub(crate) fn warm_start(&mut self, previous: &Manifold) {
+        for point in &mut self.points {
+            if let Some(old) = previous.points.iter().find(|old| old.id == point.id) {
+                point.normal_impulse = old.normal_impulse;
+                point.tangent_impulse = old.tangent_impulse;
+            }
+        }
+    }
+}
+
+// Contact between two convex pieces found this step, normal from the first
+// to the second.
+#[derive(Debug, Clone)]
+pub(crate) struct Contact {
+    pub normal: uv::Vec2,
+    pub points: Vec<ContactPoint>,
+}
+
+impl Contact {
+    fn new(normal: uv::Vec2) -> Self {
+        Self {
+            normal,
+            points: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, point: uv::Vec2, depth: f32, id: u32) {
+        self.points.push(ContactPoint {
+            point,
+            depth,
+            id,
+            normal_impulse: 0.0,
+            tangent_impulse: 0.0,
+        });
+    }
+
+    fn flipped(mut self) -> Self {
+        self.normal = -self.normal;
+        self
+    }
+}
+
+// Contacts between two bodies, each tagged with the pieces it came from
+// (chain segments, 0 otherwise) so the manifolds can be kept apart.
+pub(crate) fn contacts(a: &RigidBody, b: &RigidBody) -> Vec<((u32, u32), Contact)> {
+    let near_a = expanded(a.aabb());
+    let near_b = expanded(b.aabb());
+    let parts_a = a.shape.parts(a.position, a.rotation, &near_b);
+    let parts_b = b.shape.parts(b.position, b.rotation, &near_a);
+    let mut found = Vec::new();
+    for part_a in &parts_a {
+        for part_b in &parts_b {
+            let Some(mut contact) = convex_contact(&part_a.convex, &part_b.convex) else {
+                continue;
+            };
+            smooth_joins(&mut contact, part_a, -1.0);
+            smooth_joins(&mut contact, part_b, 1.0);
+            if !contact.points.is_empty() {
+                found.push(((part_a.index, part_b.index), contact));
+            }
+        }
+    }
+    found
+}
+
+// Whether two bodies overlap at all.
+pub(crate) fn overlaps(a: &RigidBody, b: &RigidBody) -> bool {
+    let parts_a = a.shape.parts(a.position, a.rotation, &expanded(b.aabb()));
+    let parts_b = b.shape.parts(b.position, b.rotation, &expanded(a.aabb()));
+    parts_a
+        .iter()
+        .any(|x| parts_b.iter().any(|y| distance(&x.convex, &y.convex) < 0.0))
+}
+
+fn expanded(rect: Rect) -> Rect {
+    let margin = uv::Vec2::broadcast(CONTACT_MARGIN);
+    Rect::new(rect.min - margin, rect.max + margin)
+}
+
+// Drops points where something meets the join between two chain segments
+// edge-on, which would otherwise catch things sliding along the chain.
+// `side` is 1 if the contact normal points into `part`, -1 if out of it.
+fn smooth_joins(contact: &mut Contact, part: &Part, side: f32) {
+    let Convex::Polygon { vertices, normals } = &part.convex else {
+        return;
+    };
+    if part.neighbours == [None, None] || contact.normal.dot(normals[0]).abs() > 0.999 {
+        return;
+    }
+    // From the segment towards what it touches.
+    let normal = contact.normal * -side;
+    contact.points.retain(|point| {
+        let (end, neighbour) = if (point.point - vertices[0]).mag_sq() < (point.point - vertices[1]).mag_sq() {
+            (vertices[0], part.neighbours[0])
+        } else {
+            (vertices[1], part.neighbours[1])
+        };
+        neighbour.is_none_or(|next| normal.dot((next - end).normalized()) <= JOIN_TOLERANCE)
+    });
+}
+
+// Contact between two convex pieces within `CONTACT_MARGIN` of touching.
+pub(crate) fn convex_contact(a: &Convex, b: &Convex) -> Option<Contact> {
+    match (a, b) {
+        (
+            Convex::Circle {
+                center: center_a,
+                radius: radius_a,
+            },
+            Convex::Circle {
+                center: center_b,
+                radius: radius_b,
+            },
+        ) => {
+            let offset = *center_b - *center_a;
+            let distance = offset.mag();
+            let radii = radius_a + radius_b;
+            if distance > radii + CONTACT_MARGIN {
+                return None;
+            }
+            let normal = if distance > 1e-6 {
+                offset / distance
+            } else {
+                uv::Vec2::unit_y()
+            };
+            let surface_a = *center_a + normal * *radius_a;
+            let surface_b = *center_b - normal * *radius_b;
+            let mut contact = Contact::new(normal);
+            contact.push((surface_a + surface_b) * 0.5, radii - distance, 0);
+            Some(contact)
+        }
+        (Convex::Polygon { vertices, normals }, Convex::Circle { center, radius }) => {
+            polygon_circle(vertices, normals, *center, *radius)
+        }
+        (Convex::Circle { center, radius }, Convex::Polygon { vertices, normals }) => {
+            Some(polygon_circle(vertices, normals, *center, *radius)?.flipped())
+        }
+        (
+            Convex::Polygon {
+                vertices: vertices_a,
+                normals: normals_a,
+            },
+            Convex::Polygon {
+                vertices: vertices_b,
+                normals: normals_b,
+            },
+        ) => polygon_polygon((vertices_a, normals_a), (vertices_b, normals_b)),
+    }
+}
+
+// Normal from the polygon to the circle.
+fn polygon_circle(vertices: &[uv::Vec2], normals: &[uv::Vec2], center: uv::Vec2, radius: f32) -> Option<Contact> {
+    let (separation, face) = (0..normals.len())
+        .map(|i| (normals[i].dot(center - vertices[i]), i))
+        .max_by(|x, y| x.0.total_cmp(&y.0))?;
+    if separation > radius + CONTACT_MARGIN {
+        return None;
+    }
+    let (v1, v2) = (vertices[face], vertices[(face + 1) % vertices.len()]);
+    // Beyond either end of the face the nearest feature is the corner.
+    let corner = if separation > 0.0 && (center - v1).dot(v2 - v1) < 0.0 {
+        Some(v1)
+    } else if separation > 0.0 && (center - v2).dot(v1 - v2) < 0.0 {
+        Some(v2)
+    } else {
+        None
+    };
+    let (normal, distance) = match corner {
+        Some(corner) => {
+            let offset = center - corner;
+            let distance = offset.mag();
+            if distance > radius + CONTACT_MARGIN {
+                return None;
+            }
+            (offset / distance.max(1e-6), distance)
+        }
+        None => (normals[face], separation),
+    };
+    let mut contact = Contact::new(normal);
+    let surface_polygon = center - normal * distance;
+    let surface_circle = center - normal * radius;
+    contact.push((surface_polygon + surface_circle) * 0.5, radius - distance, 0);
+    Some(contact)
+}
+
+type Hull<'a> = (&'a [uv::Vec2], &'a [uv::Vec2]);
+
+// Face of `a` that `b` is furthest outside of, and how far. Negative when
+// they overlap along every face.
+fn max_separation((vertices_a, normals_a): Hull, (vertices_b, _): Hull) -> (f32, usize) {
+    let mut best = (f32::MIN, 0);
+    for (i, (normal, vertex)) in normals_a.iter().zip(vertices_a).enumerate() {
+        let separation = vertices_b
+            .iter()
+            .map(|v| normal.dot(*v - *vertex))
+            .fold(f32::MAX, f32::min);
+        if separation > best.0 {
+            best = (separation, i);
+        }
+    }
+    best
+}
+
+// Separating axes, then the face of the other most against the reference
+// face clipped to its sides.
+fn polygon_polygon(a: Hull, b: Hull) -> Option<Contact> {
+    let (separation_a, face_a) = max_separation(a, b);
+    if separation_a > CONTACT_MARGIN {
+        return None;
+    }
+    let (separation_b, face_b) = max_separation(b, a);
+    if separation_b > CONTACT_MARGIN {
+        return None;
+    }
+    let flip = separation_b > separation_a + REFERENCE_TOLERANCE;
+    let (reference, incident, face) = if flip { (b, a, face_b) } else { (a, b, face_a) };
+    let normal = reference.1[face];
+    let v1 = reference.0[face];
+    let v2 = reference.0[(face + 1) % reference.0.len()];
+
+    let incident_face = (0..incident.1.len())
+        .min_by(|&x, &y| normal.dot(incident.1[x]).total_cmp(&normal.dot(incident.1[y])))?;
+    let count = incident.0.len();
+    let mut points = vec![
+        (incident.0[incident_face], 0u32),
+        (incident.0[(incident_face + 1) % count], 1u32),
+    ];
+    let tangent = (v2 - v1).normalized();
+    points = clip(&points, -tangent, -tangent.dot(v1));
+    if points.len() < 2 {
+        return None;
+    }
+    points = clip(&points, tangent, tangent.dot(v2));
+    if points.len() < 2 {
+        return None;
+    }
+
+    let mut contact = Contact::new(if flip { -normal } else { normal });
+    for (point, tag) in points {
+        let separation = normal.dot(point - v1);
+        if separation > CONTACT_MARGIN {
+            continue;
+        }
+        let id = (flip as u32) << 24 | (face as u32) << 16 | (incident_face as u32) << 8 | tag;
+        contact.push(point - normal * (separation * 0.5), -separation, id);
+    }
+    (!contact.points.is_empty()).then_some(contact)
+}
+
+// Keeps the part of a two point segment where `normal.dot(p) <= offset`.
+// A point made by the cut takes the tag of the one it replaces, so a vertex
+// just either side of the plane keeps the same id.
+fn clip(points: &[(uv::Vec2, u32)], normal: uv::Vec2, offset: f32) -> Vec<(uv::Vec2, u32)> {
+    let [(p0, tag0), (p1, tag1)] = [points[0], points[1]];
+    let (d0, d1) = (normal.dot(p0) - offset, normal.dot(p1) - offset);
+    let mut kept = Vec::with_capacity(2);
+    if d0 <= 0.0 {
+        kept.push((p0, tag0));
+    }
+    if d1 <= 0.0 {
+        kept.push((p1, tag1));
+    }
+    if d0 * d1 < 0.0 {
+        let tag = if d0 > 0.0 { tag0 } else { tag1 };
+        kept.push((p0 + (p1 - p0) * (d0 / (d0 - d1)), tag));
+    }
+    kept
+}
+
+// Gap between two convex pieces, negative when they overlap (then only a
+// rough depth).
+pub(crate) fn distance(a: &Convex, b: &Convex) -> f32 {
+    match (a, b) {
+        (Convex::Circle { center: ca, radius: ra }, Convex::Circle { center: cb, radius: rb }) => {
+            (*cb - *ca).mag() - ra - rb
+        }
+        (Convex::Polygon { vertices, normals }, Convex::Circle { center, radius })
+        | (Convex::Circle { center, radius }, Convex::Polygon { vertices, normals }) => {
+            point_distance((vertices, normals), *center) - radius
+        }
+        (
+            Convex::Polygon {
+                vertices: vertices_a,
+                normals: normals_a,
+            },
+            Convex::Polygon {
+                vertices: vertices_b,
+                normals: normals_b,
+            },
+        ) => {
+            let (a, b) = ((&vertices_a[..], &normals_a[..]), (&vertices_b[..], &normals_b[..]));
+            let separation = max_separation(a, b).0.max(max_separation(b, a).0);
+            if separation <= 0.0 {
+                return separation;
+            }
+            let edges_a = vertices_b.iter().map(|v| edge_distance(vertices_a, *v));
+            let edges_b = vertices_a.iter().map(|v| edge_distance(vertices_b, *v));
+            edges_a.chain(edges_b).fold(f32::MAX, f32::min)
+        }
+    }
+}
+
+// Signed distance from a polygon to a point.
+fn point_distance(hull: Hull, point: uv::Vec2) -> f32 {
+    let (vertices, normals) = hull;
+    let separation = (0..normals.len())
+        .map(|i| normals[i].dot(point - vertices[i]))
+        .fold(f32::MIN, f32::max);
+    if separation <= 0.0 && vertices.len() > 2 {
+        return separation;
+    }
+    edge_distance(vertices, point)
+}
+
+// Distance from `point` to the nearest edge of a polygon.
+fn edge_distance(vertices: &[uv::Vec2], point: uv::Vec2) -> f32 {
+    let mut best = f32::MAX;
+    for (i, &a) in vertices.iter().enumerate() {
+        let b = vertices[(i + 1) % vertices.len()];
+        let edge = b - a;
+        let t = ((point - a).dot(edge) / edge.mag_sq().max(f32::EPSILON)).clamp(0.0, 1.0);
+        best = best.min((point - (a + edge * t)).mag());
+    }
+    best
+}
+
+// How far along unit `direction` piece `a` can move before coming within
+// `target` of `b`, up to `max_distance`. Conservative advancement: it never
+// moves further than the gap, so it can't step through anything.
+pub(crate) fn time_of_impact(
+    a: &Convex,
+    b: &Convex,
+    direction: uv::Vec2,
+    max_distance: f32,
+    target: f32,
+) -> Option<f32> {
+    let mut travelled = 0.0;
+    for _ in 0..32 {
+        let moved = translated(a, direction * travelled);
+        let gap = distance(&moved, b);
+        if gap <= target {
+            return Some(travelled);
+        }
+        travelled += gap - target * 0.5;
+        if travelled > max_distance {
+            return None;
+        }
+    }
+    None
+}
+
+fn translated(convex: &Convex, offset: uv::Vec2) -> Convex {
+    match convex {
+        Convex::Circle { center, radius } => Convex::Circle {
+            center: *center + offset,
+            radius: *radius,
+        },
+        Convex::Polygon { vertices, normals } => Convex::Polygon {
+            vertices: vertices.iter().map(|v| *v + offset).collect(),
+            normals: normals.clone(),
+        },
+    }
+}
+
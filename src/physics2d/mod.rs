@@ -0,0 +1,5 @@
+pub mod shape;
+pub mod body;
+pub mod contact;
+pub mod solver;
+pub mod world;
@@ -0,0 +1,349 @@
+// 2D collision shapes, in the body's local space. Bodies turn about their
+// origin, so solid shapes should be centred on it.
+use std::sync::Arc;
+
+use ultraviolet as uv;
+
+use crate::physics::spatial_hash::Rect;
+
+// 2D cross product, the z of the 3D one.
+pub(crate) fn cross(a: uv::Vec2, b: uv::Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// `v` turned counter-clockwise by `angle` radians.
+pub(crate) fn rotate(v: uv::Vec2, angle: f32) -> uv::Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    uv::Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+// Unit normal on the right of an edge, outward for counter-clockwise
+// polygons.
+fn edge_normal(edge: uv::Vec2) -> uv::Vec2 {
+    uv::Vec2::new(edge.y, -edge.x).normalized()
+}
+
+// Convex polygon, counter-clockwise, with each edge's outward normal. Edge
+// `i` runs from vertex `i` to the next.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    vertices: Vec<uv::Vec2>,
+    normals: Vec<uv::Vec2>,
+}
+
+impl Polygon {
+    // Convex hull of `points`. None if they don't enclose any area.
+    pub fn new(points: &[uv::Vec2]) -> Option<Self> {
+        let mut sorted = points.to_vec();
+        sorted.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        sorted.dedup_by(|a, b| (*a - *b).mag_sq() < 1e-10);
+        if sorted.len() < 3 {
+            return None;
+        }
+        // Monotone chain: lower hull left to right, then upper back.
+        let mut hull: Vec<uv::Vec2> = Vec::with_capacity(sorted.len() + 1);
+        for pass in 0..2 {
+            let start = hull.len();
+            let points: Box<dyn Iterator<Item = &uv::Vec2>> = if pass == 0 {
+                Box::new(sorted.iter())
+            } else {
+                Box::new(sorted.iter().rev())
+            };
+            for &point in points {
+                while hull.len() >= start + 2 {
+                    let (a, b) = (hull[hull.len() - 2], hull[hull.len() - 1]);
+                    if cross(b - a, point - a) > 1e-7 {
+                        break;
+                    }
+                    hull.pop();
+                }
+                hull.push(point);
+            }
+            // Each half ends where the other starts.
+            hull.pop();
+        }
+        if hull.len() < 3 {
+            return None;
+        }
+        Some(Self::from_hull(hull))
+    }
+
+    pub fn rectangle(half_extents: uv::Vec2) -> Self {
+        let (x, y) = (half_extents.x, half_extents.y);
+        Self::from_hull(vec![
+            uv::Vec2::new(-x, -y),
+            uv::Vec2::new(x, -y),
+            uv::Vec2::new(x, y),
+            uv::Vec2::new(-x, y),
+        ])
+    }
+
+    // Counter-clockwise and convex already.
+    fn from_hull(vertices: Vec<uv::Vec2>) -> Self {
+        let normals = (0..vertices.len())
+            .map(|i| edge_normal(vertices[(i + 1) % vertices.len()] - vertices[i]))
+            .collect();
+        Self { vertices, normals }
+    }
+
+    pub fn vertices(&self) -> &[uv::Vec2] {
+        &self.vertices
+    }
+
+    pub fn normals(&self) -> &[uv::Vec2] {
+        &self.normals
+    }
+
+    // Area and second moment of area about the origin.
+    fn area_inertia(&self) -> (f32, f32) {
+        let mut area = 0.0;
+        let mut inertia = 0.0;
+        for (i, &a) in self.vertices.iter().enumerate() {
+            let b = self.vertices[(i + 1) % self.vertices.len()];
+            let triangle = cross(a, b) * 0.5;
+            area += triangle;
+            inertia += triangle * (a.dot(a) + a.dot(b) + b.dot(b)) / 6.0;
+        }
+        (area, inertia)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Circle { radius: f32 },
+    Polygon(Arc<Polygon>),
+    // Segments and chains are lines with no inside or mass, for level
+    // geometry on static and kinematic bodies. They collide on both sides.
+    Segment { a: uv::Vec2, b: uv::Vec2 },
+    // Segments joined end to end, and back to the first vertex if `closed`.
+    // Things slide across the joins without catching on them.
+    Chain { vertices: Arc<[uv::Vec2]>, closed: bool },
+}
+
+// One convex piece of a shape in world space, as contacts see it.
+#[derive(Debug, Clone)]
+pub(crate) enum Convex {
+    Circle { center: uv::Vec2, radius: f32 },
+    // Two vertices for a segment, with normals either side.
+    Polygon { vertices: Vec<uv::Vec2>, normals: Vec<uv::Vec2> },
+}
+
+// A piece with the chain vertices either side of it, if it is part of a
+// chain, so the joins can be smoothed over.
+#[derive(Debug, Clone)]
+pub(crate) struct Part {
+    pub index: u32,
+    pub convex: Convex,
+    pub neighbours: [Option<uv::Vec2>; 2],
+}
+
+impl Shape {
+    pub fn circle(radius: f32) -> Self {
+        Shape::Circle { radius }
+    }
+
+    pub fn cuboid(half_extents: uv::Vec2) -> Self {
+        Shape::Polygon(Arc::new(Polygon::rectangle(half_extents)))
+    }
+
+    // Convex hull of `points`. None if they don't enclose any area.
+    pub fn polygon(points: &[uv::Vec2]) -> Option<Self> {
+        Some(Shape::Polygon(Arc::new(Polygon::new(points)?)))
+    }
+
+    pub fn segment(a: uv::Vec2, b: uv::Vec2) -> Self {
+        Shape::Segment { a, b }
+    }
+
+    pub fn chain(vertices: &[uv::Vec2], closed: bool) -> Self {
+        Shape::Chain {
+            vertices: vertices.into(),
+            closed,
+        }
+    }
+
+    // Segments of a chain, in order.
+    pub fn segment_count(&self) -> usize {
+        match self {
+            Shape::Chain { vertices, closed } => match vertices.len() {
+                0 | 1 => 0,
+                2 => 1,
+                n if *closed => n,
+                n => n - 1,
+            },
+            Shape::Segment { .. } => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn aabb(&self, position: uv::Vec2, rotation: f32) -> Rect {
+        let bounds = |points: &mut dyn Iterator<Item = uv::Vec2>| {
+            let mut rect = Rect::new(uv::Vec2::broadcast(f32::MAX), uv::Vec2::broadcast(f32::MIN));
+            for point in points {
+                let point = rotate(point, rotation) + position;
+                rect.min = rect.min.min_by_component(point);
+                rect.max = rect.max.max_by_component(point);
+            }
+            rect
+        };
+        match self {
+            Shape::Circle { radius } => Rect::from_center(position, uv::Vec2::broadcast(*radius)),
+            Shape::Polygon(polygon) => bounds(&mut polygon.vertices.iter().copied()),
+            Shape::Segment { a, b } => bounds(&mut [*a, *b].into_iter()),
+            Shape::Chain { vertices, .. } => bounds(&mut vertices.iter().copied()),
+        }
+    }
+
+    // Mass and moment of inertia about the origin at `density` per unit
+    // area. Segments and chains have none.
+    pub fn mass_properties(&self, density: f32) -> (f32, f32) {
+        match self {
+            Shape::Circle { radius } => {
+                let mass = density * std::f32::consts::PI * radius * radius;
+                (mass, 0.5 * mass * radius * radius)
+            }
+            Shape::Polygon(polygon) => {
+                let (area, inertia) = polygon.area_inertia();
+                (density * area, density * inertia)
+            }
+            Shape::Segment { .. } | Shape::Chain { .. } => (0.0, 0.0),
+        }
+    }
+
+    // The convex pieces in world space whose bounds reach `near`.
+    pub(crate) fn parts(&self, position: uv::Vec2, rotation: f32, near: &Rect) -> Vec<Part> {
+        let world = |v: uv::Vec2| rotate(v, rotation) + position;
+        let single = |convex| {
+            vec![Part {
+                index: 0,
+                convex,
+                neighbours: [None; 2],
+            }]
+        };
+        match self {
+            Shape::Circle { radius } => single(Convex::Circle {
+                center: position,
+                radius: *radius,
+            }),
+            Shape::Polygon(polygon) => single(Convex::Polygon {
+                vertices: polygon.vertices.iter().map(|&v| world(v)).collect(),
+                normals: polygon.normals.iter().map(|&n| rotate(n, rotation)).collect(),
+            }),
+            Shape::Segment { a, b } => single(segment(world(*a), world(*b))),
+            Shape::Chain { vertices, closed } => {
+                let n = vertices.len();
+                let vertex = |i: isize| -> Option<uv::Vec2> {
+                    if *closed {
+                        Some(world(vertices[i.rem_euclid(n as isize) as usize]))
+                    } else {
+                        (0..n as isize).contains(&i).then(|| world(vertices[i as usize]))
+                    }
+                };
+                (0..self.segment_count())
+                    .filter_map(|i| {
+                        let (a, b) = (vertex(i as isize)?, vertex(i as isize + 1)?);
+                        let bounds = Rect::new(a.min_by_component(b), a.max_by_component(b));
+                        bounds.overlaps(near).then(|| Part {
+                            index: i as u32,
+                            convex: segment(a, b),
+                            neighbours: [vertex(i as isize - 1), vertex(i as isize + 2)],
+                        })
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    // Nearest point where a ray from `origin` along unit `direction` meets
+    // the shape, with the surface normal facing the ray. Rays starting
+    // inside a circle or polygon hit at distance 0.
+    pub fn raycast(
+        &self,
+        position: uv::Vec2,
+        rotation: f32,
+        origin: uv::Vec2,
+        direction: uv::Vec2,
+        max_distance: f32,
+    ) -> Option<(f32, uv::Vec2)> {
+        let far = Rect::new(uv::Vec2::broadcast(f32::MIN), uv::Vec2::broadcast(f32::MAX));
+        self.parts(position, rotation, &far)
+            .iter()
+            .filter_map(|part| raycast_convex(&part.convex, origin, direction, max_distance))
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+}
+
+fn segment(a: uv::Vec2, b: uv::Vec2) -> Convex {
+    let normal = edge_normal(b - a);
+    Convex::Polygon {
+        vertices: vec![a, b],
+        normals: vec![normal, -normal],
+    }
+}
+
+fn raycast_convex(
+    convex: &Convex,
+    origin: uv::Vec2,
+    direction: uv::Vec2,
+    max_distance: f32,
+) -> Option<(f32, uv::Vec2)> {
+    match convex {
+        Convex::Circle { center, radius } => {
+            let offset = origin - *center;
+            let c = offset.mag_sq() - radius * radius;
+            if c <= 0.0 {
+                return Some((0.0, -direction));
+            }
+            let b = offset.dot(direction);
+            let discriminant = b * b - c;
+            if b > 0.0 || discriminant < 0.0 {
+                return None;
+            }
+            let distance = -b - discriminant.sqrt();
+            (distance <= max_distance).then(|| (distance, (offset + direction * distance).normalized()))
+        }
+        Convex::Polygon { vertices, normals } if vertices.len() == 2 => {
+            let (a, b) = (vertices[0], vertices[1]);
+            let edge = b - a;
+            let denominator = cross(direction, edge);
+            if denominator.abs() < f32::EPSILON {
+                return None;
+            }
+            let distance = cross(a - origin, edge) / denominator;
+            let along = cross(a - origin, direction) / denominator;
+            if !(0.0..=max_distance).contains(&distance) || !(0.0..=1.0).contains(&along) {
+                return None;
+            }
+            let normal = if normals[0].dot(direction) < 0.0 { normals[0] } else { normals[1] };
+            Some((distance, normal))
+        }
+        Convex::Polygon { vertices, normals } => {
+            // Clip the ray by each edge's half plane.
+            let (mut near, mut far) = (0.0f32, max_distance);
+            let mut hit_normal = -direction;
+            for (vertex, normal) in vertices.iter().zip(normals) {
+                let distance = normal.dot(*vertex - origin);
+                let speed = normal.dot(direction);
+                if speed.abs() < f32::EPSILON {
+                    if distance < 0.0 {
+                        return None;
+                    }
+                    continue;
+                }
+                let t = distance / speed;
+                if speed < 0.0 {
+                    if t > near {
+                        near = t;
+                        hit_normal = *normal;
+                    }
+                } else {
+                    far = far.min(t);
+                }
+                if near > far {
+                    return None;
+                }
+            }
+            Some((near, hit_normal))
+        }
+    }
+}
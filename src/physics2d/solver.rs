@@ -0,0 +1,227 @@
+// Sequential impulses, as in 3D: each contact is solved in turn, clamping
+// the accumulated impulse, and the pass is repeated until it settles. The
+// two points of a manifold are solved together, which keeps thin and light
+// bodies from being spun by whichever point goes first.
+use ultraviolet as uv;
+
+use super::body::RigidBody;
+use super::contact::Manifold;
+use super::shape::cross;
+
+// Penetration left alone so resting contacts stay touching.
+const SLOP: f32 = 0.005;
+// Fraction of the remaining penetration pushed out per step.
+const BAUMGARTE: f32 = 0.2;
+// Slower impacts don't bounce, which keeps resting bodies from jittering.
+const RESTITUTION_THRESHOLD: f32 = 1.0;
+
+struct PointConstraint {
+    r_a: uv::Vec2,
+    r_b: uv::Vec2,
+    normal_mass: f32,
+    tangent_mass: f32,
+    bias: f32,
+}
+
+struct ManifoldConstraint {
+    manifold: usize,
+    a: usize,
+    b: usize,
+    normal: uv::Vec2,
+    friction: f32,
+    points: Vec<PointConstraint>,
+    // With two points, their coupling along the normal and its inverse, to
+    // solve both at once. Left out when the points nearly coincide.
+    block: Option<(uv::Mat2, uv::Mat2)>,
+}
+
+// Velocity state of one body while solving.
+#[derive(Copy, Clone)]
+pub(crate) struct SolverBody {
+    pub linear: uv::Vec2,
+    pub angular: f32,
+    pub inv_mass: f32,
+    pub inv_inertia: f32,
+    pub position: uv::Vec2,
+}
+
+impl SolverBody {
+    pub fn new(body: &RigidBody) -> Self {
+        Self {
+            linear: body.linear_velocity,
+            angular: body.angular_velocity,
+            inv_mass: body.inv_mass(),
+            inv_inertia: body.inv_inertia(),
+            position: body.position,
+        }
+    }
+
+    pub fn apply(&mut self, impulse: uv::Vec2, r: uv::Vec2) {
+        self.linear += impulse * self.inv_mass;
+        self.angular += self.inv_inertia * cross(r, impulse);
+    }
+
+    pub fn velocity_at(&self, r: uv::Vec2) -> uv::Vec2 {
+        self.linear + uv::Vec2::new(-r.y, r.x) * self.angular
+    }
+
+    // Inverse effective mass along `direction` at offset `r`.
+    pub fn inv_mass_along(&self, r: uv::Vec2, direction: uv::Vec2) -> f32 {
+        let rn = cross(r, direction);
+        self.inv_mass + self.inv_inertia * rn * rn
+    }
+}
+
+// Unit direction across `normal`.
+fn tangent(normal: uv::Vec2) -> uv::Vec2 {
+    uv::Vec2::new(normal.y, -normal.x)
+}
+
+pub(crate) struct ContactSolver {
+    constraints: Vec<ManifoldConstraint>,
+}
+
+impl ContactSolver {
+    // Applies last step's impulses as a starting guess.
+    pub fn new(manifolds: &[&mut Manifold], bodies: &mut [SolverBody], dt: f32) -> Self {
+        let mut constraints = Vec::with_capacity(manifolds.len());
+        for (m, manifold) in manifolds.iter().enumerate() {
+            let (a, b) = (manifold.body_a.0, manifold.body_b.0);
+            let normal = manifold.normal;
+            let tangent = tangent(normal);
+            let mut points = Vec::with_capacity(manifold.points.len());
+            for contact in &manifold.points {
+                let (body_a, body_b) = (bodies[a], bodies[b]);
+                let r_a = contact.point - body_a.position;
+                let r_b = contact.point - body_b.position;
+                let mass = |direction: uv::Vec2| {
+                    let k = body_a.inv_mass_along(r_a, direction) + body_b.inv_mass_along(r_b, direction);
+                    if k > 0.0 {
+                        1.0 / k
+                    } else {
+                        0.0
+                    }
+                };
+                let relative = body_b.velocity_at(r_b) - body_a.velocity_at(r_a);
+                let approach = relative.dot(normal);
+                let mut bias = BAUMGARTE / dt * (contact.depth - SLOP).max(0.0);
+                // Speculative contacts that haven't touched yet may close
+                // the gap but no more.
+                if contact.depth < 0.0 {
+                    bias = contact.depth / dt;
+                }
+                if approach < -RESTITUTION_THRESHOLD {
+                    bias = bias.max(-manifold.restitution * approach);
+                }
+                let impulse = normal * contact.normal_impulse + tangent * contact.tangent_impulse;
+                bodies[a].apply(-impulse, r_a);
+                bodies[b].apply(impulse, r_b);
+                points.push(PointConstraint {
+                    r_a,
+                    r_b,
+                    normal_mass: mass(normal),
+                    tangent_mass: mass(tangent),
+                    bias,
+                });
+            }
+            let block = match points.as_slice() {
+                [p1, p2] => {
+                    let (body_a, body_b) = (bodies[a], bodies[b]);
+                    let (rn1a, rn1b) = (cross(p1.r_a, normal), cross(p1.r_b, normal));
+                    let (rn2a, rn2b) = (cross(p2.r_a, normal), cross(p2.r_b, normal));
+                    let linear = body_a.inv_mass + body_b.inv_mass;
+                    let k11 = linear + body_a.inv_inertia * rn1a * rn1a + body_b.inv_inertia * rn1b * rn1b;
+                    let k22 = linear + body_a.inv_inertia * rn2a * rn2a + body_b.inv_inertia * rn2b * rn2b;
+                    let k12 = linear + body_a.inv_inertia * rn1a * rn2a + body_b.inv_inertia * rn1b * rn2b;
+                    let determinant = k11 * k22 - k12 * k12;
+                    // Badly conditioned, e.g. both points in the same place.
+                    (k11 * k11 < 1000.0 * determinant).then(|| {
+                        let k = uv::Mat2::new(uv::Vec2::new(k11, k12), uv::Vec2::new(k12, k22));
+                        let inverse = uv::Mat2::new(uv::Vec2::new(k22, -k12), uv::Vec2::new(-k12, k11)) * (1.0 / determinant);
+                        (k, inverse)
+                    })
+                }
+                _ => None,
+            };
+            constraints.push(ManifoldConstraint {
+                manifold: m,
+                a,
+                b,
+                normal,
+                friction: manifold.friction,
+                points,
+                block,
+            });
+        }
+        Self { constraints }
+    }
+
+    pub fn solve(&self, manifolds: &mut [&mut Manifold], bodies: &mut [SolverBody]) {
+        for constraint in &self.constraints {
+            let contacts = &mut manifolds[constraint.manifold].points;
+            let (a, b, normal) = (constraint.a, constraint.b, constraint.normal);
+            let tangent = tangent(normal);
+            let relative = |bodies: &[SolverBody], point: &PointConstraint| {
+                bodies[b].velocity_at(point.r_b) - bodies[a].velocity_at(point.r_a)
+            };
+
+            // Friction first, bounded by the normal impulse of the last pass.
+            for (point, contact) in constraint.points.iter().zip(contacts.iter_mut()) {
+                let limit = constraint.friction * contact.normal_impulse;
+                let lambda = -relative(bodies, point).dot(tangent) * point.tangent_mass;
+                let total = (contact.tangent_impulse + lambda).clamp(-limit, limit);
+                let applied = total - contact.tangent_impulse;
+                contact.tangent_impulse = total;
+                bodies[a].apply(-tangent * applied, point.r_a);
+                bodies[b].apply(tangent * applied, point.r_b);
+            }
+
+            if let (Some((k, inverse)), [p1, p2]) = (constraint.block, constraint.points.as_slice()) {
+                let old = uv::Vec2::new(contacts[0].normal_impulse, contacts[1].normal_impulse);
+                let speed = uv::Vec2::new(
+                    relative(bodies, p1).dot(normal) - p1.bias,
+                    relative(bodies, p2).dot(normal) - p2.bias,
+                );
+                // No solution only happens through rounding; keep what
+                // there was.
+                let total = solve_block(k, inverse, speed - k * old, p1.normal_mass, p2.normal_mass).unwrap_or(old);
+                let applied = total - old;
+                for (i, point) in [p1, p2].into_iter().enumerate() {
+                    contacts[i].normal_impulse = total[i];
+                    bodies[a].apply(-normal * applied[i], point.r_a);
+                    bodies[b].apply(normal * applied[i], point.r_b);
+                }
+                continue;
+            }
+            for (point, contact) in constraint.points.iter().zip(contacts.iter_mut()) {
+                let lambda = (point.bias - relative(bodies, point).dot(normal)) * point.normal_mass;
+                let total = (contact.normal_impulse + lambda).max(0.0);
+                let applied = total - contact.normal_impulse;
+                contact.normal_impulse = total;
+                bodies[a].apply(-normal * applied, point.r_a);
+                bodies[b].apply(normal * applied, point.r_b);
+            }
+        }
+    }
+}
+
+// Normal impulses for two points at once: the pair that leaves neither
+// point approaching, pushing only where needed. `b` is the approach speed
+// each would have with no impulse at all. Tries both pushing, then each
+// alone, then neither.
+fn solve_block(k: uv::Mat2, inverse: uv::Mat2, b: uv::Vec2, mass_1: f32, mass_2: f32) -> Option<uv::Vec2> {
+    let both = -(inverse * b);
+    let first = uv::Vec2::new(-mass_1 * b.x, 0.0);
+    let second = uv::Vec2::new(0.0, -mass_2 * b.y);
+    if both.x >= 0.0 && both.y >= 0.0 {
+        Some(both)
+    } else if first.x >= 0.0 && (k * first + b).y >= 0.0 {
+        Some(first)
+    } else if second.y >= 0.0 && (k * second + b).x >= 0.0 {
+        Some(second)
+    } else if b.x >= 0.0 && b.y >= 0.0 {
+        Some(uv::Vec2::zero())
+    } else {
+        None
+    }
+}
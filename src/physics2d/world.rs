@@ -0,0 +1,525 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use ultraviolet as uv;
+
+use crate::physics::body::{BodyHandle, BodyKind};
+use crate::physics::bvh::{DynamicBvh, ProxyId};
+use crate::physics::events::{Collision, CollisionEvent, ContactReport, EventQueue};
+use crate::physics::ray::{Layers, Ray};
+use crate::physics::shape::Aabb;
+use crate::physics::spatial_hash::Rect;
+use crate::physics::world::{self as world3d, Pair, StepClock, WorldBody};
+
+use super::body::RigidBody;
+use super::contact::{self, Manifold, CONTACT_MARGIN};
+use super::shape::Shape;
+use super::solver::{ContactSolver, SolverBody};
+
+pub const DEFAULT_TIMESTEP: f32 = 1.0 / 60.0;
+// Something meeting a one-way platform lands on it only if the contact
+// normal is at least this close to the platform's solid side.
+const ONE_WAY_LANDING: f32 = 0.5;
+
+// Keyed by body pair and the chain segments that met, ordered so stepping
+// never depends on hashing.
+type ManifoldKey = (BodyHandle, BodyHandle, u32, u32);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RayHit {
+    pub distance: f32,
+    pub point: uv::Vec2,
+    // Unit surface normal facing the ray.
+    pub normal: uv::Vec2,
+}
+
+// 2D rigid bodies stepped at a fixed rate, the plane's counterpart of
+// `physics::world::PhysicsWorld`. Handles and events are the same types as
+// in 3D, with z left 0 in contact reports.
+pub struct PhysicsWorld {
+    bodies: Vec<Option<RigidBody>>,
+    // Each body's leaf in `broadphase`, by handle.
+    proxies: Vec<Option<ProxyId>>,
+    // Flat boxes, z always 0.
+    broadphase: DynamicBvh<BodyHandle>,
+    manifolds: BTreeMap<ManifoldKey, Manifold>,
+    // Pairs that met a one-way platform from its passable side, ignored
+    // until they part.
+    passing: BTreeSet<Pair>,
+    events: EventQueue,
+    pub gravity: uv::Vec2,
+    pub timestep: f32,
+    pub iterations: usize,
+    // Steps taken per `update` at most, so a long frame can't snowball.
+    pub max_steps: u32,
+    clock: StepClock,
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PhysicsWorld {
+    pub fn new() -> Self {
+        Self {
+            bodies: Vec::new(),
+            proxies: Vec::new(),
+            broadphase: DynamicBvh::new(),
+            manifolds: BTreeMap::new(),
+            passing: BTreeSet::new(),
+            events: EventQueue::default(),
+            gravity: uv::Vec2::new(0.0, -9.81),
+            timestep: DEFAULT_TIMESTEP,
+            iterations: 10,
+            max_steps: 5,
+            clock: StepClock::default(),
+        }
+    }
+
+    pub fn with_gravity(mut self, gravity: uv::Vec2) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_timestep(mut self, timestep: f32) -> Self {
+        self.timestep = timestep;
+        self
+    }
+
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn add_body(&mut self, body: RigidBody) -> BodyHandle {
+        let aabb = fat(body.aabb());
+        let handle = match self.bodies.iter().position(Option::is_none) {
+            Some(i) => {
+                self.bodies[i] = Some(body);
+                BodyHandle(i)
+            }
+            None => {
+                self.bodies.push(Some(body));
+                self.proxies.push(None);
+                BodyHandle(self.bodies.len() - 1)
+            }
+        };
+        self.proxies[handle.0] = Some(self.broadphase.insert(aabb, handle));
+        handle
+    }
+
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<RigidBody> {
+        self.manifolds.retain(|(a, b, _, _), _| *a != handle && *b != handle);
+        self.passing.retain(|(a, b)| *a != handle && *b != handle);
+        self.events.remove_body(handle, |pair| world3d::sensor_first(&self.bodies, pair));
+        if let Some(proxy) = self.proxies.get_mut(handle.0).and_then(Option::take) {
+            self.broadphase.remove(proxy);
+        }
+        self.bodies.get_mut(handle.0)?.take()
+    }
+
+    pub fn body(&self, handle: BodyHandle) -> Option<&RigidBody> {
+        self.bodies.get(handle.0)?.as_ref()
+    }
+
+    pub fn body_mut(&mut self, handle: BodyHandle) -> Option<&mut RigidBody> {
+        self.bodies.get_mut(handle.0)?.as_mut()
+    }
+
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyHandle, &RigidBody)> {
+        self.bodies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, body)| Some((BodyHandle(i), body.as_ref()?)))
+    }
+
+    // Fat bounds of every body as of the last step, for queries.
+    pub fn broadphase(&self) -> &DynamicBvh<BodyHandle> {
+        &self.broadphase
+    }
+
+    // Contacts as of the last step.
+    pub fn manifolds(&self) -> impl Iterator<Item = &Manifold> {
+        self.manifolds.values()
+    }
+
+    // Collision and trigger events from the last `update` or `step`, in the
+    // order they happened, with `Stayed` ones last.
+    pub fn events(&self) -> &[CollisionEvent] {
+        self.events.events()
+    }
+
+    // Runs as many fixed steps as `dt` seconds cover and returns how many.
+    pub fn update(&mut self, dt: f32) -> u32 {
+        let steps = self.clock.steps(dt, self.timestep, self.max_steps);
+        self.events.begin();
+        for _ in 0..steps {
+            self.advance();
+        }
+        self.events.finish();
+        steps
+    }
+
+    // How far into the next step the accumulated time is, 0..1, for
+    // interpolating what is drawn.
+    pub fn interpolation(&self) -> f32 {
+        self.clock.interpolation(self.timestep)
+    }
+
+    pub fn step(&mut self) {
+        self.events.begin();
+        self.advance();
+        self.events.finish();
+    }
+
+    fn advance(&mut self) {
+        let dt = self.timestep;
+        let overlapping = self.collide();
+        for body in self.bodies.iter_mut().flatten() {
+            body.integrate_velocity(self.gravity, dt);
+        }
+        self.solve(dt);
+        self.record_events(overlapping);
+        let stops = self.sweep_bullets(dt);
+        for body in self.bodies.iter_mut().flatten() {
+            body.integrate_position(dt);
+        }
+        for (handle, position) in stops {
+            if let Some(body) = self.body_mut(handle) {
+                body.position = position;
+            }
+        }
+        self.update_broadphase();
+    }
+
+    // Nearest body on `mask` that a ray from `origin` along `direction`
+    // hits. Bodies are found through the broadphase, so one moved by hand is
+    // only seen where it was after the last step.
+    pub fn raycast(
+        &self,
+        origin: uv::Vec2,
+        direction: uv::Vec2,
+        max_distance: f32,
+        mask: Layers,
+    ) -> Option<(BodyHandle, RayHit)> {
+        let direction = direction.normalized();
+        let hit = |handle: BodyHandle| {
+            let body = self.body(handle).filter(|body| body.layers.intersects(mask))?;
+            let (distance, normal) = body
+                .shape
+                .raycast(body.position, body.rotation, origin, direction, max_distance)?;
+            Some(RayHit {
+                distance,
+                point: origin + direction * distance,
+                normal,
+            })
+        };
+        let ray = Ray::new(
+            uv::Vec3::new(origin.x, origin.y, 0.0),
+            uv::Vec3::new(direction.x, direction.y, 0.0),
+        );
+        let (proxy, _) = self
+            .broadphase
+            .raycast(&ray, max_distance, |_, handle| hit(*handle).map(|hit| hit.distance))?;
+        let handle = *self.broadphase.get(proxy)?;
+        Some((handle, hit(handle)?))
+    }
+
+    // Bodies on `mask` whose bounds overlap `rect`.
+    pub fn query_rect(&self, rect: &Rect, mask: Layers) -> Vec<BodyHandle> {
+        let mut found = Vec::new();
+        self.broadphase.query(&flat(*rect), |_, handle| {
+            if self
+                .body(*handle)
+                .is_some_and(|body| body.layers.intersects(mask) && body.aabb().overlaps(rect))
+            {
+                found.push(*handle);
+            }
+        });
+        found.sort();
+        found
+    }
+
+    fn update_broadphase(&mut self) {
+        world3d::update_broadphase(&mut self.broadphase, &self.bodies, &self.proxies);
+    }
+
+    // Pairs of bodies whose bounds overlap and that can affect each other.
+    fn candidate_pairs(&mut self) -> Vec<Pair> {
+        // Bodies may have been moved by hand since the last step.
+        self.update_broadphase();
+        world3d::candidate_pairs(&self.broadphase, &self.bodies)
+    }
+
+    // Finds this step's manifolds and returns the sensor pairs that overlap,
+    // which get none.
+    fn collide(&mut self) -> BTreeSet<Pair> {
+        let pairs = self.candidate_pairs();
+        let previous = std::mem::take(&mut self.manifolds);
+        let was_passing = std::mem::take(&mut self.passing);
+        let mut manifolds = BTreeMap::new();
+        let mut passing = BTreeSet::new();
+        let mut overlapping = BTreeSet::new();
+        for (handle_a, handle_b) in pairs {
+            let (Some(a), Some(b)) = (self.body(handle_a), self.body(handle_b)) else {
+                continue;
+            };
+            if a.sensor || b.sensor {
+                if contact::overlaps(a, b) {
+                    overlapping.insert((handle_a, handle_b));
+                }
+                continue;
+            }
+            let found = contact::contacts(a, b);
+            if found.is_empty() {
+                continue;
+            }
+            let pair_keys = (handle_a, handle_b, 0, 0)..=(handle_a, handle_b, u32::MAX, u32::MAX);
+            if let Some((direction, side)) = one_way(a, b) {
+                // Decided when they first meet and kept until they part, so
+                // whatever jumps up through a platform doesn't land halfway.
+                let touched = previous.range(pair_keys.clone()).next().is_some();
+                let from_below = !touched
+                    && found
+                        .iter()
+                        .any(|(_, contact)| (contact.normal * side).dot(direction) < ONE_WAY_LANDING);
+                if was_passing.contains(&(handle_a, handle_b)) || from_below {
+                    passing.insert((handle_a, handle_b));
+                    continue;
+                }
+            }
+            for ((part_a, part_b), found) in found {
+                let key = (handle_a, handle_b, part_a, part_b);
+                let mut manifold = Manifold::new(handle_a, handle_b, a, b, found);
+                if let Some(old) = previous.get(&key) {
+                    manifold.warm_start(old);
+                }
+                manifolds.insert(key, manifold);
+            }
+        }
+        self.manifolds = manifolds;
+        self.passing = passing;
+        overlapping
+    }
+
+    // Hands what touches after this step's solve to the event queue.
+    fn record_events(&mut self, overlapping: BTreeSet<Pair>) {
+        let mut touching: BTreeMap<Pair, Collision> = BTreeMap::new();
+        for manifold in self.manifolds.values() {
+            let flat = |v: uv::Vec2| uv::Vec3::new(v.x, v.y, 0.0);
+            touching
+                .entry((manifold.body_a, manifold.body_b))
+                .or_insert_with(|| Collision::new(manifold.body_a, manifold.body_b))
+                .contacts
+                .extend(manifold.points.iter().map(|point| ContactReport {
+                    point: flat(point.point),
+                    normal: flat(manifold.normal),
+                    depth: point.depth,
+                    normal_impulse: point.normal_impulse,
+                    friction_impulse: point.tangent_impulse.abs(),
+                }));
+        }
+        self.events.record(touching, overlapping, |pair| world3d::sensor_first(&self.bodies, pair));
+    }
+
+    // Where bullets stop this step if their path would cross something, to
+    // be placed there after moving. They keep their velocity, so the next
+    // step's contact deals with the impact.
+    fn sweep_bullets(&self, dt: f32) -> Vec<(BodyHandle, uv::Vec2)> {
+        let everywhere = Rect::new(uv::Vec2::broadcast(f32::MIN), uv::Vec2::broadcast(f32::MAX));
+        let mut stops = Vec::new();
+        for (handle, body) in self.bodies() {
+            if !body.bullet || body.sensor || body.kind == BodyKind::Static {
+                continue;
+            }
+            let motion = body.linear_velocity * dt;
+            let length = motion.mag();
+            if length < CONTACT_MARGIN {
+                continue;
+            }
+            let direction = motion / length;
+            let start = body.aabb();
+            let end = body.shape.aabb(body.position + motion, body.rotation);
+            let swept = Rect::new(start.min.min_by_component(end.min), start.max.max_by_component(end.max));
+            let pieces = body.shape.parts(body.position, body.rotation, &everywhere);
+            let mut nearest = length;
+            self.broadphase.query(&fat(swept), |_, other_handle| {
+                let Some(other) = self.body(*other_handle) else {
+                    return;
+                };
+                let pair = (handle.min(*other_handle), handle.max(*other_handle));
+                if *other_handle == handle
+                    || other.sensor
+                    || other.bullet
+                    || !body.collides_with(other)
+                    || self.passing.contains(&pair)
+                    || other.one_way_direction().is_some_and(|pass| pass.dot(direction) > 0.0)
+                {
+                    return;
+                }
+                for target in other.shape.parts(other.position, other.rotation, &swept) {
+                    for piece in &pieces {
+                        // Already touching is left to the contacts.
+                        if contact::distance(&piece.convex, &target.convex) <= CONTACT_MARGIN {
+                            continue;
+                        }
+                        let hit = contact::time_of_impact(
+                            &piece.convex,
+                            &target.convex,
+                            direction,
+                            nearest,
+                            CONTACT_MARGIN * 0.5,
+                        );
+                        if let Some(distance) = hit {
+                            nearest = nearest.min(distance);
+                        }
+                    }
+                }
+            });
+            if nearest < length {
+                stops.push((handle, body.position + direction * nearest));
+            }
+        }
+        stops
+    }
+
+    fn solve(&mut self, dt: f32) {
+        let mut solver_bodies: Vec<SolverBody> = self
+            .bodies
+            .iter()
+            .map(|body| match body {
+                Some(body) => SolverBody::new(body),
+                None => SolverBody::new(&RigidBody::fixed(Shape::circle(0.0))),
+            })
+            .collect();
+        let mut manifolds: Vec<&mut Manifold> = self.manifolds.values_mut().collect();
+        let solver = ContactSolver::new(&manifolds, &mut solver_bodies, dt);
+        for _ in 0..self.iterations {
+            solver.solve(&mut manifolds, &mut solver_bodies);
+        }
+        for (body, solved) in self.bodies.iter_mut().zip(&solver_bodies) {
+            if let Some(body) = body.as_mut().filter(|body| body.is_dynamic()) {
+                body.linear_velocity = solved.linear;
+                body.angular_velocity = solved.angular;
+            }
+        }
+    }
+}
+
+// The one-way platform of a pair, if either is one: its passable direction
+// in world space, and 1 if it's `a` (contact normals point away from it) or
+// -1 if it's `b`.
+fn one_way(a: &RigidBody, b: &RigidBody) -> Option<(uv::Vec2, f32)> {
+    a.one_way_direction()
+        .map(|direction| (direction, 1.0))
+        .or_else(|| b.one_way_direction().map(|direction| (direction, -1.0)))
+}
+
+// A broadphase box for a rectangle, grown by the contact margin.
+fn fat(rect: Rect) -> Aabb {
+    flat(rect).expanded(CONTACT_MARGIN)
+}
+
+fn flat(rect: Rect) -> Aabb {
+    Aabb::new(
+        uv::Vec3::new(rect.min.x, rect.min.y, 0.0),
+        uv::Vec3::new(rect.max.x, rect.max.y, 0.0),
+    )
+}
+
+impl WorldBody for RigidBody {
+    fn kind(&self) -> BodyKind {
+        self.kind
+    }
+
+    fn is_sensor(&self) -> bool {
+        self.sensor
+    }
+
+    fn can_collide(&self, other: &Self) -> bool {
+        self.collides_with(other)
+    }
+
+    fn fat_aabb(&self) -> Aabb {
+        fat(self.aabb())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world() -> PhysicsWorld {
+        PhysicsWorld::new().with_gravity(uv::Vec2::zero())
+    }
+
+    fn run(world: &mut PhysicsWorld, seconds: f32) {
+        for _ in 0..(seconds / DEFAULT_TIMESTEP).round() as usize {
+            world.step();
+        }
+    }
+
+    #[test]
+    fn bullets_do_not_tunnel_through_thin_walls() {
+        // Ten metres a step, against a wall a tenth of a metre thick.
+        let shoot = |bullet: bool| {
+            let mut world = world();
+            let wall = RigidBody::fixed(Shape::cuboid(uv::Vec2::new(0.05, 2.0))).with_position(uv::Vec2::new(5.0, 0.0));
+            world.add_body(wall);
+            let shot = RigidBody::dynamic(Shape::circle(0.1))
+                .with_velocity(uv::Vec2::new(600.0, 0.0))
+                .with_bullet(bullet);
+            let shot = world.add_body(shot);
+            run(&mut world, 0.1);
+            world.body(shot).unwrap().position.x
+        };
+        assert!(shoot(false) > 5.0);
+        let x = shoot(true);
+        assert!(x < 5.0 - 0.05, "went through to {}", x);
+    }
+
+    #[test]
+    fn one_way_platforms_pass_bodies_up_and_land_them_coming_down() {
+        let mut world = PhysicsWorld::new();
+        world.add_body(RigidBody::fixed(Shape::cuboid(uv::Vec2::new(2.0, 0.1))).with_one_way(uv::Vec2::unit_y()));
+        let ball = RigidBody::dynamic(Shape::circle(0.2))
+            .with_position(uv::Vec2::new(0.0, -1.0))
+            .with_velocity(uv::Vec2::new(0.0, 8.0));
+        let ball = world.add_body(ball);
+
+        let mut highest = f32::MIN;
+        for _ in 0..180 {
+            world.step();
+            highest = highest.max(world.body(ball).unwrap().position.y);
+        }
+        assert!(highest > 1.0, "only reached {}", highest);
+        let landed = world.body(ball).unwrap();
+        assert!((landed.position.y - 0.3).abs() < 0.02, "resting at {}", landed.position.y);
+        assert!(landed.linear_velocity.mag() < 0.05);
+    }
+
+    #[test]
+    fn bodies_slide_over_chain_joins_without_catching() {
+        let mut world = PhysicsWorld::new();
+        let vertices: Vec<_> = (-2..=2).map(|i| uv::Vec2::new(i as f32 * 5.0, 0.0)).collect();
+        world.add_body(RigidBody::fixed(Shape::chain(&vertices, false)).with_friction(0.0));
+        let mut slider = RigidBody::dynamic(Shape::cuboid(uv::Vec2::broadcast(0.25)))
+            .with_position(uv::Vec2::new(-7.0, 0.25))
+            .with_velocity(uv::Vec2::new(5.0, 0.0))
+            .with_friction(0.0);
+        slider.linear_damping = 0.0;
+        let slider = world.add_body(slider);
+
+        // Across the joins at x = -5, 0 and 5.
+        for _ in 0..120 {
+            world.step();
+            let body = world.body(slider).unwrap();
+            assert!((body.position.y - 0.25).abs() < 0.02, "bumped to {:?}", body.position);
+            assert!(body.rotation.abs() < 0.01, "tipped to {}", body.rotation);
+        }
+        let body = world.body(slider).unwrap();
+        assert!((body.position.x - 3.0).abs() < 0.01, "stopped at {:?}", body.position);
+        assert!((body.linear_velocity.x - 5.0).abs() < 1e-3);
+    }
+}
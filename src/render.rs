@@ -1,12 +1,33 @@
 use wgpu::util::DeviceExt;
 use winit::window::Window;
-use super::instance::Instance;
+use super::instance::{Instance, InstanceRaw};
 
 use super::graphics::gl::Vertex as Vertex;
 use super::graphics::gl::BufferContents as BufferContents;
+use super::graphics::gl::SkinnedVertex;
 use ultraviolet as uv;
-use super::camera::{Camera, CameraUniform};
-use super::graphics::texture::Texture;
+use super::camera::{Camera, CameraId, CameraSettings, CameraUniform, RenderTarget, RenderTargetId};
+use super::graphics::texture::{SamplerConfig, Texture, TextureLoader, TextureOptions};
+use super::graphics::shader::{EmbeddedShaderLoader, ShaderError};
+use super::graphics::pipeline::{DepthTest, PipelineCache, PipelineKey};
+use super::graphics::skybox::{self, Skybox};
+use super::graphics::render_graph::{PassContext, PassDesc, PassId, RenderGraph, ResourceId};
+use super::graphics::post::{PostStack, HDR_FORMAT};
+use super::graphics::msaa::{Msaa, SceneTargets};
+use super::graphics::picking::{IdBuffer, PickRequest, ID_FORMAT, NO_ENTITY};
+use super::graphics::skinning::{self, JointBuffer, SkinnedMeshId};
+use super::graphics::objects::mesh::{Mesh, SkinnedMeshData};
+use super::assets::server::{AssetServer, LoadContext};
+use super::assets::handle::Handle;
+use super::assets::bind_groups::TextureBindGroups;
+use super::assets::vfs::{Vfs, VfsShaderLoader};
+use super::assets::loaders::{GltfAssetLoader, MeshAssetLoader, ShaderAssetLoader, SoundAssetLoader, TextureAssetLoader};
+use super::audio::output::{Audio, AudioPlayer};
+use super::audio::spatial::AudioListener;
+use super::time::FrameClock;
+use super::audio::music::Music;
+use super::physics::ray::Ray;
+use super::ecs::entities::Entity;
 
 use super::game_interface::app::App;
 
@@ -25,20 +46,67 @@ pub struct State<'window> {
   config: wgpu::SurfaceConfiguration,
   size: winit::dpi::PhysicalSize<u32>,
   window: &'window Window,
-  render_pipeline: wgpu::RenderPipeline,
+  pipelines: PipelineCache,
+  scene_pipeline: PipelineKey,
+  hdr_scene_pipeline: PipelineKey,
+  skybox_pipeline: PipelineKey,
+  hdr_skybox_pipeline: PipelineKey,
+  skinned_pipeline: PipelineKey,
+  hdr_skinned_pipeline: PipelineKey,
+  joint_layout: wgpu::BindGroupLayout,
+  skinned_meshes: Vec<Option<SkinnedMeshEntry>>,
+  skybox_layout: wgpu::BindGroupLayout,
+  skybox: Option<Skybox>,
+  post: PostStack,
+  render_graph: RenderGraph<State<'window>>,
+  scene_color: ResourceId,
+  scene_pass: PassId,
+  entity_ids: ResourceId,
+  id_pipeline: PipelineKey,
+  // Only allocated and drawn while picking is enabled.
+  id_buffer: Option<IdBuffer>,
+  window_title: String,
   vertex_buffer: wgpu::Buffer,
   num_vertices: u32,
   index_buffer: wgpu::Buffer,
   num_indices: u32,
   diffuse_bind_group: wgpu::BindGroup,
   diffuse_texture: Texture,
-  pub camera: Camera,
-  camera_uniform: CameraUniform,
-  camera_buffer: wgpu::Buffer,
-  camera_bind_group: wgpu::BindGroup,
+  textures: TextureLoader,
+  assets: AssetServer,
+  texture_bind_groups: TextureBindGroups,
+  audio: Audio,
+  music: Music,
+  listener_camera: CameraId,
+  audio_clock: FrameClock,
+  texture_bind_group_layout: wgpu::BindGroupLayout,
+  camera_bind_group_layout: wgpu::BindGroupLayout,
+  cameras: Vec<Option<CameraEntry>>,
+  main_camera: CameraId,
+  render_targets: Vec<Option<OffscreenTarget>>,
   instances: Vec<Instance>,
   instance_buffer: wgpu::Buffer,
-  depth_texture: Texture,
+  scene_targets: SceneTargets,
+  supported_msaa: Vec<Msaa>,
+}
+
+struct CameraEntry {
+  camera: Camera,
+  settings: CameraSettings,
+  uniform: CameraUniform,
+  buffer: wgpu::Buffer,
+  bind_group: wgpu::BindGroup,
+}
+
+struct SkinnedMeshEntry {
+  mesh: Mesh,
+  joints: JointBuffer,
+  instance: wgpu::Buffer,
+}
+
+struct OffscreenTarget {
+  texture: Texture,
+  bind_group: wgpu::BindGroup,
 }
 
 pub async fn run(mut app: Box<dyn App>) {
@@ -128,7 +196,15 @@ impl<'window> State<'window> {
       let (device, queue) = adapter
           .request_device(
               &wgpu::DeviceDescriptor {
-                  required_features: wgpu::Features::empty(),
+                  // Adapter-specific format features let MSAA use every
+                  // sample count the adapter supports instead of only 1 and
+                  // 4; compressed textures without a feature are
+                  // decompressed on load.
+                  required_features: adapter.features()
+                      & (wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+                          | wgpu::Features::TEXTURE_COMPRESSION_BC
+                          | wgpu::Features::TEXTURE_COMPRESSION_ETC2
+                          | wgpu::Features::TEXTURE_COMPRESSION_ASTC),
                   required_limits: wgpu::Limits::default(),
                   label: None,
               },
@@ -161,9 +237,43 @@ impl<'window> State<'window> {
 
       surface.configure(&device, &config);
 
+      // Loose files in the asset directory override the packed archive.
+      let vfs = Vfs::new();
+      if std::path::Path::new(ASSET_ARCHIVE).exists() {
+          if let Err(e) = vfs.mount_archive("", 0, ASSET_ARCHIVE) {
+              log::error!("failed to mount {}: {}", ASSET_ARCHIVE, e);
+          }
+      }
+      vfs.mount_directory("", 1, ASSET_DIR);
+
+      // Shaders are read from "shaders/" in the VFS, so a mod can override
+      // any one of them. The embedded copies sit below every other mount,
+      // and debug builds put the source directory above them so shaders can
+      // be edited while the game runs. A broken override falls back to the
+      // embedded shaders entirely.
+      vfs.mount("shaders", i32::MIN, EMBEDDED_SHADERS);
+      if cfg!(debug_assertions) {
+          vfs.mount_directory("shaders", -1, SHADER_DIR);
+      }
+      let mut textures = TextureLoader::new(&device, Box::new(VfsShaderLoader::new(vfs.clone(), "shaders")))
+          .or_else(|e| {
+              log::error!("{}; using the embedded shaders", e);
+              TextureLoader::new(&device, Box::new(EMBEDDED_SHADERS))
+          })
+          .unwrap();
       let diffuse_bytes = include_bytes!("images/happy-tree.png");
-      let diffuse_texture =
-          Texture::from_bytes(&device, &queue, diffuse_bytes, "happy-tree.png").unwrap(); // CHANGED!
+      let diffuse_texture = textures
+          .load_bytes(
+              &device,
+              &queue,
+              diffuse_bytes,
+              "happy-tree.png",
+              &TextureOptions {
+                  sampler: SamplerConfig::REPEAT.with_anisotropy(16),
+                  ..Default::default()
+              },
+          )
+          .unwrap();
       let texture_bind_group_layout =
           device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
               entries: &[
@@ -218,20 +328,11 @@ impl<'window> State<'window> {
           far: 100.0,
       };
 
-      let mut camera_uniform = CameraUniform::new();
-      camera_uniform.update_view_proj(&camera);
-
-      let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-          label: Some("Camera Buffer"),
-          contents: bytemuck::cast_slice(&[camera_uniform]),
-          usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-      });
-
       let camera_bind_group_layout =
           device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
               entries: &[wgpu::BindGroupLayoutEntry {
                   binding: 0,
-                  visibility: wgpu::ShaderStages::VERTEX,
+                  visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                   ty: wgpu::BindingType::Buffer {
                       ty: wgpu::BufferBindingType::Uniform,
                       has_dynamic_offset: false,
@@ -242,14 +343,12 @@ impl<'window> State<'window> {
               label: Some("camera_bind_group_layout"),
           });
 
-      let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-          layout: &camera_bind_group_layout,
-          entries: &[wgpu::BindGroupEntry {
-              binding: 0,
-              resource: camera_buffer.as_entire_binding(),
-          }],
-          label: Some("camera_bind_group"),
-      });
+      let main_camera_entry = CameraEntry::new(
+          &device,
+          &camera_bind_group_layout,
+          camera,
+          CameraSettings::default(),
+      );
 
       let instances = (0..NUM_INSTANCES_PER_ROW)
         .flat_map(|z| {
@@ -268,66 +367,155 @@ impl<'window> State<'window> {
               position: pos,
               rotation: rot,
               scale: 1.0,
+              entity: z * NUM_INSTANCES_PER_ROW + x,
             }
           })
         }).collect::<Vec<_>>();
 
-      let depth_texture = Texture::create_depth_texture(&device, &config, "depth_texture");
+      let supported_msaa = Msaa::supported(
+          &adapter,
+          device.features(),
+          &[HDR_FORMAT, Texture::DEPTH_FORMAT],
+      );
+      let msaa = Msaa::default().clamp_to(&supported_msaa);
+      let scene_targets = SceneTargets::new(&device, &config, HDR_FORMAT, msaa);
 
       let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
       let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
           label: Some("Instance Buffer"),
           contents: bytemuck::cast_slice(&instance_data),
-          usage: wgpu::BufferUsages::VERTEX,
+          usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
       });
 
-      let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/shader.wgsl"));
-
       let render_pipeline_layout =
           device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
               label: Some("Render Pipeline Layout"),
               bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout],
               push_constant_ranges: &[],
           });
-      let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-          label: Some("Render Pipeline"),
-          layout: Some(&render_pipeline_layout),
-          vertex: wgpu::VertexState {
-              module: &shader,
-              entry_point: "vs_main",
-              buffers: &[Vertex::desc()],
-          },
-          fragment: Some(wgpu::FragmentState {
-              // 3.
-              module: &shader,
-              entry_point: "fs_main",
-              targets: &[Some(wgpu::ColorTargetState {
-                  // 4.
-                  format: config.format,
-                  blend: Some(wgpu::BlendState::REPLACE),
-                  write_mask: wgpu::ColorWrites::ALL,
-              })],
+      let id_pipeline_layout =
+          device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+              label: Some("Entity Id Pipeline Layout"),
+              bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout],
+              push_constant_ranges: &[],
+          });
+
+      let pipelines = PipelineCache::new(Box::new(VfsShaderLoader::new(vfs.clone(), "shaders")))
+          .with_fallback(Box::new(EMBEDDED_SHADERS));
+      let mut pipelines = if cfg!(debug_assertions) {
+          pipelines.with_hot_reload()
+      } else {
+          pipelines
+      };
+      pipelines.set_layout(SCENE_SHADER, render_pipeline_layout);
+      let scene_pipeline = PipelineKey::new(
+          SCENE_SHADER,
+          vec![Vertex::desc(), InstanceRaw::desc()],
+          config.format,
+      );
+      // Window cameras draw into the HDR target, multisampled and depth
+      // tested; offscreen ones straight into their surface-format textures.
+      let hdr_scene_pipeline = scene_pipeline
+          .clone()
+          .with_format(HDR_FORMAT)
+          .with_depth(Texture::DEPTH_FORMAT)
+          .with_sample_count(msaa.sample_count());
+      pipelines.get_or_create(&device, &scene_pipeline).unwrap();
+      pipelines.get_or_create(&device, &hdr_scene_pipeline).unwrap();
+      // Entity ids are drawn single-sampled: integer targets can't resolve.
+      pipelines.set_layout(ID_SHADER, id_pipeline_layout);
+      let id_pipeline = PipelineKey::new(
+          ID_SHADER,
+          vec![Vertex::desc(), InstanceRaw::desc()],
+          ID_FORMAT,
+      )
+      .with_depth(Texture::DEPTH_FORMAT);
+      pipelines.get_or_create(&device, &id_pipeline).unwrap();
+
+      let joint_layout = skinning::create_bind_group_layout(&device);
+      pipelines.set_layout(
+          SKINNED_SHADER,
+          device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+              label: Some("Skinned Pipeline Layout"),
+              bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout, &joint_layout],
+              push_constant_ranges: &[],
+          }),
+      );
+      let skinned_pipeline = PipelineKey::new(
+          SKINNED_SHADER,
+          vec![SkinnedVertex::desc(), InstanceRaw::desc()],
+          config.format,
+      );
+      let hdr_skinned_pipeline = skinned_pipeline
+          .clone()
+          .with_format(HDR_FORMAT)
+          .with_depth(Texture::DEPTH_FORMAT)
+          .with_sample_count(msaa.sample_count());
+      pipelines.get_or_create(&device, &skinned_pipeline).unwrap();
+      pipelines.get_or_create(&device, &hdr_skinned_pipeline).unwrap();
+
+      let skybox_layout = skybox::create_bind_group_layout(&device);
+      pipelines.set_layout(
+          SKYBOX_SHADER,
+          device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+              label: Some("Skybox Pipeline Layout"),
+              bind_group_layouts: &[&skybox_layout, &camera_bind_group_layout],
+              push_constant_ranges: &[],
           }),
-          primitive: wgpu::PrimitiveState {
-              topology: wgpu::PrimitiveTopology::TriangleList,
-              strip_index_format: None,
-              front_face: wgpu::FrontFace::Ccw,
-              cull_mode: Some(wgpu::Face::Back),
-              // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-              polygon_mode: wgpu::PolygonMode::Fill,
-              // Requires Features::DEPTH_CLIP_CONTROL
-              unclipped_depth: false,
-              // Requires Features::CONSERVATIVE_RASTERIZATION
-              conservative: false,
+      );
+      // The window skybox is drawn after the scene and only where its depth
+      // is still clear; offscreen targets have no depth, so there it is
+      // drawn first instead.
+      let skybox_pipeline = PipelineKey::new(SKYBOX_SHADER, Vec::new(), config.format);
+      let hdr_skybox_pipeline = skybox_pipeline
+          .clone()
+          .with_format(HDR_FORMAT)
+          .with_depth_test(DepthTest {
+              format: Texture::DEPTH_FORMAT,
+              compare: wgpu::CompareFunction::LessEqual,
+              write: false,
+          })
+          .with_sample_count(msaa.sample_count());
+      pipelines.get_or_create(&device, &skybox_pipeline).unwrap();
+      pipelines.get_or_create(&device, &hdr_skybox_pipeline).unwrap();
+
+      let post = PostStack::new(
+          &device,
+          &queue,
+          &mut pipelines,
+          config.width,
+          config.height,
+          config.format,
+      );
+
+      let mut render_graph = RenderGraph::new();
+      let surface_resource = render_graph.surface();
+      let scene_color = render_graph.import_texture("scene_color");
+      let scene_pass = render_graph.add_pass(
+          PassDesc::new("scene").write(scene_color),
+          move |ctx: &mut PassContext, state: &State| {
+              if let Some(target) = ctx.view(scene_color) {
+                  state.draw_cameras(ctx.encoder, target);
+              }
           },
-          depth_stencil: None, // 1.
-          multisample: wgpu::MultisampleState {
-              count: 1,
-              mask: !0,
-              alpha_to_coverage_enabled: false,
+      );
+      let entity_ids = render_graph.import_texture("entity_ids");
+      render_graph.add_pass(
+          PassDesc::new("entity_ids").write(entity_ids).side_effects(),
+          |ctx: &mut PassContext, state: &State| {
+              if let Some(ids) = &state.id_buffer {
+                  state.draw_entity_ids(ctx.encoder, ids);
+              }
           },
-          multiview: None, // 5.
-      });
+      );
+      render_graph.add_pass(
+          PassDesc::new("post").read(scene_color).write(surface_resource),
+          |ctx: &mut PassContext, state: &State| {
+              if let Some(surface) = ctx.surface() {
+                  state.post.execute(ctx.device, ctx.encoder, &state.pipelines, surface);
+              }
+          },
+      );
 
       let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
           label: Some("Vertex Buffer"),
@@ -345,36 +533,474 @@ impl<'window> State<'window> {
 
       let num_indices = EXAMPLE_BUFFER.num_indices;
 
-      
+      // Debug builds reload assets edited while the game runs, like shaders.
+      let mut assets = if cfg!(debug_assertions) {
+          AssetServer::new(vfs).with_hot_reload()
+      } else {
+          AssetServer::new(vfs)
+      };
+      assets.register_loader(TextureAssetLoader::default());
+      assets.register_loader(ShaderAssetLoader);
+      assets.register_loader(MeshAssetLoader);
+      assets.register_loader(SoundAssetLoader);
+      assets.register_loader(GltfAssetLoader);
+      let audio = Audio::new();
+      let music = Music::new(audio.player().clone(), assets.vfs().clone());
 
-      Self {
+      let state = Self {
           surface,
           device,
           queue,
           config,
           size,
           window: &window,
-          render_pipeline,
+          pipelines,
+          scene_pipeline,
+          hdr_scene_pipeline,
+          skybox_pipeline,
+          hdr_skybox_pipeline,
+          skinned_pipeline,
+          hdr_skinned_pipeline,
+          joint_layout,
+          skinned_meshes: Vec::new(),
+          skybox_layout,
+          skybox: None,
+          post,
+          render_graph,
+          scene_color,
+          scene_pass,
+          entity_ids,
+          id_pipeline,
+          id_buffer: None,
+          window_title: window.title(),
           vertex_buffer,
           num_vertices,
           index_buffer,
           num_indices,
           diffuse_bind_group,
           diffuse_texture,
-          camera,
-          camera_uniform,
-          camera_buffer,
-          camera_bind_group,
+          textures,
+          assets,
+          texture_bind_groups: TextureBindGroups::new(),
+          music,
+          audio,
+          listener_camera: CameraId(0),
+          audio_clock: FrameClock::new(),
+          texture_bind_group_layout,
+          camera_bind_group_layout,
+          cameras: vec![Some(main_camera_entry)],
+          main_camera: CameraId(0),
+          render_targets: Vec::new(),
           instances,
           instance_buffer,
-          depth_texture
+          scene_targets,
+          supported_msaa,
+      };
+      if state.shader_error().is_some() {
+          state.show_shader_error();
       }
+      state
   }
 
   pub fn window(&self) -> &Window {
       &self.window
   }
 
+  // The camera created with the window, rendering full-screen into it.
+  pub fn main_camera(&self) -> CameraId {
+      self.main_camera
+  }
+
+  pub fn camera_ids(&self) -> Vec<CameraId> {
+      self.cameras
+          .iter()
+          .enumerate()
+          .filter(|(_, entry)| entry.is_some())
+          .map(|(i, _)| CameraId(i))
+          .collect()
+  }
+
+  pub fn camera(&self, id: CameraId) -> Option<&Camera> {
+      self.cameras.get(id.0)?.as_ref().map(|entry| &entry.camera)
+  }
+
+  pub fn camera_mut(&mut self, id: CameraId) -> Option<&mut Camera> {
+      self.cameras.get_mut(id.0)?.as_mut().map(|entry| &mut entry.camera)
+  }
+
+  pub fn camera_settings(&self, id: CameraId) -> Option<&CameraSettings> {
+      self.cameras.get(id.0)?.as_ref().map(|entry| &entry.settings)
+  }
+
+  pub fn camera_settings_mut(&mut self, id: CameraId) -> Option<&mut CameraSettings> {
+      self.cameras.get_mut(id.0)?.as_mut().map(|entry| &mut entry.settings)
+  }
+
+  pub fn add_camera(&mut self, camera: Camera, settings: CameraSettings) -> CameraId {
+      let entry = CameraEntry::new(&self.device, &self.camera_bind_group_layout, camera, settings);
+      match self.cameras.iter().position(Option::is_none) {
+          Some(i) => {
+              self.cameras[i] = Some(entry);
+              CameraId(i)
+          }
+          None => {
+              self.cameras.push(Some(entry));
+              CameraId(self.cameras.len() - 1)
+          }
+      }
+  }
+
+  pub fn remove_camera(&mut self, id: CameraId) -> Option<Camera> {
+      self.cameras.get_mut(id.0)?.take().map(|entry| entry.camera)
+  }
+
+  // Picking ray through pixel (x, y) of the camera's target, e.g. the cursor
+  // position for window cameras. None outside the camera's viewport.
+  pub fn screen_ray(&self, id: CameraId, x: f32, y: f32) -> Option<Ray> {
+      let entry = self.cameras.get(id.0)?.as_ref()?;
+      let (width, height) = self.target_size(entry.settings.target)?;
+      let ndc = entry.settings.viewport.to_ndc(x, y, width, height)?;
+      Some(entry.camera.ray(ndc))
+  }
+
+  pub fn instances(&self) -> &[Instance] {
+      &self.instances
+  }
+
+  pub fn set_instances(&mut self, instances: Vec<Instance>) {
+      let data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
+      if instances.len() <= self.instances.len() {
+          self.queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&data));
+      } else {
+          self.instance_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+              label: Some("Instance Buffer"),
+              contents: bytemuck::cast_slice(&data),
+              usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+          });
+      }
+      self.instances = instances;
+  }
+
+  // Uploads a skinned mesh, drawn with the diffuse texture by every camera
+  // until removed. Its joints start in the bind pose; pose it with
+  // `set_joint_matrices`.
+  pub fn add_skinned_mesh(&mut self, data: &SkinnedMeshData, joint_count: usize, instance: &Instance) -> SkinnedMeshId {
+      let entry = SkinnedMeshEntry {
+          mesh: Mesh::from_skinned_data(&self.device, data, Some("Skinned Mesh")),
+          joints: JointBuffer::new(&self.device, &self.joint_layout, joint_count, "Joint Matrices"),
+          instance: self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+              label: Some("Skinned Instance Buffer"),
+              contents: bytemuck::cast_slice(&[instance.to_raw()]),
+              usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+          }),
+      };
+      match self.skinned_meshes.iter().position(Option::is_none) {
+          Some(i) => {
+              self.skinned_meshes[i] = Some(entry);
+              SkinnedMeshId(i)
+          }
+          None => {
+              self.skinned_meshes.push(Some(entry));
+              SkinnedMeshId(self.skinned_meshes.len() - 1)
+          }
+      }
+  }
+
+  pub fn remove_skinned_mesh(&mut self, id: SkinnedMeshId) {
+      if let Some(entry) = self.skinned_meshes.get_mut(id.0) {
+          *entry = None;
+      }
+  }
+
+  // Usually `Pose::joint_matrices` of the mesh's skeleton.
+  pub fn set_joint_matrices(&mut self, id: SkinnedMeshId, matrices: &[uv::Mat4]) {
+      if let Some(entry) = self.skinned_meshes.get_mut(id.0).and_then(Option::as_mut) {
+          entry.joints.write(&self.device, &self.queue, &self.joint_layout, matrices);
+      }
+  }
+
+  pub fn set_skinned_instance(&mut self, id: SkinnedMeshId, instance: &Instance) {
+      if let Some(entry) = self.skinned_meshes.get(id.0).and_then(Option::as_ref) {
+          self.queue.write_buffer(&entry.instance, 0, bytemuck::cast_slice(&[instance.to_raw()]));
+      }
+  }
+
+  // Draws entity ids for `pick` and `pick_rect` next to the scene. Costs a
+  // second pass over the window cameras, so editors turn it on as needed.
+  pub fn set_picking(&mut self, enabled: bool) {
+      match (enabled, self.id_buffer.is_some()) {
+          (true, false) => self.id_buffer = Some(IdBuffer::new(&self.device, &self.config)),
+          (false, true) => self.id_buffer = None,
+          _ => {}
+      }
+  }
+
+  pub fn picking_enabled(&self) -> bool {
+      self.id_buffer.is_some()
+  }
+
+  // The entity under window pixel (x, y), read back from the frame after
+  // next without waiting on the GPU. Resolves to None straight away when
+  // picking is off.
+  pub fn pick(&mut self, x: u32, y: u32) -> PickRequest<Option<Entity>> {
+      match self.id_buffer.as_mut() {
+          Some(ids) => ids.pick(x, y),
+          None => PickRequest::ready(None),
+      }
+  }
+
+  // Marquee selection: every entity visible in the window rectangle.
+  pub fn pick_rect(&mut self, x: u32, y: u32, width: u32, height: u32) -> PickRequest<Vec<Entity>> {
+      match self.id_buffer.as_mut() {
+          Some(ids) => ids.pick_rect(x, y, width, height),
+          None => PickRequest::ready(Vec::new()),
+      }
+  }
+
+  // Offscreen colour target a camera can render into. It uses the surface
+  // format so the scene pipeline can draw into it unchanged.
+  pub fn create_render_target(&mut self, width: u32, height: u32, label: &str) -> RenderTargetId {
+      let texture = Texture::create_render_target(&self.device, width, height, self.config.format, label);
+      let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+          layout: &self.texture_bind_group_layout,
+          entries: &[
+              wgpu::BindGroupEntry {
+                  binding: 0,
+                  resource: wgpu::BindingResource::TextureView(&texture.view),
+              },
+              wgpu::BindGroupEntry {
+                  binding: 1,
+                  resource: wgpu::BindingResource::Sampler(&texture.sampler),
+              },
+          ],
+          label: Some(label),
+      });
+      let target = OffscreenTarget { texture, bind_group };
+      match self.render_targets.iter().position(Option::is_none) {
+          Some(i) => {
+              self.render_targets[i] = Some(target);
+              RenderTargetId(i)
+          }
+          None => {
+              self.render_targets.push(Some(target));
+              RenderTargetId(self.render_targets.len() - 1)
+          }
+      }
+  }
+
+  // Cameras still pointing at a removed target are skipped when rendering.
+  pub fn remove_render_target(&mut self, id: RenderTargetId) {
+      if let Some(target) = self.render_targets.get_mut(id.0) {
+          *target = None;
+      }
+  }
+
+  pub fn render_target(&self, id: RenderTargetId) -> Option<&Texture> {
+      self.render_targets.get(id.0)?.as_ref().map(|target| &target.texture)
+  }
+
+  // Bind group matching the diffuse texture layout, for sampling a render
+  // target from a material.
+  pub fn render_target_bind_group(&self, id: RenderTargetId) -> Option<&wgpu::BindGroup> {
+      self.render_targets.get(id.0)?.as_ref().map(|target| &target.bind_group)
+  }
+
+  // Custom passes (shadows, UI) are added here. The scene pass writes the
+  // HDR `scene_color` texture, which the post pass reads and brings onto the
+  // surface, so passes added later that write either draw on top.
+  pub fn render_graph_mut(&mut self) -> &mut RenderGraph<State<'window>> {
+      &mut self.render_graph
+  }
+
+  pub fn scene_pass(&self) -> PassId {
+      self.scene_pass
+  }
+
+  pub fn scene_color(&self) -> ResourceId {
+      self.scene_color
+  }
+
+  pub fn post(&self) -> &PostStack {
+      &self.post
+  }
+
+  pub fn post_mut(&mut self) -> &mut PostStack {
+      &mut self.post
+  }
+
+  // Inserts a WGSL post-processing stage, see `PostStack::add_stage`.
+  pub fn add_post_stage(&mut self, index: usize, name: &str, source: &str) -> Result<(), ShaderError> {
+      self.post.add_stage(&self.device, &mut self.pipelines, index, name, source)
+  }
+
+  pub fn set_color_grading_lut(&mut self, img: &image::DynamicImage) -> anyhow::Result<()> {
+      self.post.set_color_grading_lut(&self.device, &self.queue, img)
+  }
+
+  // Loads an image through the engine's texture loader, so samplers are
+  // shared with every other texture loaded this way.
+  pub fn load_texture(
+      &mut self,
+      bytes: &[u8],
+      label: &str,
+      options: &TextureOptions,
+  ) -> anyhow::Result<Texture> {
+      self.textures.load_bytes(&self.device, &self.queue, bytes, label, options)
+  }
+
+  // See `TextureLoader::load_cubemap` for the face order.
+  pub fn load_cubemap(
+      &mut self,
+      faces: &[image::DynamicImage; 6],
+      label: &str,
+      options: &TextureOptions,
+  ) -> anyhow::Result<Texture> {
+      self.textures.load_cubemap(&self.device, &self.queue, faces, Some(label), options)
+  }
+
+  // Loads an equirectangular panorama, e.g. an .hdr file, as an HDR cubemap.
+  pub fn load_equirect(
+      &mut self,
+      bytes: &[u8],
+      face_size: u32,
+      label: &str,
+      options: &TextureOptions,
+  ) -> anyhow::Result<Texture> {
+      let img = image::load_from_memory(bytes)?;
+      self.textures
+          .load_equirect(&self.device, &self.queue, &img, face_size, Some(label), options)
+  }
+
+  // `texture` must have a cube view, as `load_cubemap` and `load_equirect`
+  // return.
+  pub fn set_skybox(&mut self, texture: Texture) {
+      self.skybox = Some(Skybox::new(&self.device, &self.skybox_layout, texture));
+  }
+
+  pub fn clear_skybox(&mut self) -> Option<Texture> {
+      self.skybox.take().map(|skybox| skybox.into_texture())
+  }
+
+  pub fn skybox(&self) -> Option<&Texture> {
+      self.skybox.as_ref().map(Skybox::texture)
+  }
+
+  pub fn textures_mut(&mut self) -> &mut TextureLoader {
+      &mut self.textures
+  }
+
+  // Loads through `vfs()`. Assets become available in the frame update
+  // after their background decode finishes.
+  pub fn assets(&self) -> &AssetServer {
+      &self.assets
+  }
+
+  // `assets/` under the working directory layered over `assets.pak`. Mount
+  // mods with a priority above 1 to override either.
+  pub fn vfs(&self) -> &Vfs {
+      self.assets.vfs()
+  }
+
+  pub fn assets_mut(&mut self) -> &mut AssetServer {
+      &mut self.assets
+  }
+
+  // Bind group matching the diffuse texture layout for a texture asset,
+  // rebuilt whenever the texture is reloaded. None while it loads.
+  pub fn texture_bind_group(&mut self, handle: &Handle<Texture>) -> Option<&wgpu::BindGroup> {
+      self.texture_bind_groups
+          .get(&self.device, &self.texture_bind_group_layout, &self.assets, handle)
+  }
+
+  // Plays sounds through the default output device, or nowhere when there
+  // is none. Clone the player to hand it to ECS systems.
+  pub fn audio(&self) -> &AudioPlayer {
+      self.audio.player()
+  }
+
+  // Streamed background music with crossfades and playlists.
+  pub fn music(&self) -> &Music {
+      &self.music
+  }
+
+  pub fn music_mut(&mut self) -> &mut Music {
+      &mut self.music
+  }
+
+  // The camera spatial audio is heard from, the main camera by default.
+  pub fn set_listener_camera(&mut self, id: CameraId) {
+      self.listener_camera = id;
+  }
+
+  pub fn listener_camera(&self) -> CameraId {
+      self.listener_camera
+  }
+
+  pub fn msaa(&self) -> Msaa {
+      self.scene_targets.msaa()
+  }
+
+  // MSAA levels this adapter can render the scene with, always including `Off`.
+  pub fn supported_msaa(&self) -> &[Msaa] {
+      &self.supported_msaa
+  }
+
+  // Switches MSAA at runtime, recreating the scene attachments and pipeline.
+  // Unsupported levels are lowered to the nearest supported one, which is
+  // returned.
+  pub fn set_msaa(&mut self, msaa: Msaa) -> Msaa {
+      let msaa = msaa.clamp_to(&self.supported_msaa);
+      if msaa == self.msaa() {
+          return msaa;
+      }
+      let key = self.hdr_scene_pipeline.clone().with_sample_count(msaa.sample_count());
+      let skybox_key = self.hdr_skybox_pipeline.clone().with_sample_count(msaa.sample_count());
+      let skinned_key = self.hdr_skinned_pipeline.clone().with_sample_count(msaa.sample_count());
+      for key in [&key, &skybox_key, &skinned_key] {
+          if let Err(e) = self.pipelines.get_or_create(&self.device, key) {
+              log::error!("{}", e);
+              return self.msaa();
+          }
+      }
+      self.hdr_scene_pipeline = key;
+      self.hdr_skybox_pipeline = skybox_key;
+      self.hdr_skinned_pipeline = skinned_key;
+      self.scene_targets = SceneTargets::new(&self.device, &self.config, HDR_FORMAT, msaa);
+      msaa
+  }
+
+  // Last shader build error, while the pipeline it affects runs on an older
+  // or embedded copy of the shader.
+  pub fn shader_error(&self) -> Option<&ShaderError> {
+      self.pipelines.error()
+  }
+
+  fn reload_shaders(&mut self) {
+      if !self.pipelines.reload(&self.device) {
+          return;
+      }
+      self.show_shader_error();
+  }
+
+  // The error stays in the window title until the shader is fixed.
+  fn show_shader_error(&self) {
+      match self.pipelines.error() {
+          Some(e) => self
+              .window
+              .set_title(&format!("{} - shader error: {}", self.window_title, e)),
+          None => self.window.set_title(&self.window_title),
+      }
+  }
+
+  fn target_size(&self, target: RenderTarget) -> Option<(u32, u32)> {
+      match target {
+          RenderTarget::Window => Some((self.config.width, self.config.height)),
+          RenderTarget::Texture(id) => self
+              .render_target(id)
+              .map(|texture| (texture.texture.width(), texture.texture.height())),
+      }
+  }
+
   fn resize(&mut self, app: &mut Box<dyn App>, new_size: winit::dpi::PhysicalSize<u32>) {
     app.resize(self, new_size);
     if new_size.width > 0 && new_size.height > 0 {
@@ -382,6 +1008,11 @@ impl<'window> State<'window> {
       self.config.width = new_size.width;
       self.config.height = new_size.height;
       self.surface.configure(&self.device, &self.config);
+      self.post.resize(&self.device, new_size.width, new_size.height);
+      self.scene_targets = SceneTargets::new(&self.device, &self.config, HDR_FORMAT, self.msaa());
+      if let Some(ids) = self.id_buffer.as_mut() {
+          ids.resize(&self.device, &self.config);
+      }
       self.window().request_redraw();
     }
   }
@@ -392,12 +1023,44 @@ impl<'window> State<'window> {
 
   fn update(&mut self, app: &mut Box<dyn App>) {
       app.update(self);
-      self.camera_uniform.update_view_proj(&self.camera);
-      self.queue.write_buffer(
-          &self.camera_buffer,
-          0,
-          bytemuck::cast_slice(&[self.camera_uniform]),
-      );
+      self.assets.update(&mut LoadContext {
+          device: &self.device,
+          queue: &self.queue,
+          textures: &mut self.textures,
+      });
+      self.texture_bind_groups.update(&self.assets);
+      self.reload_shaders();
+      for i in 0..self.cameras.len() {
+          let Some(target) = self.cameras[i].as_ref().map(|entry| entry.settings.target) else {
+              continue;
+          };
+          let Some((width, height)) = self.target_size(target) else {
+              continue;
+          };
+          if let Some(entry) = self.cameras[i].as_mut() {
+              entry.camera.aspect = entry.settings.viewport.aspect(width, height);
+              entry.uniform.update_view_proj(&entry.camera);
+              self.queue.write_buffer(&entry.buffer, 0, bytemuck::cast_slice(&[entry.uniform]));
+          }
+      }
+      self.post.update(&self.queue);
+      if let Some(ids) = self.id_buffer.as_mut() {
+          ids.poll(&self.device);
+      }
+      self.update_listener();
+      self.music.update();
+  }
+
+  fn update_listener(&mut self) {
+      let dt = self.audio_clock.tick();
+      let player = self.audio.player();
+      if let Some(camera) = self.camera(self.listener_camera) {
+          let mut listener = player.listener();
+          listener.follow(camera, dt);
+          player.set_listener(listener);
+      } else {
+          player.set_listener(AudioListener::default());
+      }
   }
 
   fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
@@ -410,44 +1073,269 @@ impl<'window> State<'window> {
           .create_command_encoder(&wgpu::CommandEncoderDescriptor {
               label: Some("Render Encoder"),
           });
-      {
+      let mut graph = std::mem::take(&mut self.render_graph);
+      let mut imports = vec![(graph.surface(), &view), (self.scene_color, self.post.hdr_view())];
+      if let Some(ids) = &self.id_buffer {
+          imports.push((self.entity_ids, ids.view()));
+      }
+      let result = graph.execute(
+          &self.device,
+          &self.queue,
+          &mut encoder,
+          &imports,
+          (self.config.width, self.config.height),
+          self,
+      );
+      self.render_graph = graph;
+      if let Err(e) = result {
+          log::error!("{}", e);
+      }
+      if let Some(ids) = self.id_buffer.as_mut() {
+          ids.record_readbacks(&self.device, &mut encoder);
+      }
+
+      // submit will accept anything that implements IntoIter
+      self.queue.submit(std::iter::once(encoder.finish()));
+      if let Some(ids) = self.id_buffer.as_mut() {
+          ids.map_readbacks();
+      }
+      output.present();
+
+      Ok(())
+  }
+
+  // The built-in scene pass: every camera in order, each into its own target.
+  // Window cameras draw into `scene_view`, the HDR target.
+  fn draw_cameras(&self, encoder: &mut wgpu::CommandEncoder, scene_view: &wgpu::TextureView) {
+      let mut order: Vec<usize> = self
+          .cameras
+          .iter()
+          .enumerate()
+          .filter(|(_, entry)| entry.is_some())
+          .map(|(i, _)| i)
+          .collect();
+      order.sort_by_key(|&i| (self.cameras[i].as_ref().map_or(0, |entry| entry.settings.order), i));
+
+      let mut cleared: Vec<RenderTarget> = Vec::new();
+      for i in order {
+          let Some(entry) = self.cameras[i].as_ref() else {
+              continue;
+          };
+          let target = entry.settings.target;
+          let (target_view, width, height) = match target {
+              RenderTarget::Window => (scene_view, self.config.width, self.config.height),
+              RenderTarget::Texture(id) => match self.render_target(id) {
+                  Some(texture) => (&texture.view, texture.texture.width(), texture.texture.height()),
+                  None => continue,
+              },
+          };
+          let (x, y, w, h) = entry.settings.viewport.to_pixels(width, height);
+          if w < 1.0 || h < 1.0 {
+              continue;
+          }
+
+          // A clear op wipes the whole attachment, not just the viewport, so
+          // only the first camera on a target may use one.
+          let load = if !cleared.contains(&target) {
+              cleared.push(target);
+              wgpu::LoadOp::Clear(entry.settings.clear_color.unwrap_or(wgpu::Color::BLACK))
+          } else {
+              wgpu::LoadOp::Load
+          };
+
+          let (color_attachment, depth_stencil_attachment) = match target {
+              RenderTarget::Window => (
+                  self.scene_targets.color_attachment(target_view, load),
+                  Some(self.scene_targets.depth_attachment()),
+              ),
+              RenderTarget::Texture(_) => (
+                  wgpu::RenderPassColorAttachment {
+                      view: target_view,
+                      resolve_target: None,
+                      ops: wgpu::Operations {
+                          load,
+                          store: wgpu::StoreOp::Store,
+                      },
+                  },
+                  None,
+              ),
+          };
           let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
               label: Some("Render Pass"),
-              color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                  view: &view,
-                  resolve_target: None,
-                  ops: wgpu::Operations {
-                      load: wgpu::LoadOp::Clear(wgpu::Color {
-                          r: 0.1,
-                          g: 0.2,
-                          b: 0.3,
-                          a: 1.0,
-                      }),
-                      store: wgpu::StoreOp::Store,
-                  },
-              })],
-              depth_stencil_attachment: None,
+              color_attachments: &[Some(color_attachment)],
+              depth_stencil_attachment,
               occlusion_query_set: None,
               timestamp_writes: None,
           });
+          render_pass.set_viewport(x, y, w, h, 0.0, 1.0);
 
-          render_pass.set_pipeline(&self.render_pipeline);
-          render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-          render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+          let (key, skybox_key) = match target {
+              RenderTarget::Window => (&self.hdr_scene_pipeline, &self.hdr_skybox_pipeline),
+              RenderTarget::Texture(_) => (&self.scene_pipeline, &self.skybox_pipeline),
+          };
+          let skybox_first = target != RenderTarget::Window;
+          if skybox_first {
+              self.draw_skybox(&mut render_pass, skybox_key, &entry.bind_group);
+          }
+          if let Some(pipeline) = self.pipelines.get(key) {
+              render_pass.set_pipeline(pipeline);
+              render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
+              render_pass.set_bind_group(1, &entry.bind_group, &[]);
+
+              render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+              render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+              render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+              render_pass.draw_indexed(0..self.num_indices, 0, 0..self.instances.len() as u32);
+          }
+          let skinned_key = match target {
+              RenderTarget::Window => &self.hdr_skinned_pipeline,
+              RenderTarget::Texture(_) => &self.skinned_pipeline,
+          };
+          if let Some(pipeline) = self.pipelines.get(skinned_key) {
+              render_pass.set_pipeline(pipeline);
+              render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
+              render_pass.set_bind_group(1, &entry.bind_group, &[]);
+              for skinned in self.skinned_meshes.iter().flatten() {
+                  render_pass.set_bind_group(2, skinned.joints.bind_group(), &[]);
+                  render_pass.set_vertex_buffer(0, skinned.mesh.vertex_buffer.slice(..));
+                  render_pass.set_vertex_buffer(1, skinned.instance.slice(..));
+                  render_pass.set_index_buffer(skinned.mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                  render_pass.draw_indexed(0..skinned.mesh.num_indices, 0, 0..1);
+              }
+          }
+          if !skybox_first {
+              self.draw_skybox(&mut render_pass, skybox_key, &entry.bind_group);
+          }
+      }
+  }
 
+  // Window cameras again, writing entity ids instead of colour. Each camera
+  // only covers its viewport, like in the colour pass.
+  fn draw_entity_ids(&self, encoder: &mut wgpu::CommandEncoder, ids: &IdBuffer) {
+      let Some(pipeline) = self.pipelines.get(&self.id_pipeline) else {
+          return;
+      };
+      let mut order: Vec<usize> = self
+          .cameras
+          .iter()
+          .enumerate()
+          .filter(|(_, entry)| entry.as_ref().is_some_and(|entry| entry.settings.target == RenderTarget::Window))
+          .map(|(i, _)| i)
+          .collect();
+      order.sort_by_key(|&i| (self.cameras[i].as_ref().map_or(0, |entry| entry.settings.order), i));
+
+      let mut load = wgpu::LoadOp::Clear(wgpu::Color {
+          r: NO_ENTITY as f64,
+          g: 0.0,
+          b: 0.0,
+          a: 0.0,
+      });
+      for i in order {
+          let Some(entry) = self.cameras[i].as_ref() else {
+              continue;
+          };
+          let (x, y, w, h) = entry.settings.viewport.to_pixels(self.config.width, self.config.height);
+          if w < 1.0 || h < 1.0 {
+              continue;
+          }
+          let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+              label: Some("Entity Id Pass"),
+              color_attachments: &[Some(ids.color_attachment(load))],
+              depth_stencil_attachment: Some(ids.depth_attachment()),
+              occlusion_query_set: None,
+              timestamp_writes: None,
+          });
+          load = wgpu::LoadOp::Load;
+          render_pass.set_viewport(x, y, w, h, 0.0, 1.0);
+          render_pass.set_pipeline(pipeline);
+          render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
+          render_pass.set_bind_group(1, &entry.bind_group, &[]);
           render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+          render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
           render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-          render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+          render_pass.draw_indexed(0..self.num_indices, 0, 0..self.instances.len() as u32);
       }
+  }
 
-      // submit will accept anything that implements IntoIter
-      self.queue.submit(std::iter::once(encoder.finish()));
-      output.present();
+  fn draw_skybox<'a>(
+      &'a self,
+      render_pass: &mut wgpu::RenderPass<'a>,
+      key: &PipelineKey,
+      camera_bind_group: &'a wgpu::BindGroup,
+  ) {
+      let (Some(skybox), Some(pipeline)) = (&self.skybox, self.pipelines.get(key)) else {
+          return;
+      };
+      render_pass.set_pipeline(pipeline);
+      render_pass.set_bind_group(0, skybox.bind_group(), &[]);
+      render_pass.set_bind_group(1, camera_bind_group, &[]);
+      render_pass.draw(0..3, 0..1);
+  }
+}
 
-      Ok(())
+impl CameraEntry {
+  fn new(
+      device: &wgpu::Device,
+      layout: &wgpu::BindGroupLayout,
+      camera: Camera,
+      settings: CameraSettings,
+  ) -> Self {
+      let mut uniform = CameraUniform::new();
+      uniform.update_view_proj(&camera);
+
+      let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+          label: Some("Camera Buffer"),
+          contents: bytemuck::cast_slice(&[uniform]),
+          usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      });
+
+      let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+          layout,
+          entries: &[wgpu::BindGroupEntry {
+              binding: 0,
+              resource: buffer.as_entire_binding(),
+          }],
+          label: Some("camera_bind_group"),
+      });
+
+      Self {
+          camera,
+          settings,
+          uniform,
+          buffer,
+          bind_group,
+      }
   }
 }
 
+const ASSET_DIR: &str = "assets";
+const ASSET_ARCHIVE: &str = "assets.pak";
+const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+const EMBEDDED_SHADERS: EmbeddedShaderLoader = EmbeddedShaderLoader::new(&[
+    ("shader.wgsl", include_str!("shaders/shader.wgsl")),
+    ("camera.wgsl", include_str!("shaders/camera.wgsl")),
+    ("skybox.wgsl", include_str!("shaders/skybox.wgsl")),
+    ("skinned.wgsl", include_str!("shaders/skinned.wgsl")),
+    ("entity_id.wgsl", include_str!("shaders/entity_id.wgsl")),
+    ("post/common.wgsl", include_str!("shaders/post/common.wgsl")),
+    ("post/blit.wgsl", include_str!("shaders/post/blit.wgsl")),
+    ("post/bloom_threshold.wgsl", include_str!("shaders/post/bloom_threshold.wgsl")),
+    ("post/bloom_blur.wgsl", include_str!("shaders/post/bloom_blur.wgsl")),
+    ("post/bloom_composite.wgsl", include_str!("shaders/post/bloom_composite.wgsl")),
+    ("post/tonemap.wgsl", include_str!("shaders/post/tonemap.wgsl")),
+    ("post/color_grading.wgsl", include_str!("shaders/post/color_grading.wgsl")),
+    ("post/chromatic_aberration.wgsl", include_str!("shaders/post/chromatic_aberration.wgsl")),
+    ("post/vignette.wgsl", include_str!("shaders/post/vignette.wgsl")),
+    ("post/film_grain.wgsl", include_str!("shaders/post/film_grain.wgsl")),
+    ("post/fxaa.wgsl", include_str!("shaders/post/fxaa.wgsl")),
+    ("mipmap.wgsl", include_str!("shaders/mipmap.wgsl")),
+    ("equirect_to_cube.wgsl", include_str!("shaders/equirect_to_cube.wgsl")),
+]);
+const SCENE_SHADER: &str = "shader.wgsl";
+const SKYBOX_SHADER: &str = "skybox.wgsl";
+const ID_SHADER: &str = "entity_id.wgsl";
+const SKINNED_SHADER: &str = "skinned.wgsl";
+
 const NUM_INSTANCES_PER_ROW: u32 = 10;
 // const INSTANCE_DISPLACEMENT: cgmath::Vector3<f32> = cgmath::Vector3::new(NUM_INSTANCES_PER_ROW as f32 * 0.5, 0.0, NUM_INSTANCES_PER_ROW as f32 * 0.5);
 const INSTANCE_DISPLACEMENT: uv::Vec3 = uv::Vec3::new(
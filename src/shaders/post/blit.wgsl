@@ -0,0 +1,6 @@
+#include "common.wgsl"
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_input, s_input, in.uv);
+}
@@ -0,0 +1,21 @@
+#include "common.wgsl"
+
+// Separable 9-tap gaussian at half resolution, HORIZONTAL picks the axis.
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+#ifdef HORIZONTAL
+    let step = vec2<f32>(params.texel_size.x, 0.0);
+#else
+    let step = vec2<f32>(0.0, params.texel_size.y);
+#endif
+    var weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+    var color = textureSample(t_input, s_input, in.uv).rgb * weights[0];
+    for (var i = 1; i < 5; i++) {
+        let offset = step * f32(i);
+        color += textureSample(t_input, s_input, in.uv + offset).rgb * weights[i];
+        color += textureSample(t_input, s_input, in.uv - offset).rgb * weights[i];
+    }
+    return vec4<f32>(color, 1.0);
+}
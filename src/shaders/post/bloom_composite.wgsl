@@ -0,0 +1,12 @@
+#include "common.wgsl"
+
+// values[0].z: bloom intensity
+@group(0) @binding(3)
+var t_bloom: texture_2d<f32>;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let scene = textureSample(t_input, s_input, in.uv);
+    let bloom = textureSample(t_bloom, s_input, in.uv).rgb;
+    return vec4<f32>(scene.rgb + bloom * params.values[0].z, scene.a);
+}
@@ -0,0 +1,24 @@
+#include "common.wgsl"
+
+// values[0].x: threshold, values[0].y: soft knee
+// Runs at half resolution, texel_size is that of the half-size target; the
+// four taps average the 2x2 block of the full-size input below.
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let o = params.texel_size * 0.25;
+    let color = 0.25 * (
+        textureSample(t_input, s_input, in.uv + vec2<f32>(-o.x, -o.y)).rgb +
+        textureSample(t_input, s_input, in.uv + vec2<f32>(o.x, -o.y)).rgb +
+        textureSample(t_input, s_input, in.uv + vec2<f32>(-o.x, o.y)).rgb +
+        textureSample(t_input, s_input, in.uv + vec2<f32>(o.x, o.y)).rgb
+    );
+
+    let threshold = params.values[0].x;
+    let knee = max(threshold * params.values[0].y, 1e-5);
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    let contribution = max(soft, brightness - threshold) / max(brightness, 1e-5);
+    return vec4<f32>(color * contribution, 1.0);
+}
@@ -0,0 +1,12 @@
+#include "common.wgsl"
+
+// values[0].x: offset at the screen edge, in uv units
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let offset = (in.uv - vec2<f32>(0.5)) * params.values[0].x;
+    let r = textureSample(t_input, s_input, in.uv + offset).r;
+    let center = textureSample(t_input, s_input, in.uv);
+    let b = textureSample(t_input, s_input, in.uv - offset).b;
+    return vec4<f32>(r, center.g, b, center.a);
+}
@@ -0,0 +1,17 @@
+#include "common.wgsl"
+
+// values[0].x: strength, blends between the input and the graded colour.
+// The LUT is indexed with gamma-encoded colour, like LUTs authored in image
+// editors expect.
+@group(0) @binding(3)
+var t_lut: texture_3d<f32>;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let sample = textureSample(t_input, s_input, in.uv);
+    let size = f32(textureDimensions(t_lut).x);
+    let encoded = pow(clamp(sample.rgb, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(1.0 / 2.2));
+    let coord = encoded * ((size - 1.0) / size) + 0.5 / size;
+    let graded = pow(textureSample(t_lut, s_input, coord).rgb, vec3<f32>(2.2));
+    return vec4<f32>(mix(sample.rgb, graded, params.values[0].x), sample.a);
+}
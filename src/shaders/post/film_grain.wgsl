@@ -0,0 +1,19 @@
+#include "common.wgsl"
+
+// values[0].x: intensity
+
+fn hash(p: vec2<f32>) -> f32 {
+    let q = fract(p * vec2<f32>(123.34, 456.21));
+    let r = q + dot(q, q + 45.32);
+    return fract(r.x * r.y);
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let sample = textureSample(t_input, s_input, in.uv);
+    let pixel = floor(in.uv / params.texel_size);
+    let noise = hash(pixel + fract(params.time) * 1000.0) - 0.5;
+    // Stronger in the darks, where grain is most visible on film.
+    let amount = params.values[0].x * (1.0 - luminance(sample.rgb) * 0.5);
+    return vec4<f32>(max(sample.rgb + noise * amount, vec3<f32>(0.0)), sample.a);
+}
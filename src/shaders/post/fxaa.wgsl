@@ -0,0 +1,46 @@
+#include "common.wgsl"
+
+// Simplified FXAA (Timothy Lottes). Luma is taken from gamma-encoded colour
+// since edges are judged perceptually.
+
+const REDUCE_MIN: f32 = 0.0078125;
+const REDUCE_MUL: f32 = 0.125;
+const SPAN_MAX: f32 = 8.0;
+
+fn fxaa_luma(color: vec3<f32>) -> f32 {
+    return sqrt(luminance(color));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let t = params.texel_size;
+    let center = textureSample(t_input, s_input, in.uv);
+    let luma_nw = fxaa_luma(textureSample(t_input, s_input, in.uv + vec2<f32>(-t.x, -t.y)).rgb);
+    let luma_ne = fxaa_luma(textureSample(t_input, s_input, in.uv + vec2<f32>(t.x, -t.y)).rgb);
+    let luma_sw = fxaa_luma(textureSample(t_input, s_input, in.uv + vec2<f32>(-t.x, t.y)).rgb);
+    let luma_se = fxaa_luma(textureSample(t_input, s_input, in.uv + vec2<f32>(t.x, t.y)).rgb);
+    let luma_m = fxaa_luma(center.rgb);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    var dir = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2<f32>(-SPAN_MAX), vec2<f32>(SPAN_MAX)) * t;
+
+    let rgb_a = 0.5 * (
+        textureSample(t_input, s_input, in.uv + dir * (1.0 / 3.0 - 0.5)).rgb +
+        textureSample(t_input, s_input, in.uv + dir * (2.0 / 3.0 - 0.5)).rgb
+    );
+    let rgb_b = rgb_a * 0.5 + 0.25 * (
+        textureSample(t_input, s_input, in.uv + dir * -0.5).rgb +
+        textureSample(t_input, s_input, in.uv + dir * 0.5).rgb
+    );
+    let luma_b = fxaa_luma(rgb_b);
+    let outside = luma_b < luma_min || luma_b > luma_max;
+    return vec4<f32>(select(rgb_b, rgb_a, outside), center.a);
+}
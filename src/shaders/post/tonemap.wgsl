@@ -0,0 +1,64 @@
+#include "common.wgsl"
+
+// values[0].x: exposure in EV, values[0].y: operator
+// (0 = clamp, 1 = Reinhard, 2 = ACES, 3 = AgX)
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (vec3<f32>(1.0) + color);
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve.
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn agx_contrast(x: vec3<f32>) -> vec3<f32> {
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+// AgX base look (Troy Sobotka), polynomial fit by Benjamin Wrensch.
+fn agx(color: vec3<f32>) -> vec3<f32> {
+    let inset = mat3x3<f32>(
+        vec3<f32>(0.842479062253094, 0.0423282422610123, 0.0423756549057051),
+        vec3<f32>(0.0784335999999992, 0.878468636469772, 0.0784336),
+        vec3<f32>(0.0792237451477643, 0.0791661274605434, 0.879142973793104),
+    );
+    let outset = mat3x3<f32>(
+        vec3<f32>(1.19687900512017, -0.0528968517574562, -0.0529716355144438),
+        vec3<f32>(-0.0980208811401368, 1.15190312990417, -0.0980434501171241),
+        vec3<f32>(-0.0990297440797205, -0.0989611768448433, 1.15107367264116),
+    );
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    var c = inset * color;
+    c = clamp(log2(max(c, vec3<f32>(1e-10))), vec3<f32>(min_ev), vec3<f32>(max_ev));
+    c = (c - min_ev) / (max_ev - min_ev);
+    c = agx_contrast(c);
+    c = outset * c;
+    return pow(max(c, vec3<f32>(0.0)), vec3<f32>(2.2));
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let sample = textureSample(t_input, s_input, in.uv);
+    let color = sample.rgb * exp2(params.values[0].x);
+    let tonemapper = u32(params.values[0].y);
+
+    var mapped = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    if tonemapper == 1u {
+        mapped = reinhard(color);
+    } else if tonemapper == 2u {
+        mapped = aces(color);
+    } else if tonemapper == 3u {
+        mapped = agx(color);
+    }
+    return vec4<f32>(mapped, sample.a);
+}
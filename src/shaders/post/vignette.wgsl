@@ -0,0 +1,12 @@
+#include "common.wgsl"
+
+// values[0].x: intensity, values[0].y: radius, values[0].z: smoothness
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let sample = textureSample(t_input, s_input, in.uv);
+    let distance = length(in.uv - vec2<f32>(0.5)) * 1.41421356;
+    let radius = params.values[0].y;
+    let falloff = smoothstep(radius, radius + params.values[0].z, distance);
+    return vec4<f32>(sample.rgb * (1.0 - falloff * params.values[0].x), sample.a);
+}
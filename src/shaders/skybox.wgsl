@@ -0,0 +1,32 @@
+// Draws a cubemap behind the scene. A fullscreen triangle on the far plane is
+// unprojected into world-space view directions, so moving the camera does
+// not move the sky, only turning it does.
+
+#include "camera.wgsl"
+
+@group(0) @binding(0)
+var t_skybox: texture_cube<f32>;
+@group(0) @binding(1)
+var s_skybox: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32(index & 2u), f32((index << 1u) & 2u));
+    var out: VertexOutput;
+    out.ndc = uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+    // Depth 1 only passes where nothing else was drawn.
+    out.clip_position = vec4<f32>(out.ndc, 1.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let far = camera.inv_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+    let dir = far.xyz / far.w - camera.view_position.xyz;
+    return textureSample(t_skybox, s_skybox, dir);
+}
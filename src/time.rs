@@ -0,0 +1,21 @@
+use std::time::Instant;
+
+// Measures the time between updates, for systems that aren't handed one.
+#[derive(Default)]
+pub struct FrameClock {
+    last: Option<Instant>,
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        Self { last: None }
+    }
+
+    // Seconds since the previous tick, 0 on the first.
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let dt = self.last.map_or(0.0, |last| (now - last).as_secs_f32());
+        self.last = Some(now);
+        dt
+    }
+}